@@ -0,0 +1,80 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Times looking transactions up by id in a 100k transaction ledger through
+//! the `LocalBank`'s id index against the linear scan it replaced.
+//!
+//! Run with `cargo bench -p dst_demo_server --bench id_index`.
+
+use std::{path::Path, time::Instant};
+
+use dst_demo_server::bank::{Bank as _, LocalBank, Origin, Transaction, TransactionId};
+use rust_decimal::Decimal;
+
+const TRANSACTION_COUNT: TransactionId = 100_000;
+const LOOKUPS: TransactionId = 1_000;
+
+fn ledger() -> Vec<Transaction> {
+    (1..=TRANSACTION_COUNT)
+        .map(|id| Transaction {
+            id,
+            seq: u64::try_from(id).unwrap(),
+            amount: Decimal::new(i64::from(id), 2),
+            created_at: 1_700_000_000_000,
+        })
+        .collect()
+}
+
+/// Spreads the looked up ids across the whole ledger, weighted towards the
+/// end where a linear scan is slowest.
+fn lookup_ids() -> impl Iterator<Item = TransactionId> {
+    (0..LOOKUPS).map(|i| TRANSACTION_COUNT - i * (TRANSACTION_COUNT / LOOKUPS / 2))
+}
+
+/// Removes the ledger at `path` if it's on disk. It isn't when the bank is
+/// built against the simulated filesystem, e.g. in a workspace build with the
+/// simulator.
+fn remove(path: &Path) {
+    if path.exists() {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+fn main() {
+    let db_path =
+        std::env::temp_dir().join(format!("dst_demo_id_index_bench_{}.db", std::process::id()));
+    remove(&db_path);
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    let path = db_path.clone();
+    let (indexed, linear) = runtime.block_on(async move {
+        let bank = LocalBank::open(&path).unwrap();
+        bank.import(ledger(), &Origin::local()).await.unwrap();
+
+        let start = Instant::now();
+        for id in lookup_ids() {
+            let transaction = bank.get_transaction(id).await.unwrap().unwrap();
+            assert_eq!(transaction.id, id);
+        }
+        let indexed = start.elapsed();
+
+        let transactions = bank.list_transactions().await.unwrap();
+        let start = Instant::now();
+        for id in lookup_ids() {
+            let transaction = transactions.iter().find(|x| x.id == id).cloned().unwrap();
+            assert_eq!(transaction.id, id);
+        }
+        let linear = start.elapsed();
+        drop(transactions);
+
+        (indexed, linear)
+    });
+
+    remove(&db_path);
+
+    println!(
+        "{LOOKUPS} lookups in {TRANSACTION_COUNT} transactions: indexed={indexed:?} linear={linear:?}"
+    );
+}
@@ -0,0 +1,139 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Compares reading terminated messages through a [`MessageBuffer`] with the
+//! `String` accumulation `read_message` used to do, feeding both the same
+//! bytes in [`READ_CHUNK_SIZE`] chunks the way they'd arrive from a
+//! connection.
+//!
+//! Run with `cargo bench -p dst_demo_server --bench read_message`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use dst_demo_server::message::{MessageBuffer, READ_CHUNK_SIZE, TERMINATOR};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The accumulation `read_message` did before it kept a [`MessageBuffer`]:
+/// each chunk is decoded into a new `String` and searched for a terminator,
+/// each complete message is cloned out of the accumulated one, and the
+/// leftover is searched again by the next call.
+fn legacy(message: &mut String, chunk: &[u8], messages: &mut usize) {
+    let value = String::from_utf8(chunk.to_vec()).unwrap();
+    message.push_str(&value);
+
+    let Some(index) = value.chars().position(|x| x == 0 as char) else {
+        return;
+    };
+    let mut remaining = message.split_off(message.len() - value.len() + index);
+    black_box(message.clone());
+    remaining.remove(0);
+    *message = remaining;
+    *messages += 1;
+
+    while let Some(index) = message.chars().position(|x| x == 0 as char) {
+        let mut remaining = message.split_off(index);
+        black_box(message.clone());
+        remaining.remove(0);
+        *message = remaining;
+        *messages += 1;
+    }
+}
+
+fn buffered(buffer: &mut MessageBuffer, chunk: &[u8], messages: &mut usize) {
+    buffer.extend(chunk);
+
+    while let Some(value) = buffer.next_message().unwrap() {
+        black_box(value);
+        *messages += 1;
+    }
+}
+
+fn stream(frames: &[String]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for frame in frames {
+        bytes.extend_from_slice(frame.as_bytes());
+        bytes.push(TERMINATOR);
+    }
+    bytes
+}
+
+/// Feeds `bytes` to `read` a chunk at a time, returning how many messages
+/// were read, how long it took, and how many allocations it made.
+fn measure<T: Default>(
+    bytes: &[u8],
+    read: impl Fn(&mut T, &[u8], &mut usize),
+) -> (usize, Duration, usize) {
+    let mut state = T::default();
+    let mut messages = 0;
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst);
+    let start = Instant::now();
+
+    for chunk in bytes.chunks(READ_CHUNK_SIZE) {
+        read(&mut state, chunk, &mut messages);
+    }
+
+    let elapsed = start.elapsed();
+    (
+        messages,
+        elapsed,
+        ALLOCATIONS.load(Ordering::SeqCst) - allocations,
+    )
+}
+
+fn compare(name: &str, frames: &[String]) {
+    let bytes = stream(frames);
+
+    let (legacy_messages, legacy_elapsed, legacy_allocations) = measure::<String>(&bytes, legacy);
+    let (messages, elapsed, allocations) = measure::<MessageBuffer>(&bytes, buffered);
+
+    assert_eq!(legacy_messages, frames.len());
+    assert_eq!(messages, frames.len());
+
+    println!(
+        "{name}: {} messages, {} bytes\n  \
+        legacy:   {legacy_elapsed:?} {legacy_allocations} allocations\n  \
+        buffered: {elapsed:?} {allocations} allocations",
+        frames.len(),
+        bytes.len(),
+    );
+}
+
+fn main() {
+    let small = (0..100_000)
+        .map(|x| format!("GET_TRANSACTION {x}"))
+        .collect::<Vec<_>>();
+    compare("100k small messages", &small);
+
+    let huge = (0..4)
+        .map(|x| format!("id={x} amount=$1.00\n").repeat(200_000))
+        .collect::<Vec<_>>();
+    compare("4 huge messages", &huge);
+}
@@ -0,0 +1,25 @@
+fn main() {
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .and_then(|x| String::from_utf8(x.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |x| x.trim().to_string());
+
+    let mut features = std::env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|x| x.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    println!("cargo:rustc-env=DST_DEMO_SERVER_GIT_HASH={git_hash}");
+    println!(
+        "cargo:rustc-env=DST_DEMO_SERVER_FEATURES={}",
+        features.join(",")
+    );
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+}
@@ -0,0 +1,322 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Serves the bank protocol in front of a ledger that only lives in memory,
+//! to show how the server can be embedded with a custom `Bank`.
+//!
+//! Everything is lost when the process exits.
+
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use dst_demo_server::{
+    Error,
+    bank::{
+        self, AuditAction, AuditRecord, Bank, BankAccountBalance, CreateTime, ImportError, Origin,
+        ReplicateError, StatementLine, Transaction, TransactionId,
+    },
+    search::TransactionFilter,
+};
+use rust_decimal::Decimal;
+use switchy::{
+    tcp::TcpListener,
+    unsync::{
+        sync::{RwLock, RwLockReadGuard},
+        util::CancellationToken,
+    },
+};
+
+#[derive(Debug, Clone, Default)]
+struct InMemoryBank {
+    transactions: Arc<RwLock<Vec<Transaction>>>,
+    audit: Arc<RwLock<Vec<AuditRecord>>>,
+    read_only: Arc<AtomicBool>,
+}
+
+fn millis_since_epoch() -> CreateTime {
+    let millis = switchy::time::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    CreateTime::try_from(millis).unwrap()
+}
+
+/// Appends a transaction for each of `amounts` to `transactions`, returning
+/// the new ones
+fn append(transactions: &mut Vec<Transaction>, amounts: Vec<Decimal>) -> Vec<Transaction> {
+    let first = transactions.len();
+
+    for amount in amounts {
+        let transaction = Transaction {
+            id: transactions.last().map_or(1, |x| x.id + 1),
+            seq: transactions.last().map_or(1, |x| x.seq + 1),
+            amount,
+            created_at: millis_since_epoch(),
+        };
+        transactions.push(transaction);
+    }
+
+    transactions[first..].to_vec()
+}
+
+impl InMemoryBank {
+    /// Appends a transaction for each of `amounts`, along with the audit
+    /// record `audit` makes for each of them, while holding the ledger's lock
+    async fn commit(
+        &self,
+        amounts: Vec<Decimal>,
+        audit: impl Fn(&Transaction) -> AuditRecord + Send,
+    ) -> Result<Vec<Transaction>, bank::Error> {
+        let mut transactions = self.transactions.write().await;
+
+        if self.is_read_only() {
+            return Err(bank::Error::ReadOnly);
+        }
+
+        let created = append(&mut transactions, amounts);
+        self.audit.write().await.extend(created.iter().map(audit));
+
+        drop(transactions);
+
+        Ok(created)
+    }
+}
+
+#[async_trait]
+impl Bank for InMemoryBank {
+    async fn list_transactions(&self) -> Result<RwLockReadGuard<Vec<Transaction>>, bank::Error> {
+        Ok(self.transactions.read().await)
+    }
+
+    async fn get_transaction(&self, id: TransactionId) -> Result<Option<Transaction>, bank::Error> {
+        Ok(self
+            .transactions
+            .read()
+            .await
+            .iter()
+            .find(|x| x.id == id)
+            .cloned())
+    }
+
+    async fn create_transaction(
+        &self,
+        amount: Decimal,
+        origin: &Origin,
+    ) -> Result<Transaction, bank::Error> {
+        let mut created = self
+            .commit(vec![amount], |x| {
+                AuditRecord::new(
+                    origin,
+                    AuditAction::CreateTransaction,
+                    format!("amount={amount}"),
+                    x,
+                )
+            })
+            .await?;
+        Ok(created.remove(0))
+    }
+
+    async fn create_transactions_atomic(
+        &self,
+        amounts: Vec<Decimal>,
+        origin: &Origin,
+    ) -> Result<Vec<Transaction>, bank::Error> {
+        self.commit(amounts, |x| {
+            AuditRecord::new(
+                origin,
+                AuditAction::CommitBatch,
+                format!("amount={}", x.amount),
+                x,
+            )
+        })
+        .await
+    }
+
+    async fn void_transaction(
+        &self,
+        id: TransactionId,
+        origin: &Origin,
+    ) -> Result<Option<Transaction>, bank::Error> {
+        let Some(existing) = self.get_transaction(id).await? else {
+            return Ok(None);
+        };
+
+        let mut created = self
+            .commit(vec![-existing.amount], |x| {
+                AuditRecord::new(origin, AuditAction::VoidTransaction, format!("id={id}"), x)
+            })
+            .await?;
+        Ok(Some(created.remove(0)))
+    }
+
+    async fn get_balance(&self) -> Result<BankAccountBalance, bank::Error> {
+        Ok(self
+            .transactions
+            .read()
+            .await
+            .iter()
+            .map(|x| x.amount)
+            .sum())
+    }
+
+    async fn statement(
+        &self,
+        range: RangeInclusive<TransactionId>,
+    ) -> Result<Vec<StatementLine>, bank::Error> {
+        let transactions = self.transactions.read().await;
+        let mut balance = Decimal::ZERO;
+        let mut lines = vec![];
+
+        for transaction in transactions.iter().take_while(|x| x.id <= *range.end()) {
+            balance += transaction.amount;
+            if range.contains(&transaction.id) {
+                lines.push(StatementLine {
+                    id: transaction.id,
+                    amount: transaction.amount,
+                    balance_after: balance,
+                });
+            }
+        }
+
+        drop(transactions);
+
+        Ok(lines)
+    }
+
+    async fn search(&self, filter: &TransactionFilter) -> Result<Vec<Transaction>, bank::Error> {
+        Ok(self
+            .transactions
+            .read()
+            .await
+            .iter()
+            .filter(|x| filter.matches(x))
+            .take(filter.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn export(&self) -> Result<Vec<Transaction>, bank::Error> {
+        Ok(self.transactions.read().await.clone())
+    }
+
+    async fn import(&self, imported: Vec<Transaction>, origin: &Origin) -> Result<(), ImportError> {
+        let mut transactions = self.transactions.write().await;
+
+        if self.is_read_only() {
+            return Err(bank::Error::ReadOnly.into());
+        }
+
+        if !transactions.is_empty() {
+            return Err(ImportError::NotEmpty(transactions.len()));
+        }
+
+        for (previous, transaction) in imported.iter().zip(imported.iter().skip(1)) {
+            if transaction.id <= previous.id {
+                return Err(ImportError::NonIncreasingId {
+                    previous: previous.id,
+                    id: transaction.id,
+                });
+            }
+            if transaction.seq <= previous.seq {
+                return Err(ImportError::NonIncreasingSeq {
+                    previous: previous.seq,
+                    id: transaction.id,
+                    seq: transaction.seq,
+                });
+            }
+        }
+
+        self.audit.write().await.extend(imported.iter().map(|x| {
+            AuditRecord::new(
+                origin,
+                AuditAction::ImportLedger,
+                format!("amount={}", x.amount),
+                x,
+            )
+        }));
+        *transactions = imported;
+
+        drop(transactions);
+
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    async fn set_read_only(&self, read_only: bool) -> Result<Option<TransactionId>, bank::Error> {
+        let transactions = self.transactions.write().await;
+        self.read_only.store(read_only, Ordering::SeqCst);
+        Ok(transactions.last().map(|x| x.id))
+    }
+
+    async fn apply_replicated(
+        &self,
+        transaction: Transaction,
+        origin: &Origin,
+    ) -> Result<(), ReplicateError> {
+        let mut transactions = self.transactions.write().await;
+
+        if !self.is_read_only() {
+            return Err(ReplicateError::NotReadOnly);
+        }
+
+        let expected = transactions.last().map_or(1, |x| x.id + 1);
+        if transaction.id < expected {
+            return Ok(());
+        }
+        if transaction.id != expected {
+            return Err(ReplicateError::Gap {
+                expected,
+                id: transaction.id,
+            });
+        }
+
+        self.audit.write().await.push(AuditRecord::new(
+            origin,
+            AuditAction::Replicate,
+            format!("amount={}", transaction.amount),
+            &transaction,
+        ));
+        transactions.push(transaction);
+
+        drop(transactions);
+
+        Ok(())
+    }
+
+    async fn tail_audit(&self, count: usize) -> Result<Vec<AuditRecord>, bank::Error> {
+        let audit = self.audit.read().await;
+        Ok(audit[audit.len().saturating_sub(count)..].to_vec())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+
+    let shutdown = CancellationToken::new();
+    ctrlc::set_handler({
+        let shutdown = shutdown.clone();
+        move || shutdown.cancel()
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let addr = std::env::var("ADDR").unwrap_or_else(|_| "0.0.0.0".to_string());
+    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
+
+    let runtime = switchy::unsync::runtime::Builder::new().build()?;
+
+    runtime.block_on(async move {
+        let listener = TcpListener::bind(format!("{addr}:{port}")).await?;
+        dst_demo_server::serve(listener, InMemoryBank::default(), shutdown).await
+    })
+}
@@ -0,0 +1,451 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use strum::IntoEnumIterator as _;
+use switchy::unsync::{inject_yields, io::AsyncRead, util::CancellationToken};
+
+use crate::{
+    Error, ServerAction,
+    bank::{Bank, Origin},
+    config::SharedConfig,
+    message::MessageBuffer,
+    protocol::ResponseWriter,
+    read_message,
+};
+
+/// A value the server asks the client for before handling an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prompt {
+    /// The field named in the protocol v2 `PROMPT` line
+    pub field: &'static str,
+    /// The text a protocol v1 client is shown
+    pub text: &'static str,
+}
+
+impl Prompt {
+    #[must_use]
+    pub const fn new(field: &'static str, text: &'static str) -> Self {
+        Self { field, text }
+    }
+}
+
+/// Everything an action may need from the connection it was received on
+pub struct ActionContext<'a> {
+    pub bank: &'a dyn Bank,
+    pub config: &'a SharedConfig,
+    /// Cancelled to shut the server down
+    pub shutdown: &'a CancellationToken,
+    /// The identity the client sent in its preamble, if any
+    pub peer_identity: Option<&'a str>,
+    /// Who the connection's state-changing operations are audited as
+    pub origin: &'a Origin,
+    pub registry: &'a Registry,
+    /// The amounts queued since `BEGIN_BATCH`, if a batch is open
+    pub batch: &'a mut Option<Vec<Decimal>>,
+    /// The bytes read past the last message
+    pub message: &'a mut MessageBuffer,
+    pub writer: &'a mut ResponseWriter,
+    pub reader: &'a mut (dyn AsyncRead + Unpin + Send),
+}
+
+#[async_trait]
+pub trait ActionHandler: Send + Sync {
+    /// The values the client is prompted for, in order, before the action is
+    /// handled
+    fn prompts(&self) -> &[Prompt] {
+        &[]
+    }
+
+    /// Handles the action. `args` holds the client's answer to each of
+    /// [`Self::prompts`], in the same order.
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error>;
+}
+
+/// The handler of every `ServerAction`, built once when the server starts
+pub struct Registry {
+    handlers: BTreeMap<ServerAction, Box<dyn ActionHandler>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handlers: ServerAction::iter().map(|x| (x, handler(x))).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn prompts(&self, action: ServerAction) -> &[Prompt] {
+        self.handlers[&action].prompts()
+    }
+
+    /// One line per action, in the form `ACTION field...`, listing the fields
+    /// the action prompts for
+    #[must_use]
+    pub fn help(&self) -> Vec<String> {
+        self.handlers
+            .iter()
+            .map(|(action, handler)| {
+                std::iter::once(action.as_ref())
+                    .chain(handler.prompts().iter().map(|x| x.field))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    /// Sends each of the action's prompts, reading the client's answer to each
+    /// before sending the next, and then handles the action with the answers.
+    ///
+    /// # Errors
+    ///
+    /// * If the client disconnects before answering every prompt
+    /// * If the action fails to be handled
+    #[inject_yields]
+    pub async fn dispatch(
+        &self,
+        action: ServerAction,
+        ctx: &mut ActionContext<'_>,
+    ) -> Result<(), Error> {
+        let handler = &self.handlers[&action];
+        let mut args = Vec::with_capacity(handler.prompts().len());
+
+        for prompt in handler.prompts() {
+            ctx.writer.prompt(prompt.field, prompt.text).await?;
+            let Some(arg) = read_message(ctx.message, &mut ctx.reader).await? else {
+                use std::io::{Error, ErrorKind};
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("{action}: No {} received from TCP client", prompt.field),
+                )
+                .into());
+            };
+            args.push(arg);
+        }
+
+        handler.handle(ctx, args).await
+    }
+}
+
+fn handler(action: ServerAction) -> Box<dyn ActionHandler> {
+    match action {
+        ServerAction::Health => Box::new(Health),
+        ServerAction::Help => Box::new(Help),
+        ServerAction::ListTransactions => Box::new(ListTransactions),
+        ServerAction::SearchTransactions => Box::new(SearchTransactions),
+        ServerAction::GetTransaction => Box::new(GetTransaction),
+        ServerAction::CreateTransaction => Box::new(CreateTransaction),
+        ServerAction::VoidTransaction => Box::new(VoidTransaction),
+        ServerAction::BeginBatch => Box::new(BeginBatch),
+        ServerAction::CommitBatch => Box::new(CommitBatch),
+        ServerAction::RollbackBatch => Box::new(RollbackBatch),
+        ServerAction::GetBalance => Box::new(GetBalance),
+        ServerAction::GetStatement => Box::new(GetStatement),
+        ServerAction::ExportLedger => Box::new(ExportLedger),
+        ServerAction::TailAudit => Box::new(TailAudit),
+        ServerAction::ImportLedger => Box::new(ImportLedger),
+        ServerAction::Replicate => Box::new(Replicate),
+        ServerAction::Promote => Box::new(Promote),
+        ServerAction::Demote => Box::new(Demote),
+        ServerAction::ReloadConfig => Box::new(ReloadConfig),
+        ServerAction::ListConnections => Box::new(ListConnections),
+        ServerAction::Version => Box::new(Version),
+        ServerAction::Close => Box::new(Close),
+        ServerAction::Exit => Box::new(Exit),
+    }
+}
+
+const TRANSACTION_ID: Prompt = Prompt::new("transaction_id", "Enter the transaction ID:");
+
+struct Health;
+
+#[async_trait]
+impl ActionHandler for Health {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::health(ctx.writer).await
+    }
+}
+
+struct Help;
+
+#[async_trait]
+impl ActionHandler for Help {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        ctx.writer.ok_lines(ctx.registry.help().into_iter()).await
+    }
+}
+
+struct ListTransactions;
+
+#[async_trait]
+impl ActionHandler for ListTransactions {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        let budget = ctx.config.get().response_budget_bytes;
+        crate::list_transactions(ctx.bank, budget, ctx.writer).await
+    }
+}
+
+struct SearchTransactions;
+
+#[async_trait]
+impl ActionHandler for SearchTransactions {
+    fn prompts(&self) -> &[Prompt] {
+        const { &[Prompt::new("filter", "Enter the search filter:")] }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::search_transactions(ctx.bank, &args[0], ctx.writer).await
+    }
+}
+
+struct GetTransaction;
+
+#[async_trait]
+impl ActionHandler for GetTransaction {
+    fn prompts(&self) -> &[Prompt] {
+        &[TRANSACTION_ID]
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::get_transaction(ctx.bank, &args[0], ctx.writer).await
+    }
+}
+
+struct CreateTransaction;
+
+#[async_trait]
+impl ActionHandler for CreateTransaction {
+    fn prompts(&self) -> &[Prompt] {
+        const { &[Prompt::new("amount", "Enter the transaction amount:")] }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::create_transaction(
+            ctx.bank,
+            ctx.origin,
+            ctx.batch.as_mut(),
+            &args[0],
+            ctx.writer,
+        )
+        .await
+    }
+}
+
+struct VoidTransaction;
+
+#[async_trait]
+impl ActionHandler for VoidTransaction {
+    fn prompts(&self) -> &[Prompt] {
+        &[TRANSACTION_ID]
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::void_transaction(ctx.bank, ctx.origin, &args[0], ctx.writer).await
+    }
+}
+
+struct BeginBatch;
+
+#[async_trait]
+impl ActionHandler for BeginBatch {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::begin_batch(ctx.batch, ctx.writer).await
+    }
+}
+
+struct CommitBatch;
+
+#[async_trait]
+impl ActionHandler for CommitBatch {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::commit_batch(ctx.bank, ctx.origin, ctx.batch, ctx.writer).await
+    }
+}
+
+struct RollbackBatch;
+
+#[async_trait]
+impl ActionHandler for RollbackBatch {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::rollback_batch(ctx.batch, ctx.writer).await
+    }
+}
+
+struct GetBalance;
+
+#[async_trait]
+impl ActionHandler for GetBalance {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::get_balance(ctx.bank, ctx.writer).await
+    }
+}
+
+struct GetStatement;
+
+#[async_trait]
+impl ActionHandler for GetStatement {
+    fn prompts(&self) -> &[Prompt] {
+        const {
+            &[
+                Prompt::new("start_transaction_id", "Enter the start transaction ID:"),
+                Prompt::new("end_transaction_id", "Enter the end transaction ID:"),
+            ]
+        }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        let budget = ctx.config.get().response_budget_bytes;
+        crate::get_statement(ctx.bank, &args[0], &args[1], budget, ctx.writer).await
+    }
+}
+
+struct ExportLedger;
+
+#[async_trait]
+impl ActionHandler for ExportLedger {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        let budget = ctx.config.get().response_budget_bytes;
+        crate::export_ledger(ctx.bank, budget, ctx.writer).await
+    }
+}
+
+struct TailAudit;
+
+#[async_trait]
+impl ActionHandler for TailAudit {
+    fn prompts(&self) -> &[Prompt] {
+        const {
+            &[Prompt::new(
+                "count",
+                "Enter the number of audit records to show:",
+            )]
+        }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        let budget = ctx.config.get().response_budget_bytes;
+        crate::tail_audit(ctx.bank, &args[0], budget, ctx.writer).await
+    }
+}
+
+struct ImportLedger;
+
+#[async_trait]
+impl ActionHandler for ImportLedger {
+    fn prompts(&self) -> &[Prompt] {
+        const {
+            &[Prompt::new(
+                "ledger",
+                "Enter the ledger to import (JSON lines):",
+            )]
+        }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::import_ledger(ctx.bank, ctx.origin, &args[0], ctx.writer).await
+    }
+}
+
+/// Has no prompts since the replicated transactions are streamed until the
+/// primary disconnects
+struct Replicate;
+
+#[async_trait]
+impl ActionHandler for Replicate {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::replicate(
+            ctx.bank,
+            ctx.origin,
+            ctx.message,
+            ctx.writer,
+            &mut ctx.reader,
+        )
+        .await
+    }
+}
+
+struct Promote;
+
+#[async_trait]
+impl ActionHandler for Promote {
+    fn prompts(&self) -> &[Prompt] {
+        const {
+            &[Prompt::new(
+                "last_transaction_id",
+                "Enter the last transaction ID the replica must have:",
+            )]
+        }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::promote(ctx.bank, &args[0], ctx.writer).await
+    }
+}
+
+struct Demote;
+
+#[async_trait]
+impl ActionHandler for Demote {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::demote(ctx.bank, ctx.writer).await
+    }
+}
+
+struct ReloadConfig;
+
+#[async_trait]
+impl ActionHandler for ReloadConfig {
+    fn prompts(&self) -> &[Prompt] {
+        const { &[Prompt::new("config", "Enter the config overrides (JSON):")] }
+    }
+
+    async fn handle(&self, ctx: &mut ActionContext<'_>, args: Vec<String>) -> Result<(), Error> {
+        crate::reload_config(ctx.config, &args[0], ctx.writer).await
+    }
+}
+
+struct ListConnections;
+
+#[async_trait]
+impl ActionHandler for ListConnections {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::list_connections(ctx.writer).await
+    }
+}
+
+struct Version;
+
+#[async_trait]
+impl ActionHandler for Version {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        crate::version(ctx.writer).await
+    }
+}
+
+/// The connection is closed once the action is handled, see
+/// [`ServerAction::closes_connection`]
+struct Close;
+
+#[async_trait]
+impl ActionHandler for Close {
+    async fn handle(&self, _ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct Exit;
+
+#[async_trait]
+impl ActionHandler for Exit {
+    async fn handle(&self, ctx: &mut ActionContext<'_>, _args: Vec<String>) -> Result<(), Error> {
+        ctx.shutdown.cancel();
+        Ok(())
+    }
+}
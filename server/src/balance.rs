@@ -0,0 +1,75 @@
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicBool, Ordering},
+};
+
+use rust_decimal_macros::dec;
+
+use crate::bank::{BankAccountBalance, Transaction};
+
+/// Whether every `get_balance` also recomputes the balance from the ledger
+/// and asserts that it matches the cache. Defaults to `BALANCE_CHECK`.
+static CHECK: LazyLock<AtomicBool> = LazyLock::new(|| {
+    AtomicBool::new(std::env::var("BALANCE_CHECK").is_ok_and(|x| x == "1" || x == "true"))
+});
+
+/// Whether the balance consistency check is enabled for this process.
+#[must_use]
+pub fn check_enabled() -> bool {
+    CHECK.load(Ordering::SeqCst)
+}
+
+/// Enables or disables the balance consistency check for this process,
+/// overriding `BALANCE_CHECK`.
+pub fn set_check_enabled(enabled: bool) {
+    CHECK.store(enabled, Ordering::SeqCst);
+}
+
+/// The running total of the ledger, kept up to date in the same critical
+/// section as each commit so reading the balance doesn't have to walk every
+/// transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceCache {
+    total: BankAccountBalance,
+}
+
+impl BalanceCache {
+    /// Computes the balance from scratch, e.g. when the ledger is loaded or
+    /// replaced by an import. It starts from `0.0` like the uncached
+    /// balance did, so an empty ledger still answers `OK $0.0`.
+    #[must_use]
+    pub fn recompute(transactions: &[Transaction]) -> Self {
+        Self {
+            total: transactions
+                .iter()
+                .fold(dec!(0.0), |balance, x| balance + x.amount),
+        }
+    }
+
+    /// Applies a transaction that was just committed to the ledger.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        self.total += transaction.amount;
+    }
+
+    #[must_use]
+    pub const fn get(&self) -> BankAccountBalance {
+        self.total
+    }
+
+    /// Recomputes the balance from `transactions` and asserts that it matches
+    /// the cache.
+    ///
+    /// # Panics
+    ///
+    /// * If the cached balance diverged from the ledger
+    pub fn assert_consistent(&self, transactions: &[Transaction]) {
+        let recomputed = Self::recompute(transactions).total;
+
+        assert!(
+            self.total == recomputed,
+            "cached balance=${} diverged from the ledger's balance=${recomputed} over {} transactions",
+            self.total,
+            transactions.len(),
+        );
+    }
+}
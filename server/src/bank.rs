@@ -1,9 +1,14 @@
 #![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
 
 use std::{
-    io::{Read as _, Write},
-    path::PathBuf,
-    sync::Arc,
+    io::{Read as _, Seek as _, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr as _,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::SystemTime,
 };
 
@@ -11,6 +16,7 @@ use async_trait::async_trait;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
 use switchy::{
     fs::sync::{File, OpenOptions},
     unsync::{
@@ -19,9 +25,21 @@ use switchy::{
     },
 };
 
+use crate::{
+    balance::{self, BalanceCache},
+    cancel_safety,
+    config::default_db_path,
+    hooks,
+    id_index::IdIndex,
+    search::TransactionFilter,
+};
+
 pub type TransactionId = i32;
 pub type BankAccountBalance = Decimal;
-pub type CreateTime = u64;
+/// Milliseconds since the epoch
+pub type CreateTime = i64;
+/// The order in which transactions were committed to the ledger
+pub type Sequence = u64;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -29,6 +47,42 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error("The bank is read-only")]
+    ReadOnly,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("Can't import into a bank that already has {0} transactions")]
+    NotEmpty(usize),
+    #[error("Transaction ids must be strictly increasing, but id={id} follows id={previous}")]
+    NonIncreasingId {
+        previous: TransactionId,
+        id: TransactionId,
+    },
+    #[error(
+        "Transaction seqs must be strictly increasing, but id={id} seq={seq} follows seq={previous}"
+    )]
+    NonIncreasingSeq {
+        previous: Sequence,
+        id: TransactionId,
+        seq: Sequence,
+    },
+    #[error(transparent)]
+    Bank(#[from] Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplicateError {
+    #[error("Only a read-only bank can apply replicated transactions")]
+    NotReadOnly,
+    #[error("Expected replicated transaction id={expected}, instead got id={id}")]
+    Gap {
+        expected: TransactionId,
+        id: TransactionId,
+    },
+    #[error(transparent)]
+    Bank(#[from] Error),
 }
 
 #[async_trait]
@@ -48,31 +102,170 @@ pub trait Bank: Send + Sync {
     /// # Errors
     ///
     /// * If the `Bank` implementation fails to create the `Transaction`
-    async fn create_transaction(&self, amount: Decimal) -> Result<Transaction, Error>;
+    async fn create_transaction(
+        &self,
+        amount: Decimal,
+        origin: &Origin,
+    ) -> Result<Transaction, Error>;
+
+    /// Creates a `Transaction` for each of the `amounts` with contiguous ids,
+    /// either committing all of them or none.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to create the `Transaction`s
+    async fn create_transactions_atomic(
+        &self,
+        amounts: Vec<Decimal>,
+        origin: &Origin,
+    ) -> Result<Vec<Transaction>, Error>;
 
     /// # Errors
     ///
     /// * If the `Bank` implementation fails to void the `Transaction`
-    async fn void_transaction(&self, id: TransactionId) -> Result<Option<Transaction>, Error>;
+    async fn void_transaction(
+        &self,
+        id: TransactionId,
+        origin: &Origin,
+    ) -> Result<Option<Transaction>, Error>;
 
     /// # Errors
     ///
     /// * If the `Bank` implementation fails to get the balance
     async fn get_balance(&self) -> Result<BankAccountBalance, Error>;
+
+    /// Returns each transaction in `range` along with the balance after
+    /// applying it, in seq order.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to compute the statement
+    async fn statement(
+        &self,
+        range: RangeInclusive<TransactionId>,
+    ) -> Result<Vec<StatementLine>, Error>;
+
+    /// Returns the `Transaction`s that match `filter`, in id order, stopping
+    /// once the filter's limit is reached.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to read the `Transaction`s
+    async fn search(&self, filter: &TransactionFilter) -> Result<Vec<Transaction>, Error>;
+
+    /// Returns a copy of every `Transaction` in the ledger, in id order.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to read the `Transaction`s
+    async fn export(&self) -> Result<Vec<Transaction>, Error>;
+
+    /// Replaces the ledger of an empty bank with `transactions`, both in
+    /// memory and in the persisted file. Nothing is applied unless every
+    /// transaction is valid.
+    ///
+    /// # Errors
+    ///
+    /// * If the bank already has transactions
+    /// * If the transaction ids aren't strictly increasing
+    /// * If the transaction seqs aren't strictly increasing
+    /// * If the `Bank` implementation fails to persist the `Transaction`s
+    async fn import(
+        &self,
+        transactions: Vec<Transaction>,
+        origin: &Origin,
+    ) -> Result<(), ImportError>;
+
+    /// Whether the bank rejects new transactions, e.g. because it's a replica.
+    fn is_read_only(&self) -> bool;
+
+    /// Starts or stops rejecting new transactions, returning the id of the
+    /// last transaction in the ledger. Every transaction that was already
+    /// being created is in the ledger by the time this returns.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to read the `Transaction`s
+    async fn set_read_only(&self, read_only: bool) -> Result<Option<TransactionId>, Error>;
+
+    /// Applies a transaction replicated from the primary. Transactions that
+    /// were already applied are ignored, so the primary can safely resend
+    /// them after reconnecting.
+    ///
+    /// # Errors
+    ///
+    /// * If the bank isn't read-only
+    /// * If the transaction doesn't immediately follow the last one in the ledger
+    /// * If the `Bank` implementation fails to persist the `Transaction`
+    async fn apply_replicated(
+        &self,
+        transaction: Transaction,
+        origin: &Origin,
+    ) -> Result<(), ReplicateError>;
+
+    /// Returns the last `count` records of the audit log, oldest first.
+    /// Every state-changing operation above records who asked for it, and
+    /// which transactions it added, in the same commit.
+    ///
+    /// # Errors
+    ///
+    /// * If the `Bank` implementation fails to read the audit log
+    async fn tail_audit(&self, count: usize) -> Result<Vec<AuditRecord>, Error>;
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredTransaction")]
 pub struct Transaction {
     pub id: TransactionId,
+    pub seq: Sequence,
     pub amount: Decimal,
     pub created_at: CreateTime,
 }
 
+impl Transaction {
+    /// Converts a transaction from before `seq` existed, when `created_at`
+    /// was in seconds. Transactions were always committed in id order back
+    /// then, so the id doubles as the seq.
+    #[must_use]
+    pub fn from_legacy(id: TransactionId, amount: Decimal, created_at_seconds: i64) -> Self {
+        Self {
+            id,
+            seq: Sequence::try_from(id).unwrap_or_default(),
+            amount,
+            created_at: created_at_seconds.saturating_mul(1000),
+        }
+    }
+}
+
+/// The persisted form of a `Transaction`, which may predate `seq`
+#[derive(Deserialize)]
+struct StoredTransaction {
+    id: TransactionId,
+    seq: Option<Sequence>,
+    amount: Decimal,
+    created_at: CreateTime,
+}
+
+impl From<StoredTransaction> for Transaction {
+    fn from(value: StoredTransaction) -> Self {
+        let Some(seq) = value.seq else {
+            return Self::from_legacy(value.id, value.amount, value.created_at);
+        };
+
+        Self {
+            id: value.id,
+            seq,
+            amount: value.amount,
+            created_at: value.created_at,
+        }
+    }
+}
+
 impl std::fmt::Display for Transaction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "id={} created_at={} amount=${:.2}",
-            self.id, self.created_at, self.amount
+            "id={} seq={} created_at={} amount=${:.2}",
+            self.id, self.seq, self.created_at, self.amount
         ))
     }
 }
@@ -94,54 +287,431 @@ pub enum TransactionFromStrError {
 impl std::str::FromStr for Transaction {
     type Err = TransactionFromStrError;
 
+    /// Also parses the format from before `seq` existed, where `created_at`
+    /// was in seconds.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut components = s.split(' ');
+        let mut components = s.split(' ').peekable();
 
         let id = components
             .next()
+            .and_then(|x| x.strip_prefix("id="))
             .ok_or(TransactionFromStrError::MissingId)?;
-        let id = &id["id=".len()..];
         let id = id.parse::<TransactionId>()?;
 
+        let seq = components
+            .next_if(|x| x.starts_with("seq="))
+            .map(|x| x["seq=".len()..].parse::<Sequence>())
+            .transpose()?;
+
         let created_at = components
             .next()
+            .and_then(|x| x.strip_prefix("created_at="))
             .ok_or(TransactionFromStrError::MissingCreatedAt)?;
-        let created_at = &created_at["created_at=".len()..];
         let created_at = created_at.parse::<CreateTime>()?;
 
         let amount = components
             .next()
-            .ok_or(TransactionFromStrError::MissingCreatedAt)?;
-        let amount = &amount["amount=$".len()..];
+            .and_then(|x| x.strip_prefix("amount=$"))
+            .ok_or(TransactionFromStrError::MissingAmount)?;
         let amount = Decimal::from_str(amount)?;
 
+        let Some(seq) = seq else {
+            return Ok(Self::from_legacy(id, amount, created_at));
+        };
+
         Ok(Self {
             id,
+            seq,
             amount,
             created_at,
         })
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum AmountError {
+    #[error("Invalid amount '{0}'")]
+    Invalid(String),
+    #[error("Invalid amount '{0}': more than 2 decimal places")]
+    TooPrecise(String),
+    #[error("Invalid amount: {0}")]
+    Decimal(#[from] rust_decimal::Error),
+}
+
+/// Parses a user entered amount, accepting currency formatting like
+/// `$1,234.56` or `-$5`, and normalizes it.
+///
+/// # Errors
+///
+/// * If the input is not a valid decimal once the formatting is stripped
+/// * If the amount has more than 2 decimal places, since the bank can't represent fractions of a cent
+pub fn parse_amount(input: &str) -> Result<Decimal, AmountError> {
+    let input = input.trim();
+    let (negative, rest) = input
+        .strip_prefix('-')
+        .map_or((false, input), |rest| (true, rest));
+    let rest = rest.strip_prefix('$').unwrap_or(rest);
+
+    if negative && rest.starts_with(['-', '+']) {
+        return Err(AmountError::Invalid(input.to_string()));
+    }
+
+    let amount = Decimal::from_str(&rest.replace(',', ""))?.normalize();
+
+    if amount.scale() > 2 {
+        return Err(AmountError::TooPrecise(input.to_string()));
+    }
+
+    Ok(if negative { -amount } else { amount })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementLine {
+    pub id: TransactionId,
+    pub amount: Decimal,
+    pub balance_after: BankAccountBalance,
+}
+
+impl std::fmt::Display for StatementLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "id={} amount=${} balance_after=${}",
+            self.id, self.amount, self.balance_after
+        ))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatementLineFromStrError {
+    #[error("Missing id")]
+    MissingId,
+    #[error("Missing amount")]
+    MissingAmount,
+    #[error("Missing balance_after")]
+    MissingBalanceAfter,
+    #[error(transparent)]
+    ParseInt(#[from] std::num::ParseIntError),
+    #[error(transparent)]
+    FromStrDecimal(#[from] rust_decimal::Error),
+}
+
+impl std::str::FromStr for StatementLine {
+    type Err = StatementLineFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s.split(' ');
+
+        let id = components
+            .next()
+            .and_then(|x| x.strip_prefix("id="))
+            .ok_or(StatementLineFromStrError::MissingId)?;
+        let id = id.parse::<TransactionId>()?;
+
+        let amount = components
+            .next()
+            .and_then(|x| x.strip_prefix("amount=$"))
+            .ok_or(StatementLineFromStrError::MissingAmount)?;
+        let amount = Decimal::from_str(amount)?;
+
+        let balance_after = components
+            .next()
+            .and_then(|x| x.strip_prefix("balance_after=$"))
+            .ok_or(StatementLineFromStrError::MissingBalanceAfter)?;
+        let balance_after = Decimal::from_str(balance_after)?;
+
+        Ok(Self {
+            id,
+            amount,
+            balance_after,
+        })
+    }
+}
+
+/// Who asked for a state-changing operation, as recorded in its audit
+/// records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The peer address of the connection the operation was received on
+    pub peer: String,
+}
+
+impl Origin {
+    #[must_use]
+    pub fn new(peer: impl Into<String>) -> Self {
+        Self { peer: peer.into() }
+    }
+
+    /// An operation that wasn't received on a connection, like seeding the
+    /// ledger before the server starts
+    #[must_use]
+    pub fn local() -> Self {
+        Self::new("local")
+    }
+}
+
+/// The state-changing operation an [`AuditRecord`] was written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, AsRefStr)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditAction {
+    CreateTransaction,
+    VoidTransaction,
+    CommitBatch,
+    ImportLedger,
+    Replicate,
+    SeedHistory,
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// A single entry of the [`AuditLog`], written for each transaction a
+/// state-changing operation added to the ledger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the operation was committed, in milliseconds since the epoch
+    pub at: CreateTime,
+    pub peer: String,
+    pub action: AuditAction,
+    /// The operation's arguments, e.g. `amount=12.34` or the `id=3` of the
+    /// transaction a void voided
+    pub arguments: String,
+    /// The id of the transaction the operation added
+    pub transaction_id: TransactionId,
+}
+
+impl AuditRecord {
+    /// A record of `origin` adding `transaction` through `action`, dated now
+    ///
+    /// # Panics
+    ///
+    /// * If the clock is before the epoch
+    #[must_use]
+    pub fn new(
+        origin: &Origin,
+        action: AuditAction,
+        arguments: impl Into<String>,
+        transaction: &Transaction,
+    ) -> Self {
+        Self {
+            at: millis_since_epoch(),
+            peer: origin.peer.clone(),
+            action,
+            arguments: arguments.into(),
+            transaction_id: transaction.id,
+        }
+    }
+}
+
+/// Where the audit log of the ledger at `db_path` is kept, next to it
+#[must_use]
+pub fn audit_path(db_path: impl AsRef<Path>) -> PathBuf {
+    db_path.as_ref().with_extension("audit")
+}
+
+/// An append-only log of every state-changing operation on a ledger, kept in
+/// its own file as JSON lines, separate from the ledger itself.
+///
+/// The records are kept in memory too, so they can be read back without
+/// going to the file.
+pub struct AuditLog {
+    file: File,
+    records: Vec<AuditRecord>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, creating it if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// * If the file fails to be opened or read
+    /// * If an existing record is invalid
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let records = contents
+            .split('\n')
+            .filter(|x| !x.is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<AuditRecord>, _>>()?;
+
+        Ok(Self { file, records })
+    }
+
+    /// Replaces the audit log at `path` with `records`.
+    ///
+    /// # Errors
+    ///
+    /// * If the file fails to be written
+    pub fn replace(path: impl AsRef<Path>, records: &[AuditRecord]) -> Result<(), Error> {
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path.as_ref())?
+            .write_all(serialize_records(records)?.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Appends `records` to the file with a single write, and then to the
+    /// ones in memory. Nothing is appended in memory if the write fails.
+    ///
+    /// # Errors
+    ///
+    /// * If the records fail to be written
+    pub fn append(&mut self, records: Vec<AuditRecord>) -> Result<(), Error> {
+        self.file
+            .write_all(serialize_records(&records)?.as_bytes())?;
+        self.records.extend(records);
+
+        Ok(())
+    }
+
+    /// Every record, oldest first
+    #[must_use]
+    pub fn records(&self) -> &[AuditRecord] {
+        &self.records
+    }
+
+    /// The last `count` records, oldest first
+    #[must_use]
+    pub fn tail(&self, count: usize) -> &[AuditRecord] {
+        &self.records[self.records.len().saturating_sub(count)..]
+    }
+}
+
+fn serialize_records(records: &[AuditRecord]) -> Result<String, serde_json::Error> {
+    let mut serialized = String::new();
+    for record in records {
+        serialized.push_str(&serde_json::to_string(record)?);
+        serialized.push('\n');
+    }
+    Ok(serialized)
+}
+
+/// The current time in milliseconds since the epoch
+///
+/// # Panics
+///
+/// * If the clock is before the epoch
+fn millis_since_epoch() -> CreateTime {
+    let millis = switchy::time::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    CreateTime::try_from(millis).unwrap()
+}
+
+/// Dates and sequences a new transaction with the given id, to be appended
+/// after `last_transaction`.
+///
+/// A void passes the `created_at` of the transaction it voids as
+/// `not_before`, and is dated at the latest of now, `not_before`, and
+/// `last_transaction.created_at`, so it's never dated before the transaction
+/// it voids, even if the clock is behind the ledger.
+///
+/// # Panics
+///
+/// * If the clock is before the epoch
+/// * If `id` doesn't immediately follow `last_transaction`
+fn new_transaction(
+    id: TransactionId,
+    amount: Decimal,
+    last_transaction: Option<&Transaction>,
+    not_before: Option<CreateTime>,
+) -> Transaction {
+    let mut transaction = Transaction {
+        id,
+        seq: last_transaction.map_or(1, |x| x.seq + 1),
+        amount,
+        created_at: millis_since_epoch(),
+    };
+    assert!(
+        transaction.created_at > 0,
+        "created_at={} must be > 0",
+        transaction.created_at
+    );
+    if let Some(last_transaction) = last_transaction {
+        // The persisted ledger may come from a run with a clock that was
+        // ahead of this one, e.g. after restarting with a different wall
+        // clock. The ledger is ordered by seq, so created_at is allowed to go
+        // backwards, except for a void
+        if last_transaction.created_at > transaction.created_at {
+            log::warn!(
+                "new_transaction: clock is behind the ledger, dating id={id} seq={} at created_at={} after created_at={}",
+                transaction.seq,
+                transaction.created_at,
+                last_transaction.created_at,
+            );
+            if not_before.is_some() {
+                transaction.created_at = last_transaction.created_at;
+            }
+        }
+        assert!(
+            transaction.id == last_transaction.id + 1,
+            "expected id to be least transaction.id + 1 last_transaction.id={} to transaction_id={}",
+            last_transaction.id,
+            transaction.id,
+        );
+    }
+    if let Some(not_before) = not_before
+        && not_before > transaction.created_at
+    {
+        log::warn!(
+            "new_transaction: clock is behind the voided transaction, dating id={id} at created_at={not_before} instead of created_at={}",
+            transaction.created_at,
+        );
+        transaction.created_at = not_before;
+    }
+
+    transaction
+}
+
 #[derive(Clone)]
 pub struct LocalBank {
     file: Arc<Mutex<File>>,
     transactions: Arc<RwLock<Vec<Transaction>>>,
+    index: Arc<RwLock<IdIndex>>,
     current_id: Arc<RwLock<TransactionId>>,
-    balance: Arc<RwLock<BankAccountBalance>>,
+    balance: Arc<RwLock<BalanceCache>>,
+    audit: Arc<Mutex<AuditLog>>,
+    read_only: Arc<AtomicBool>,
 }
 
 impl LocalBank {
+    /// Opens the ledger at the default path.
+    ///
     /// # Errors
     ///
     /// * If there is IO error reading existing transactions from the filesystem
     pub fn new() -> Result<Self, std::io::Error> {
+        Self::open(default_db_path())
+    }
+
+    /// Opens the ledger at `path`, along with its audit log next to it.
+    ///
+    /// # Errors
+    ///
+    /// * If there is IO error reading existing transactions or audit records
+    ///   from the filesystem
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
         let mut file = OpenOptions::new()
             .create(true)
             .read(true)
             .write(true)
             .truncate(false)
-            .open(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("transactions.db"))?;
+            .open(path.as_ref())?;
 
         let mut transactions = String::new();
         file.read_to_string(&mut transactions)?;
@@ -150,14 +720,199 @@ impl LocalBank {
             .filter(|x| !x.is_empty())
             .map(serde_json::from_str)
             .collect::<Result<Vec<Transaction>, _>>()?;
+        let balance = BalanceCache::recompute(&transactions);
+        let index = IdIndex::rebuild(&transactions);
+        let audit = AuditLog::open(audit_path(path))?;
 
         Ok(Self {
             file: Arc::new(Mutex::new(file)),
             current_id: Arc::new(RwLock::new(transactions.last().map_or(1, |x| x.id + 1))),
             transactions: Arc::new(RwLock::new(transactions)),
-            balance: Arc::new(RwLock::new(dec!(0.0))),
+            index: Arc::new(RwLock::new(index)),
+            balance: Arc::new(RwLock::new(balance)),
+            audit: Arc::new(Mutex::new(audit)),
+            read_only: Arc::new(AtomicBool::new(false)),
         })
     }
+
+    /// Replaces the ledger at `path` with a history of transactions with the
+    /// given amounts and creation times, as if they had been created one after
+    /// the other. Ids and seqs are assigned sequentially from 1, and the whole
+    /// ledger is written in a single pass. The audit log is replaced with a
+    /// `SEED_HISTORY` record for each of them.
+    ///
+    /// Returns the transactions that were written.
+    ///
+    /// # Errors
+    ///
+    /// * If the ledger or its audit log fails to be written
+    pub fn seed_history(
+        path: impl AsRef<Path>,
+        history: Vec<(Decimal, CreateTime)>,
+    ) -> Result<Vec<Transaction>, Error> {
+        log::debug!("seed_history: count={}", history.len());
+
+        let transactions = history
+            .into_iter()
+            .zip(1..)
+            .map(|((amount, created_at), id)| Transaction {
+                id,
+                seq: Sequence::try_from(id).unwrap_or_default(),
+                amount,
+                created_at,
+            })
+            .collect::<Vec<_>>();
+
+        let mut serialized = String::new();
+        for transaction in &transactions {
+            serialized.push_str(&serde_json::to_string(transaction)?);
+            serialized.push('\n');
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path.as_ref())?
+            .write_all(serialized.as_bytes())?;
+
+        let origin = Origin::local();
+        let records = transactions
+            .iter()
+            .map(|x| AuditRecord {
+                // Dated when the transaction was, as if it had been audited
+                // as it was created
+                at: x.created_at,
+                ..AuditRecord::new(
+                    &origin,
+                    AuditAction::SeedHistory,
+                    format!("amount={}", x.amount),
+                    x,
+                )
+            })
+            .collect::<Vec<_>>();
+        AuditLog::replace(audit_path(path), &records)?;
+
+        Ok(transactions)
+    }
+
+    /// Creates a single transaction for `amount`, audited as `action` with
+    /// `arguments` and dated no earlier than `not_before`.
+    #[inject_yields]
+    async fn commit(
+        &self,
+        amount: Decimal,
+        origin: &Origin,
+        action: AuditAction,
+        arguments: String,
+        not_before: Option<CreateTime>,
+    ) -> Result<Transaction, Error> {
+        // Acquire every lock up front so that nothing is mutated until there
+        // are no await points left. If the future is dropped while waiting on
+        // any of these, no state has been touched yet.
+        let mut current_id = self.current_id.write().await;
+        let mut transactions = self.transactions.write().await;
+        let mut index = self.index.write().await;
+        let mut balance = self.balance.write().await;
+        let mut file = self.file.lock().await;
+        let mut audit = self.audit.lock().await;
+
+        // Checked while holding the locks so that a transaction can't sneak
+        // in after set_read_only reported the last id
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        let transaction = new_transaction(*current_id, amount, transactions.last(), not_before);
+
+        let mut serialized = serde_json::to_string(&transaction)?;
+        serialized.push('\n');
+
+        hooks::delay_point("create_transaction::before_file_write").await;
+
+        let guard = cancel_safety::guard("create_transaction");
+
+        if let Err(e) = append(&mut file, &serialized) {
+            // Nothing has been applied in memory and the id hasn't been
+            // consumed, so the next transaction simply reuses it
+            guard.disarm();
+            return Err(e.into());
+        }
+
+        record(
+            &mut audit,
+            vec![AuditRecord::new(origin, action, arguments, &transaction)],
+        );
+
+        // Apply the transaction to the in-memory state without any further
+        // await points so it can't diverge from what was persisted
+        *current_id += 1;
+        index.insert(&transaction, transactions.len());
+        transactions.push(transaction.clone());
+        balance.apply(&transaction);
+        index.debug_assert_appended(&transactions, 1);
+
+        guard.disarm();
+
+        // Deliberately holds the locks a while longer when sim hooks are
+        // enabled. The transaction is already persisted and applied, so
+        // cancelling here can't leave them apart.
+        hooks::delay_point("create_transaction::after_file_write").await;
+
+        drop(audit);
+        drop(file);
+        drop(balance);
+        drop(index);
+        drop(transactions);
+        drop(current_id);
+
+        Ok(transaction)
+    }
+}
+
+/// Appends the audit records of a commit whose transactions were just
+/// written to the ledger, before anything else can be awaited.
+///
+/// The ledger is the source of truth, so a failed append doesn't undo the
+/// commit. It's logged instead, and shows up as a transaction without an
+/// audit record.
+fn record(audit: &mut AuditLog, records: Vec<AuditRecord>) {
+    if let Err(e) = audit.append(records) {
+        log::error!("failed to append to the audit log: {e:?}");
+    }
+}
+
+/// Appends `serialized` to the ledger with a single write.
+///
+/// If the write fails, the ledger is truncated back to where it was, so a
+/// partially written line doesn't keep it from being reopened.
+fn append(file: &mut File, serialized: &str) -> Result<(), std::io::Error> {
+    let len = file.stream_position()?;
+
+    if let Err(e) = file.write_all(serialized.as_bytes()) {
+        if let Err(e) = truncate(file, len) {
+            log::error!("failed to truncate the ledger after a failed write: {e:?}");
+        }
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Truncates the ledger back to `len` bytes, and moves back to its end.
+#[cfg(not(feature = "simulator"))]
+fn truncate(file: &mut File, len: u64) -> Result<(), std::io::Error> {
+    file.set_len(len)?;
+    file.seek(std::io::SeekFrom::Start(len))?;
+    Ok(())
+}
+
+/// A simulated file takes a write in full or not at all, so there's never
+/// a partially written line to truncate.
+#[cfg(feature = "simulator")]
+#[allow(clippy::unnecessary_wraps)]
+const fn truncate(_file: &mut File, _len: u64) -> Result<(), std::io::Error> {
+    Ok(())
 }
 
 #[inject_yields]
@@ -169,99 +924,390 @@ impl Bank for LocalBank {
 
     async fn get_transaction(&self, id: TransactionId) -> Result<Option<Transaction>, Error> {
         log::debug!("get_transaction: id={id}");
-        Ok(self
-            .transactions
-            .read()
-            .await
-            .iter()
-            .find(|x| x.id == id)
-            .cloned())
-    }
-
-    async fn create_transaction(&self, amount: Decimal) -> Result<Transaction, Error> {
-        log::debug!("create_transaction: amount={amount}");
-        let mut binding = self.current_id.write().await;
-        let id = *binding;
-        *binding += 1;
-        let now = switchy::time::now();
-        let seconds_since_epoch = now
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let transaction = Transaction {
-            id,
+        let transactions = self.transactions.read().await;
+        let index = self.index.read().await;
+        let transaction = index.get(id).map(|position| transactions[position].clone());
+
+        drop(index);
+        drop(transactions);
+
+        Ok(transaction)
+    }
+
+    async fn create_transaction(
+        &self,
+        amount: Decimal,
+        origin: &Origin,
+    ) -> Result<Transaction, Error> {
+        log::debug!("create_transaction: amount={amount} peer={}", origin.peer);
+        self.commit(
             amount,
-            created_at: seconds_since_epoch as CreateTime,
-        };
-        {
-            let binding = self.transactions.read().await;
-            if let Some(last_transaction) = binding.last() {
-                assert!(
-                    transaction.created_at >= last_transaction.created_at,
-                    "expected transaction.created_at={} >= last_transaction.created_at={}",
-                    transaction.created_at,
-                    last_transaction.created_at,
-                );
-                assert!(
-                    transaction.id == last_transaction.id + 1,
-                    "expected id to be least transaction.id + 1 last_transaction.id={} to transaction_id={}",
-                    last_transaction.id,
-                    transaction.id,
-                );
-            }
-            drop(binding);
-        }
-        assert!(
-            transaction.created_at > 0,
-            "created_at={} must be > 0",
-            transaction.created_at
+            origin,
+            AuditAction::CreateTransaction,
+            format!("amount={amount}"),
+            None,
+        )
+        .await
+    }
+
+    async fn create_transactions_atomic(
+        &self,
+        amounts: Vec<Decimal>,
+        origin: &Origin,
+    ) -> Result<Vec<Transaction>, Error> {
+        log::debug!(
+            "create_transactions_atomic: count={} peer={}",
+            amounts.len(),
+            origin.peer
         );
-        assert!(
-            seconds_since_epoch >= transaction.created_at as u64,
-            "Time went backwards {now:?} seconds_since_epoch={seconds_since_epoch} created_at={}",
-            transaction.created_at,
+        // Same as create_transaction: take every lock before touching any state
+        let mut current_id = self.current_id.write().await;
+        let mut transactions = self.transactions.write().await;
+        let mut index = self.index.write().await;
+        let mut balance = self.balance.write().await;
+        let mut file = self.file.lock().await;
+        let mut audit = self.audit.lock().await;
+
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        let mut created: Vec<Transaction> = Vec::with_capacity(amounts.len());
+        for (id, amount) in (*current_id..).zip(amounts) {
+            let last_transaction = created.last().or_else(|| transactions.last());
+            let transaction = new_transaction(id, amount, last_transaction, None);
+            created.push(transaction);
+        }
+
+        let mut serialized = String::new();
+        for transaction in &created {
+            serialized.push_str(&serde_json::to_string(transaction)?);
+            serialized.push('\n');
+        }
+
+        hooks::delay_point("create_transactions_atomic::before_file_write").await;
+
+        let guard = cancel_safety::guard("create_transactions_atomic");
+
+        // The whole batch is persisted with a single write, so a failed write
+        // leaves none of it applied
+        if let Err(e) = append(&mut file, &serialized) {
+            guard.disarm();
+            return Err(e.into());
+        }
+
+        record(
+            &mut audit,
+            created
+                .iter()
+                .map(|x| {
+                    AuditRecord::new(
+                        origin,
+                        AuditAction::CommitBatch,
+                        format!("amount={}", x.amount),
+                        x,
+                    )
+                })
+                .collect(),
         );
 
-        let mut serialized = serde_json::to_string(&transaction)?;
-        serialized.push('\n');
-        self.file.lock().await.write_all(serialized.as_bytes())?;
+        *current_id += TransactionId::try_from(created.len()).unwrap();
+        for transaction in &created {
+            index.insert(transaction, transactions.len());
+            balance.apply(transaction);
+            transactions.push(transaction.clone());
+        }
+        index.debug_assert_appended(&transactions, created.len());
 
-        *self.balance.write().await += transaction.amount;
+        guard.disarm();
 
-        self.transactions.write().await.push(transaction.clone());
-        drop(binding);
+        hooks::delay_point("create_transactions_atomic::after_file_write").await;
 
-        Ok(transaction)
+        drop(audit);
+        drop(file);
+        drop(balance);
+        drop(index);
+        drop(transactions);
+        drop(current_id);
+
+        Ok(created)
+    }
+
+    async fn void_transaction(
+        &self,
+        id: TransactionId,
+        origin: &Origin,
+    ) -> Result<Option<Transaction>, Error> {
+        log::debug!("void_transaction: id={id} peer={}", origin.peer);
+        let Some(existing) = self.get_transaction(id).await? else {
+            return Ok(None);
+        };
+
+        hooks::delay_point("void_transaction::after_lookup").await;
+
+        let originally_created_at = existing.created_at;
+        let now = millis_since_epoch();
+        if originally_created_at > now {
+            log::warn!(
+                "void_transaction: id={id} was created in the future at created_at={originally_created_at} (now={now})"
+            );
+        }
+
+        // The void is committed after the transaction it voids, so it always
+        // gets a later seq and is dated no earlier, even if the clock went
+        // backwards since
+        let new_transaction = self
+            .commit(
+                -existing.amount,
+                origin,
+                AuditAction::VoidTransaction,
+                format!("id={id}"),
+                Some(originally_created_at),
+            )
+            .await?;
+
+        Ok(Some(new_transaction))
+    }
+
+    async fn get_balance(&self) -> Result<BankAccountBalance, Error> {
+        log::debug!("get_balance");
+
+        if balance::check_enabled() {
+            // Taken in the same order as the commits take them, so the ledger
+            // can't change while it's being compared against the cache
+            let transactions = self.transactions.read().await;
+            let balance = self.balance.read().await;
+            balance.assert_consistent(&transactions);
+            return Ok(balance.get());
+        }
+
+        Ok(self.balance.read().await.get())
     }
 
-    async fn void_transaction(&self, id: TransactionId) -> Result<Option<Transaction>, Error> {
-        log::debug!("void_transaction: id={id}");
-        let Some(existing) = self
+    async fn statement(
+        &self,
+        range: RangeInclusive<TransactionId>,
+    ) -> Result<Vec<StatementLine>, Error> {
+        log::debug!("statement: range={range:?}");
+        let transactions = self.transactions.read().await;
+        let mut balance = dec!(0.0);
+        let mut lines = vec![];
+        let mut previous_seq = None;
+
+        // The ledger is appended to in seq order, so the balance after each
+        // transaction only depends on the ones before it
+        for transaction in transactions.iter() {
+            if transaction.id > *range.end() {
+                break;
+            }
+            debug_assert!(
+                previous_seq.is_none_or(|x| transaction.seq > x),
+                "statement: ledger out of seq order at id={} seq={} after seq={previous_seq:?}",
+                transaction.id,
+                transaction.seq,
+            );
+            previous_seq = Some(transaction.seq);
+            balance += transaction.amount;
+            if range.contains(&transaction.id) {
+                lines.push(StatementLine {
+                    id: transaction.id,
+                    amount: transaction.amount,
+                    balance_after: balance,
+                });
+            }
+        }
+
+        drop(transactions);
+
+        Ok(lines)
+    }
+
+    async fn search(&self, filter: &TransactionFilter) -> Result<Vec<Transaction>, Error> {
+        log::debug!("search: filter={filter}");
+        Ok(self
             .transactions
             .read()
             .await
             .iter()
-            .find(|x| x.id == id)
+            .filter(|x| filter.matches(x))
+            .take(filter.limit)
             .cloned()
-        else {
-            return Ok(None);
-        };
+            .collect())
+    }
 
-        let originally_created_at = existing.created_at;
+    async fn export(&self) -> Result<Vec<Transaction>, Error> {
+        log::debug!("export");
+        Ok(self.transactions.read().await.clone())
+    }
 
-        let new_transaction = self.create_transaction(-existing.amount).await?;
+    async fn import(&self, imported: Vec<Transaction>, origin: &Origin) -> Result<(), ImportError> {
+        log::debug!("import: count={} peer={}", imported.len(), origin.peer);
+        // Same as create_transaction: take every lock before touching any state
+        let mut current_id = self.current_id.write().await;
+        let mut transactions = self.transactions.write().await;
+        let mut index = self.index.write().await;
+        let mut balance = self.balance.write().await;
+        let mut file = self.file.lock().await;
+        let mut audit = self.audit.lock().await;
 
-        assert!(
-            new_transaction.created_at >= originally_created_at,
-            "Time went backwards new_transaction.created_at={} originally_created_at={originally_created_at}",
-            new_transaction.created_at
+        if self.is_read_only() {
+            return Err(Error::ReadOnly.into());
+        }
+
+        if !transactions.is_empty() {
+            return Err(ImportError::NotEmpty(transactions.len()));
+        }
+
+        for (previous, transaction) in imported.iter().zip(imported.iter().skip(1)) {
+            if transaction.id <= previous.id {
+                return Err(ImportError::NonIncreasingId {
+                    previous: previous.id,
+                    id: transaction.id,
+                });
+            }
+            if transaction.seq <= previous.seq {
+                return Err(ImportError::NonIncreasingSeq {
+                    previous: previous.seq,
+                    id: transaction.id,
+                    seq: transaction.seq,
+                });
+            }
+        }
+
+        let mut serialized = String::new();
+        for transaction in &imported {
+            serialized.push_str(&serde_json::to_string(transaction).map_err(Error::from)?);
+            serialized.push('\n');
+        }
+
+        let guard = cancel_safety::guard("import");
+
+        // The bank is empty, so the file is too and the whole ledger is
+        // persisted with a single write
+        if let Err(e) = append(&mut file, &serialized) {
+            guard.disarm();
+            return Err(Error::from(e).into());
+        }
+
+        record(
+            &mut audit,
+            imported
+                .iter()
+                .map(|x| {
+                    AuditRecord::new(
+                        origin,
+                        AuditAction::ImportLedger,
+                        format!("amount={}", x.amount),
+                        x,
+                    )
+                })
+                .collect(),
         );
 
-        Ok(Some(new_transaction))
+        *current_id = imported.last().map_or(1, |x| x.id + 1);
+        *balance = BalanceCache::recompute(&imported);
+        *index = IdIndex::rebuild(&imported);
+        *transactions = imported;
+
+        guard.disarm();
+
+        drop(audit);
+        drop(file);
+        drop(balance);
+        drop(index);
+        drop(transactions);
+        drop(current_id);
+
+        Ok(())
     }
 
-    async fn get_balance(&self) -> Result<BankAccountBalance, Error> {
-        log::debug!("get_balance");
-        Ok(*self.balance.read().await)
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    async fn set_read_only(&self, read_only: bool) -> Result<Option<TransactionId>, Error> {
+        log::debug!("set_read_only: read_only={read_only}");
+        // Waits for any transaction that's in the middle of being created
+        let transactions = self.transactions.write().await;
+        self.read_only.store(read_only, Ordering::SeqCst);
+        Ok(transactions.last().map(|x| x.id))
+    }
+
+    async fn apply_replicated(
+        &self,
+        transaction: Transaction,
+        origin: &Origin,
+    ) -> Result<(), ReplicateError> {
+        log::debug!(
+            "apply_replicated: id={} peer={}",
+            transaction.id,
+            origin.peer
+        );
+        let mut current_id = self.current_id.write().await;
+        let mut transactions = self.transactions.write().await;
+        let mut index = self.index.write().await;
+        let mut balance = self.balance.write().await;
+        let mut file = self.file.lock().await;
+        let mut audit = self.audit.lock().await;
+
+        if !self.is_read_only() {
+            return Err(ReplicateError::NotReadOnly);
+        }
+
+        if transaction.id < *current_id {
+            log::debug!(
+                "apply_replicated: ignoring already applied id={}",
+                transaction.id
+            );
+            return Ok(());
+        }
+        if transaction.id != *current_id {
+            return Err(ReplicateError::Gap {
+                expected: *current_id,
+                id: transaction.id,
+            });
+        }
+
+        let mut serialized = serde_json::to_string(&transaction).map_err(Error::from)?;
+        serialized.push('\n');
+
+        let guard = cancel_safety::guard("apply_replicated");
+
+        if let Err(e) = append(&mut file, &serialized) {
+            guard.disarm();
+            return Err(Error::from(e).into());
+        }
+
+        record(
+            &mut audit,
+            vec![AuditRecord::new(
+                origin,
+                AuditAction::Replicate,
+                format!("amount={}", transaction.amount),
+                &transaction,
+            )],
+        );
+
+        *current_id = transaction.id + 1;
+        balance.apply(&transaction);
+        index.insert(&transaction, transactions.len());
+        transactions.push(transaction);
+        index.debug_assert_appended(&transactions, 1);
+
+        guard.disarm();
+
+        drop(audit);
+        drop(file);
+        drop(balance);
+        drop(index);
+        drop(transactions);
+        drop(current_id);
+
+        Ok(())
+    }
+
+    async fn tail_audit(&self, count: usize) -> Result<Vec<AuditRecord>, Error> {
+        log::debug!("tail_audit: count={count}");
+        Ok(self.audit.lock().await.tail(count).to_vec())
     }
 }
@@ -0,0 +1,60 @@
+/// A response that would take more than the server's response budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Response exceeds the budget of {budget} bytes")]
+pub struct BudgetExceeded {
+    pub budget: usize,
+    /// How many bytes of the response had already been written to the peer
+    /// when it ran out of budget. Only a response that ran out before any of
+    /// it was written can still be refused with `resource_exhausted`.
+    pub written: u64,
+}
+
+/// Counts the bytes of a response against a budget as they're produced, so
+/// that nothing past the budget has to be formatted or copied.
+#[derive(Debug, Clone, Copy)]
+pub struct Meter {
+    budget: usize,
+    len: usize,
+}
+
+impl Meter {
+    #[must_use]
+    pub const fn new(budget: usize) -> Self {
+        Self { budget, len: 0 }
+    }
+
+    /// Counts `len` more bytes of the response.
+    ///
+    /// # Errors
+    ///
+    /// * If the bytes counted so far take more than the budget
+    pub const fn add(&mut self, len: usize) -> Result<(), BudgetExceeded> {
+        self.len = self.len.saturating_add(len);
+        if self.len > self.budget {
+            return Err(BudgetExceeded {
+                budget: self.budget,
+                written: 0,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// How many bytes `value` takes when it's displayed, without formatting it
+/// into a `String`.
+#[must_use]
+pub fn display_len(value: &impl std::fmt::Display) -> usize {
+    struct Counter(usize);
+
+    impl std::fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Counting can't fail, so only a broken `Display` impl could
+    let _ = std::fmt::write(&mut counter, format_args!("{value}"));
+    counter.0
+}
@@ -0,0 +1,62 @@
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+};
+
+static CANCELLED: LazyLock<Mutex<BTreeMap<&'static str, u64>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Marks the start of a multi-step section that must not be cancelled part
+/// way through.
+///
+/// If the returned guard is dropped without being disarmed (e.g. the future
+/// holding it was dropped at an await point), the label is recorded as a
+/// cancellation safety violation.
+#[must_use]
+pub const fn guard(label: &'static str) -> CancellationGuard {
+    CancellationGuard { label, armed: true }
+}
+
+pub struct CancellationGuard {
+    label: &'static str,
+    armed: bool,
+}
+
+impl CancellationGuard {
+    /// Marks the guarded section as completed.
+    pub fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancellationGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        log::error!(
+            "cancellation safety: '{}' was dropped part way through",
+            self.label
+        );
+
+        *CANCELLED.lock().unwrap().entry(self.label).or_default() += 1;
+    }
+}
+
+/// The number of times each guarded section was dropped without being disarmed
+///
+/// # Panics
+///
+/// * If the `CANCELLED` `Mutex` fails to lock
+#[must_use]
+pub fn cancelled() -> BTreeMap<&'static str, u64> {
+    CANCELLED.lock().unwrap().clone()
+}
+
+/// # Panics
+///
+/// * If the `CANCELLED` `Mutex` fails to lock
+pub fn reset() {
+    CANCELLED.lock().unwrap().clear();
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    str::FromStr as _,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ServerAction, replication::ServerRole};
+
+pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_mins(1);
+pub const DEFAULT_RATE_LIMIT_CAPACITY: u32 = 50;
+pub const DEFAULT_RATE_LIMIT_REFILL_PER_SECOND: u32 = 10;
+pub const DEFAULT_RESPONSE_BUDGET_BYTES: usize = 64 * 1024 * 1024;
+
+/// Where the ledger is persisted unless `DB_PATH` is set
+#[must_use]
+pub fn default_db_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("transactions.db")
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[error("Invalid {field}: {reason}")]
+    Invalid {
+        field: &'static str,
+        reason: &'static str,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// How long writing a single response may take before the connection is
+    /// considered stalled and dropped.
+    pub write_timeout: Duration,
+    /// How long a connection may go without sending an action before it's
+    /// closed, or `None` to keep idle connections open indefinitely.
+    pub idle_timeout: Option<Duration>,
+    /// How many actions a connection can send in a burst before being rate
+    /// limited.
+    pub rate_limit_capacity: u32,
+    /// How many actions per second a connection's rate limit recovers.
+    pub rate_limit_refill_per_second: u32,
+    /// The most bytes a single listing, statement, or export may take. A
+    /// larger one is refused with `resource_exhausted` instead of being
+    /// built.
+    pub response_budget_bytes: usize,
+    /// Where the ledger is persisted.
+    pub db_path: PathBuf,
+    /// Whether the server starts out accepting writes or only applying
+    /// replicated transactions.
+    pub role: ServerRole,
+    /// The address committed transactions are replicated to while this
+    /// server is the primary.
+    pub replica_addr: Option<String>,
+    /// The identity prefix a connection needs to perform each action. Actions
+    /// that aren't listed are allowed for every connection, identified or
+    /// not.
+    pub authorization: BTreeMap<ServerAction, String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            idle_timeout: None,
+            rate_limit_capacity: DEFAULT_RATE_LIMIT_CAPACITY,
+            rate_limit_refill_per_second: DEFAULT_RATE_LIMIT_REFILL_PER_SECOND,
+            response_budget_bytes: DEFAULT_RESPONSE_BUDGET_BYTES,
+            db_path: default_db_path(),
+            role: ServerRole::default(),
+            replica_addr: None,
+            authorization: BTreeMap::new(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Builds a `ServerConfig` from the environment, falling back to the
+    /// defaults for any variables that aren't set.
+    ///
+    /// # Panics
+    ///
+    /// * If an environment variable is set to an invalid value
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(value) = std::env::var("WRITE_TIMEOUT_MS") {
+            config.write_timeout = Duration::from_millis(value.parse::<u64>().unwrap());
+        }
+        if let Ok(value) = std::env::var("IDLE_TIMEOUT_MS") {
+            config.idle_timeout =
+                Some(Duration::from_millis(value.parse::<u64>().unwrap())).filter(|x| !x.is_zero());
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_CAPACITY") {
+            config.rate_limit_capacity = value.parse::<u32>().unwrap();
+        }
+        if let Ok(value) = std::env::var("RATE_LIMIT_REFILL_PER_SECOND") {
+            config.rate_limit_refill_per_second = value.parse::<u32>().unwrap();
+        }
+        if let Ok(value) = std::env::var("RESPONSE_BUDGET_BYTES") {
+            config.response_budget_bytes = value.parse::<usize>().unwrap();
+        }
+        if let Ok(value) = std::env::var("DB_PATH") {
+            config.db_path = PathBuf::from(value);
+        }
+        if let Ok(value) = std::env::var("SERVER_ROLE") {
+            config.role = ServerRole::from_str(&value).unwrap();
+        }
+        if let Ok(value) = std::env::var("REPLICA_ADDR") {
+            config.replica_addr = Some(value);
+        }
+        if let Ok(value) = std::env::var("AUTHORIZATION") {
+            config.authorization = parse_authorization(&value).unwrap();
+        }
+
+        config
+    }
+
+    /// The identity prefix a connection needs to perform `action`, if any
+    #[must_use]
+    pub fn required_identity(&self, action: ServerAction) -> Option<&str> {
+        self.authorization.get(&action).map(String::as_str)
+    }
+
+    /// Applies the overrides on top of this config.
+    ///
+    /// # Errors
+    ///
+    /// * If an overridden value is invalid
+    pub fn reload(&self, overrides: &ReloadableConfig) -> Result<Self, ConfigError> {
+        let mut config = self.clone();
+
+        if let Some(value) = overrides.write_timeout_ms {
+            if value == 0 {
+                return Err(ConfigError::Invalid {
+                    field: "write_timeout_ms",
+                    reason: "must be greater than 0",
+                });
+            }
+            config.write_timeout = Duration::from_millis(value);
+        }
+        if let Some(value) = overrides.rate_limit_capacity {
+            if value == 0 {
+                return Err(ConfigError::Invalid {
+                    field: "rate_limit_capacity",
+                    reason: "must be greater than 0",
+                });
+            }
+            config.rate_limit_capacity = value;
+        }
+        if let Some(value) = overrides.rate_limit_refill_per_second {
+            config.rate_limit_refill_per_second = value;
+        }
+        if let Some(value) = overrides.response_budget_bytes {
+            if value == 0 {
+                return Err(ConfigError::Invalid {
+                    field: "response_budget_bytes",
+                    reason: "must be greater than 0",
+                });
+            }
+            config.response_budget_bytes = usize::try_from(value).unwrap_or(usize::MAX);
+        }
+
+        Ok(config)
+    }
+
+    /// The current values of the fields that can be reloaded.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn reloadable(&self) -> ReloadableConfig {
+        ReloadableConfig {
+            write_timeout_ms: Some(self.write_timeout.as_millis() as u64),
+            rate_limit_capacity: Some(self.rate_limit_capacity),
+            rate_limit_refill_per_second: Some(self.rate_limit_refill_per_second),
+            response_budget_bytes: Some(
+                u64::try_from(self.response_budget_bytes).unwrap_or(u64::MAX),
+            ),
+        }
+    }
+}
+
+/// Parses the identity prefix each action requires, formatted as
+/// `ACTION=prefix,ACTION2=prefix2` (e.g. `EXIT=admin,IMPORT_LEDGER=admin`).
+///
+/// # Errors
+///
+/// * If an entry isn't an action followed by `=` and a non-empty prefix
+pub fn parse_authorization(value: &str) -> Result<BTreeMap<ServerAction, String>, ConfigError> {
+    value
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(|entry| {
+            let invalid = ConfigError::Invalid {
+                field: "authorization",
+                reason: "expected ACTION=prefix entries",
+            };
+            let (action, prefix) = entry.split_once('=').ok_or(invalid)?;
+            let action =
+                ServerAction::from_str(action.trim()).map_err(|_| ConfigError::Invalid {
+                    field: "authorization",
+                    reason: "unknown action",
+                })?;
+            let prefix = prefix.trim();
+            if prefix.is_empty() {
+                return Err(ConfigError::Invalid {
+                    field: "authorization",
+                    reason: "identity prefixes can't be empty",
+                });
+            }
+            Ok((action, prefix.to_string()))
+        })
+        .collect()
+}
+
+/// The fields of a running server's config that can be changed by
+/// `RELOAD_CONFIG`. Fields that are left out keep their current value.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReloadableConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_capacity: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit_refill_per_second: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_budget_bytes: Option<u64>,
+}
+
+/// The config of a running server, shared by all of its connections. Each
+/// connection reads it before every action, so a reload takes effect on
+/// existing connections too.
+#[derive(Debug, Clone)]
+pub struct SharedConfig(Arc<RwLock<ServerConfig>>);
+
+impl SharedConfig {
+    #[must_use]
+    pub fn new(config: ServerConfig) -> Self {
+        Self(Arc::new(RwLock::new(config)))
+    }
+
+    /// # Panics
+    ///
+    /// * If the config `RwLock` is poisoned
+    #[must_use]
+    pub fn get(&self) -> ServerConfig {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Validates the overrides and swaps in the resulting config. The active
+    /// config is left unchanged if the overrides are invalid.
+    ///
+    /// # Errors
+    ///
+    /// * If an overridden value is invalid
+    ///
+    /// # Panics
+    ///
+    /// * If the config `RwLock` is poisoned
+    pub fn reload(&self, overrides: &ReloadableConfig) -> Result<ServerConfig, ConfigError> {
+        let mut config = self.0.write().unwrap();
+        let reloaded = config.reload(overrides)?;
+        *config = reloaded.clone();
+        drop(config);
+        Ok(reloaded)
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+static CONNECTIONS: LazyLock<Mutex<ConnectionRegistry>> =
+    LazyLock::new(|| Mutex::new(ConnectionRegistry::new()));
+
+struct ConnectionRegistry {
+    next_id: u64,
+    active: BTreeMap<u64, Entry>,
+}
+
+struct Entry {
+    info: ConnectionInfo,
+    /// Shared with the connection's `ResponseWriter`, which counts the bytes
+    /// as it writes them
+    bytes_out: Arc<AtomicU64>,
+}
+
+impl Entry {
+    fn snapshot(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            ..self.info.clone()
+        }
+    }
+}
+
+impl ConnectionRegistry {
+    const fn new() -> Self {
+        Self {
+            next_id: 0,
+            active: BTreeMap::new(),
+        }
+    }
+}
+
+/// What the server knows about one of its active connections
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub peer: String,
+    pub connected_at: SystemTime,
+    /// How many frames the connection has sent outside of prompts, including
+    /// actions that were invalid or rejected
+    pub actions: u64,
+    pub last_action: Option<String>,
+    /// The bytes of the frames counted in `actions`, including their
+    /// terminators. Answers to prompts aren't included
+    pub bytes_in: u64,
+    /// The bytes of the frames written to the connection, including their
+    /// terminators
+    pub bytes_out: u64,
+}
+
+impl std::fmt::Display for ConnectionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let connected_at = self
+            .connected_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        f.write_fmt(format_args!(
+            "id={} peer={} connected_at_ms={connected_at} actions={} last_action={} bytes_in={} bytes_out={}",
+            self.id,
+            self.peer,
+            self.actions,
+            self.last_action.as_deref().unwrap_or("none"),
+            self.bytes_in,
+            self.bytes_out,
+        ))
+    }
+}
+
+/// Registers a connection from `peer` as active until the returned guard is
+/// dropped.
+///
+/// The guard is dropped both when the connection's handler exits and when it
+/// is cancelled. `bytes_out` is the counter of the connection's
+/// `ResponseWriter`.
+///
+/// # Panics
+///
+/// * If the `CONNECTIONS` `Mutex` fails to lock
+#[must_use]
+pub fn register(peer: impl Into<String>, bytes_out: Arc<AtomicU64>) -> ConnectionGuard {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    let id = connections.next_id;
+    connections.next_id += 1;
+    connections.active.insert(
+        id,
+        Entry {
+            info: ConnectionInfo {
+                id,
+                peer: peer.into(),
+                connected_at: switchy::time::now(),
+                actions: 0,
+                last_action: None,
+                bytes_in: 0,
+                bytes_out: 0,
+            },
+            bytes_out,
+        },
+    );
+    drop(connections);
+
+    ConnectionGuard { id }
+}
+
+/// The active connections, ordered by when they connected.
+///
+/// # Panics
+///
+/// * If the `CONNECTIONS` `Mutex` fails to lock
+#[must_use]
+pub fn list() -> Vec<ConnectionInfo> {
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .active
+        .values()
+        .map(Entry::snapshot)
+        .collect()
+}
+
+/// Forgets every registered connection.
+///
+/// Like the task registry, ids keep counting up across resets, so the guard
+/// of a connection registered before the reset can't unregister a newer one.
+///
+/// # Panics
+///
+/// * If the `CONNECTIONS` `Mutex` fails to lock
+pub fn reset() {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    *connections = ConnectionRegistry {
+        next_id: connections.next_id,
+        ..ConnectionRegistry::new()
+    };
+}
+
+pub struct ConnectionGuard {
+    id: u64,
+}
+
+impl ConnectionGuard {
+    /// Records an action received on the connection along with the size of
+    /// its frame.
+    ///
+    /// # Panics
+    ///
+    /// * If the `CONNECTIONS` `Mutex` fails to lock
+    pub fn record_action(&self, action: &str, frame_len: usize) {
+        if let Some(entry) = CONNECTIONS.lock().unwrap().active.get_mut(&self.id) {
+            entry.info.actions += 1;
+            entry.info.last_action = Some(action.to_string());
+            entry.info.bytes_in += frame_len as u64;
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        CONNECTIONS.lock().unwrap().active.remove(&self.id);
+    }
+}
@@ -0,0 +1,219 @@
+use std::string::FromUtf8Error;
+
+use miniz_oxide::deflate::core::{
+    CompressorOxide, TDEFLFlush, TDEFLStatus, compress_to_output, create_comp_flags_from_zip_params,
+};
+use strum::{AsRefStr, EnumString};
+
+/// Payloads shorter than this many bytes are sent raw even on a compressed
+/// connection, since deflating them wouldn't save anything
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The deflate level used for every compressed frame. It's fixed so that the
+/// same payload always compresses to the same bytes.
+pub const COMPRESSION_LEVEL: u8 = 6;
+
+/// The largest payload a frame may carry, before or after decompression
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// The flag byte and big-endian `u32` body length that precede each frame's
+/// body
+pub const HEADER_LEN: usize = 5;
+
+const RAW: u8 = 0x00;
+const DEFLATE: u8 = 0x01;
+
+/// The compression a client can negotiate with `COMPRESS <compression>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Compression {
+    Deflate,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FramingError {
+    #[error("Unknown frame flag {0:#04x}")]
+    UnknownFlag(u8),
+    #[error("Frame body of {0} bytes exceeds the maximum")]
+    TooLarge(usize),
+    #[error("Invalid compressed frame body")]
+    Decompress,
+    #[error(transparent)]
+    FromUtf8(#[from] FromUtf8Error),
+}
+
+/// Encodes a payload as a frame for a connection that negotiated compression.
+///
+/// A frame is a flag byte, the length of the body, then the body, which is
+/// deflated if the payload is at least [`COMPRESSION_THRESHOLD`] bytes.
+///
+/// Frames are length prefixed instead of terminated since a deflated body can
+/// contain any byte.
+///
+/// # Panics
+///
+/// * If the body is longer than a `u32` can describe
+#[must_use]
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut encoder = FrameEncoder::new();
+    encoder.write(payload);
+    encoder.finish()
+}
+
+/// Encodes a frame like [`encode`] from a payload that's handed to it a piece
+/// at a time, deflating the pieces as they come instead of joining them
+/// first.
+///
+/// Since the frame's header holds the length of its body, the body itself is
+/// still buffered until [`FrameEncoder::finish`]. Only the deflated body is,
+/// though, along with up to [`COMPRESSION_THRESHOLD`] bytes of the payload
+/// until it's known whether the payload is long enough to be deflated at all.
+pub struct FrameEncoder {
+    /// The payload so far, until it reaches [`COMPRESSION_THRESHOLD`] bytes
+    raw: Vec<u8>,
+    compressor: Option<Box<CompressorOxide>>,
+    /// The deflated body so far
+    body: Vec<u8>,
+    payload_len: usize,
+}
+
+impl Default for FrameEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameEncoder {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            raw: vec![],
+            compressor: None,
+            body: vec![],
+            payload_len: 0,
+        }
+    }
+
+    /// The number of payload bytes written so far
+    #[must_use]
+    pub const fn payload_len(&self) -> usize {
+        self.payload_len
+    }
+
+    /// Appends `piece` to the payload.
+    pub fn write(&mut self, piece: &[u8]) {
+        self.payload_len += piece.len();
+
+        if let Some(compressor) = &mut self.compressor {
+            deflate(compressor, piece, TDEFLFlush::None, &mut self.body);
+            return;
+        }
+
+        self.raw.extend_from_slice(piece);
+
+        if self.raw.len() >= COMPRESSION_THRESHOLD {
+            let flags = create_comp_flags_from_zip_params(COMPRESSION_LEVEL.into(), 0, 0);
+            let mut compressor = Box::new(CompressorOxide::new(flags));
+            let raw = std::mem::take(&mut self.raw);
+            deflate(&mut compressor, &raw, TDEFLFlush::None, &mut self.body);
+            self.compressor = Some(compressor);
+        }
+    }
+
+    /// Returns the frame for everything written so far.
+    ///
+    /// # Panics
+    ///
+    /// * If the body is longer than a `u32` can describe
+    #[must_use]
+    pub fn finish(mut self) -> Vec<u8> {
+        let (flag, body) = match &mut self.compressor {
+            Some(compressor) => {
+                deflate(compressor, &[], TDEFLFlush::Finish, &mut self.body);
+                (DEFLATE, self.body)
+            }
+            None => (RAW, self.raw),
+        };
+
+        let mut frame = Vec::with_capacity(HEADER_LEN + body.len());
+        frame.push(flag);
+        frame.extend_from_slice(&u32::try_from(body.len()).unwrap().to_be_bytes());
+        frame.extend_from_slice(&body);
+        frame
+    }
+}
+
+/// Feeds `input` through `compressor`, appending whatever it outputs to
+/// `body`.
+fn deflate(
+    compressor: &mut CompressorOxide,
+    mut input: &[u8],
+    flush: TDEFLFlush,
+    body: &mut Vec<u8>,
+) {
+    loop {
+        let (status, consumed) = compress_to_output(compressor, input, flush, |output| {
+            body.extend_from_slice(output);
+            true
+        });
+        input = &input[consumed..];
+
+        match status {
+            TDEFLStatus::Done => return,
+            TDEFLStatus::Okay if input.is_empty() && flush != TDEFLFlush::Finish => return,
+            TDEFLStatus::Okay => {}
+            TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                panic!("Failed to deflate a frame body: {status:?}")
+            }
+        }
+    }
+}
+
+/// Takes the first complete frame off the front of `buffer` and returns its
+/// payload, or `None` if the buffer doesn't hold a complete frame yet.
+///
+/// # Errors
+///
+/// * If the frame's flag is unknown
+/// * If the frame's body, or its decompressed payload, is larger than
+///   [`MAX_FRAME_LEN`]
+/// * If a compressed body isn't valid deflate data
+/// * If the payload isn't valid UTF-8
+pub fn decode(buffer: &mut Vec<u8>) -> Result<Option<String>, FramingError> {
+    let Some(header) = buffer.get(..HEADER_LEN) else {
+        return Ok(None);
+    };
+
+    let flag = header[0];
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+
+    if flag != RAW && flag != DEFLATE {
+        return Err(FramingError::UnknownFlag(flag));
+    }
+    if len > MAX_FRAME_LEN {
+        return Err(FramingError::TooLarge(len));
+    }
+    if buffer.len() < HEADER_LEN + len {
+        return Ok(None);
+    }
+
+    let body = buffer
+        .drain(..HEADER_LEN + len)
+        .skip(HEADER_LEN)
+        .collect::<Vec<_>>();
+
+    let payload = if flag == DEFLATE {
+        miniz_oxide::inflate::decompress_to_vec_with_limit(&body, MAX_FRAME_LEN)
+            .map_err(|_| FramingError::Decompress)?
+    } else {
+        body
+    };
+
+    Ok(Some(String::from_utf8(payload)?))
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+};
+
+/// Every delay point in the server, so a run can report which of them fired
+pub const DELAY_POINTS: &[&str] = &[
+    "connection::before_dispatch",
+    "create_transaction::before_file_write",
+    "create_transaction::after_file_write",
+    "create_transactions_atomic::before_file_write",
+    "create_transactions_atomic::after_file_write",
+    "void_transaction::after_lookup",
+];
+
+/// How many times each delay point fired. Global, like the other server
+/// registries, so a delay point that fires on a different thread than the one
+/// reading the counts, e.g. a runtime worker, is still counted.
+static FIRED: LazyLock<Mutex<BTreeMap<&'static str, u64>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// The max delay in millis from `SIM_HOOKS_MAX_DELAY_MS`. Defaults to `0`,
+/// which disables the delay points.
+#[cfg(feature = "sim-hooks")]
+static MAX_DELAY_MS: std::sync::LazyLock<u64> = std::sync::LazyLock::new(|| {
+    std::env::var("SIM_HOOKS_MAX_DELAY_MS")
+        .ok()
+        .map_or(0, |x| x.parse::<u64>().unwrap())
+});
+
+/// Sleeps for a random `0..=SIM_HOOKS_MAX_DELAY_MS` millis to widen the race
+/// window at an interesting point in the server. This is a no-op unless the
+/// `sim-hooks` feature is enabled.
+///
+/// # Panics
+///
+/// * If `name` isn't one of the `DELAY_POINTS`
+/// * If `SIM_HOOKS_MAX_DELAY_MS` is not a valid integer
+/// * If the `FIRED` `Mutex` fails to lock
+#[allow(clippy::unused_async)]
+pub async fn delay_point(name: &'static str) {
+    #[cfg(feature = "sim-hooks")]
+    {
+        assert!(
+            DELAY_POINTS.contains(&name),
+            "unregistered delay point '{name}'"
+        );
+
+        let max = *MAX_DELAY_MS;
+        if max == 0 {
+            return;
+        }
+
+        *FIRED.lock().unwrap().entry(name).or_default() += 1;
+
+        let millis = switchy::random::rng().gen_range(0..max + 1);
+        log::trace!("delay_point: delaying '{name}' by {millis}ms");
+        switchy::unsync::time::sleep(std::time::Duration::from_millis(millis)).await;
+    }
+
+    #[cfg(not(feature = "sim-hooks"))]
+    let _ = name;
+}
+
+/// How many times each delay point fired since the last `reset`
+///
+/// # Panics
+///
+/// * If the `FIRED` `Mutex` fails to lock
+#[must_use]
+pub fn fired() -> BTreeMap<&'static str, u64> {
+    FIRED.lock().unwrap().clone()
+}
+
+/// # Panics
+///
+/// * If the `FIRED` `Mutex` fails to lock
+pub fn reset() {
+    FIRED.lock().unwrap().clear();
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use crate::bank::{Transaction, TransactionId};
+
+/// Where each transaction sits in the ledger's `Vec`.
+///
+/// It's kept up to date in the same critical section as each commit so
+/// looking a transaction up by id doesn't have to scan the ledger. The `Vec`
+/// stays the source of truth for ordering and persistence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdIndex {
+    positions: BTreeMap<TransactionId, usize>,
+}
+
+impl IdIndex {
+    /// Builds the index from scratch, e.g. when the ledger is loaded or
+    /// replaced by an import.
+    #[must_use]
+    pub fn rebuild(transactions: &[Transaction]) -> Self {
+        Self {
+            positions: transactions
+                .iter()
+                .enumerate()
+                .map(|(position, x)| (x.id, position))
+                .collect(),
+        }
+    }
+
+    /// Indexes a transaction that was just appended to the ledger at
+    /// `position`.
+    pub fn insert(&mut self, transaction: &Transaction, position: usize) {
+        self.positions.insert(transaction.id, position);
+    }
+
+    /// The position of the transaction with the given id in the ledger
+    #[must_use]
+    pub fn get(&self, id: TransactionId) -> Option<usize> {
+        self.positions.get(&id).copied()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Asserts that the index points at every transaction in `transactions`
+    /// and at nothing else.
+    ///
+    /// # Panics
+    ///
+    /// * If the index diverged from the ledger
+    pub fn assert_consistent(&self, transactions: &[Transaction]) {
+        assert!(
+            self.positions.len() == transactions.len(),
+            "id index has {} entries but the ledger has {} transactions",
+            self.positions.len(),
+            transactions.len(),
+        );
+
+        for (position, transaction) in transactions.iter().enumerate() {
+            let indexed = self.get(transaction.id);
+            assert!(
+                indexed == Some(position),
+                "id index points id={} at position={indexed:?} but it's at position={position}",
+                transaction.id,
+            );
+        }
+    }
+
+    /// Asserts in debug builds that the last `count` transactions of
+    /// `transactions`, the ones just appended, are indexed at their
+    /// positions, and that the index has exactly one entry per transaction.
+    ///
+    /// Unlike [`Self::assert_consistent`] this only looks at the appended
+    /// entries, so checking every commit doesn't rescan the whole ledger.
+    ///
+    /// # Panics
+    ///
+    /// * If an appended transaction isn't indexed at its position
+    /// * If the index and ledger sizes differ
+    pub fn debug_assert_appended(&self, transactions: &[Transaction], count: usize) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        debug_assert!(
+            self.positions.len() == transactions.len(),
+            "id index has {} entries but the ledger has {} transactions",
+            self.positions.len(),
+            transactions.len(),
+        );
+
+        let start = transactions.len().saturating_sub(count);
+        for (position, transaction) in transactions.iter().enumerate().skip(start) {
+            let indexed = self.get(transaction.id);
+            debug_assert!(
+                indexed == Some(position),
+                "id index points id={} at position={indexed:?} but it's at position={position}",
+                transaction.id,
+            );
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use switchy::{
+    tcp::TcpStream,
+    unsync::{
+        inject_yields,
+        io::{AsyncWrite, AsyncWriteExt as _},
+    },
+};
+
+use crate::Error;
+
+/// Starts the frame a client sends first to identify itself, followed by a
+/// space and its identity
+pub const PREAMBLE_PREFIX: &str = "IDENTITY ";
+
+pub const UNAUTHORIZED_MESSAGE: &str = "The connection's identity isn't allowed this action";
+
+/// The preamble frame identifying a connection as `identity`, without its
+/// terminator
+#[must_use]
+pub fn preamble(identity: &str) -> String {
+    format!("{PREAMBLE_PREFIX}{identity}")
+}
+
+/// The identity in a preamble frame, or `None` if `message` isn't one
+#[must_use]
+pub fn parse_preamble(message: &str) -> Option<&str> {
+    message
+        .strip_prefix(PREAMBLE_PREFIX)
+        .filter(|x| !x.is_empty())
+}
+
+/// Connects to `addr` identified as `identity`. The server strips the
+/// preamble before anything else sees the connection's messages, so the
+/// stream is used exactly like one from `TcpStream::connect`.
+///
+/// The preamble is sent the same way over real and simulated TCP, so both
+/// behave the same. It carries no proof of the identity, which makes it only
+/// suitable for testing authorization logic.
+///
+/// # Errors
+///
+/// * If the connection fails
+/// * If the preamble fails to be written
+#[inject_yields]
+pub async fn connect_with_identity(addr: &str, identity: &str) -> Result<TcpStream, Error> {
+    let mut stream = TcpStream::connect(addr).await?;
+
+    send_preamble(&mut stream, identity).await?;
+
+    Ok(stream)
+}
+
+/// Identifies an already connected `stream` as `identity`. It has to be
+/// sent before anything else on the connection.
+///
+/// # Errors
+///
+/// * If the preamble fails to be written
+#[inject_yields]
+pub async fn send_preamble(
+    stream: &mut (impl AsyncWrite + Unpin),
+    identity: &str,
+) -> Result<(), Error> {
+    let mut bytes = preamble(identity).into_bytes();
+    bytes.push(0_u8);
+    stream.write_all(&bytes).await?;
+
+    Ok(())
+}
+
+/// Whether a connection identified as `identity` may perform an action that
+/// requires `required`, an identity prefix, if anything
+#[must_use]
+pub fn is_authorized(required: Option<&str>, identity: Option<&str>) -> bool {
+    required.is_none_or(|required| identity.is_some_and(|x| x.starts_with(required)))
+}
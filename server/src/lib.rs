@@ -4,28 +4,72 @@
 
 use std::{
     str::{self, FromStr as _},
-    string::FromUtf8Error,
-    sync::LazyLock,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
-use bank::{Bank, LocalBank, TransactionId};
+use actions::{ActionContext, Registry};
+use bank::{
+    Bank, ImportError, LocalBank, Origin, ReplicateError, Transaction, TransactionId, parse_amount,
+};
+use config::{ReloadableConfig, ServerConfig, SharedConfig};
+use framing::Compression;
+use message::{InvalidMessage, MessageBuffer, READ_CHUNK_SIZE};
+use protocol::{
+    ProtocolError, ProtocolVersion, READ_ONLY_MESSAGE, RESOURCE_EXHAUSTED_MESSAGE, ResponseWriter,
+};
+use rate_limit::TokenBucket;
+use replication::ServerRole;
 use rust_decimal::Decimal;
-use strum::{AsRefStr, EnumString, ParseError};
+use search::TransactionFilter;
+use strum::{AsRefStr, EnumIter, EnumString, ParseError};
 use switchy::{
     tcp::{GenericTcpListener, GenericTcpStream, TcpListener},
     unsync::{
+        futures::{FutureExt as _, future::try_join_all},
         inject_yields,
         io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
         task,
         util::CancellationToken,
     },
 };
+use version::VersionInfo;
 
+pub mod actions;
+pub mod balance;
 pub mod bank;
+pub mod budget;
+pub mod cancel_safety;
+pub mod config;
+pub mod connections;
+pub mod framing;
+pub mod hooks;
+pub mod id_index;
+pub mod identity;
+pub mod message;
+pub mod metrics;
+pub mod protocol;
+pub mod rate_limit;
+pub mod replication;
+pub mod search;
+pub mod tasks;
+pub mod version;
 
 pub static SERVER_CANCELLATION_TOKEN: LazyLock<CancellationToken> =
     LazyLock::new(CancellationToken::new);
 
+static WRITE_TIMEOUT_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The number of connections that have been dropped because a response write
+/// timed out
+#[must_use]
+pub fn write_timeout_count() -> u64 {
+    WRITE_TIMEOUT_COUNT.load(Ordering::SeqCst)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -33,7 +77,7 @@ pub enum Error {
     #[error(transparent)]
     IO(#[from] std::io::Error),
     #[error(transparent)]
-    FromUtf8(#[from] FromUtf8Error),
+    InvalidMessage(#[from] InvalidMessage),
     #[error(transparent)]
     Parse(#[from] ParseError),
     #[error(transparent)]
@@ -44,21 +88,63 @@ pub enum Error {
     Bank(#[from] bank::Error),
     #[error(transparent)]
     ParseInt(#[from] std::num::ParseIntError),
+    #[error("Timed out writing response after {0:?}")]
+    WriteTimeout(Duration),
+    #[error(transparent)]
+    BudgetExceeded(#[from] budget::BudgetExceeded),
+    #[error("Replication failed: {0}")]
+    Replication(String),
 }
 
-#[derive(Debug, EnumString, AsRefStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString, AsRefStr, EnumIter)]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum ServerAction {
     Health,
+    Help,
     ListTransactions,
+    SearchTransactions,
     GetTransaction,
     CreateTransaction,
     VoidTransaction,
+    BeginBatch,
+    CommitBatch,
+    RollbackBatch,
     GetBalance,
+    GetStatement,
+    ExportLedger,
+    TailAudit,
+    ImportLedger,
+    Replicate,
+    Promote,
+    Demote,
+    ReloadConfig,
+    ListConnections,
+    Version,
     Close,
     Exit,
 }
 
+impl ServerAction {
+    /// Whether the action adds transactions to the ledger, which a replica
+    /// rejects
+    #[must_use]
+    pub const fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::CreateTransaction
+                | Self::VoidTransaction
+                | Self::CommitBatch
+                | Self::ImportLedger
+        )
+    }
+
+    /// Whether the connection is closed once the action is handled
+    #[must_use]
+    pub const fn closes_connection(&self) -> bool {
+        matches!(self, Self::Close | Self::Exit)
+    }
+}
+
 impl std::fmt::Display for ServerAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.as_ref())
@@ -69,117 +155,426 @@ impl std::fmt::Display for ServerAction {
 ///
 /// * If the `TcpListener` fails to bind
 /// * If the server TCP loop produces an error
-#[inject_yields]
 pub async fn run(addr: impl Into<String>) -> Result<(), Error> {
-    let addr = addr.into();
-    let listener = TcpListener::bind(&addr).await?;
-    log::info!("Server listening on {addr}");
-
-    let bank = LocalBank::new()?;
-
-    SERVER_CANCELLATION_TOKEN
-        .run_until_cancelled(async move {
-            while let Ok((stream, addr)) = listener.accept().await {
-                log::debug!("client connected");
-                let (mut read, mut write) = stream.into_split();
-                let mut message = String::new();
-                let bank = bank.clone();
-
-                task::spawn(async move {
-                    while let Ok(Some(action)) = read_message(&mut message, &mut read).await {
-                        log::debug!("[{addr}] parsing action={action}");
-                        let Ok(action) = ServerAction::from_str(&action).inspect_err(|_| {
-                            log::error!("[{addr}] Invalid action '{action}'");
-                        }) else {
-                            continue;
-                        };
-
-                        log::info!("[{addr}] received {action} action");
-
-                        let resp = match action {
-                            ServerAction::Health => health(&mut write).await,
-                            ServerAction::ListTransactions => {
-                                list_transactions(&bank, &mut write).await
-                            }
-                            ServerAction::GetTransaction => {
-                                get_transaction(&bank, &mut message, &mut write, &mut read).await
-                            }
-                            ServerAction::CreateTransaction => {
-                                create_transaction(&bank, &mut message, &mut write, &mut read).await
-                            }
-                            ServerAction::VoidTransaction => {
-                                void_transaction(&bank, &mut message, &mut write, &mut read).await
-                            }
-                            ServerAction::GetBalance => get_balance(&bank, &mut write).await,
-                            ServerAction::Close => {
-                                return;
-                            }
-                            ServerAction::Exit => {
-                                SERVER_CANCELLATION_TOKEN.cancel();
-                                return;
+    run_with_config(addr, ServerConfig::from_env()).await
+}
+
+/// # Errors
+///
+/// * If the `TcpListener` fails to bind
+/// * If the server TCP loop produces an error
+#[inject_yields]
+pub async fn run_with_config(addr: impl Into<String>, config: ServerConfig) -> Result<(), Error> {
+    run_with_listeners(vec![addr.into()], config).await
+}
+
+/// Runs a single server that accepts connections on each of `addrs`. Every
+/// listener serves the same bank, config, and replication.
+///
+/// # Errors
+///
+/// * If any of the `TcpListener`s fail to bind
+/// * If the server TCP loop of any of the listeners produces an error
+#[inject_yields]
+pub async fn run_with_listeners(addrs: Vec<String>, config: ServerConfig) -> Result<(), Error> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in &addrs {
+        listeners.push(TcpListener::bind(addr).await?);
+        log::info!("Server listening on {addr}");
+    }
+
+    let bank = LocalBank::open(&config.db_path)?;
+
+    serve_with_config(listeners, bank, config, SERVER_CANCELLATION_TOKEN.clone()).await?;
+
+    log::debug!("run finished");
+
+    Ok(())
+}
+
+/// Serves `bank` over the bank protocol on `listener`, with the config from
+/// the environment, until `shutdown` is cancelled or a client sends `EXIT`.
+///
+/// This is the entry point for embedding the server in front of another
+/// `Bank` implementation. [`run`] serves a [`LocalBank`] with it.
+///
+/// # Errors
+///
+/// * If the server TCP loop produces an error
+pub async fn serve(
+    listener: TcpListener,
+    bank: impl Bank + Clone + 'static,
+    shutdown: CancellationToken,
+) -> Result<(), Error> {
+    serve_with_config(vec![listener], bank, ServerConfig::from_env(), shutdown).await
+}
+
+/// Serves `bank` on each of `listeners` until `shutdown` is cancelled or a
+/// client sends `EXIT`.
+///
+/// The bank is made read-only if `config` runs the server as a replica, and
+/// its commits are replicated to the configured replica otherwise.
+/// `config.db_path` isn't used, since the bank is already open.
+///
+/// # Errors
+///
+/// * If the bank fails to be made read-only
+/// * If the server TCP loop of any of the listeners produces an error
+#[inject_yields]
+pub async fn serve_with_config(
+    listeners: Vec<TcpListener>,
+    bank: impl Bank + Clone + 'static,
+    config: ServerConfig,
+    shutdown: CancellationToken,
+) -> Result<(), Error> {
+    bank.set_read_only(config.role == ServerRole::Replica)
+        .await?;
+    log::info!("Server running as {}", config.role);
+
+    let (commits, pending_commits) = flume::unbounded();
+    let config = SharedConfig::new(config);
+    let registry = Arc::new(Registry::new());
+
+    if let Some(replica_addr) = config.get().replica_addr {
+        task::spawn(replication::replicate(
+            bank.clone(),
+            replica_addr,
+            pending_commits,
+        ));
+    }
+
+    shutdown
+        .run_until_cancelled(try_join_all(listeners.into_iter().map(|listener| {
+            accept_connections(
+                listener,
+                bank.clone(),
+                commits.clone(),
+                config.clone(),
+                registry.clone(),
+                shutdown.clone(),
+            )
+        })))
+        .await
+        .transpose()?;
+
+    log::debug!("serve finished");
+
+    Ok(())
+}
+
+/// Accepts connections on `listener` until it fails, serving each on its own
+/// task.
+#[allow(clippy::too_many_lines)]
+#[inject_yields]
+async fn accept_connections(
+    listener: TcpListener,
+    bank: impl Bank + Clone + 'static,
+    commits: flume::Sender<()>,
+    config: SharedConfig,
+    registry: Arc<Registry>,
+    shutdown: CancellationToken,
+) -> Result<(), Error> {
+    while let Ok((stream, addr)) = listener.accept().await {
+        log::debug!("client connected");
+        metrics::increment(&metrics::CONNECTIONS_TOTAL);
+        let (mut read, write) = stream.into_split();
+        let initial = config.get();
+        let mut write = ResponseWriter::spawn(write, initial.write_timeout);
+        let mut message = MessageBuffer::new();
+        let bank = bank.clone();
+        let origin = Origin::new(addr.to_string());
+        let commits = commits.clone();
+        let config = config.clone();
+        let registry = registry.clone();
+        let shutdown = shutdown.clone();
+
+        let task = tasks::register(format!("connection {addr}"));
+        let connection = connections::register(addr.to_string(), write.bytes_written());
+
+        let mut rate_limit = TokenBucket::new(
+            initial.rate_limit_capacity,
+            initial.rate_limit_refill_per_second,
+        );
+
+        task::spawn(async move {
+            let _task = task;
+            let mut negotiated = false;
+            // Set by the preamble frame a client may send before anything
+            // else
+            let mut peer_identity: Option<String> = None;
+            let mut preamble = true;
+            // The amounts queued since BEGIN_BATCH. An open batch is
+            // simply dropped if the connection closes, which rolls it
+            // back
+            let mut batch: Option<Vec<Decimal>> = None;
+
+            loop {
+                let next = read_message(&mut message, &mut read);
+                let action = if let Some(idle_timeout) = config.get().idle_timeout {
+                    switchy::unsync::select! {
+                        action = next.fuse() => action,
+                        () = switchy::unsync::time::sleep(idle_timeout) => {
+                            log::debug!(
+                                "[{addr}] closing connection after being idle for {idle_timeout:?}"
+                            );
+                            metrics::increment(&metrics::IDLE_TIMEOUTS_TOTAL);
+                            // Best-effort, the connection is closed
+                            // either way
+                            if let Err(e) = write.idle_timeout(idle_timeout).await {
+                                log::debug!("[{addr}] Failed to write idle timeout: {e:?}");
                             }
-                        };
+                            break;
+                        }
+                    }
+                } else {
+                    next.await
+                };
+                let Ok(Some(action)) = action else {
+                    break;
+                };
+
+                // The preamble is stripped before the connection's actions
+                // are recorded, as if it were part of connecting
+                if std::mem::take(&mut preamble)
+                    && let Some(identity) = identity::parse_preamble(&action)
+                {
+                    log::debug!("[{addr}] identified as '{identity}'");
+                    peer_identity = Some(identity.to_string());
+                    continue;
+                }
+
+                connection.record_action(&action, action.len() + 1);
+
+                let first = !std::mem::replace(&mut negotiated, true);
+                if let Some(version) = action.strip_prefix("PROTO ").filter(|_| first) {
+                    if let Err(e) = negotiate(version, &mut write).await {
+                        log::error!("[{addr}] Failed to negotiate protocol: {e:?}");
+                    }
+                    continue;
+                }
+
+                if let Some(compression) = action.strip_prefix("COMPRESS ") {
+                    if let Err(e) = negotiate_compression(compression, &mut write).await {
+                        log::error!("[{addr}] Failed to negotiate compression: {e:?}");
+                    }
+                    continue;
+                }
+
+                log::debug!("[{addr}] parsing action={action}");
+                let Ok(action) = ServerAction::from_str(&action).inspect_err(|_| {
+                    log::error!("[{addr}] Invalid action '{action}'");
+                }) else {
+                    let error = format!("Invalid action '{action}'");
+                    let resp = write_error(&mut write, ProtocolError::InvalidAction, error).await;
+                    if let Err(e) = resp {
+                        log::error!("[{addr}] Failed to write error: {e:?}");
+                    }
+                    continue;
+                };
 
-                        if let Err(e) = resp {
-                            log::error!("[{addr}] Failed to handle action={action}: {e:?}");
+                log::info!("[{addr}] received {action} action");
+                metrics::increment(&metrics::ACTIONS_TOTAL);
+
+                hooks::delay_point("connection::before_dispatch").await;
+
+                // The config may have been reloaded since the last
+                // action
+                let current = config.get();
+                rate_limit.set_limits(
+                    current.rate_limit_capacity,
+                    current.rate_limit_refill_per_second,
+                );
+                write.set_write_timeout(current.write_timeout);
+
+                if let Err(retry_after) = rate_limit.try_acquire() {
+                    log::warn!(
+                        "[{addr}] rate limiting {action} action retry_after={retry_after:?}"
+                    );
+                    metrics::increment(&metrics::RATE_LIMITED_TOTAL);
+                    let resp = write.rate_limited(retry_after).await;
+                    if let Err(e) = resp {
+                        log::error!("[{addr}] Failed to write rate limit error: {e:?}");
+                        if matches!(e, Error::WriteTimeout(..)) {
+                            break;
                         }
                     }
+                    continue;
+                }
 
-                    log::debug!("[{addr}] client connection connection dropped");
-                });
+                if !identity::is_authorized(
+                    current.required_identity(action),
+                    peer_identity.as_deref(),
+                ) {
+                    log::warn!(
+                        "[{addr}] rejecting unauthorized {action} action from identity={peer_identity:?}"
+                    );
+                    metrics::increment(&metrics::UNAUTHORIZED_TOTAL);
+                    let resp = write
+                        .error(ProtocolError::Unauthorized, identity::UNAUTHORIZED_MESSAGE)
+                        .await;
+                    if let Err(e) = resp {
+                        log::error!("[{addr}] Failed to write unauthorized error: {e:?}");
+                        if matches!(e, Error::WriteTimeout(..)) {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+
+                if action.is_write() && bank.is_read_only() {
+                    log::debug!("[{addr}] rejecting {action} action on a replica");
+                    let resp = write
+                        .error(ProtocolError::ReadOnly, READ_ONLY_MESSAGE)
+                        .await;
+                    if let Err(e) = resp {
+                        log::error!("[{addr}] Failed to write read-only error: {e:?}");
+                        if matches!(e, Error::WriteTimeout(..)) {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+
+                let resp = {
+                    let mut ctx = ActionContext {
+                        bank: &bank,
+                        config: &config,
+                        shutdown: &shutdown,
+                        peer_identity: peer_identity.as_deref(),
+                        origin: &origin,
+                        registry: &registry,
+                        batch: &mut batch,
+                        message: &mut message,
+                        writer: &mut write,
+                        reader: &mut read,
+                    };
+                    registry.dispatch(action, &mut ctx).await
+                };
+
+                if action.closes_connection() {
+                    break;
+                }
+
+                if resp.is_ok() && action.is_write() {
+                    // The replicator only needs to know something
+                    // changed, not what
+                    let _ = commits.send(());
+                }
+
+                if let Err(e) = resp {
+                    log::error!("[{addr}] Failed to handle action={action}: {e:?}");
+                    metrics::increment(&metrics::ACTION_ERRORS_TOTAL);
+                    if matches!(e, Error::WriteTimeout(..)) {
+                        log::warn!("[{addr}] dropping stalled connection");
+                        break;
+                    }
+                    if matches!(e, Error::BudgetExceeded(..)) {
+                        log::warn!("[{addr}] dropping connection with a cut off response");
+                        break;
+                    }
+                    let resp = write_error(&mut write, (&e).into(), e.to_string()).await;
+                    if let Err(e) = resp {
+                        log::error!("[{addr}] Failed to write error: {e:?}");
+                    }
+                }
             }
 
-            log::debug!("server finished");
+            if let Some(batch) = batch {
+                log::debug!(
+                    "[{addr}] rolling back open batch of {} entries",
+                    batch.len()
+                );
+            }
 
-            Ok::<_, Error>(())
-        })
-        .await
-        .transpose()?;
+            log::debug!("[{addr}] client connection connection dropped");
+        });
+    }
 
-    log::debug!("run finished");
+    log::debug!("server finished");
 
     Ok(())
 }
 
+#[inject_yields]
+async fn negotiate(version: &str, writer: &mut ResponseWriter) -> Result<(), Error> {
+    let Ok(version) = ProtocolVersion::from_str(version) else {
+        return writer
+            .error(
+                ProtocolError::UnsupportedVersion,
+                format!("Unsupported protocol version '{version}'"),
+            )
+            .await;
+    };
+
+    log::debug!("negotiate: using protocol version={version}");
+    writer.set_version(version);
+    writer.ok(version.to_string()).await
+}
+
+/// Switches the connection's responses to length prefixed frames that are
+/// compressed when they're large. The acknowledgement is still written as a
+/// terminated message, and the client's messages stay terminated either way.
+#[inject_yields]
+async fn negotiate_compression(
+    compression: &str,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let Ok(compression) = Compression::from_str(compression) else {
+        return writer
+            .error(
+                ProtocolError::UnsupportedCompression,
+                format!("Unsupported compression '{compression}'"),
+            )
+            .await;
+    };
+
+    log::debug!("negotiate_compression: using compression={compression}");
+    writer.ok(compression.to_string()).await?;
+    writer.set_compression(Some(compression));
+    Ok(())
+}
+
+/// Errors are only reported back to the client when it negotiated protocol v2,
+/// since v1 clients don't expect a response for a failed action.
+#[inject_yields]
+async fn write_error(
+    writer: &mut ResponseWriter,
+    code: ProtocolError,
+    message: String,
+) -> Result<(), Error> {
+    if writer.version() != ProtocolVersion::V2 {
+        return Ok(());
+    }
+
+    writer.error(code, message).await
+}
+
 #[inject_yields]
 async fn read_message(
-    message: &mut String,
+    message: &mut MessageBuffer,
     reader: &mut (impl AsyncRead + Unpin),
 ) -> Result<Option<String>, Error> {
-    if let Some(index) = message.chars().position(|x| x == 0 as char) {
-        let mut remaining = message.split_off(index);
-        let value = message.clone();
-        remaining.remove(0);
-        *message = remaining;
-        return Ok(Some(value));
-    }
+    let mut buf = [0_u8; READ_CHUNK_SIZE];
 
-    let mut buf = [0_u8; 1024];
+    loop {
+        if let Some(value) = message.next_message()? {
+            return Ok(Some(value));
+        }
 
-    Ok(loop {
         let count = match reader.read(&mut buf).await {
             Ok(count) => count,
             Err(e) => {
                 log::error!("read_message: failed to read from stream: {e:?}");
-                break None;
+                return Ok(None);
             }
         };
         if count == 0 {
             log::debug!("read_message: received empty response");
-            break None;
+            return Ok(None);
         }
         log::trace!("read count={count}");
-        let value = String::from_utf8(buf[..count].to_vec())?;
-        message.push_str(&value);
-
-        if let Some(index) = value.chars().position(|x| x == 0 as char) {
-            let mut remaining = message.split_off(message.len() - value.len() + index);
-            let value = message.clone();
-            remaining.remove(0);
-            *message = remaining;
-            break Some(value);
-        }
-    })
+        message.extend(&buf[..count]);
+    }
 }
 
 #[inject_yields]
@@ -191,116 +586,537 @@ async fn write_message(
     log::debug!("write_message: writing message={message}");
     let mut bytes = message.into_bytes();
     bytes.push(0_u8);
-    Ok(stream.write_all(&bytes).await?)
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// The most bytes [`write_message_from_iter`] buffers before writing them to
+/// the stream
+pub const WRITE_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Writes `values` separated by `sep` as a single message, without building
+/// the whole message in memory first.
+///
+/// The values are written out in chunks of about [`WRITE_CHUNK_SIZE`] bytes
+/// as they're produced, and the terminator is only written after the last
+/// one, so the peer still receives a single frame. Returns the number of
+/// bytes written, including the terminator.
+///
+/// The values and separators may take at most `budget` bytes, which is
+/// checked as each value is produced, so no value past the budget is
+/// formatted. A message that runs out of budget within its first chunk
+/// hasn't been written at all, but a larger one is left cut off.
+///
+/// # Errors
+///
+/// * If a chunk fails to be written to the stream
+/// * If the message takes more than `budget` bytes
+#[inject_yields]
+pub async fn write_message_from_iter(
+    values: impl Iterator<Item = String>,
+    sep: &str,
+    budget: usize,
+    stream: &mut (impl AsyncWrite + Unpin),
+) -> Result<u64, Error> {
+    let mut buffer = Vec::with_capacity(WRITE_CHUNK_SIZE);
+    let mut meter = budget::Meter::new(budget);
+    let mut written = 0;
+
+    for (index, value) in values.enumerate() {
+        let sep = if index > 0 { sep } else { "" };
+        meter
+            .add(sep.len() + value.len())
+            .map_err(|e| budget::BudgetExceeded { written, ..e })?;
+
+        buffer.extend_from_slice(sep.as_bytes());
+        buffer.extend_from_slice(value.as_bytes());
+
+        if buffer.len() >= WRITE_CHUNK_SIZE {
+            log::trace!(
+                "write_message_from_iter: writing chunk len={}",
+                buffer.len()
+            );
+            stream.write_all(&buffer).await?;
+            written += buffer.len() as u64;
+            buffer.clear();
+        }
+    }
+
+    buffer.push(0_u8);
+    stream.write_all(&buffer).await?;
+    stream.flush().await?;
+    written += buffer.len() as u64;
+
+    log::debug!("write_message_from_iter: wrote message len={written}");
+
+    Ok(written)
 }
 
 #[inject_yields]
 async fn list_transactions(
-    bank: &impl Bank,
-    writer: &mut (impl AsyncWrite + Unpin),
+    bank: &dyn Bank,
+    budget: usize,
+    writer: &mut ResponseWriter,
 ) -> Result<(), Error> {
-    let message = {
+    // Copy the transactions out so that the read guard isn't held while the
+    // response is written, which can take a while for a large ledger. Only
+    // the ones that fit in the budget are copied, so a listing that's
+    // refused doesn't copy the whole ledger first. The writer enforces the
+    // budget again as the lines are written.
+    let transactions = {
         let transactions = bank.list_transactions().await?;
+        let mut meter = budget::Meter::new(budget);
+        transactions
+            .iter()
+            .enumerate()
+            .map(|(index, x)| {
+                meter
+                    .add(budget::display_len(x) + usize::from(index > 0))
+                    .map(|()| x.clone())
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
 
-        if transactions.is_empty() {
-            log::debug!("list_transactions: no transactions");
+    let transactions = match transactions {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            log::debug!("list_transactions: {e}");
+            return writer
+                .error(ProtocolError::ResourceExhausted, RESOURCE_EXHAUSTED_MESSAGE)
+                .await;
         }
+    };
 
-        transactions
-            .iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>()
-            .join("\n")
+    if transactions.is_empty() {
+        log::debug!("list_transactions: no transactions");
+    }
+
+    writer
+        .ok_lines_within_budget(transactions.into_iter().map(|x| x.to_string()), budget)
+        .await
+}
+
+#[inject_yields]
+async fn search_transactions(
+    bank: &dyn Bank,
+    filter: &str,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let filter = match TransactionFilter::from_str(filter) {
+        Ok(filter) => filter,
+        Err(e) => {
+            log::debug!("search_transactions: rejecting filter: {e}");
+            writer
+                .error(ProtocolError::InvalidInput, e.to_string())
+                .await?;
+            return Ok(());
+        }
     };
 
-    write_message(message, writer).await?;
+    let transactions = bank.search(&filter).await?;
 
-    Ok(())
+    writer
+        .ok_lines(transactions.into_iter().map(|x| x.to_string()))
+        .await
 }
 
 #[inject_yields]
 async fn get_transaction(
-    bank: &impl Bank,
-    message: &mut String,
-    writer: &mut (impl AsyncWrite + Unpin),
-    reader: &mut (impl AsyncRead + Unpin),
+    bank: &dyn Bank,
+    id: &str,
+    writer: &mut ResponseWriter,
 ) -> Result<(), Error> {
-    write_message("Enter the transaction ID:", writer).await?;
-    let Some(message) = read_message(message, reader).await? else {
-        use std::io::{Error, ErrorKind};
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            "get_transaction: No message received from TCP client",
-        )
-        .into());
-    };
-    let id = message.parse::<TransactionId>()?;
+    let id = id.parse::<TransactionId>()?;
     if let Some(transaction) = bank.get_transaction(id).await? {
-        write_message(transaction.to_string(), writer).await?;
+        writer.ok(transaction.to_string()).await?;
     } else {
-        write_message("Transaction not found", writer).await?;
+        writer
+            .error(ProtocolError::NotFound, "Transaction not found")
+            .await?;
     }
     Ok(())
 }
 
 #[inject_yields]
 async fn create_transaction(
-    bank: &impl Bank,
-    message: &mut String,
-    writer: &mut (impl AsyncWrite + Unpin),
-    reader: &mut (impl AsyncRead + Unpin),
+    bank: &dyn Bank,
+    origin: &Origin,
+    batch: Option<&mut Vec<Decimal>>,
+    amount: &str,
+    writer: &mut ResponseWriter,
 ) -> Result<(), Error> {
-    write_message("Enter the transaction amount:", writer).await?;
-    let Some(message) = read_message(message, reader).await? else {
-        use std::io::{Error, ErrorKind};
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            "create_transaction: No message received from TCP client",
-        )
-        .into());
+    let amount = match parse_amount(amount) {
+        Ok(amount) => amount,
+        Err(e) => {
+            log::debug!("create_transaction: rejecting amount: {e}");
+            writer
+                .error(ProtocolError::InvalidInput, e.to_string())
+                .await?;
+            return Ok(());
+        }
     };
-    let transaction = bank
-        .create_transaction(Decimal::from_str(&message)?)
-        .await?;
-    write_message(transaction.to_string(), writer).await?;
+    if let Some(batch) = batch {
+        batch.push(amount);
+        log::debug!("create_transaction: batched amount={amount}");
+        return writer.ok(format!("batched={}", batch.len())).await;
+    }
+    let transaction = match bank.create_transaction(amount, origin).await {
+        Ok(transaction) => transaction,
+        Err(bank::Error::ReadOnly) => {
+            return writer
+                .error(ProtocolError::ReadOnly, READ_ONLY_MESSAGE)
+                .await;
+        }
+        Err(e) => return Err(e.into()),
+    };
+    writer.ok(transaction.to_string()).await?;
     Ok(())
 }
 
 #[inject_yields]
 async fn void_transaction(
-    bank: &impl Bank,
-    message: &mut String,
-    writer: &mut (impl AsyncWrite + Unpin),
-    reader: &mut (impl AsyncRead + Unpin),
+    bank: &dyn Bank,
+    origin: &Origin,
+    id: &str,
+    writer: &mut ResponseWriter,
 ) -> Result<(), Error> {
-    write_message("Enter the transaction ID:", writer).await?;
-    let Some(message) = read_message(message, reader).await? else {
-        use std::io::{Error, ErrorKind};
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            "void_transaction: No message received from TCP client",
-        )
-        .into());
+    let id = id.parse::<TransactionId>()?;
+    let transaction = match bank.void_transaction(id, origin).await {
+        Ok(transaction) => transaction,
+        Err(bank::Error::ReadOnly) => {
+            return writer
+                .error(ProtocolError::ReadOnly, READ_ONLY_MESSAGE)
+                .await;
+        }
+        Err(e) => return Err(e.into()),
     };
-    let id = message.parse::<TransactionId>()?;
-    if let Some(transaction) = bank.void_transaction(id).await? {
-        write_message(transaction.to_string(), writer).await?;
+    if let Some(transaction) = transaction {
+        writer.ok(transaction.to_string()).await?;
     } else {
-        write_message("Transaction not found", writer).await?;
+        writer
+            .error(ProtocolError::NotFound, "Transaction not found")
+            .await?;
     }
     Ok(())
 }
 
+/// Opens a batch on the connection. Until it's committed or rolled back,
+/// the connection's `CREATE_TRANSACTION`s are queued in the batch instead of
+/// being committed.
 #[inject_yields]
-async fn health(stream: &mut (impl AsyncWrite + Unpin)) -> Result<(), Error> {
-    write_message("healthy", stream).await
+async fn begin_batch(
+    batch: &mut Option<Vec<Decimal>>,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    if batch.is_some() {
+        return writer
+            .error(ProtocolError::InvalidAction, "A batch is already open")
+            .await;
+    }
+
+    *batch = Some(vec![]);
+    writer.ok("batch=open").await
 }
 
+/// Commits every transaction queued in the connection's batch at once,
+/// responding with the created transactions in id order.
 #[inject_yields]
-async fn get_balance(
-    bank: &impl Bank,
-    stream: &mut (impl AsyncWrite + Unpin),
+async fn commit_batch(
+    bank: &dyn Bank,
+    origin: &Origin,
+    batch: &mut Option<Vec<Decimal>>,
+    writer: &mut ResponseWriter,
 ) -> Result<(), Error> {
+    let Some(amounts) = batch.take() else {
+        return writer
+            .error(ProtocolError::InvalidAction, "No batch is open")
+            .await;
+    };
+
+    let transactions = match bank.create_transactions_atomic(amounts, origin).await {
+        Ok(transactions) => transactions,
+        Err(bank::Error::ReadOnly) => {
+            return writer
+                .error(ProtocolError::ReadOnly, READ_ONLY_MESSAGE)
+                .await;
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    writer
+        .ok_lines(transactions.into_iter().map(|x| x.to_string()))
+        .await
+}
+
+/// Discards the connection's batch without committing any of it.
+#[inject_yields]
+async fn rollback_batch(
+    batch: &mut Option<Vec<Decimal>>,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let Some(amounts) = batch.take() else {
+        return writer
+            .error(ProtocolError::InvalidAction, "No batch is open")
+            .await;
+    };
+
+    writer.ok(format!("rolled_back={}", amounts.len())).await
+}
+
+#[inject_yields]
+async fn health(stream: &mut ResponseWriter) -> Result<(), Error> {
+    stream.ok("healthy").await
+}
+
+#[inject_yields]
+async fn version(stream: &mut ResponseWriter) -> Result<(), Error> {
+    stream.ok(VersionInfo::current().to_string()).await
+}
+
+#[inject_yields]
+async fn list_connections(writer: &mut ResponseWriter) -> Result<(), Error> {
+    let message = connections::list()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    writer.ok(message).await
+}
+
+#[inject_yields]
+async fn get_balance(bank: &dyn Bank, stream: &mut ResponseWriter) -> Result<(), Error> {
     let balance = bank.get_balance().await?;
-    write_message(format!("${balance}"), stream).await
+    stream.ok(format!("${balance}")).await
+}
+
+#[inject_yields]
+async fn get_statement(
+    bank: &dyn Bank,
+    start: &str,
+    end: &str,
+    budget: usize,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let start = start.parse::<TransactionId>()?;
+    let end = end.parse::<TransactionId>()?;
+
+    let lines = bank.statement(start..=end).await?;
+
+    writer
+        .ok_lines_within_budget(lines.into_iter().map(|x| x.to_string()), budget)
+        .await
+}
+
+/// Writes every transaction in the ledger as a JSON line, in the same format
+/// as the persisted `transactions.db`.
+#[inject_yields]
+async fn export_ledger(
+    bank: &dyn Bank,
+    budget: usize,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let transactions = bank.export().await?;
+
+    // A `Transaction` only has plain fields, so serializing it can't fail
+    writer
+        .ok_lines_within_budget(
+            transactions
+                .into_iter()
+                .map(|x| serde_json::to_string(&x).unwrap()),
+            budget,
+        )
+        .await
+}
+
+/// Writes the last `count` records of the audit log as JSON lines, oldest
+/// first.
+#[inject_yields]
+async fn tail_audit(
+    bank: &dyn Bank,
+    count: &str,
+    budget: usize,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let count = count.parse::<usize>()?;
+
+    let lines = bank
+        .tail_audit(count)
+        .await?
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(bank::Error::from)?;
+
+    writer
+        .ok_lines_within_budget(lines.into_iter(), budget)
+        .await
+}
+
+#[inject_yields]
+async fn import_ledger(
+    bank: &dyn Bank,
+    origin: &Origin,
+    ledger: &str,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let mut transactions = vec![];
+
+    for (index, line) in ledger.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Transaction>(line) {
+            Ok(transaction) => transactions.push(transaction),
+            Err(e) => {
+                log::debug!("import_ledger: rejecting line {}: {e}", index + 1);
+                writer
+                    .error(
+                        ProtocolError::InvalidInput,
+                        format!("Invalid transaction on line {}: {e}", index + 1),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let count = transactions.len();
+
+    match bank.import(transactions, origin).await {
+        Ok(()) => writer.ok(format!("imported={count}")).await,
+        Err(ImportError::Bank(bank::Error::ReadOnly)) => {
+            writer
+                .error(ProtocolError::ReadOnly, READ_ONLY_MESSAGE)
+                .await
+        }
+        Err(ImportError::Bank(e)) => Err(e.into()),
+        Err(e) => {
+            log::debug!("import_ledger: rejecting ledger: {e}");
+            writer
+                .error(ProtocolError::InvalidInput, e.to_string())
+                .await
+        }
+    }
+}
+
+/// Applies the transactions streamed by the primary's replicator, responding
+/// with the id of each transaction once it's persisted. Before streaming, the
+/// replica responds with the id of the last transaction it has so the
+/// primary knows where to resume.
+#[inject_yields]
+async fn replicate(
+    bank: &dyn Bank,
+    origin: &Origin,
+    message: &mut MessageBuffer,
+    writer: &mut ResponseWriter,
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<(), Error> {
+    if !bank.is_read_only() {
+        return writer
+            .error(
+                ProtocolError::InvalidAction,
+                "Only a replica accepts replicated transactions",
+            )
+            .await;
+    }
+
+    let last = bank.list_transactions().await?.last().map_or(0, |x| x.id);
+    writer.ok(last.to_string()).await?;
+
+    while let Some(record) = read_message(message, reader).await? {
+        let transaction =
+            serde_json::from_str::<Transaction>(&record).map_err(bank::Error::from)?;
+        let id = transaction.id;
+
+        match bank.apply_replicated(transaction, origin).await {
+            Ok(()) => writer.ok(id.to_string()).await?,
+            Err(ReplicateError::Bank(e)) => return Err(e.into()),
+            Err(e) => {
+                log::warn!("replicate: rejecting replicated transaction: {e}");
+                return writer
+                    .error(ProtocolError::InvalidInput, e.to_string())
+                    .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How many times a replica checks whether it caught up before giving up on
+/// being promoted
+const PROMOTE_POLLS: u32 = 100;
+const PROMOTE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Promotes a replica to the primary once it has every transaction up to the
+/// given id, which should be the id that `DEMOTE` returned from the old
+/// primary. Promoting an up-to-date replica this way guarantees the id
+/// sequence continues without reusing any id the old primary handed out.
+#[inject_yields]
+async fn promote(
+    bank: &dyn Bank,
+    expected: &str,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let expected = expected.parse::<TransactionId>()?;
+
+    let mut last = 0;
+
+    for _ in 0..PROMOTE_POLLS {
+        last = bank.list_transactions().await?.last().map_or(0, |x| x.id);
+        if last >= expected {
+            bank.set_read_only(false).await?;
+            log::info!("promote: promoted to primary at id={last}");
+            return writer.ok(ServerRole::Primary.to_string()).await;
+        }
+        switchy::unsync::time::sleep(PROMOTE_POLL_INTERVAL).await;
+    }
+
+    writer
+        .error(
+            ProtocolError::Lagging,
+            format!("Replica only has transactions up to id={last}, expected id={expected}"),
+        )
+        .await
+}
+
+/// Makes the server read-only, responding with the id of the last
+/// transaction it committed. The server keeps replicating until the replica
+/// has that transaction.
+#[inject_yields]
+async fn demote(bank: &dyn Bank, writer: &mut ResponseWriter) -> Result<(), Error> {
+    let last = bank.set_read_only(true).await?.unwrap_or(0);
+    log::info!("demote: demoted to replica at id={last}");
+    writer.ok(last.to_string()).await
+}
+
+/// Swaps in a new config built from the JSON overrides sent by the client,
+/// responding with the effective values of the reloadable fields. Invalid
+/// overrides are rejected without changing the active config.
+#[inject_yields]
+async fn reload_config(
+    config: &SharedConfig,
+    overrides: &str,
+    writer: &mut ResponseWriter,
+) -> Result<(), Error> {
+    let reloaded = serde_json::from_str::<ReloadableConfig>(overrides)
+        .map_err(config::ConfigError::from)
+        .and_then(|overrides| config.reload(&overrides));
+
+    match reloaded {
+        Ok(reloaded) => {
+            let effective = reloaded.reloadable();
+            log::info!("reload_config: reloaded config={effective:?}");
+            let serialized = serde_json::to_string(&effective).map_err(bank::Error::from)?;
+            writer.ok(serialized).await
+        }
+        Err(e) => {
+            log::debug!("reload_config: rejecting config: {e}");
+            writer
+                .error(ProtocolError::InvalidInput, e.to_string())
+                .await
+        }
+    }
 }
@@ -0,0 +1,82 @@
+use std::str::Utf8Error;
+
+use bytes::BytesMut;
+
+/// The byte that terminates each message on a connection that didn't
+/// negotiate compression
+pub const TERMINATOR: u8 = 0;
+
+/// How many bytes are read from a connection at a time
+pub const READ_CHUNK_SIZE: usize = 1024;
+
+/// A complete message that isn't valid UTF-8
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Message of {len} bytes is not valid UTF-8: {source}")]
+pub struct InvalidMessage {
+    pub len: usize,
+    #[source]
+    pub source: Utf8Error,
+}
+
+/// The bytes read from a connection that haven't been returned as a message
+/// yet.
+///
+/// A connection keeps a single buffer for as long as it's open, so the bytes
+/// read past the end of one message stay in place for the next one instead of
+/// being copied into a new buffer. Each message is only validated as UTF-8
+/// once it's complete, so a character split across two reads is fine.
+#[derive(Debug, Default)]
+pub struct MessageBuffer {
+    bytes: BytesMut,
+    /// How many bytes at the front of the buffer are known not to contain a
+    /// terminator, so they aren't searched again after the next read
+    searched: usize,
+}
+
+impl MessageBuffer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many bytes are buffered
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Appends bytes read from the connection.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete message off the front of the buffer, or
+    /// returns `None` if the buffer doesn't hold one yet.
+    ///
+    /// # Errors
+    ///
+    /// * If the message isn't valid UTF-8, in which case it's dropped from
+    ///   the buffer
+    pub fn next_message(&mut self) -> Result<Option<String>, InvalidMessage> {
+        let Some(index) = memchr::memchr(TERMINATOR, &self.bytes[self.searched..]) else {
+            self.searched = self.bytes.len();
+            return Ok(None);
+        };
+
+        let frame = self.bytes.split_to(self.searched + index + 1).freeze();
+        self.searched = 0;
+        let frame = &frame[..frame.len() - 1];
+
+        std::str::from_utf8(frame)
+            .map(|x| Some(x.to_string()))
+            .map_err(|source| InvalidMessage {
+                len: frame.len(),
+                source,
+            })
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    fmt::Write as _,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::write_timeout_count;
+
+pub static CONNECTIONS_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static ACTIONS_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static ACTION_ERRORS_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static RATE_LIMITED_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static IDLE_TIMEOUTS_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static UNAUTHORIZED_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Increments one of the counters above
+pub fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Renders the server's counters in the Prometheus text exposition format.
+///
+/// The counters are process-wide and never reset, so they only ever
+/// increase for the lifetime of the process.
+#[must_use]
+pub fn render() -> String {
+    [
+        (
+            "dst_demo_connections_total",
+            "Connections accepted",
+            CONNECTIONS_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_actions_total",
+            "Actions received",
+            ACTIONS_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_action_errors_total",
+            "Actions that failed to be handled",
+            ACTION_ERRORS_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_rate_limited_total",
+            "Actions rejected by the rate limiter",
+            RATE_LIMITED_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_idle_timeouts_total",
+            "Connections closed because no action arrived within the idle timeout",
+            IDLE_TIMEOUTS_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_unauthorized_total",
+            "Actions rejected because the connection's identity wasn't allowed them",
+            UNAUTHORIZED_TOTAL.load(Ordering::SeqCst),
+        ),
+        (
+            "dst_demo_write_timeouts_total",
+            "Connections dropped because a response write timed out",
+            write_timeout_count(),
+        ),
+    ]
+    .into_iter()
+    .fold(String::new(), |mut rendered, (name, help, value)| {
+        let _ = write!(
+            rendered,
+            "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n"
+        );
+        rendered
+    })
+}
@@ -0,0 +1,564 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use strum::{AsRefStr, EnumString};
+use switchy::unsync::{
+    futures::FutureExt as _,
+    inject_yields,
+    io::{AsyncWrite, AsyncWriteExt as _},
+    task,
+};
+
+use crate::{
+    Error, WRITE_TIMEOUT_COUNT,
+    budget::Meter,
+    framing::{Compression, FrameEncoder},
+    write_message, write_message_from_iter,
+};
+
+/// The message a replica responds with when it's sent a write
+pub const READ_ONLY_MESSAGE: &str = "This server is a read-only replica";
+
+/// The message a server responds with when a response would take more than
+/// its response budget
+pub const RESOURCE_EXHAUSTED_MESSAGE: &str = "Response exceeds the server's memory budget";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+pub enum ProtocolVersion {
+    #[default]
+    #[strum(serialize = "1")]
+    V1,
+    #[strum(serialize = "2")]
+    V2,
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ProtocolError {
+    UnsupportedVersion,
+    UnsupportedCompression,
+    InvalidAction,
+    InvalidInput,
+    NotFound,
+    RateLimited,
+    IdleTimeout,
+    ReadOnly,
+    Unauthorized,
+    Lagging,
+    ResourceExhausted,
+    Internal,
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl From<&Error> for ProtocolError {
+    fn from(value: &Error) -> Self {
+        match value {
+            Error::InvalidMessage(..)
+            | Error::Parse(..)
+            | Error::Decimal(..)
+            | Error::ParseInt(..) => Self::InvalidInput,
+            Error::Async(..)
+            | Error::IO(..)
+            | Error::Tcp(..)
+            | Error::Bank(..)
+            | Error::WriteTimeout(..)
+            | Error::Replication(..) => Self::Internal,
+            Error::BudgetExceeded(..) => Self::ResourceExhausted,
+        }
+    }
+}
+
+/// A structured protocol v2 response line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Ok(String),
+    Prompt(String),
+    Err {
+        code: ProtocolError,
+        message: String,
+    },
+    /// A frame the server sends without it being the response to the
+    /// client's current request. Clients skip these while waiting for a
+    /// response.
+    Push(String),
+}
+
+impl std::fmt::Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok(payload) if payload.is_empty() => f.write_str("OK"),
+            Self::Ok(payload) => f.write_fmt(format_args!("OK {payload}")),
+            Self::Prompt(field) => f.write_fmt(format_args!("PROMPT {field}")),
+            Self::Err { code, message } => f.write_fmt(format_args!("ERR {code} {message}")),
+            Self::Push(payload) => f.write_fmt(format_args!("PUSH {payload}")),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResponseFromStrError {
+    #[error("Invalid response kind")]
+    InvalidKind,
+    #[error("Missing prompt field")]
+    MissingField,
+    #[error("Missing error code")]
+    MissingCode,
+    #[error(transparent)]
+    Parse(#[from] strum::ParseError),
+}
+
+impl std::str::FromStr for Response {
+    type Err = ResponseFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, ResponseFromStrError> {
+        let (kind, rest) = s.split_once(' ').unwrap_or((s, ""));
+
+        Ok(match kind {
+            "OK" => Self::Ok(rest.to_string()),
+            "PROMPT" => {
+                if rest.is_empty() {
+                    return Err(ResponseFromStrError::MissingField);
+                }
+                Self::Prompt(rest.to_string())
+            }
+            "ERR" => {
+                let (code, message) = rest.split_once(' ').unwrap_or((rest, ""));
+                if code.is_empty() {
+                    return Err(ResponseFromStrError::MissingCode);
+                }
+                Self::Err {
+                    code: code.parse()?,
+                    message: message.to_string(),
+                }
+            }
+            "PUSH" => Self::Push(rest.to_string()),
+            _ => return Err(ResponseFromStrError::InvalidKind),
+        })
+    }
+}
+
+/// A complete response frame queued for the connection's writer task, along
+/// with where to report whether it was written.
+struct Frame {
+    payload: Payload,
+    write_timeout: Duration,
+    compressed: bool,
+    written: oneshot::Sender<Result<(), Error>>,
+}
+
+enum Payload {
+    Message(String),
+    /// Lines that are formatted and written a chunk at a time, so that a large
+    /// response is never held in memory all at once. If they take more than
+    /// `budget` bytes before any of them were written, `over_budget` is
+    /// written instead.
+    Lines {
+        lines: Box<dyn Iterator<Item = String> + Send>,
+        budget: usize,
+        over_budget: String,
+    },
+}
+
+/// Writes responses in the format of the protocol version negotiated by the
+/// connection.
+///
+/// v1 clients get the original human readable messages while v2 clients get
+/// structured `OK`/`PROMPT`/`ERR` lines. Once the connection negotiates
+/// compression, each response is written as a length prefixed frame instead
+/// of a terminated message.
+///
+/// The frames are written by a dedicated writer task that owns the stream, so
+/// dropping the connection's task (e.g. when it's cancelled) can't cut a frame
+/// off part way through. The writer task finishes writing any queued frames
+/// before it exits.
+pub struct ResponseWriter {
+    frames: flume::Sender<Frame>,
+    version: ProtocolVersion,
+    compression: Option<Compression>,
+    write_timeout: Duration,
+    bytes_written: Arc<AtomicU64>,
+}
+
+impl ResponseWriter {
+    /// Spawns the writer task for the connection's write half.
+    pub fn spawn(
+        writer: impl AsyncWrite + Unpin + Send + 'static,
+        write_timeout: Duration,
+    ) -> Self {
+        let (frames, rx) = flume::unbounded();
+
+        let bytes_written = Arc::new(AtomicU64::new(0));
+
+        task::spawn(drain(rx, writer, bytes_written.clone()));
+
+        Self {
+            frames,
+            version: ProtocolVersion::V1,
+            compression: None,
+            write_timeout,
+            bytes_written,
+        }
+    }
+
+    #[must_use]
+    pub const fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    pub const fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = version;
+    }
+
+    #[must_use]
+    pub const fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Sets the compression of the frames queued from now on.
+    pub const fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    /// A counter of the bytes of the frames written so far, including their
+    /// terminators. It's updated by the writer task as each frame is flushed.
+    #[must_use]
+    pub fn bytes_written(&self) -> Arc<AtomicU64> {
+        self.bytes_written.clone()
+    }
+
+    /// Sets how long each frame queued from now on may take to write.
+    pub const fn set_write_timeout(&mut self, write_timeout: Duration) {
+        self.write_timeout = write_timeout;
+    }
+
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn ok(&mut self, payload: impl Into<String>) -> Result<(), Error> {
+        let payload = payload.into();
+        match self.version {
+            ProtocolVersion::V1 => self.write(payload).await,
+            ProtocolVersion::V2 => self.write(Response::Ok(payload).to_string()).await,
+        }
+    }
+
+    /// Like [`Self::ok`] with the lines joined by newlines, except that the
+    /// payload is written as the lines are produced instead of being built up
+    /// front.
+    ///
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn ok_lines(
+        &mut self,
+        lines: impl Iterator<Item = String> + Send + 'static,
+    ) -> Result<(), Error> {
+        self.ok_lines_within_budget(lines, usize::MAX).await
+    }
+
+    /// Like [`Self::ok_lines`], except that the response may take at most
+    /// `budget` bytes. The budget is enforced as the lines are written, and a
+    /// response that runs out of it before any of it was written is refused
+    /// with `resource_exhausted` instead.
+    ///
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    /// * If the response ran out of budget after part of it was written, which
+    ///   leaves it cut off
+    #[inject_yields]
+    pub async fn ok_lines_within_budget(
+        &mut self,
+        lines: impl Iterator<Item = String> + Send + 'static,
+        budget: usize,
+    ) -> Result<(), Error> {
+        let lines: Box<dyn Iterator<Item = String> + Send> = match self.version {
+            ProtocolVersion::V1 => Box::new(lines),
+            ProtocolVersion::V2 => {
+                let mut lines = lines.peekable();
+                if lines.peek().is_none() {
+                    return self.ok(String::new()).await;
+                }
+                Box::new(lines.enumerate().map(
+                    |(index, x)| {
+                        if index == 0 { format!("OK {x}") } else { x }
+                    },
+                ))
+            }
+        };
+
+        let over_budget =
+            self.error_message(ProtocolError::ResourceExhausted, RESOURCE_EXHAUSTED_MESSAGE);
+
+        self.send(Payload::Lines {
+            lines,
+            budget,
+            over_budget,
+        })
+        .await
+    }
+
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn prompt(&mut self, field: &str, text: &str) -> Result<(), Error> {
+        match self.version {
+            ProtocolVersion::V1 => self.write(text.to_string()).await,
+            ProtocolVersion::V2 => {
+                self.write(Response::Prompt(field.to_string()).to_string())
+                    .await
+            }
+        }
+    }
+
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn error(
+        &mut self,
+        code: ProtocolError,
+        message: impl Into<String>,
+    ) -> Result<(), Error> {
+        let message = self.error_message(code, message.into());
+        self.write(message).await
+    }
+
+    fn error_message(&self, code: ProtocolError, message: impl Into<String>) -> String {
+        let message = message.into();
+        match self.version {
+            ProtocolVersion::V1 => message,
+            ProtocolVersion::V2 => Response::Err { code, message }.to_string(),
+        }
+    }
+
+    /// Rejects an action because the connection is rate limited. This is
+    /// always written as a structured error, regardless of the protocol
+    /// version, so that any client can tell it apart from a normal response.
+    ///
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn rate_limited(&mut self, retry_after: Duration) -> Result<(), Error> {
+        let message = format!("retry_after_ms={}", retry_after.as_millis().max(1));
+        self.write(
+            Response::Err {
+                code: ProtocolError::RateLimited,
+                message,
+            }
+            .to_string(),
+        )
+        .await
+    }
+
+    /// Tells the client its connection is being closed for being idle. Like
+    /// [`Self::rate_limited`], this is always written as a structured error.
+    ///
+    /// # Errors
+    ///
+    /// * If the message fails to be written to the stream
+    #[inject_yields]
+    pub async fn idle_timeout(&mut self, idle_timeout: Duration) -> Result<(), Error> {
+        let message = format!("idle_timeout_ms={}", idle_timeout.as_millis());
+        self.write(
+            Response::Err {
+                code: ProtocolError::IdleTimeout,
+                message,
+            }
+            .to_string(),
+        )
+        .await
+    }
+
+    #[inject_yields]
+    async fn write(&self, message: String) -> Result<(), Error> {
+        self.send(Payload::Message(message)).await
+    }
+
+    /// Queues the payload for the writer task and waits until it's been
+    /// written and flushed.
+    #[inject_yields]
+    async fn send(&self, payload: Payload) -> Result<(), Error> {
+        let (written, rx) = oneshot::channel();
+
+        let frame = Frame {
+            payload,
+            write_timeout: self.write_timeout,
+            compressed: self.compression.is_some(),
+            written,
+        };
+
+        if self.frames.send(frame).is_err() {
+            return Err(writer_closed());
+        }
+
+        rx.await.unwrap_or_else(|_| Err(writer_closed()))
+    }
+}
+
+fn writer_closed() -> Error {
+    std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "The connection's writer task has stopped",
+    )
+    .into()
+}
+
+/// Writes each queued frame in full, giving up on the connection if the peer
+/// doesn't accept a frame within the write timeout so that a stalled reader
+/// can't wedge the writer task.
+#[inject_yields]
+async fn drain(
+    frames: flume::Receiver<Frame>,
+    mut writer: impl AsyncWrite + Unpin,
+    bytes_written: Arc<AtomicU64>,
+) {
+    while let Ok(Frame {
+        payload,
+        write_timeout,
+        compressed,
+        written,
+    }) = frames.recv_async().await
+    {
+        let write = async {
+            match payload {
+                Payload::Message(message) => write_payload(message, compressed, &mut writer).await,
+                Payload::Lines {
+                    lines,
+                    budget,
+                    over_budget,
+                } => {
+                    let resp = if compressed {
+                        write_frame_from_iter(lines, "\n", budget, &mut writer).await
+                    } else {
+                        write_message_from_iter(lines, "\n", budget, &mut writer).await
+                    };
+                    match resp {
+                        Err(Error::BudgetExceeded(e)) if e.written == 0 => {
+                            log::debug!("drain: {e}, refusing the response");
+                            write_payload(over_budget, compressed, &mut writer).await
+                        }
+                        resp => resp,
+                    }
+                }
+            }
+        };
+
+        let resp = switchy::unsync::select! {
+            resp = write.fuse() => resp.map(|len| {
+                bytes_written.fetch_add(len, Ordering::Relaxed);
+            }),
+            () = switchy::unsync::time::sleep(write_timeout) => {
+                WRITE_TIMEOUT_COUNT.fetch_add(1, Ordering::SeqCst);
+                Err(Error::WriteTimeout(write_timeout))
+            }
+        };
+        // A frame that was cut off can't be followed by another one
+        let cut_off = matches!(
+            resp,
+            Err(Error::WriteTimeout(..) | Error::BudgetExceeded(..))
+        );
+
+        // The connection's task may have gone away while waiting, which is
+        // fine since the frame was still written in full
+        let _ = written.send(resp);
+
+        if cut_off {
+            break;
+        }
+    }
+
+    log::debug!("drain: writer task finished");
+}
+
+/// Writes a whole message, as a frame if the connection is compressed,
+/// returning the number of bytes written.
+#[inject_yields]
+async fn write_payload(
+    message: String,
+    compressed: bool,
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u64, Error> {
+    if compressed {
+        return write_frame(message.as_bytes(), writer).await;
+    }
+
+    let len = message.len() as u64 + 1;
+    write_message(message, writer).await.map(|()| len)
+}
+
+/// Writes the payload as a single length prefixed frame, returning the number
+/// of bytes written. Unlike [`write_message_from_iter`], the whole frame is
+/// built up front since its header holds the length of the compressed body.
+#[inject_yields]
+async fn write_frame(payload: &[u8], writer: &mut (impl AsyncWrite + Unpin)) -> Result<u64, Error> {
+    let mut encoder = FrameEncoder::new();
+    encoder.write(payload);
+
+    write_encoded(encoder, writer).await
+}
+
+/// Writes `values` separated by `sep` as a single length prefixed frame,
+/// deflating them as they're produced rather than joining them into one
+/// payload first. The compressed body is still built up in full before it's
+/// written, but the lines are bounded by `budget`, which counts them before
+/// they're compressed. Nothing is written if they run out of it.
+#[inject_yields]
+async fn write_frame_from_iter(
+    values: impl Iterator<Item = String>,
+    sep: &str,
+    budget: usize,
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u64, Error> {
+    let mut encoder = FrameEncoder::new();
+    let mut meter = Meter::new(budget);
+
+    for (index, value) in values.enumerate() {
+        if index > 0 {
+            meter.add(sep.len())?;
+            encoder.write(sep.as_bytes());
+        }
+        meter.add(value.len())?;
+        encoder.write(value.as_bytes());
+    }
+
+    write_encoded(encoder, writer).await
+}
+
+#[inject_yields]
+async fn write_encoded(
+    encoder: FrameEncoder,
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u64, Error> {
+    let payload_len = encoder.payload_len();
+    let frame = encoder.finish();
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+
+    log::debug!(
+        "write_frame: wrote frame len={} payload_len={payload_len}",
+        frame.len(),
+    );
+
+    Ok(frame.len() as u64)
+}
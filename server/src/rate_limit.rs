@@ -0,0 +1,66 @@
+use std::time::{Duration, SystemTime};
+
+/// A per-connection token bucket. Each action takes a token, and tokens are
+/// refilled continuously based on the (possibly simulated) time.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    tokens: f64,
+    last_refill: SystemTime,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    #[must_use]
+    pub fn new(capacity: u32, refill_per_second: u32) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            refill_per_second: f64::from(refill_per_second),
+            tokens: f64::from(capacity),
+            last_refill: switchy::time::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = switchy::time::now();
+        let elapsed = now.duration_since(self.last_refill).unwrap_or_default();
+
+        self.tokens = elapsed
+            .as_secs_f64()
+            .mul_add(self.refill_per_second, self.tokens)
+            .min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Changes the bucket's capacity and refill rate, keeping the tokens it
+    /// has accumulated up to the new capacity.
+    pub fn set_limits(&mut self, capacity: u32, refill_per_second: u32) {
+        self.refill();
+        self.capacity = f64::from(capacity);
+        self.refill_per_second = f64::from(refill_per_second);
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    /// Takes a token from the bucket.
+    ///
+    /// # Errors
+    ///
+    /// * If the bucket is exhausted, with how long until a token is available
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.refill();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        if self.refill_per_second <= 0.0 {
+            return Err(Duration::MAX);
+        }
+
+        Err(Duration::from_secs_f64(
+            (1.0 - self.tokens) / self.refill_per_second,
+        ))
+    }
+}
@@ -0,0 +1,143 @@
+use std::{str::FromStr as _, time::Duration};
+
+use strum::{AsRefStr, EnumString};
+use switchy::{
+    tcp::{GenericTcpStream, TcpStream},
+    unsync::{futures::FutureExt as _, inject_yields, io::AsyncRead},
+};
+
+use crate::{
+    Error, ServerAction,
+    bank::{Bank, TransactionId},
+    message::MessageBuffer,
+    protocol::{ProtocolVersion, Response},
+    read_message, write_message,
+};
+
+/// How long the replicator waits before reconnecting to the replica
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// How often an idle replicator checks whether its server was demoted or
+/// promoted
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ServerRole {
+    /// Accepts writes and replicates them to the replica, if one is configured
+    #[default]
+    Primary,
+    /// Rejects writes and applies the transactions replicated from the primary
+    Replica,
+}
+
+impl std::fmt::Display for ServerRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// Streams every committed transaction to the replica at `replica_addr` for
+/// as long as the server runs. `commits` is notified after each transaction
+/// is committed.
+///
+/// Only the primary replicates, so nothing is sent while the bank is
+/// read-only. On every (re)connect the replica reports the last transaction it
+/// has, and everything after it is resent from the ledger, so nothing is lost
+/// if the connection drops or either side restarts.
+#[inject_yields]
+pub async fn replicate(bank: impl Bank, replica_addr: String, commits: flume::Receiver<()>) {
+    loop {
+        if bank.is_read_only() {
+            wait_for_commit(&commits).await;
+            continue;
+        }
+
+        if let Err(e) = stream_to_replica(&bank, &replica_addr, &commits).await {
+            log::debug!("replicate: replication to {replica_addr} failed: {e:?}");
+            switchy::unsync::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+#[inject_yields]
+async fn stream_to_replica(
+    bank: &impl Bank,
+    replica_addr: &str,
+    commits: &flume::Receiver<()>,
+) -> Result<(), Error> {
+    let stream = TcpStream::connect(replica_addr).await?;
+    let (mut read, mut write) = stream.into_split();
+    let mut buffer = MessageBuffer::new();
+
+    write_message(format!("PROTO {}", ProtocolVersion::V2), &mut write).await?;
+    expect_ok(&mut buffer, &mut read).await?;
+
+    write_message(ServerAction::Replicate.to_string(), &mut write).await?;
+    let mut last = expect_ok(&mut buffer, &mut read)
+        .await?
+        .parse::<TransactionId>()?;
+
+    log::info!("stream_to_replica: replicating to {replica_addr} after id={last}");
+
+    loop {
+        let pending = bank
+            .list_transactions()
+            .await?
+            .iter()
+            .filter(|x| x.id > last)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for transaction in pending {
+            let serialized =
+                serde_json::to_string(&transaction).map_err(crate::bank::Error::from)?;
+            write_message(serialized, &mut write).await?;
+            last = expect_ok(&mut buffer, &mut read)
+                .await?
+                .parse::<TransactionId>()?;
+        }
+
+        // A demoted primary finishes sending what it committed before it
+        // stops, so the replica can catch up before being promoted
+        if bank.is_read_only() {
+            log::info!("stream_to_replica: no longer the primary, stopping at id={last}");
+            return Ok(());
+        }
+
+        wait_for_commit(commits).await;
+    }
+}
+
+/// Waits for the next commit, checking in periodically so a change of role
+/// is noticed even if nothing is committed.
+#[inject_yields]
+async fn wait_for_commit(commits: &flume::Receiver<()>) {
+    switchy::unsync::select! {
+        _ = commits.recv_async().fuse() => {}
+        () = switchy::unsync::time::sleep(IDLE_CHECK_INTERVAL) => {}
+    }
+
+    // Any other commits that happened in the meantime are covered by the
+    // same catch up
+    while commits.try_recv().is_ok() {}
+}
+
+#[inject_yields]
+async fn expect_ok(
+    buffer: &mut MessageBuffer,
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<String, Error> {
+    let Some(message) = read_message(buffer, reader).await? else {
+        return Err(Error::Replication(
+            "The replica closed the connection".to_string(),
+        ));
+    };
+
+    match Response::from_str(&message) {
+        Ok(Response::Ok(payload)) => Ok(payload),
+        _ => Err(Error::Replication(format!(
+            "Unexpected response from the replica: '{message}'"
+        ))),
+    }
+}
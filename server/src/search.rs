@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::bank::{AmountError, CreateTime, Transaction, parse_amount};
+
+/// How many transactions a search returns when its filter doesn't set a
+/// `limit`
+pub const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FilterError {
+    #[error("Invalid filter term '{0}'")]
+    InvalidTerm(String),
+    #[error("Unknown filter key '{0}'")]
+    UnknownKey(String),
+    #[error("Filter key '{0}' is set more than once")]
+    DuplicateKey(String),
+    #[error("Invalid value '{value}' for filter key '{key}'")]
+    InvalidValue { key: String, value: String },
+    #[error(transparent)]
+    Amount(#[from] AmountError),
+    #[error("Filter limit must be at least 1")]
+    ZeroLimit,
+}
+
+/// Which transactions a search returns, parsed from space separated terms
+/// like `amount>=100 amount<=200 created_after=1700000000000 limit=50`.
+///
+/// The amount bounds are inclusive while the `created_at` bounds, in
+/// milliseconds since the epoch, are exclusive. Every term is optional, and an empty filter matches every
+/// transaction up to the [`DEFAULT_LIMIT`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionFilter {
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    pub created_after: Option<CreateTime>,
+    pub created_before: Option<CreateTime>,
+    pub limit: usize,
+}
+
+impl Default for TransactionFilter {
+    fn default() -> Self {
+        Self {
+            min_amount: None,
+            max_amount: None,
+            created_after: None,
+            created_before: None,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl TransactionFilter {
+    #[must_use]
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        self.min_amount.is_none_or(|x| transaction.amount >= x)
+            && self.max_amount.is_none_or(|x| transaction.amount <= x)
+            && self
+                .created_after
+                .is_none_or(|x| transaction.created_at > x)
+            && self
+                .created_before
+                .is_none_or(|x| transaction.created_at < x)
+    }
+}
+
+impl std::fmt::Display for TransactionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(min_amount) = self.min_amount {
+            f.write_fmt(format_args!("amount>={min_amount} "))?;
+        }
+        if let Some(max_amount) = self.max_amount {
+            f.write_fmt(format_args!("amount<={max_amount} "))?;
+        }
+        if let Some(created_after) = self.created_after {
+            f.write_fmt(format_args!("created_after={created_after} "))?;
+        }
+        if let Some(created_before) = self.created_before {
+            f.write_fmt(format_args!("created_before={created_before} "))?;
+        }
+        f.write_fmt(format_args!("limit={}", self.limit))
+    }
+}
+
+fn set<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<(), FilterError> {
+    if slot.replace(value).is_some() {
+        return Err(FilterError::DuplicateKey(key.to_string()));
+    }
+    Ok(())
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, FilterError> {
+    value.parse::<T>().map_err(|_| FilterError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Parses a `created_at` bound, which can't be before the epoch
+fn parse_time(key: &str, value: &str) -> Result<CreateTime, FilterError> {
+    let millis = parse_value::<u64>(key, value)?;
+    CreateTime::try_from(millis).map_err(|_| FilterError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+impl FromStr for TransactionFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut min_amount = None;
+        let mut max_amount = None;
+        let mut created_after = None;
+        let mut created_before = None;
+        let mut limit = None;
+
+        for term in s.split_whitespace() {
+            if let Some(value) = term.strip_prefix("amount>=") {
+                set(&mut min_amount, "amount>=", parse_amount(value)?)?;
+            } else if let Some(value) = term.strip_prefix("amount<=") {
+                set(&mut max_amount, "amount<=", parse_amount(value)?)?;
+            } else {
+                let Some((key, value)) = term.split_once('=') else {
+                    return Err(FilterError::InvalidTerm(term.to_string()));
+                };
+                if value.is_empty() {
+                    return Err(FilterError::InvalidTerm(term.to_string()));
+                }
+
+                match key {
+                    "created_after" => set(&mut created_after, key, parse_time(key, value)?)?,
+                    "created_before" => set(&mut created_before, key, parse_time(key, value)?)?,
+                    "limit" => set(&mut limit, key, parse_value::<usize>(key, value)?)?,
+                    _ => return Err(FilterError::UnknownKey(key.to_string())),
+                }
+            }
+        }
+
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 {
+            return Err(FilterError::ZeroLimit);
+        }
+
+        Ok(Self {
+            min_amount,
+            max_amount,
+            created_after,
+            created_before,
+            limit,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+};
+
+static TASKS: LazyLock<Mutex<TaskRegistry>> = LazyLock::new(|| Mutex::new(TaskRegistry::new()));
+
+struct TaskRegistry {
+    next_id: u64,
+    spawned: u64,
+    completed: u64,
+    alive: BTreeMap<u64, String>,
+}
+
+impl TaskRegistry {
+    const fn new() -> Self {
+        Self {
+            next_id: 0,
+            spawned: 0,
+            completed: 0,
+            alive: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskStats {
+    pub spawned: u64,
+    pub completed: u64,
+    /// The names of the tasks that are still alive
+    pub alive: Vec<String>,
+}
+
+/// Registers a named task as alive until the returned guard is dropped,
+/// which happens both when the task completes and when it is aborted.
+///
+/// # Panics
+///
+/// * If the `TASKS` `Mutex` fails to lock
+#[must_use]
+pub fn register(name: impl Into<String>) -> TaskGuard {
+    let mut tasks = TASKS.lock().unwrap();
+    let id = tasks.next_id;
+    tasks.next_id += 1;
+    tasks.spawned += 1;
+    tasks.alive.insert(id, name.into());
+    drop(tasks);
+
+    TaskGuard { id }
+}
+
+/// # Panics
+///
+/// * If the `TASKS` `Mutex` fails to lock
+#[must_use]
+pub fn stats() -> TaskStats {
+    let tasks = TASKS.lock().unwrap();
+
+    TaskStats {
+        spawned: tasks.spawned,
+        completed: tasks.completed,
+        alive: tasks.alive.values().cloned().collect(),
+    }
+}
+
+/// Clears the counts and the alive tasks.
+///
+/// Ids keep counting up from where they were rather than starting over, so
+/// a guard left over from before the reset, such as one held by a task of a
+/// previous run that's only dropped now, can't remove a newer task that
+/// would otherwise have been handed the same id.
+///
+/// # Panics
+///
+/// * If the `TASKS` `Mutex` fails to lock
+pub fn reset() {
+    let mut tasks = TASKS.lock().unwrap();
+    *tasks = TaskRegistry {
+        next_id: tasks.next_id,
+        ..TaskRegistry::new()
+    };
+}
+
+pub struct TaskGuard {
+    id: u64,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let mut tasks = TASKS.lock().unwrap();
+        if tasks.alive.remove(&self.id).is_some() {
+            tasks.completed += 1;
+        }
+    }
+}
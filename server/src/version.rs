@@ -0,0 +1,77 @@
+/// Build metadata for the running server binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub git_hash: String,
+    /// The Cargo features the server crate was built with
+    pub features: Vec<String>,
+}
+
+impl VersionInfo {
+    #[must_use]
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: env!("DST_DEMO_SERVER_GIT_HASH").to_string(),
+            features: env!("DST_DEMO_SERVER_FEATURES")
+                .split(',')
+                .filter(|x| !x.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "version={} git_hash={} features={}",
+            self.version,
+            self.git_hash,
+            self.features.join(",")
+        ))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VersionInfoFromStrError {
+    #[error("Missing version")]
+    MissingVersion,
+    #[error("Missing git_hash")]
+    MissingGitHash,
+    #[error("Missing features")]
+    MissingFeatures,
+}
+
+impl std::str::FromStr for VersionInfo {
+    type Err = VersionInfoFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s.split(' ');
+
+        let version = components
+            .next()
+            .and_then(|x| x.strip_prefix("version="))
+            .ok_or(VersionInfoFromStrError::MissingVersion)?;
+
+        let git_hash = components
+            .next()
+            .and_then(|x| x.strip_prefix("git_hash="))
+            .ok_or(VersionInfoFromStrError::MissingGitHash)?;
+
+        let features = components
+            .next()
+            .and_then(|x| x.strip_prefix("features="))
+            .ok_or(VersionInfoFromStrError::MissingFeatures)?;
+
+        Ok(Self {
+            version: version.to_string(),
+            git_hash: git_hash.to_string(),
+            features: features
+                .split(',')
+                .filter(|x| !x.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,95 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Creates, voids, and batches transactions on a `LocalBank` and checks that
+//! each of them is audited with the peer that asked for it, both from the
+//! tail of the audit log and after reopening the bank from its files.
+
+use dst_demo_server::bank::{AuditAction, AuditLog, Bank as _, LocalBank, Origin, audit_path};
+use rust_decimal::Decimal;
+
+#[test]
+fn state_changes_are_audited_with_their_peer() {
+    let dir = std::env::temp_dir().join(format!("dst_demo_audit_log_{}", std::process::id()));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("bank.db");
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    runtime.block_on(async move {
+        let bank = LocalBank::open(&db_path).unwrap();
+        let alice = Origin::new("127.0.0.1:5000");
+        let bob = Origin::new("127.0.0.1:5001");
+
+        let created = bank
+            .create_transaction(Decimal::new(1_250, 2), &alice)
+            .await
+            .unwrap();
+        let voided = bank
+            .void_transaction(created.id, &bob)
+            .await
+            .unwrap()
+            .unwrap();
+        let batch = bank
+            .create_transactions_atomic(vec![Decimal::ONE, Decimal::TWO], &alice)
+            .await
+            .unwrap();
+
+        let records = bank.tail_audit(10).await.unwrap();
+        let actual = records
+            .iter()
+            .map(|x| {
+                (
+                    x.transaction_id,
+                    x.peer.as_str(),
+                    x.action,
+                    x.arguments.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            vec![
+                (
+                    created.id,
+                    "127.0.0.1:5000",
+                    AuditAction::CreateTransaction,
+                    "amount=12.50",
+                ),
+                (
+                    voided.id,
+                    "127.0.0.1:5001",
+                    AuditAction::VoidTransaction,
+                    "id=1",
+                ),
+                (
+                    batch[0].id,
+                    "127.0.0.1:5000",
+                    AuditAction::CommitBatch,
+                    "amount=1",
+                ),
+                (
+                    batch[1].id,
+                    "127.0.0.1:5000",
+                    AuditAction::CommitBatch,
+                    "amount=2",
+                ),
+            ]
+        );
+
+        // The tail only returns the most recent records, oldest first
+        assert_eq!(bank.tail_audit(2).await.unwrap(), records[2..]);
+
+        // The records were persisted along with the ledger
+        let reopened = LocalBank::open(&db_path).unwrap();
+        assert_eq!(reopened.tail_audit(10).await.unwrap(), records);
+        assert_eq!(
+            AuditLog::open(audit_path(&db_path)).unwrap().records(),
+            records
+        );
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,85 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Hammers a `LocalBank` with concurrent creates, voids and balance reads
+//! with the balance consistency check enabled, so any divergence between the
+//! cached balance and the ledger panics.
+//!
+//! With the simulator's runtime and fs (as in workspace builds), the workers
+//! are interleaved at their awaits the way the simulated server's connection
+//! tasks are, and the ledger lives in the simulated fs.
+
+use dst_demo_server::{
+    balance,
+    balance::BalanceCache,
+    bank::{Bank as _, LocalBank, Origin},
+};
+use rust_decimal::Decimal;
+use switchy::unsync::task;
+
+const WORKERS: i64 = 8;
+const ITERATIONS: i64 = 50;
+
+#[test]
+fn cached_balance_stays_consistent_under_concurrency() {
+    balance::set_check_enabled(true);
+
+    let dir = std::env::temp_dir().join(format!("dst_demo_balance_{}", std::process::id()));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    let path = dir.join("bank.db");
+    runtime.block_on(async move {
+        let bank = LocalBank::open(&path).unwrap();
+
+        let workers = (0..WORKERS)
+            .map(|worker| {
+                let bank = bank.clone();
+                task::spawn(async move {
+                    for i in 0..ITERATIONS {
+                        let amount = Decimal::new(worker * 1_000 + i + 1, 2);
+                        let transaction = bank
+                            .create_transaction(amount, &Origin::local())
+                            .await
+                            .unwrap();
+
+                        if i % 3 == 0 {
+                            bank.void_transaction(transaction.id, &Origin::local())
+                                .await
+                                .unwrap();
+                        }
+
+                        bank.get_balance().await.unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            worker.await.unwrap();
+        }
+
+        let expected = bank
+            .list_transactions()
+            .await
+            .unwrap()
+            .iter()
+            .fold(Decimal::ZERO, |balance, x| balance + x.amount);
+
+        assert_eq!(bank.get_balance().await.unwrap(), expected);
+
+        // A reopened bank recomputes its balance from the persisted ledger
+        let reopened = LocalBank::open(&path).unwrap();
+        assert_eq!(reopened.get_balance().await.unwrap(), expected);
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_ledger_balance_keeps_its_scale() {
+    assert_eq!(BalanceCache::recompute(&[]).get().to_string(), "0.0");
+}
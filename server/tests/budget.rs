@@ -0,0 +1,142 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Checks that the response budget is enforced as a response is written, so
+//! nothing past the budget is formatted, and that a listing that used to be
+//! built in full regardless of its size is refused once it outgrows a
+//! lowered budget.
+
+use std::{
+    str::FromStr as _,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use dst_demo_server::{
+    Error, WRITE_CHUNK_SIZE,
+    bank::Transaction,
+    budget::{BudgetExceeded, Meter, display_len},
+    write_message_from_iter,
+};
+
+fn lines(lines: &'static [&str]) -> impl Iterator<Item = String> + Send + 'static {
+    lines.iter().map(|x| (*x).to_string())
+}
+
+/// Writes `values` to a buffer within `budget`, returning what was written
+/// along with the result.
+fn write(
+    values: impl Iterator<Item = String> + Send + 'static,
+    budget: usize,
+) -> (Vec<u8>, Result<u64, BudgetExceeded>) {
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    runtime.block_on(async move {
+        let mut buffer = vec![];
+        let resp = write_message_from_iter(values, "\n", budget, &mut buffer)
+            .await
+            .map_err(|e| match e {
+                Error::BudgetExceeded(e) => e,
+                e => panic!("unexpected error: {e:?}"),
+            });
+        (buffer, resp)
+    })
+}
+
+#[test]
+fn lines_that_fit_are_written() {
+    // 3 + 1 + 3 bytes, counting the newline between them
+    let (buffer, resp) = write(lines(&["abc", "def"]), 7);
+    assert_eq!(buffer, b"abc\ndef\0");
+    assert_eq!(resp, Ok(8));
+
+    let (buffer, resp) = write(lines(&[]), 0);
+    assert_eq!(buffer, b"\0");
+    assert_eq!(resp, Ok(1));
+}
+
+#[test]
+fn lines_over_the_budget_are_refused_before_anything_is_written() {
+    let (buffer, resp) = write(lines(&["abc", "def"]), 6);
+    assert!(buffer.is_empty());
+    assert_eq!(
+        resp,
+        Err(BudgetExceeded {
+            budget: 6,
+            written: 0
+        })
+    );
+}
+
+#[test]
+fn formatting_stops_once_the_budget_is_exceeded() {
+    static FORMATTED: AtomicUsize = AtomicUsize::new(0);
+
+    let lines = (0..1000).map(|_| {
+        FORMATTED.fetch_add(1, Ordering::SeqCst);
+        "x".repeat(100)
+    });
+
+    let (_, resp) = write(lines, 250);
+    assert!(resp.is_err());
+    assert_eq!(FORMATTED.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn response_over_the_budget_after_its_first_chunk_is_cut_off() {
+    let lines = (0..1000).map(|_| "x".repeat(100));
+
+    let (buffer, resp) = write(lines, 2 * WRITE_CHUNK_SIZE);
+    let e = resp.unwrap_err();
+    assert!(e.written > 0);
+    assert_eq!(buffer.len() as u64, e.written);
+    assert!(!buffer.contains(&0), "a cut off response isn't terminated");
+}
+
+#[test]
+fn meter_counts_what_is_displayed() {
+    let transaction =
+        Transaction::from_str("id=1 seq=1 created_at=1700000000000 amount=$1234.56").unwrap();
+    let len = display_len(&transaction);
+    assert_eq!(len, transaction.to_string().len());
+
+    let mut meter = Meter::new(2 * len);
+    assert_eq!(meter.add(len), Ok(()));
+    assert_eq!(meter.add(len), Ok(()));
+    assert_eq!(
+        meter.add(1),
+        Err(BudgetExceeded {
+            budget: 2 * len,
+            written: 0
+        })
+    );
+}
+
+#[test]
+fn large_listing_exceeds_a_lowered_budget() {
+    let transactions = (1..=100)
+        .map(|id| {
+            Transaction::from_str(&format!(
+                "id={id} seq={id} created_at=1700000000000 amount=$1234.56"
+            ))
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let listing = || transactions.clone().into_iter().map(|x| x.to_string());
+    let len = listing().map(|x| x.len() + 1).sum::<usize>() - 1;
+
+    let (buffer, resp) = write(listing(), len);
+    assert_eq!(resp, Ok(len as u64 + 1));
+    assert_eq!(buffer.split(|x| *x == b'\n').count(), 100);
+
+    let (buffer, resp) = write(listing(), 4096);
+    assert!(buffer.is_empty());
+    assert_eq!(
+        resp,
+        Err(BudgetExceeded {
+            budget: 4096,
+            written: 0
+        })
+    );
+}
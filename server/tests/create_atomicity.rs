@@ -0,0 +1,163 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Drops a `create_transaction` future while it's parked on its
+//! `before_file_write` delay point, the way a connection task aborted by a
+//! server bounce would be.
+//!
+//! Before the atomicity fix, the id was consumed as soon as the lock was
+//! taken, so cancelling there left a gap in the ledger that the next create
+//! tripped over. `LocalBank` now takes every lock before mutating anything, so
+//! the same cancellation leaves it untouched.
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{Mutex as SyncMutex, MutexGuard},
+    task::{Context, Waker},
+};
+
+use dst_demo_server::{
+    bank::{Bank as _, LocalBank, Origin, TransactionId},
+    cancel_safety, hooks,
+};
+use rust_decimal::Decimal;
+use switchy::unsync::sync::{Mutex, RwLock};
+
+/// The delay points' fired counts are global, so the tests that reset and
+/// read them take turns.
+static HOOKS: SyncMutex<()> = SyncMutex::new(());
+
+fn lock_hooks() -> MutexGuard<'static, ()> {
+    HOOKS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Pins the seed so the delay drawn at the delay point is the same on every
+/// run, and makes the max delay large enough that the draw is never zero.
+fn enable_delay_points() {
+    // SAFETY: every test in this binary sets the same values, and they're
+    // read once, lazily, by the first delay point to fire
+    unsafe {
+        std::env::set_var("SIMULATOR_SEED", "1114");
+        std::env::set_var("SIM_HOOKS_MAX_DELAY_MS", "3600000");
+    }
+}
+
+/// Polls `future` once and drops it, asserting that it was parked on an
+/// await point rather than run to completion.
+fn cancel_at_first_await(future: impl Future) {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    assert!(
+        future.as_mut().poll(&mut cx).is_pending(),
+        "expected the future to be parked on a delay point"
+    );
+}
+
+/// A fresh directory for the bank's file. It goes through `switchy::fs` so it
+/// lives wherever the bank's file does, on disk or in the simulated fs.
+fn db_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "dst_demo_create_atomicity_{name}_{}",
+        std::process::id()
+    ));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// `LocalBank::create_transaction` as it was before the atomicity fix: the
+/// id is consumed as soon as its lock is taken, and the ledger is only
+/// updated after the awaits that follow.
+struct PreFixBank {
+    current_id: RwLock<TransactionId>,
+    transactions: RwLock<Vec<TransactionId>>,
+    file: Mutex<Vec<TransactionId>>,
+}
+
+impl PreFixBank {
+    fn new() -> Self {
+        Self {
+            current_id: RwLock::new(1),
+            transactions: RwLock::new(vec![]),
+            file: Mutex::new(vec![]),
+        }
+    }
+
+    async fn create_transaction(&self) -> TransactionId {
+        let mut current_id = self.current_id.write().await;
+        let id = *current_id;
+        *current_id += 1;
+
+        hooks::delay_point("create_transaction::before_file_write").await;
+        self.file.lock().await.push(id);
+        hooks::delay_point("create_transaction::after_file_write").await;
+        self.transactions.write().await.push(id);
+
+        drop(current_id);
+        id
+    }
+}
+
+#[test]
+fn cancelled_create_skipped_an_id_before_the_atomicity_fix() {
+    let _hooks = lock_hooks();
+    enable_delay_points();
+    hooks::reset();
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    runtime.block_on(async {
+        let bank = PreFixBank::new();
+
+        cancel_at_first_await(bank.create_transaction());
+
+        assert_eq!(
+            hooks::fired().get("create_transaction::before_file_write"),
+            Some(&1)
+        );
+
+        // Nothing was written, but the next create is handed id 2, so the
+        // ledger no longer starts at 1
+        assert!(bank.transactions.read().await.is_empty());
+        assert!(bank.file.lock().await.is_empty());
+        assert_eq!(*bank.current_id.read().await, 2);
+    });
+}
+
+#[test]
+fn cancelled_create_leaves_the_bank_untouched() {
+    let _hooks = lock_hooks();
+    enable_delay_points();
+    hooks::reset();
+
+    let dir = db_dir("local");
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    let bank_path = dir.join("bank.db");
+    runtime.block_on(async move {
+        let bank = LocalBank::open(&bank_path).unwrap();
+
+        cancel_at_first_await(bank.create_transaction(Decimal::new(1_000, 2), &Origin::local()));
+
+        assert_eq!(
+            hooks::fired().get("create_transaction::before_file_write"),
+            Some(&1)
+        );
+        assert!(bank.list_transactions().await.unwrap().is_empty());
+        assert_eq!(bank.get_balance().await.unwrap(), Decimal::ZERO);
+
+        // The locks were released with the dropped future, and the id wasn't
+        // consumed, so reopening the file sees the same empty ledger
+        let reopened = LocalBank::open(&bank_path).unwrap();
+        assert!(reopened.list_transactions().await.unwrap().is_empty());
+    });
+
+    assert!(!cancel_safety::cancelled().contains_key("create_transaction"));
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,167 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Round trips payloads around the compression threshold through the framing
+//! used by connections that negotiated `COMPRESS deflate`, and checks that
+//! malformed frames are reported as typed errors rather than panics. Payloads
+//! handed to the encoder a piece at a time decode to the same payload.
+
+use dst_demo_server::framing::{
+    COMPRESSION_THRESHOLD, FrameEncoder, FramingError, HEADER_LEN, MAX_FRAME_LEN, decode, encode,
+};
+
+/// A payload of `len` bytes that compresses well, like a transaction list
+fn payload(len: usize) -> String {
+    "id=1 seq=1 created_at=1700000000000 amount=$12.34\n"
+        .chars()
+        .cycle()
+        .take(len)
+        .collect()
+}
+
+fn round_trip(payload: &str) -> Vec<u8> {
+    let frame = encode(payload.as_bytes());
+
+    let mut buffer = frame.clone();
+    assert_eq!(decode(&mut buffer).unwrap().as_deref(), Some(payload));
+    assert!(buffer.is_empty());
+
+    frame
+}
+
+#[test]
+fn payloads_below_the_threshold_are_sent_raw() {
+    for len in [0, 1, COMPRESSION_THRESHOLD - 1] {
+        let payload = payload(len);
+        let frame = round_trip(&payload);
+
+        assert_eq!(frame[0], 0x00);
+        assert_eq!(&frame[HEADER_LEN..], payload.as_bytes());
+    }
+}
+
+#[test]
+fn payloads_at_or_above_the_threshold_are_compressed() {
+    for len in [
+        COMPRESSION_THRESHOLD,
+        COMPRESSION_THRESHOLD + 1,
+        1024 * 1024,
+    ] {
+        let payload = payload(len);
+        let frame = round_trip(&payload);
+
+        assert_eq!(frame[0], 0x01);
+        assert!(frame.len() < payload.len());
+    }
+}
+
+#[test]
+fn payloads_written_in_pieces_decode_to_the_whole() {
+    for len in [
+        COMPRESSION_THRESHOLD - 1,
+        COMPRESSION_THRESHOLD,
+        1024 * 1024,
+    ] {
+        let payload = payload(len);
+        let mut encoder = FrameEncoder::new();
+        for piece in payload.as_bytes().chunks(100) {
+            encoder.write(piece);
+        }
+        assert_eq!(encoder.payload_len(), len);
+
+        let mut frame = encoder.finish();
+        assert_eq!(frame[0], u8::from(len >= COMPRESSION_THRESHOLD));
+        assert_eq!(
+            decode(&mut frame).unwrap().as_deref(),
+            Some(payload.as_str())
+        );
+        assert!(frame.is_empty());
+    }
+}
+
+#[test]
+fn compression_is_deterministic() {
+    let payload = payload(64 * 1024);
+
+    assert_eq!(encode(payload.as_bytes()), encode(payload.as_bytes()));
+}
+
+#[test]
+fn frames_are_decoded_as_they_arrive() {
+    let first = payload(COMPRESSION_THRESHOLD * 4);
+    let second = payload(10);
+
+    let mut stream = encode(first.as_bytes());
+    stream.extend(encode(second.as_bytes()));
+
+    let mut buffer = vec![];
+    let mut decoded = vec![];
+
+    for byte in stream {
+        buffer.push(byte);
+        if let Some(message) = decode(&mut buffer).unwrap() {
+            decoded.push(message);
+        }
+    }
+
+    assert_eq!(decoded, vec![first, second]);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn corrupted_compressed_body_is_an_error() {
+    let mut frame = encode(payload(COMPRESSION_THRESHOLD * 4).as_bytes());
+    // A final block with the reserved block type
+    frame[HEADER_LEN] = 0xff;
+
+    assert!(matches!(decode(&mut frame), Err(FramingError::Decompress)));
+}
+
+#[test]
+fn truncated_compressed_body_is_an_error() {
+    let frame = encode(payload(COMPRESSION_THRESHOLD * 4).as_bytes());
+    let body = &frame[HEADER_LEN..frame.len() / 2];
+
+    let mut truncated = vec![0x01];
+    truncated.extend_from_slice(&u32::try_from(body.len()).unwrap().to_be_bytes());
+    truncated.extend_from_slice(body);
+
+    assert!(matches!(
+        decode(&mut truncated),
+        Err(FramingError::Decompress)
+    ));
+}
+
+#[test]
+fn unknown_flag_is_an_error() {
+    let mut frame = encode(b"OK");
+    frame[0] = 0x02;
+
+    assert!(matches!(
+        decode(&mut frame),
+        Err(FramingError::UnknownFlag(0x02))
+    ));
+}
+
+#[test]
+fn oversized_frame_is_an_error() {
+    let len = u32::try_from(MAX_FRAME_LEN + 1).unwrap();
+    let mut frame = vec![0x00];
+    frame.extend_from_slice(&len.to_be_bytes());
+
+    assert!(matches!(
+        decode(&mut frame),
+        Err(FramingError::TooLarge(..))
+    ));
+}
+
+#[test]
+fn invalid_utf8_is_an_error() {
+    let mut frame = encode(&[0xff, 0xfe]);
+
+    assert!(matches!(
+        decode(&mut frame),
+        Err(FramingError::FromUtf8(..))
+    ));
+}
@@ -0,0 +1,107 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Checks the `LocalBank`'s id index against the ledger it indexes, and that
+//! lookups through it find the same transactions as a linear scan. How much
+//! faster it is is measured by the `id_index` bench.
+
+use dst_demo_server::{
+    bank::{Bank as _, LocalBank, Origin, Transaction, TransactionId},
+    id_index::IdIndex,
+};
+use rust_decimal::Decimal;
+
+const TRANSACTION_COUNT: TransactionId = 100_000;
+const LOOKUPS: TransactionId = 1_000;
+
+fn ledger() -> Vec<Transaction> {
+    (1..=TRANSACTION_COUNT)
+        .map(|id| Transaction {
+            id,
+            seq: u64::try_from(id).unwrap(),
+            amount: Decimal::new(i64::from(id), 2),
+            created_at: 1_700_000_000_000,
+        })
+        .collect()
+}
+
+/// Spreads the looked up ids across the whole ledger, weighted towards the
+/// end where a linear scan is slowest.
+fn lookup_ids() -> impl Iterator<Item = TransactionId> {
+    (0..LOOKUPS).map(|i| TRANSACTION_COUNT - i * (TRANSACTION_COUNT / LOOKUPS / 2))
+}
+
+#[test]
+fn index_matches_the_ledger() {
+    let transactions = ledger();
+    let index = IdIndex::rebuild(&transactions);
+
+    index.assert_consistent(&transactions);
+    assert_eq!(index.len(), transactions.len());
+    assert_eq!(index.get(1), Some(0));
+    assert_eq!(index.get(TRANSACTION_COUNT), Some(transactions.len() - 1));
+    assert_eq!(index.get(TRANSACTION_COUNT + 1), None);
+}
+
+#[test]
+#[should_panic(expected = "id index points id=")]
+fn diverged_index_is_caught() {
+    let transactions = ledger();
+    let mut index = IdIndex::rebuild(&transactions);
+
+    // Points the last id at the wrong position
+    index.insert(&transactions[transactions.len() - 1], 0);
+
+    index.assert_consistent(&transactions);
+}
+
+#[test]
+#[should_panic(expected = "id index points id=")]
+#[cfg(debug_assertions)]
+fn diverged_appended_entry_is_caught() {
+    let transactions = ledger();
+    let mut index = IdIndex::rebuild(&transactions);
+
+    index.insert(&transactions[transactions.len() - 1], 0);
+
+    index.debug_assert_appended(&transactions, 1);
+}
+
+#[test]
+fn appended_check_only_looks_at_the_appended_entries() {
+    let transactions = ledger();
+    let mut index = IdIndex::rebuild(&transactions);
+
+    // Diverges an entry before the appended one, which only the full check
+    // looks at
+    index.insert(&transactions[0], 1);
+
+    index.debug_assert_appended(&transactions, 1);
+}
+
+#[test]
+fn indexed_lookups_match_a_linear_scan() {
+    let dir = std::env::temp_dir().join(format!("dst_demo_id_index_{}", std::process::id()));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    let path = dir.join("bank.db");
+    runtime.block_on(async move {
+        let bank = LocalBank::open(&path).unwrap();
+        bank.import(ledger(), &Origin::local()).await.unwrap();
+
+        let transactions = bank.list_transactions().await.unwrap().clone();
+        for id in lookup_ids().chain([TRANSACTION_COUNT + 1]) {
+            let indexed = bank.get_transaction(id).await.unwrap();
+            let indexed = indexed.map(|x| x.to_string());
+            let linear = transactions.iter().find(|x| x.id == id);
+            let linear = linear.map(ToString::to_string);
+            assert_eq!(indexed, linear, "id={id}");
+        }
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,65 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Feeds terminated messages into a [`MessageBuffer`] split at awkward
+//! points to prove that messages are only taken once complete, that bytes
+//! past the end of a message are kept for the next one, and that only a
+//! complete message is validated as UTF-8.
+
+use dst_demo_server::message::{MessageBuffer, TERMINATOR};
+
+fn messages(buffer: &mut MessageBuffer) -> Vec<String> {
+    std::iter::from_fn(|| buffer.next_message().unwrap()).collect()
+}
+
+#[test]
+fn messages_are_only_taken_once_complete() {
+    let mut buffer = MessageBuffer::new();
+
+    buffer.extend(b"GET_TRANS");
+    assert_eq!(buffer.next_message(), Ok(None));
+    buffer.extend(b"ACTION");
+    assert_eq!(buffer.next_message(), Ok(None));
+    buffer.extend(&[TERMINATOR]);
+
+    assert_eq!(messages(&mut buffer), vec!["GET_TRANSACTION"]);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn bytes_past_a_message_are_kept_for_the_next() {
+    let mut buffer = MessageBuffer::new();
+
+    buffer.extend(b"PROTO v2\0HEALTH\0\0CREATE_");
+    assert_eq!(messages(&mut buffer), vec!["PROTO v2", "HEALTH", ""]);
+    assert_eq!(buffer.len(), "CREATE_".len());
+
+    buffer.extend(b"TRANSACTION\0");
+    assert_eq!(messages(&mut buffer), vec!["CREATE_TRANSACTION"]);
+}
+
+#[test]
+fn characters_split_across_reads_are_kept_whole() {
+    let mut buffer = MessageBuffer::new();
+    let bytes = "amount=€12\0".as_bytes();
+    let split = "amount=".len() + 1;
+
+    buffer.extend(&bytes[..split]);
+    assert_eq!(buffer.next_message(), Ok(None));
+    buffer.extend(&bytes[split..]);
+
+    assert_eq!(messages(&mut buffer), vec!["amount=€12"]);
+}
+
+#[test]
+fn invalid_utf8_is_rejected_without_losing_the_next_message() {
+    let mut buffer = MessageBuffer::new();
+
+    buffer.extend(b"ab\xffcd\0HEALTH\0");
+
+    let error = buffer.next_message().unwrap_err();
+    assert_eq!(error.len, 5);
+    assert_eq!(error.source.valid_up_to(), 2);
+    assert_eq!(messages(&mut buffer), vec!["HEALTH"]);
+}
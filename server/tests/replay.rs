@@ -0,0 +1,369 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+// The delay points would slow down every response of a real server
+#![cfg(not(feature = "sim-hooks"))]
+
+//! Replays scripted byte-level sessions against a real server, outside of the
+//! simulator, asserting the exact response frames.
+
+use std::{
+    io::{ErrorKind, Read as _, Write as _},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use dst_demo_server::{
+    bank::LocalBank,
+    config::{ServerConfig, parse_authorization},
+    serve_with_config,
+};
+use switchy::{tcp::TcpListener as ServerListener, unsync::util::CancellationToken};
+
+/// How long a session waits for a response frame before failing
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+enum Step {
+    /// Writes the bytes as-is, without a terminator
+    Write(Vec<u8>),
+    /// Waits before the next step so consecutive writes aren't coalesced
+    Pause(Duration),
+    /// Reads the next frame and asserts it's exactly the given message
+    Expect(String),
+    /// Reads the next frame and asserts it starts with the given prefix
+    ExpectPrefix(String),
+}
+
+/// A scripted session on a single connection.
+#[derive(Debug, Default, Clone)]
+struct Session {
+    steps: Vec<Step>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `message` as a single frame.
+    fn send(self, message: &str) -> Self {
+        let mut bytes = message.as_bytes().to_vec();
+        bytes.push(0);
+        self.send_raw(bytes)
+    }
+
+    /// Sends the bytes as-is, e.g. part of a frame or several frames at once.
+    fn send_raw(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(Step::Write(bytes.into()));
+        self
+    }
+
+    fn pause(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Pause(duration));
+        self
+    }
+
+    fn expect(mut self, message: &str) -> Self {
+        self.steps.push(Step::Expect(message.to_string()));
+        self
+    }
+
+    fn expect_prefix(mut self, prefix: &str) -> Self {
+        self.steps.push(Step::ExpectPrefix(prefix.to_string()));
+        self
+    }
+
+    /// Replays the session against the server at `addr`.
+    fn run(self, addr: &str) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        let mut buffer = vec![];
+
+        for (index, step) in self.steps.into_iter().enumerate() {
+            match step {
+                Step::Write(bytes) => stream.write_all(&bytes).unwrap(),
+                Step::Pause(duration) => std::thread::sleep(duration),
+                Step::Expect(expected) => {
+                    let frame = read_frame(&mut stream, &mut buffer);
+                    assert_eq!(frame, expected, "unexpected frame at step {index}");
+                }
+                Step::ExpectPrefix(prefix) => {
+                    let frame = read_frame(&mut stream, &mut buffer);
+                    assert!(
+                        frame.starts_with(&prefix),
+                        "expected a frame starting with '{prefix}' at step {index}, instead got:\n'{frame}'"
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn read_frame(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> String {
+    loop {
+        if let Some(index) = buffer.iter().position(|x| *x == 0) {
+            let frame = buffer.drain(..=index).collect::<Vec<_>>();
+            return String::from_utf8(frame[..index].to_vec()).unwrap();
+        }
+
+        let mut buf = [0_u8; 1024];
+        let count = match stream.read(&mut buf) {
+            Ok(0) => panic!("connection closed while waiting for a frame"),
+            Ok(count) => count,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                panic!("timed out waiting for a frame, received so far: {buffer:?}")
+            }
+            Err(e) => panic!("failed to read a frame: {e:?}"),
+        };
+        buffer.extend_from_slice(&buf[..count]);
+    }
+}
+
+/// Starts a server with an empty ledger on its own thread, returning its
+/// address once it's listening.
+fn start_server(name: &str) -> String {
+    start_server_with_config(name, ServerConfig::default())
+}
+
+/// Starts a server like [`start_server`] with `config`, apart from its ledger
+fn start_server_with_config(name: &str, config: ServerConfig) -> String {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let name = name.to_string();
+
+    std::thread::spawn(move || {
+        let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+        runtime.block_on(async move {
+            let (listener, port) = bind().await;
+
+            let db_path = std::env::temp_dir().join(format!("dst_demo_replay_{name}_{port}.db"));
+            remove(&db_path);
+            let bank = LocalBank::open(&db_path).unwrap();
+            let config = ServerConfig { db_path, ..config };
+
+            tx.send(format!("127.0.0.1:{port}")).unwrap();
+
+            serve_with_config(vec![listener], bank, config, CancellationToken::new())
+                .await
+                .unwrap();
+        });
+    });
+
+    rx.recv().unwrap()
+}
+
+/// Binds the listener a server is handed, on a free port.
+///
+/// The port is found by binding port 0 and releasing it, so binding it again
+/// is retried if something else takes it in between.
+async fn bind() -> (ServerListener, u16) {
+    loop {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = format!("127.0.0.1:{port}");
+
+        match ServerListener::bind(addr.as_str()).await {
+            Ok(listener) => return (listener, port),
+            Err(switchy::tcp::Error::IO(e)) if e.kind() == ErrorKind::AddrInUse => {
+                log::debug!("bind: retrying after {e:?}");
+            }
+            Err(e) => panic!("failed to bind {addr}: {e:?}"),
+        }
+    }
+}
+
+fn remove(path: &Path) {
+    if path.exists() {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn health() {
+    let addr = start_server("health");
+
+    Session::new().send("HEALTH").expect("healthy").run(&addr);
+}
+
+#[test]
+fn frame_split_across_writes() {
+    let addr = start_server("frame_split_across_writes");
+
+    Session::new()
+        .send_raw("HEA")
+        .pause(Duration::from_millis(50))
+        .send_raw("LT")
+        .pause(Duration::from_millis(50))
+        .send_raw("H\0")
+        .expect("healthy")
+        .run(&addr);
+}
+
+#[test]
+fn several_frames_in_one_write() {
+    let addr = start_server("several_frames_in_one_write");
+
+    Session::new()
+        .send_raw("HEALTH\0HEALTH\0HEA")
+        .expect("healthy")
+        .expect("healthy")
+        .send_raw("LTH\0")
+        .expect("healthy")
+        .run(&addr);
+}
+
+#[test]
+fn double_terminator_is_an_empty_action() {
+    let addr = start_server("double_terminator_is_an_empty_action");
+
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send_raw("HEALTH\0\0")
+        .expect("OK healthy")
+        .expect("ERR invalid_action Invalid action ''")
+        .send("HEALTH")
+        .expect("OK healthy")
+        .run(&addr);
+}
+
+#[test]
+fn protocol_is_only_negotiated_first() {
+    let addr = start_server("protocol_is_only_negotiated_first");
+
+    Session::new()
+        .send("PROTO 3")
+        .send("HEALTH")
+        .expect("Unsupported protocol version '3'")
+        .expect("healthy")
+        .run(&addr);
+
+    Session::new()
+        .send("HEALTH")
+        .send("PROTO 2")
+        .send("HEALTH")
+        .expect("healthy")
+        .expect("healthy")
+        .run(&addr);
+}
+
+#[test]
+fn interleaved_actions_on_one_connection() {
+    let addr = start_server("interleaved_actions_on_one_connection");
+
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("GET_TRANSACTION")
+        .expect("PROMPT transaction_id")
+        .send("1")
+        .expect("ERR not_found Transaction not found")
+        .send("GET_BALANCE")
+        .expect("OK $0.0")
+        .send("CREATE_TRANSACTION")
+        .expect("PROMPT amount")
+        .send("12.34")
+        .expect_prefix("OK ")
+        .send("HEALTH")
+        .expect("OK healthy")
+        .send("GET_BALANCE")
+        .expect("OK $12.34")
+        .run(&addr);
+}
+
+#[test]
+fn prompt_answer_split_across_writes() {
+    let addr = start_server("prompt_answer_split_across_writes");
+
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send_raw("GET_TRANSACTION\0")
+        .expect("PROMPT transaction_id")
+        .send_raw("4")
+        .pause(Duration::from_millis(50))
+        .send_raw("2\0HEALTH\0")
+        .expect("ERR not_found Transaction not found")
+        .expect("OK healthy")
+        .run(&addr);
+}
+
+#[test]
+fn help_lists_each_action_with_its_prompts() {
+    let addr = start_server("help_lists_each_action_with_its_prompts");
+
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("HELP")
+        .expect_prefix(concat!(
+            "OK HEALTH\n",
+            "HELP\n",
+            "LIST_TRANSACTIONS\n",
+            "SEARCH_TRANSACTIONS filter\n",
+            "GET_TRANSACTION transaction_id\n",
+            "CREATE_TRANSACTION amount\n",
+        ))
+        .send("GET_STATEMENT")
+        .expect("PROMPT start_transaction_id")
+        .send("1")
+        .expect("PROMPT end_transaction_id")
+        .send("2")
+        .expect("OK")
+        .run(&addr);
+}
+
+#[test]
+fn admin_actions_require_an_admin_identity() {
+    let addr = start_server_with_config(
+        "admin_actions_require_an_admin_identity",
+        ServerConfig {
+            authorization: parse_authorization("EXIT=admin,IMPORT_LEDGER=admin").unwrap(),
+            ..ServerConfig::default()
+        },
+    );
+    let unauthorized = "ERR unauthorized The connection's identity isn't allowed this action";
+
+    Session::new()
+        .send("IDENTITY intruder")
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("IMPORT_LEDGER")
+        .expect(unauthorized)
+        .send("EXIT")
+        .expect(unauthorized)
+        .send("HEALTH")
+        .expect("OK healthy")
+        .run(&addr);
+
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("EXIT")
+        .expect(unauthorized)
+        .run(&addr);
+
+    // The preamble is only recognized as the first frame
+    Session::new()
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("IDENTITY admin")
+        .expect("ERR invalid_action Invalid action 'IDENTITY admin'")
+        .send("IMPORT_LEDGER")
+        .expect(unauthorized)
+        .run(&addr);
+
+    Session::new()
+        .send("IDENTITY admin_1")
+        .send("PROTO 2")
+        .expect("OK 2")
+        .send("IMPORT_LEDGER")
+        .expect("PROMPT ledger")
+        .send("")
+        .expect("OK imported=0")
+        .run(&addr);
+}
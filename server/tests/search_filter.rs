@@ -0,0 +1,136 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Parses `SEARCH_TRANSACTIONS` filters and checks which transactions they
+//! match.
+
+use std::str::FromStr as _;
+
+use dst_demo_server::{
+    bank::{CreateTime, Transaction},
+    search::{DEFAULT_LIMIT, FilterError, TransactionFilter},
+};
+use rust_decimal::Decimal;
+
+fn transaction(amount: &str, created_at: CreateTime) -> Transaction {
+    Transaction {
+        id: 1,
+        seq: 1,
+        amount: Decimal::from_str(amount).unwrap(),
+        created_at,
+    }
+}
+
+#[test]
+fn empty_filter_matches_everything() {
+    let filter = TransactionFilter::from_str("").unwrap();
+
+    assert_eq!(filter, TransactionFilter::default());
+    assert_eq!(filter.limit, DEFAULT_LIMIT);
+    assert!(filter.matches(&transaction("-12.34", 0)));
+}
+
+#[test]
+fn parses_every_term_in_any_order() {
+    let filter = TransactionFilter::from_str(
+        "limit=50 created_after=1700000000 amount<=200 created_before=1800000000 amount>=$1,000.5",
+    )
+    .unwrap();
+
+    assert_eq!(
+        filter,
+        TransactionFilter {
+            min_amount: Some(Decimal::from_str("1000.5").unwrap()),
+            max_amount: Some(Decimal::from(200)),
+            created_after: Some(1_700_000_000),
+            created_before: Some(1_800_000_000),
+            limit: 50,
+        }
+    );
+}
+
+#[test]
+fn display_round_trips() {
+    let filter =
+        TransactionFilter::from_str("amount>=-5 amount<=12.34 created_before=10 limit=3").unwrap();
+
+    assert_eq!(
+        TransactionFilter::from_str(&filter.to_string()).unwrap(),
+        filter
+    );
+}
+
+#[test]
+fn amount_bounds_are_inclusive() {
+    let filter = TransactionFilter::from_str("amount>=100 amount<=200").unwrap();
+
+    assert!(!filter.matches(&transaction("99.99", 0)));
+    assert!(filter.matches(&transaction("100", 0)));
+    assert!(filter.matches(&transaction("200.00", 0)));
+    assert!(!filter.matches(&transaction("200.01", 0)));
+}
+
+#[test]
+fn created_at_bounds_are_exclusive() {
+    let filter = TransactionFilter::from_str("created_after=10 created_before=20").unwrap();
+
+    assert!(!filter.matches(&transaction("1", 10)));
+    assert!(filter.matches(&transaction("1", 11)));
+    assert!(filter.matches(&transaction("1", 19)));
+    assert!(!filter.matches(&transaction("1", 20)));
+}
+
+#[test]
+fn rejects_malformed_filters() {
+    let cases = [
+        "amount>5",
+        "amount",
+        "limit=",
+        "created_after=-1",
+        "created_before=soon",
+        "limit=abc",
+        "limit=0",
+        "amount>=$abc",
+        "amount<=1.001",
+        "amount=5",
+        "colour=red",
+        "limit=1 limit=2",
+        "amount>=1 amount>=2",
+    ];
+
+    for input in cases {
+        assert!(
+            TransactionFilter::from_str(input).is_err(),
+            "expected '{input}' to be rejected"
+        );
+    }
+}
+
+#[test]
+fn malformed_filters_have_typed_errors() {
+    assert!(matches!(
+        TransactionFilter::from_str("amount>5"),
+        Err(FilterError::InvalidTerm(..))
+    ));
+    assert!(matches!(
+        TransactionFilter::from_str("colour=red"),
+        Err(FilterError::UnknownKey(key)) if key == "colour"
+    ));
+    assert!(matches!(
+        TransactionFilter::from_str("limit=1 limit=2"),
+        Err(FilterError::DuplicateKey(key)) if key == "limit"
+    ));
+    assert!(matches!(
+        TransactionFilter::from_str("created_after=yesterday"),
+        Err(FilterError::InvalidValue { key, .. }) if key == "created_after"
+    ));
+    assert!(matches!(
+        TransactionFilter::from_str("amount<=1.001"),
+        Err(FilterError::Amount(..))
+    ));
+    assert!(matches!(
+        TransactionFilter::from_str("limit=0"),
+        Err(FilterError::ZeroLimit)
+    ));
+}
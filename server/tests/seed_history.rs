@@ -0,0 +1,74 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Seeds a ledger through `LocalBank::seed_history` and opens it, to prove
+//! the history comes back in order with sequential ids and seqs, audited as
+//! it was created, and that seeding again replaces it.
+
+use dst_demo_server::bank::{AuditAction, Bank as _, CreateTime, LocalBank, Origin};
+use rust_decimal::Decimal;
+
+const TRANSACTION_COUNT: i64 = 1_000;
+const START: CreateTime = 1_700_000_000_000;
+
+fn history(count: i64) -> Vec<(Decimal, CreateTime)> {
+    (0..count)
+        .map(|i| (Decimal::new(i % 200 - 100, 2), START - count + i))
+        .collect()
+}
+
+#[test]
+fn seeded_history_is_loaded_in_order() {
+    let dir = std::env::temp_dir().join(format!("dst_demo_seed_history_{}", std::process::id()));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("bank.db");
+
+    // Seeding again replaces the ledger and its audit log rather than
+    // appending to them
+    LocalBank::seed_history(&db_path, history(TRANSACTION_COUNT * 2)).unwrap();
+    let history = history(TRANSACTION_COUNT);
+    let seeded = LocalBank::seed_history(&db_path, history.clone()).unwrap();
+    assert_eq!(seeded.len(), history.len());
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    runtime.block_on(async move {
+        let bank = LocalBank::open(&db_path).unwrap();
+
+        let transactions = bank.list_transactions().await.unwrap();
+        assert_eq!(*transactions, seeded);
+
+        for ((transaction, (amount, created_at)), id) in transactions.iter().zip(&history).zip(1..)
+        {
+            assert_eq!(transaction.id, id);
+            assert_eq!(u64::try_from(transaction.id).unwrap(), transaction.seq);
+            assert_eq!(transaction.amount, *amount);
+            assert_eq!(transaction.created_at, *created_at);
+        }
+        drop(transactions);
+
+        let balance = history.iter().map(|(amount, _)| amount).sum::<Decimal>();
+        assert_eq!(bank.get_balance().await.unwrap(), balance);
+
+        // Each transaction is audited as seeded, dated when it was created
+        let records = bank.tail_audit(usize::MAX).await.unwrap();
+        assert_eq!(records.len(), seeded.len());
+        for (record, transaction) in records.iter().zip(&seeded) {
+            assert_eq!(record.action, AuditAction::SeedHistory);
+            assert_eq!(record.transaction_id, transaction.id);
+            assert_eq!(record.at, transaction.created_at);
+            assert_eq!(record.arguments, format!("amount={}", transaction.amount));
+        }
+
+        // New transactions continue after the seeded history
+        let created = bank
+            .create_transaction(Decimal::ONE, &Origin::local())
+            .await
+            .unwrap();
+        assert_eq!(created.id, i32::try_from(TRANSACTION_COUNT).unwrap() + 1);
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
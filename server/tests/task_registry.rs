@@ -0,0 +1,25 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Resets the process-wide task registry while a task of the previous run is
+//! still alive, the way the simulator does between runs, and drops that task
+//! afterwards.
+
+use dst_demo_server::tasks;
+
+#[test]
+fn stale_guard_does_not_remove_a_newer_task() {
+    tasks::reset();
+
+    let stale = tasks::register("previous run");
+    tasks::reset();
+
+    let _current = tasks::register("current run");
+    drop(stale);
+
+    let stats = tasks::stats();
+    assert_eq!(stats.alive, vec!["current run".to_string()]);
+    assert_eq!(stats.spawned, 1);
+    assert_eq!(stats.completed, 0);
+}
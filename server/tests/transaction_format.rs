@@ -0,0 +1,119 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Loads transactions persisted before `seq` existed, when `created_at` was
+//! in seconds, and round trips timestamps around the 2038 boundary where the
+//! original `i32` seconds overflowed.
+
+use std::{io::Write as _, str::FromStr as _};
+
+use dst_demo_server::bank::{Bank as _, CreateTime, LocalBank, Origin, Transaction};
+use rust_decimal::Decimal;
+use switchy::fs::sync::OpenOptions;
+
+/// The first second that doesn't fit in an `i32`, 2038-01-19T03:14:08Z
+const EPOCHALYPSE_SECONDS: i64 = i32::MAX as i64 + 1;
+
+#[test]
+fn legacy_ledger_is_loaded_and_extended() {
+    let dir = std::env::temp_dir().join(format!(
+        "dst_demo_transaction_format_{}",
+        std::process::id()
+    ));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+
+    // Written through `switchy::fs` so it lands wherever the bank reads its
+    // file from, on disk or in the simulated fs
+    let db_path = dir.join("bank.db");
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&db_path)
+        .unwrap()
+        .write_all(
+            concat!(
+                "{\"id\":1,\"amount\":\"12.34\",\"created_at\":1700000000}\n",
+                "{\"id\":2,\"amount\":\"-12.34\",\"created_at\":1700000001}\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    let path = db_path.clone();
+    let created = runtime.block_on(async move {
+        let bank = LocalBank::open(&path).unwrap();
+
+        let transactions = bank.list_transactions().await.unwrap().clone();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].seq, 1);
+        assert_eq!(transactions[0].created_at, 1_700_000_000_000);
+        assert_eq!(transactions[1].seq, 2);
+        assert_eq!(transactions[1].created_at, 1_700_000_001_000);
+
+        bank.create_transaction(Decimal::new(500, 2), &Origin::local())
+            .await
+            .unwrap()
+    });
+
+    assert_eq!(created.id, 3);
+    assert_eq!(created.seq, 3);
+
+    // The new transaction is persisted in the new format after the legacy
+    // ones, and the mixed ledger still loads
+    let reopened = runtime.block_on(async move {
+        let bank = LocalBank::open(&db_path).unwrap();
+        bank.list_transactions().await.unwrap().clone()
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+
+    let seqs = reopened.iter().map(|x| x.seq).collect::<Vec<_>>();
+    assert_eq!(seqs, vec![1, 2, 3]);
+    assert_eq!(reopened[2].created_at, created.created_at);
+}
+
+#[test]
+fn legacy_display_format_is_parsed() {
+    let transaction = Transaction::from_str("id=7 created_at=1700000000 amount=$1.00").unwrap();
+
+    assert_eq!(transaction.id, 7);
+    assert_eq!(transaction.seq, 7);
+    assert_eq!(transaction.created_at, 1_700_000_000_000);
+    assert_eq!(transaction.amount, Decimal::new(100, 2));
+}
+
+#[test]
+fn timestamps_past_2038_round_trip() {
+    let created_at: CreateTime = EPOCHALYPSE_SECONDS * 1000 + 999;
+    let transaction = Transaction {
+        id: 1,
+        seq: 1,
+        amount: Decimal::new(100, 2),
+        created_at,
+    };
+
+    let displayed = Transaction::from_str(&transaction.to_string()).unwrap();
+    assert_eq!(displayed.created_at, created_at);
+    assert_eq!(displayed.seq, 1);
+
+    let serialized = serde_json::to_string(&transaction).unwrap();
+    let deserialized = serde_json::from_str::<Transaction>(&serialized).unwrap();
+    assert_eq!(deserialized.created_at, created_at);
+    assert_eq!(deserialized.seq, 1);
+}
+
+#[test]
+fn legacy_seconds_past_2038_are_converted() {
+    for seconds in [EPOCHALYPSE_SECONDS - 1, EPOCHALYPSE_SECONDS] {
+        let legacy = format!("{{\"id\":1,\"amount\":\"1.00\",\"created_at\":{seconds}}}");
+        let transaction = serde_json::from_str::<Transaction>(&legacy).unwrap();
+
+        assert_eq!(transaction.created_at, seconds * 1000);
+        assert_eq!(transaction.seq, 1);
+    }
+}
@@ -0,0 +1,70 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Voids transactions on a `LocalBank` after rewinding the simulated clock,
+//! as a restart with an earlier wall clock would. A void must never be dated
+//! before the transaction it voids, and must not take the server down.
+
+use dst_demo_server::bank::{Bank as _, LocalBank, Origin};
+use rust_decimal::Decimal;
+use switchy::time::simulator::set_step;
+
+#[test]
+fn void_is_dated_no_earlier_than_the_voided_transaction_after_a_rewind() {
+    let dir = std::env::temp_dir().join(format!("dst_demo_void_clock_{}", std::process::id()));
+    let _ = switchy::fs::sync::remove_dir_all(&dir);
+    switchy::fs::sync::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("bank.db");
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    runtime.block_on(async move {
+        let origin = Origin::local();
+
+        let _ = set_step(1_000);
+        let bank = LocalBank::open(&db_path).unwrap();
+        let future = bank
+            .create_transaction(Decimal::new(1_250, 2), &origin)
+            .await
+            .unwrap();
+        drop(bank);
+
+        // Restart with a clock behind everything in the persisted ledger
+        let _ = set_step(1);
+        let bank = LocalBank::open(&db_path).unwrap();
+
+        let created = bank
+            .create_transaction(Decimal::ONE, &origin)
+            .await
+            .unwrap();
+        assert!(created.seq > future.seq);
+        assert!(
+            created.created_at < future.created_at,
+            "only voids are clamped to the ledger, created={created:?} future={future:?}"
+        );
+
+        let voided = bank
+            .void_transaction(future.id, &origin)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(voided.amount, -future.amount);
+        assert!(voided.seq > created.seq);
+        assert!(
+            voided.created_at >= future.created_at,
+            "void dated before the transaction it voids, voided={voided:?} future={future:?}"
+        );
+
+        let ids = bank
+            .list_transactions()
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![future.id, created.id, voided.id]);
+    });
+
+    switchy::fs::sync::remove_dir_all(&dir).unwrap();
+}
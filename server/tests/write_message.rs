@@ -0,0 +1,116 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Checks that streaming a large response doesn't build it up in memory,
+//! using an allocator that tracks the peak number of bytes allocated.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use dst_demo_server::{
+    WRITE_CHUNK_SIZE,
+    bank::{Transaction, TransactionId},
+    write_message_from_iter,
+};
+use rust_decimal::Decimal;
+use switchy::unsync::io::AsyncWrite;
+
+const TRANSACTION_COUNT: TransactionId = 100_000;
+
+/// How far above the baseline allocations may peak while the transactions
+/// are written, which is a few chunks' worth
+const MAX_PEAK_GROWTH: usize = 8 * WRITE_CHUNK_SIZE;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Discards everything written to it, only counting the bytes.
+#[derive(Default)]
+struct Sink {
+    written: usize,
+}
+
+impl AsyncWrite for Sink {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().written += buf.len();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn transactions() -> impl Iterator<Item = String> {
+    (1..=TRANSACTION_COUNT).map(|id| {
+        Transaction {
+            id,
+            seq: u64::try_from(id).unwrap(),
+            amount: Decimal::new(123_456, 2),
+            created_at: 1_700_000_000_000 + i64::from(id),
+        }
+        .to_string()
+    })
+}
+
+#[test]
+fn large_response_is_streamed_with_bounded_memory() {
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+
+    // The frame is every transaction separated by newlines, plus the
+    // terminator
+    let expected_len = transactions().map(|x| x.len() + 1).sum::<usize>();
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+
+    let (sink, written) = runtime.block_on(async {
+        let mut sink = Sink::default();
+        let written = write_message_from_iter(transactions(), "\n", usize::MAX, &mut sink)
+            .await
+            .unwrap();
+        (sink, written)
+    });
+
+    let peak_growth = PEAK.load(Ordering::SeqCst).saturating_sub(baseline);
+
+    assert_eq!(sink.written, expected_len);
+    assert_eq!(written, expected_len as u64);
+    assert!(
+        peak_growth <= MAX_PEAK_GROWTH,
+        "writing a {expected_len} byte message peaked at {peak_growth} bytes above the baseline (max={MAX_PEAK_GROWTH})"
+    );
+}
@@ -0,0 +1,226 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! A minimal simulation of a TCP echo server, showing the pieces of the
+//! harness that the bank simulator is built from without any of the bank:
+//!
+//! * a host (`sim.host`) that's restarted whenever it's bounced
+//! * a client (`sim.client`) driven by an `InteractionPlan`, sending seeded
+//!   random payloads and asserting that they're echoed back unchanged
+//! * a fault injector client that bounces the host every so often
+//! * the simulated time and RNG facades
+//!
+//! Run it with `cargo run -p dst_demo_server_simulator --example echo`. It
+//! honors the same `SIMULATOR_*` environment variables as the bank
+//! simulator, e.g. `SIMULATOR_SEED`, `SIMULATOR_RUNS` and
+//! `SIMULATOR_DURATION`.
+//!
+//! Clients can't reach the `Sim` to bounce a host themselves, so the fault
+//! injector queues its bounces for `on_step` to apply, the same way the bank
+//! simulator's `handle_actions` does.
+
+use std::{cell::RefCell, process::ExitCode, time::Duration};
+
+use simvar::{
+    Sim, SimBootstrap, SimConfig,
+    plan::InteractionPlan,
+    run_simulation,
+    switchy::{
+        self,
+        random::{rand::rand::Rng as _, rng},
+        tcp::{GenericTcpListener as _, GenericTcpStream as _, TcpListener, TcpStream},
+        time::simulator::step_multiplier,
+        unsync::io::{AsyncReadExt as _, AsyncWriteExt as _},
+    },
+    utils::run_until_simulation_cancelled,
+};
+
+const HOST: &str = "echo_server";
+const PORT: u16 = 7777;
+
+type BoxError = Box<dyn std::error::Error + Send>;
+
+thread_local! {
+    static PENDING_BOUNCES: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Echoes every byte each connection sends back to it.
+async fn serve(addr: &str) -> Result<(), switchy::tcp::Error> {
+    let listener = TcpListener::bind(addr).await?;
+
+    while let Ok((stream, peer)) = listener.accept().await {
+        log::debug!("serve: {peer} connected");
+        let (mut read, mut write) = stream.into_split();
+
+        switchy::unsync::task::spawn(async move {
+            let mut buf = [0_u8; 1024];
+            while let Ok(count) = read.read(&mut buf).await {
+                if count == 0 || write.write_all(&buf[..count]).await.is_err() {
+                    break;
+                }
+            }
+            log::debug!("serve: {peer} disconnected");
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum Interaction {
+    Sleep(Duration),
+    Echo(Vec<u8>),
+}
+
+#[derive(Default)]
+struct EchoPlan {
+    step: usize,
+    plan: Vec<Interaction>,
+}
+
+impl InteractionPlan<Interaction> for EchoPlan {
+    fn step(&mut self) -> Option<&Interaction> {
+        let item = self.plan.get(self.step)?;
+        self.step += 1;
+        Some(item)
+    }
+
+    fn gen_interactions(&mut self, count: u64) {
+        let mut rng = rng();
+
+        for _ in 0..count {
+            let interaction = if rng.gen_bool(0.2) {
+                Interaction::Sleep(Duration::from_millis(rng.gen_range(0..10_000)))
+            } else {
+                let len = rng.gen_range(1..4096);
+                Interaction::Echo((0..len).map(|_| rng.r#gen()).collect())
+            };
+            self.add_interaction(interaction);
+        }
+    }
+
+    fn add_interaction(&mut self, interaction: Interaction) {
+        self.plan.push(interaction);
+    }
+}
+
+/// Sends the payload and asserts that exactly the same bytes come back,
+/// reconnecting and resending whenever the host goes away mid-echo.
+async fn echo(payload: &[u8]) {
+    let addr = format!("{HOST}:{PORT}");
+
+    loop {
+        let Ok(mut stream) = TcpStream::connect(&addr).await else {
+            switchy::unsync::time::sleep(Duration::from_millis(step_multiplier() * 100)).await;
+            continue;
+        };
+
+        let mut echoed = vec![0_u8; payload.len()];
+        if stream.write_all(payload).await.is_err() || stream.read_exact(&mut echoed).await.is_err()
+        {
+            log::debug!("echo: connection dropped, retrying");
+            continue;
+        }
+
+        assert!(
+            echoed == payload,
+            "expected the {} byte payload to be echoed back unchanged",
+            payload.len()
+        );
+        return;
+    }
+}
+
+pub struct EchoSimulator;
+
+impl SimBootstrap for EchoSimulator {
+    fn build_sim(&self, config: SimConfig) -> SimConfig {
+        PENDING_BOUNCES.with_borrow_mut(|x| *x = 0);
+        config
+    }
+
+    fn props(&self) -> Vec<(String, String)> {
+        vec![("scenario".to_string(), "echo".to_string())]
+    }
+
+    fn on_start(&self, sim: &mut impl Sim) {
+        sim.host(HOST, || async {
+            run_until_simulation_cancelled(serve(&format!("0.0.0.0:{PORT}")))
+                .await
+                .transpose()
+                .map_err(|x| Box::new(std::io::Error::other(x.to_string())) as BoxError)?;
+            Ok(())
+        });
+
+        let mut plan = EchoPlan::default().with_gen_interactions(1000);
+
+        sim.client("echo_client", async move {
+            loop {
+                while let Some(interaction) = plan.step().cloned() {
+                    match interaction {
+                        Interaction::Sleep(duration) => {
+                            switchy::unsync::time::sleep(duration).await;
+                        }
+                        Interaction::Echo(payload) => echo(&payload).await,
+                    }
+                }
+
+                plan.gen_interactions(1000);
+            }
+        });
+
+        sim.client("echo_fault_injector", async move {
+            loop {
+                let millis = rng().gen_range(10_000..100_000) * step_multiplier();
+                switchy::unsync::time::sleep(Duration::from_millis(millis)).await;
+                PENDING_BOUNCES.with_borrow_mut(|x| *x += 1);
+            }
+        });
+    }
+
+    fn on_step(&self, sim: &mut impl Sim) {
+        let bounces = PENDING_BOUNCES.with_borrow_mut(std::mem::take);
+
+        for _ in 0..bounces {
+            log::debug!("bouncing '{HOST}'");
+            sim.bounce(HOST);
+        }
+    }
+
+    fn on_end(&self, _sim: &mut impl Sim) {
+        log::info!("echo simulation finished");
+    }
+}
+
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let results = run_simulation(EchoSimulator)?;
+
+    if results.iter().any(|x| !x.is_success()) {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    /// Ignored by default since the simulation starts the TUI, which needs a
+    /// terminal, unless it's built with `NO_TUI` set. Run it with
+    /// `NO_TUI=1 cargo test -p dst_demo_server_simulator --example echo -- --ignored`.
+    #[test]
+    #[ignore = "needs a terminal for the TUI unless built with NO_TUI set"]
+    fn runs_one_short_seed() {
+        // SAFETY: this is the only test in the example, so nothing reads the
+        // environment concurrently
+        unsafe {
+            std::env::set_var("SIMULATOR_SEED", "1");
+            std::env::set_var("SIMULATOR_RUNS", "1");
+            std::env::set_var("SIMULATOR_DURATION", "10000");
+        }
+
+        let results = simvar::run_simulation(super::EchoSimulator).unwrap();
+
+        assert!(results.iter().all(simvar::SimResult::is_success));
+    }
+}
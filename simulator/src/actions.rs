@@ -0,0 +1,300 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    sync::LazyLock,
+    time::Duration,
+};
+
+use crate::timing;
+
+/// The most actions that can be queued at once, unless
+/// `SIMULATOR_MAX_QUEUED_ACTIONS` is set
+pub const DEFAULT_MAX_QUEUED: usize = 1000;
+
+/// The most queued actions applied in a single step, unless
+/// `SIMULATOR_MAX_ACTIONS_PER_STEP` is set.
+///
+/// A burst of queued faults is spread over the following steps instead of
+/// being applied all at once, which keeps each `on_step` short.
+pub const DEFAULT_MAX_PER_STEP: usize = 4;
+
+static LIMITS: LazyLock<Limits> = LazyLock::new(|| Limits {
+    max_queued: std::env::var("SIMULATOR_MAX_QUEUED_ACTIONS")
+        .ok()
+        .map_or(DEFAULT_MAX_QUEUED, |x| x.parse().unwrap()),
+    max_per_step: std::env::var("SIMULATOR_MAX_ACTIONS_PER_STEP")
+        .ok()
+        .map_or(DEFAULT_MAX_PER_STEP, |x| x.parse().unwrap()),
+});
+
+/// How many actions can be queued, and how many are taken off the queue each
+/// step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_queued: usize,
+    pub max_per_step: usize,
+}
+
+impl Limits {
+    /// The limits from `SIMULATOR_MAX_QUEUED_ACTIONS` and
+    /// `SIMULATOR_MAX_ACTIONS_PER_STEP`
+    ///
+    /// # Panics
+    ///
+    /// * If either of them is not a valid integer
+    #[must_use]
+    pub fn from_env() -> Self {
+        *LIMITS
+    }
+}
+
+/// An action a client asked the simulation to apply at the start of a later
+/// step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Bounce(String),
+    Crash(String),
+    Disrupt { client: String, failures: u64 },
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bounce(host) => write!(f, "bounce of '{host}'"),
+            Self::Crash(host) => write!(f, "crash of '{host}'"),
+            Self::Disrupt { client, failures } => {
+                write!(f, "disruption of '{client}' failures={failures}")
+            }
+        }
+    }
+}
+
+/// An action along with who queued it and when
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedAction {
+    /// The name of the client that queued the action
+    pub producer: String,
+    /// The step during which the action was queued
+    pub step: u64,
+    /// How many actions the producer queued before this one in the run
+    pub seq: u64,
+    pub action: Action,
+}
+
+impl QueuedAction {
+    /// Actions are applied in this order. Clients are polled in an order
+    /// that's deterministic per seed but otherwise arbitrary, so actions
+    /// queued in the same step are ordered by producer name rather than by
+    /// when they were queued.
+    fn key(&self) -> (u64, &str, u64) {
+        (self.step, &self.producer, self.seq)
+    }
+}
+
+impl std::fmt::Display for QueuedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (queued by {} at step={} seq={})",
+            self.action, self.producer, self.step, self.seq
+        )
+    }
+}
+
+/// A queued action that was taken off the queue, and whether it was applied
+#[derive(Debug, Clone)]
+pub struct AppliedAction {
+    pub queued: QueuedAction,
+    /// The step at which the action was taken off the queue
+    pub step: u64,
+    /// The elapsed simulated time at which the action was taken off the queue
+    pub at: Duration,
+    /// Whether the action was dropped because faults were disabled in the
+    /// run's phase at the time
+    pub skipped: bool,
+}
+
+/// What happened to the actions queued in a run besides being applied
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Actions dropped because the queue was full
+    pub dropped: u64,
+    /// Bounces dropped because they repeated the bounce queued right before
+    /// them by the same producer in the same step
+    pub collapsed: u64,
+    /// How many times an action was left on the queue at the end of a step,
+    /// summed over every step
+    pub carried_forward: u64,
+    pub peak_queued: usize,
+}
+
+#[derive(Debug)]
+struct ActionQueue {
+    limits: Limits,
+    queued: VecDeque<QueuedAction>,
+    seqs: BTreeMap<String, u64>,
+    applied: Vec<AppliedAction>,
+    stats: QueueStats,
+}
+
+thread_local! {
+    static ACTIONS: RefCell<ActionQueue> = const {
+        RefCell::new(ActionQueue {
+            limits: Limits {
+                max_queued: DEFAULT_MAX_QUEUED,
+                max_per_step: DEFAULT_MAX_PER_STEP,
+            },
+            queued: VecDeque::new(),
+            seqs: BTreeMap::new(),
+            applied: vec![],
+            stats: QueueStats {
+                dropped: 0,
+                collapsed: 0,
+                carried_forward: 0,
+                peak_queued: 0,
+            },
+        })
+    };
+}
+
+/// # Panics
+///
+/// * If `SIMULATOR_MAX_QUEUED_ACTIONS` or `SIMULATOR_MAX_ACTIONS_PER_STEP` is
+///   not a valid integer
+pub fn reset() {
+    reset_with_limits(Limits::from_env());
+}
+
+/// Empties the queue, which holds at most `limits.max_queued` actions from now
+/// on.
+pub fn reset_with_limits(limits: Limits) {
+    ACTIONS.with_borrow_mut(|x| {
+        x.limits = limits;
+        x.queued.clear();
+        x.seqs.clear();
+        x.applied.clear();
+        x.stats = QueueStats::default();
+    });
+}
+
+/// Queues an action to be applied at the start of a later step, returning
+/// whether it was queued.
+///
+/// The action is dropped if the queue is full. A bounce that repeats the
+/// bounce the same producer queued right before it in the same step is
+/// collapsed into that one, which counts as queued.
+#[must_use]
+pub fn queue(producer: &str, action: Action) -> bool {
+    ACTIONS.with_borrow_mut(|x| {
+        let step = timing::step();
+
+        let repeated = matches!(action, Action::Bounce(..))
+            && x.queued.back().is_some_and(|last| {
+                last.producer == producer && last.step == step && last.action == action
+            });
+
+        if repeated {
+            log::debug!("collapsed repeated {action} queued by {producer} at step={step}");
+            x.stats.collapsed += 1;
+            return true;
+        }
+
+        if x.queued.len() >= x.limits.max_queued {
+            if x.stats.dropped == 0 {
+                log::warn!(
+                    "action queue is full, dropping {action} queued by {producer} at step={step}"
+                );
+            }
+            x.stats.dropped += 1;
+            return false;
+        }
+
+        let seq = x.seqs.entry(producer.to_string()).or_default();
+        let queued = QueuedAction {
+            producer: producer.to_string(),
+            step,
+            seq: *seq,
+            action,
+        };
+        *seq += 1;
+
+        log::debug!("queued {queued}");
+
+        x.queued.push_back(queued);
+        x.stats.peak_queued = x.stats.peak_queued.max(x.queued.len());
+        true
+    })
+}
+
+/// Takes up to `max` queued actions off the queue, ordered by the step they
+/// were queued in, then the producer's name, then the producer's sequence
+/// number.
+#[must_use]
+pub fn take(max: usize) -> Vec<QueuedAction> {
+    ACTIONS.with_borrow_mut(|x| {
+        x.queued
+            .make_contiguous()
+            .sort_by(|a, b| a.key().cmp(&b.key()));
+
+        let count = x.queued.len().min(max);
+        x.queued.drain(..count).collect()
+    })
+}
+
+/// Takes the actions to apply in this step off the queue, carrying the rest
+/// forward to the following steps.
+#[must_use]
+pub fn take_for_step() -> Vec<QueuedAction> {
+    let max = ACTIONS.with_borrow(|x| x.limits.max_per_step);
+    let taken = take(max);
+
+    ACTIONS.with_borrow_mut(|x| {
+        x.stats.carried_forward += x.queued.len() as u64;
+    });
+
+    taken
+}
+
+#[must_use]
+pub fn stats() -> QueueStats {
+    ACTIONS.with_borrow(|x| x.stats)
+}
+
+/// The dropped, collapsed, and carried-forward actions and the peak queue
+/// length, to be included in the end-of-run summary.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    let stats = stats();
+
+    vec![
+        ("actions_dropped".to_string(), stats.dropped.to_string()),
+        ("actions_collapsed".to_string(), stats.collapsed.to_string()),
+        (
+            "actions_carried_forward".to_string(),
+            stats.carried_forward.to_string(),
+        ),
+        (
+            "peak_queued_actions".to_string(),
+            stats.peak_queued.to_string(),
+        ),
+    ]
+}
+
+/// Records that an action taken off the queue was applied, or skipped.
+pub fn record_applied(queued: QueuedAction, skipped: bool) {
+    ACTIONS.with_borrow_mut(|x| {
+        x.applied.push(AppliedAction {
+            queued,
+            step: timing::step(),
+            at: timing::elapsed(),
+            skipped,
+        });
+    });
+}
+
+/// Every action taken off the queue so far in the run, in the order they
+/// were taken, so faults can be correlated with the anomalies they caused.
+#[must_use]
+pub fn applied() -> Vec<AppliedAction> {
+    ACTIONS.with_borrow(|x| x.applied.clone())
+}
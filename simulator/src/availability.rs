@@ -0,0 +1,236 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+use crate::{faults, timing};
+
+/// A contiguous stretch of time during which a host failed its health checks
+#[derive(Debug, Clone, Copy)]
+pub struct Downtime {
+    /// When the host was last seen healthy before this window, which is where
+    /// a fault that caused it would have been applied
+    pub after: Duration,
+    /// When the host was first seen unhealthy
+    pub start: Duration,
+    /// When the host was seen healthy again, if it was before the run ended
+    pub end: Option<Duration>,
+}
+
+impl Downtime {
+    #[must_use]
+    pub fn duration(&self, now: Duration) -> Duration {
+        self.end.unwrap_or(now).saturating_sub(self.start)
+    }
+
+    /// Whether a fault injected into `host` explains this window.
+    #[must_use]
+    pub fn is_attributed(&self, host: &str, now: Duration) -> bool {
+        let end = self.end.unwrap_or(now);
+
+        faults::applied()
+            .iter()
+            .any(|x| x.host == host && x.at >= self.after && x.at <= end)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Availability {
+    pub healthy: Duration,
+    pub unhealthy: Duration,
+    pub downtimes: Vec<Downtime>,
+    last: Option<(Duration, bool)>,
+}
+
+impl Availability {
+    fn observe(&mut self, healthy: bool, now: Duration) {
+        let was_healthy = self.last.map(|(_, x)| x);
+
+        if let Some((at, was_healthy)) = self.last {
+            let elapsed = now.saturating_sub(at);
+            if was_healthy {
+                self.healthy += elapsed;
+            } else {
+                self.unhealthy += elapsed;
+            }
+        }
+
+        match (was_healthy, healthy) {
+            (None | Some(true), false) => {
+                self.downtimes.push(Downtime {
+                    after: self.last.map_or(Duration::ZERO, |(at, _)| at),
+                    start: now,
+                    end: None,
+                });
+            }
+            (Some(false), true) => {
+                if let Some(downtime) = self.downtimes.last_mut() {
+                    downtime.end = Some(now);
+                }
+            }
+            _ => {}
+        }
+
+        self.last = Some((now, healthy));
+    }
+
+    /// The healthy and unhealthy time, including the time since the last
+    /// health check.
+    #[must_use]
+    pub fn totals(&self, now: Duration) -> (Duration, Duration) {
+        let (mut healthy, mut unhealthy) = (self.healthy, self.unhealthy);
+
+        if let Some((at, was_healthy)) = self.last {
+            let elapsed = now.saturating_sub(at);
+            if was_healthy {
+                healthy += elapsed;
+            } else {
+                unhealthy += elapsed;
+            }
+        }
+
+        (healthy, unhealthy)
+    }
+
+    #[must_use]
+    pub fn longest_downtime(&self, now: Duration) -> Duration {
+        self.downtimes
+            .iter()
+            .map(|x| x.duration(now))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The unhealthy time that isn't explained by a fault injected into
+    /// `host`.
+    #[must_use]
+    pub fn unattributed_downtime(&self, host: &str, now: Duration) -> Duration {
+        self.downtimes
+            .iter()
+            .filter(|x| !x.is_attributed(host, now))
+            .map(|x| x.duration(now))
+            .sum()
+    }
+}
+
+thread_local! {
+    static AVAILABILITY: RefCell<BTreeMap<String, Availability>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+pub fn reset() {
+    AVAILABILITY.with_borrow_mut(BTreeMap::clear);
+}
+
+/// Records the outcome of a health check against `host` at the current
+/// simulated time. The time since the host's previous health check is
+/// accounted to the state that check observed.
+pub fn observe(host: &str, healthy: bool) {
+    let now = timing::elapsed();
+
+    AVAILABILITY.with_borrow_mut(|x| {
+        x.entry(host.to_string()).or_default().observe(healthy, now);
+    });
+}
+
+#[must_use]
+pub fn snapshot() -> BTreeMap<String, Availability> {
+    AVAILABILITY.with_borrow(Clone::clone)
+}
+
+/// Logs a table of each host's availability.
+#[allow(clippy::cast_possible_truncation)]
+pub fn log_summary() {
+    let availability = snapshot();
+
+    if availability.is_empty() {
+        return;
+    }
+
+    let now = timing::elapsed();
+    let header = format!(
+        "{:<20} {:>12} {:>14} {:>10} {:>14} {:>18}",
+        "host", "healthy (ms)", "unhealthy (ms)", "downtimes", "longest (ms)", "unattributed (ms)"
+    );
+    let rows = availability.iter().map(|(host, x)| {
+        let (healthy, unhealthy) = x.totals(now);
+        format!(
+            "{host:<20} {:>12} {:>14} {:>10} {:>14} {:>18}",
+            healthy.as_millis() as u64,
+            unhealthy.as_millis() as u64,
+            x.downtimes.len(),
+            x.longest_downtime(now).as_millis() as u64,
+            x.unattributed_downtime(host, now).as_millis() as u64,
+        )
+    });
+    let table = std::iter::once(header)
+        .chain(rows)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    log::info!("host availability:\n{table}");
+}
+
+/// Each host's availability, to be included in the end-of-run summary.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn summary() -> Vec<(String, String)> {
+    let now = timing::elapsed();
+
+    snapshot()
+        .into_iter()
+        .flat_map(|(host, x)| {
+            let (healthy, unhealthy) = x.totals(now);
+            [
+                (
+                    format!("availability.{host}.healthy_ms"),
+                    (healthy.as_millis() as u64).to_string(),
+                ),
+                (
+                    format!("availability.{host}.unhealthy_ms"),
+                    (unhealthy.as_millis() as u64).to_string(),
+                ),
+                (
+                    format!("availability.{host}.downtimes"),
+                    x.downtimes.len().to_string(),
+                ),
+                (
+                    format!("availability.{host}.longest_downtime_ms"),
+                    (x.longest_downtime(now).as_millis() as u64).to_string(),
+                ),
+            ]
+        })
+        .collect()
+}
+
+/// Checks each host's downtime that isn't explained by an injected fault
+/// against `SIMULATOR_HEALTH_SLO_PERCENT`, the percentage of the run a host
+/// may be unhealthy for, if set.
+///
+/// # Panics
+///
+/// * If a host was unhealthy for longer than the SLO allows
+/// * If `SIMULATOR_HEALTH_SLO_PERCENT` is not a valid number
+pub fn check() {
+    let Some(slo) = std::env::var("SIMULATOR_HEALTH_SLO_PERCENT")
+        .ok()
+        .map(|x| x.parse::<f64>().unwrap())
+    else {
+        return;
+    };
+
+    let now = timing::elapsed();
+    if now.is_zero() {
+        return;
+    }
+
+    for (host, x) in snapshot() {
+        let unattributed = x.unattributed_downtime(&host, now);
+        let percent = unattributed.as_secs_f64() / now.as_secs_f64() * 100.0;
+
+        assert!(
+            percent <= slo,
+            "{host} was unhealthy for {unattributed:?} ({percent:.2}% of the run) without an injected fault to explain it (slo={slo}%):\n{:#?}",
+            x.downtimes
+                .iter()
+                .filter(|x| !x.is_attributed(&host, now))
+                .collect::<Vec<_>>(),
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use simvar::switchy::{random::rand::rand::Rng, time::simulator::step_multiplier};
+
+/// Exponential backoff with full jitter.
+///
+/// The jitter is drawn from the given RNG, so with the per-run RNG the delays
+/// stay reproducible for a seed while still spreading out clients that fail at
+/// the same time.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    #[must_use]
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// The backoff used by the clients' connect retry loops
+    #[must_use]
+    pub fn connect() -> Self {
+        Self::new(
+            Duration::from_millis(step_multiplier()),
+            Duration::from_secs(step_multiplier()),
+        )
+    }
+
+    /// The delay before the next attempt, a random duration between zero and
+    /// `base * 2^attempt`, capped at `max`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn next_delay(&mut self, rng: &mut impl Rng) -> Duration {
+        let ceiling = self
+            .base
+            .saturating_mul(2_u32.saturating_pow(self.attempt))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        Duration::from_millis(rng.gen_range(0..=ceiling.as_millis() as u64))
+    }
+
+    /// Resets the backoff after a successful attempt.
+    pub const fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
@@ -0,0 +1,30 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+use std::{path::PathBuf, process::ExitCode};
+
+use dst_demo_server_simulator::capture;
+
+fn main() -> ExitCode {
+    let paths = std::env::args()
+        .skip(1)
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    if paths.is_empty() {
+        eprintln!("Usage: dst-demo-capture-dump <capture file>...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut stdout = std::io::stdout().lock();
+
+    for path in paths {
+        if let Err(e) = capture::dump(&path, &mut stdout) {
+            eprintln!("Failed to dump capture {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,42 @@
+use dst_demo_server::cancel_safety;
+
+/// Whether `SIMULATOR_CANCEL_SAFETY=strict` was set for this process.
+#[must_use]
+pub fn strict() -> bool {
+    std::env::var("SIMULATOR_CANCEL_SAFETY").is_ok_and(|x| x == "strict")
+}
+
+/// Resets the server's cancellation safety registry at the start of a run.
+///
+/// Like the task registry, it is process-wide, so the counts are only
+/// attributable to a single run when runs aren't executed in parallel.
+pub fn reset() {
+    cancel_safety::reset();
+}
+
+/// Reports the guarded sections that were dropped part way through during the
+/// run.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_CANCEL_SAFETY=strict` and any guarded section was cancelled
+pub fn check() {
+    let cancelled = cancel_safety::cancelled();
+
+    if cancelled.is_empty() {
+        return;
+    }
+
+    let report = cancelled
+        .iter()
+        .map(|(label, count)| format!("{label}: {count}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    log::warn!("cancellation safety violations:\n{report}");
+
+    assert!(
+        !strict(),
+        "guarded sections were cancelled part way through:\n{report}"
+    );
+}
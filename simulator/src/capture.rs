@@ -0,0 +1,398 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{Read, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+use dst_demo_server::identity;
+use simvar::switchy::{
+    self,
+    tcp::TcpStream,
+    unsync::io::{AsyncRead, AsyncWrite, ReadBuf},
+};
+
+/// The most bytes of encoded records buffered per run. Older records are
+/// dropped to make room, so a long run keeps the traffic leading up to its end.
+pub const CAPACITY: usize = 16 * 1024 * 1024;
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Write,
+    Read,
+}
+
+impl Direction {
+    const fn as_byte(self) -> u8 {
+        match self {
+            Self::Write => 0,
+            Self::Read => 1,
+        }
+    }
+
+    const fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Write),
+            1 => Some(Self::Read),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Write => "->",
+            Self::Read => "<-",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub direction: Direction,
+    pub timestamp: u64,
+    pub local_addr: String,
+    pub peer_addr: String,
+    pub payload: Vec<u8>,
+}
+
+struct Capture {
+    path: PathBuf,
+    records: VecDeque<Vec<u8>>,
+    buffered: usize,
+    dropped: u64,
+}
+
+impl Capture {
+    fn push(&mut self, record: Vec<u8>) {
+        self.buffered += record.len();
+        self.records.push_back(record);
+
+        while self.buffered > CAPACITY
+            && let Some(oldest) = self.records.pop_front()
+        {
+            self.buffered -= oldest.len();
+            self.dropped += 1;
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.dropped > 0 {
+            log::warn!(
+                "capture: dropped the {} oldest records of {} to stay within {CAPACITY} bytes",
+                self.dropped,
+                self.path.display()
+            );
+            self.dropped = 0;
+        }
+
+        if self.records.is_empty() {
+            return;
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path);
+
+        let written = file.and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            for record in &self.records {
+                writer.write_all(record)?;
+            }
+            writer.flush()
+        });
+
+        match written {
+            Ok(()) => {
+                self.records.clear();
+                self.buffered = 0;
+            }
+            Err(e) => {
+                log::error!(
+                    "capture: failed to write capture file {}: {e:?}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Starts a new capture for the current run if `SIMULATOR_CAPTURE_DIR` is set and
+/// the `capture` feature is enabled, flushing any capture left over from a
+/// previous run on this thread.
+///
+/// The capture is flushed at the end of the run, which also covers runs that
+/// fail, since a failing step's panic is caught by the harness.
+///
+/// # Panics
+///
+/// * If the `SIMULATOR_CAPTURE_DIR` directory fails to be created
+pub fn reset(seed: u64) {
+    let dir = std::env::var("SIMULATOR_CAPTURE_DIR").ok();
+
+    let capture = dir.and_then(|dir| {
+        if !cfg!(feature = "capture") {
+            log::warn!(
+                "capture: SIMULATOR_CAPTURE_DIR is set, but the simulator was built without the `capture` feature"
+            );
+            return None;
+        }
+
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(format!(
+            "capture-seed-{seed}-thread-{}.bin",
+            switchy::unsync::thread_id()
+        ));
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        }
+        log::debug!("capture: capturing messages to {}", path.display());
+
+        Some(Capture {
+            path,
+            records: VecDeque::new(),
+            buffered: 0,
+            dropped: 0,
+        })
+    });
+
+    CAPTURE.with_borrow_mut(|x| *x = capture);
+}
+
+/// Flushes the buffered records of the current run's capture to disk.
+pub fn flush() {
+    CAPTURE.with_borrow_mut(|x| {
+        if let Some(capture) = x {
+            capture.flush();
+        }
+    });
+}
+
+/// Records the bytes of a completed read or write. This is a no-op unless capturing was
+/// enabled for the current run.
+///
+/// # Panics
+///
+/// * If the simulated clock is before the epoch
+#[allow(clippy::cast_possible_truncation)]
+fn record(direction: Direction, local_addr: &str, peer_addr: &str, payload: &[u8]) {
+    CAPTURE.with_borrow_mut(|x| {
+        let Some(capture) = x else {
+            return;
+        };
+
+        let timestamp = switchy::time::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let mut buffer =
+            Vec::with_capacity(1 + 8 + 12 + local_addr.len() + peer_addr.len() + payload.len());
+        buffer.push(direction.as_byte());
+        buffer.extend_from_slice(&timestamp.to_le_bytes());
+        for bytes in [local_addr.as_bytes(), peer_addr.as_bytes(), payload] {
+            buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(bytes);
+        }
+
+        capture.push(buffer);
+    });
+}
+
+/// A client connection that records everything read from and written to it
+/// in the run's capture.
+///
+/// Every client connects through it, so none of them has to remember to
+/// record its messages. Each record holds the bytes of a single read or
+/// write, which isn't necessarily a whole message.
+pub struct CapturedStream {
+    inner: TcpStream,
+    local_addr: String,
+    peer_addr: String,
+}
+
+impl CapturedStream {
+    /// Connects to `addr`, capturing the connection.
+    ///
+    /// # Errors
+    ///
+    /// * If the connection fails
+    pub async fn connect(addr: &str) -> Result<Self, switchy::tcp::Error> {
+        let inner = TcpStream::connect(addr).await?;
+        let local_addr = inner.local_addr()?.to_string();
+
+        Ok(Self {
+            inner,
+            local_addr,
+            peer_addr: addr.to_string(),
+        })
+    }
+
+    /// Connects to `addr` identified as `identity`, capturing the connection
+    /// including its preamble.
+    ///
+    /// # Errors
+    ///
+    /// * If the connection fails
+    /// * If the preamble fails to be written
+    pub async fn connect_with_identity(
+        addr: &str,
+        identity: &str,
+    ) -> Result<Self, dst_demo_server::Error> {
+        let mut stream = Self::connect(addr).await?;
+
+        identity::send_preamble(&mut stream, identity).await?;
+
+        Ok(stream)
+    }
+
+    /// # Errors
+    ///
+    /// * If the connection's local address couldn't be determined
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsyncRead for CapturedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+
+        if matches!(poll, Poll::Ready(Ok(()))) {
+            let read = &buf.filled()[filled..];
+            if !read.is_empty() {
+                record(Direction::Read, &this.local_addr, &this.peer_addr, read);
+            }
+        }
+
+        poll
+    }
+}
+
+impl AsyncWrite for CapturedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(written)) = &poll
+            && *written > 0
+        {
+            record(
+                Direction::Write,
+                &this.local_addr,
+                &this.peer_addr,
+                &buf[..*written],
+            );
+        }
+
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// # Errors
+///
+/// * If the capture file fails to be read
+/// * If the capture file contains a malformed record
+pub fn read_capture(path: &Path) -> Result<Vec<Record>, std::io::Error> {
+    fn invalid(message: &str) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+    }
+
+    fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>, std::io::Error> {
+        let mut len = [0_u8; 4];
+        reader.read_exact(&mut len)?;
+        let mut bytes = vec![0_u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut records = vec![];
+
+    loop {
+        let mut direction = [0_u8; 1];
+        match reader.read_exact(&mut direction) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        let direction =
+            Direction::from_byte(direction[0]).ok_or_else(|| invalid("Invalid direction"))?;
+
+        let mut timestamp = [0_u8; 8];
+        reader.read_exact(&mut timestamp)?;
+
+        let local_addr = String::from_utf8(read_bytes(&mut reader)?)
+            .map_err(|_| invalid("Invalid local_addr"))?;
+        let peer_addr = String::from_utf8(read_bytes(&mut reader)?)
+            .map_err(|_| invalid("Invalid peer_addr"))?;
+        let payload = read_bytes(&mut reader)?;
+
+        records.push(Record {
+            direction,
+            timestamp: u64::from_le_bytes(timestamp),
+            local_addr,
+            peer_addr,
+            payload,
+        });
+    }
+
+    Ok(records)
+}
+
+/// Pretty-prints a capture file, decoding the payloads as text with their
+/// message terminators escaped.
+///
+/// # Errors
+///
+/// * If the capture file fails to be read
+/// * If writing to the output fails
+pub fn dump(path: &Path, output: &mut impl Write) -> Result<(), std::io::Error> {
+    for record in read_capture(path)? {
+        let payload = String::from_utf8_lossy(&record.payload).replace('\0', "\\0");
+
+        writeln!(
+            output,
+            "[{}] {} {} {}: {payload}",
+            record.timestamp, record.local_addr, record.direction, record.peer_addr,
+        )?;
+    }
+
+    Ok(())
+}
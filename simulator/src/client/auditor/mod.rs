@@ -0,0 +1,274 @@
+use std::{collections::BTreeMap, str::FromStr as _};
+
+use dst_demo_server::{
+    ServerAction,
+    bank::{AuditRecord, Transaction, TransactionId},
+    message::MessageBuffer,
+    protocol::{ProtocolVersion, RESOURCE_EXHAUSTED_MESSAGE},
+};
+use plan::{AuditorInteractionPlan, Interaction};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{self, random::rng, unsync::io::AsyncWriteExt as _},
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff,
+    capture::CapturedStream,
+    connections, expected_ledger, replication, should_start,
+    timing::{self, Phase},
+};
+
+const NAME: &str = "auditor";
+
+/// How many more audit records than listed transactions are tailed, to leave
+/// room for the writes committed between the listing and the tail
+const AUDIT_SLACK: usize = 100;
+
+/// Starts a client that, once the run reaches the recovery phase, lists the
+/// primary's ledger every simulated minute and asserts that it matches the
+/// writes acknowledged to the other clients.
+///
+/// It then tails the primary's audit log and asserts that every listed
+/// transaction has exactly one audit record, and every audit record a listed
+/// transaction.
+pub fn start(sim: &mut impl Sim) {
+    if !should_start(NAME) {
+        return;
+    }
+
+    let mut plan = AuditorInteractionPlan::new().with_gen_interactions(1000);
+
+    sim.client(NAME, async move {
+        loop {
+            while let Some(interaction) = plan.step() {
+                perform_interaction(interaction).await?;
+            }
+
+            plan.gen_interactions(1000);
+        }
+    });
+}
+
+async fn perform_interaction(
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::Audit => {
+            // Faults can leave writes in doubt and the primary unreachable,
+            // so the ledger is only expected to be settled once they stop
+            if timing::phase() == Phase::Recovery {
+                audit(&replication::primary_addr(ProtocolVersion::V1)).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn audit(server_addr: &str) {
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Auditor] Connecting to server...");
+        connections::attempt();
+        match CapturedStream::connect(server_addr).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Auditor] Failed to connect to server: {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track(NAME);
+    let Ok(addr) = stream.local_addr().map(|x| x.to_string()) else {
+        return;
+    };
+    let mut buffer = MessageBuffer::new();
+
+    // Writes acknowledged before the listing was requested must be in it,
+    // and writes acknowledged while it was in flight may be
+    let before = expected_ledger::snapshot();
+
+    let action = ServerAction::ListTransactions.to_string();
+    if send_message(server_addr, &addr, &mut stream, &action)
+        .await
+        .is_none()
+    {
+        return;
+    }
+    let Some(message) = read_message(server_addr, &addr, &mut stream, &mut buffer).await else {
+        return;
+    };
+    if message == RESOURCE_EXHAUSTED_MESSAGE {
+        log::debug!("[Auditor] the listing exceeded the primary's response budget");
+        return;
+    }
+
+    let after = expected_ledger::snapshot();
+
+    let ledger = if message.is_empty() {
+        vec![]
+    } else {
+        message
+            .split('\n')
+            .map(Transaction::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| {
+                panic!("[Auditor] Invalid formatted transactions ({e:?}):\n{message}")
+            })
+    };
+
+    if let Some(diff) = expected_ledger::diff(&before, &after, &ledger) {
+        panic!(
+            "[Auditor] the primary's ledger at {server_addr} doesn't match the acknowledged writes at {:?}:\n{diff}",
+            timing::elapsed(),
+        );
+    }
+
+    log::debug!(
+        "[Auditor] {} transactions in the primary's ledger match the acknowledged writes",
+        ledger.len(),
+    );
+
+    let count = ledger.len() + AUDIT_SLACK;
+    let Some(records) = tail_audit(server_addr, &addr, &mut stream, &mut buffer, count).await
+    else {
+        return;
+    };
+
+    // Anything short of the requested count is the whole log
+    let complete = records.len() < count;
+    if let Some(mismatch) = audit_mismatch(&ledger, &records, complete) {
+        panic!(
+            "[Auditor] the primary's audit log at {server_addr} doesn't match its ledger at {:?}: {mismatch}",
+            timing::elapsed(),
+        );
+    }
+
+    log::debug!(
+        "[Auditor] {} audit records match the primary's ledger",
+        records.len(),
+    );
+}
+
+/// Reads the last `count` records of the primary's audit log.
+///
+/// Returns `None` if the tail couldn't be read or exceeded the response
+/// budget.
+async fn tail_audit(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    buffer: &mut MessageBuffer,
+    count: usize,
+) -> Option<Vec<AuditRecord>> {
+    let action = ServerAction::TailAudit.to_string();
+    let count = count.to_string();
+    send_message(server_addr, addr, stream, &action).await?;
+    send_message(server_addr, addr, stream, &count).await?;
+
+    let prompt = read_message(server_addr, addr, stream, buffer).await?;
+    assert!(
+        prompt == "Enter the number of audit records to show:",
+        "[Auditor] expected the TAIL_AUDIT prompt, instead got:\n'{prompt}'"
+    );
+
+    let message = read_message(server_addr, addr, stream, buffer).await?;
+    if message == RESOURCE_EXHAUSTED_MESSAGE {
+        log::debug!("[Auditor] the audit log exceeded the primary's response budget");
+        return None;
+    }
+
+    let records = message
+        .split('\n')
+        .filter(|x| !x.is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<AuditRecord>, _>>()
+        .unwrap_or_else(|e| {
+            panic!("[Auditor] Invalid formatted audit records ({e:?}):\n{message}")
+        });
+
+    Some(records)
+}
+
+/// Pairs the audit `records` with the transactions in `ledger` both ways,
+/// describing the first transaction or record that has no counterpart.
+///
+/// Records are appended in the same commit as their transactions, so they're
+/// in id order, and every listed transaction's record was appended before
+/// the listing. Unless the tail is the `complete` log, only the ids from the
+/// first tailed record onwards are covered by it. Records of transactions
+/// committed after the listing are ignored.
+fn audit_mismatch(
+    ledger: &[Transaction],
+    records: &[AuditRecord],
+    complete: bool,
+) -> Option<String> {
+    let first = if complete {
+        TransactionId::MIN
+    } else {
+        records
+            .first()
+            .map_or(TransactionId::MAX, |x| x.transaction_id)
+    };
+    let last = ledger.last().map_or(0, |x| x.id);
+
+    let mut audited = BTreeMap::new();
+    for record in records.iter().filter(|x| x.transaction_id <= last) {
+        if let Some(previous) = audited.insert(record.transaction_id, record) {
+            return Some(format!(
+                "transaction id={} was audited twice, as {previous:?} and {record:?}",
+                record.transaction_id
+            ));
+        }
+    }
+
+    for transaction in ledger.iter().filter(|x| x.id >= first) {
+        if audited.remove(&transaction.id).is_none() {
+            return Some(format!("transaction {transaction} has no audit record"));
+        }
+    }
+
+    audited
+        .into_values()
+        .next()
+        .map(|record| format!("audit record {record:?} has no transaction"))
+}
+
+async fn send_message(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    message: &str,
+) -> Option<()> {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.push(0_u8);
+
+    if let Err(e) = stream.write_all(&bytes).await {
+        log::debug!("[{addr}->{server_addr}] failed to send message: {e:?}");
+        return None;
+    }
+
+    Some(())
+}
+
+async fn read_message(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    buffer: &mut MessageBuffer,
+) -> Option<String> {
+    crate::read_message(buffer, Box::pin(stream))
+        .await
+        .inspect_err(|e| log::debug!("[{addr}->{server_addr}] failed to read message: {e:?}"))
+        .ok()?
+}
@@ -0,0 +1,207 @@
+use std::{cell::RefCell, collections::BTreeMap, str::FromStr as _, time::Duration};
+
+use dst_demo_server::bank::{Transaction, TransactionId};
+use strum::{AsRefStr, EnumString};
+
+use super::plan::BankerInteractionPlan;
+
+/// How the bankers cache `GET_TRANSACTION` responses, configured through
+/// `SIMULATOR_BANKER_CACHE`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum CacheMode {
+    #[default]
+    Off,
+    On,
+    /// Deliberately keeps entries around after their transaction is voided,
+    /// so the stale read check has a bug to catch
+    SkipInvalidation,
+}
+
+impl CacheMode {
+    /// # Panics
+    ///
+    /// * If `SIMULATOR_BANKER_CACHE` is not `off`, `on`, or `skip_invalidation`
+    #[must_use]
+    pub fn from_env() -> Self {
+        std::env::var("SIMULATOR_BANKER_CACHE")
+            .ok()
+            .map_or_else(Self::default, |x| {
+                Self::from_str(&x)
+                    .unwrap_or_else(|_| panic!("Invalid SIMULATOR_BANKER_CACHE '{x}'"))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Cached transactions that were served even though the banker voided
+    /// them after they were cached
+    pub stale_served: u64,
+}
+
+thread_local! {
+    static STATS: RefCell<CacheStats> = const {
+        RefCell::new(CacheStats {
+            hits: 0,
+            misses: 0,
+            stale_served: 0,
+        })
+    };
+}
+
+pub fn reset() {
+    STATS.with_borrow_mut(|x| *x = CacheStats::default());
+}
+
+/// The cache stats of every banker in this run
+#[must_use]
+pub fn stats() -> CacheStats {
+    STATS.with_borrow(|x| *x)
+}
+
+/// The cache mode, to be included in the run's props when the cache is
+/// enabled.
+#[must_use]
+pub fn props() -> Vec<(String, String)> {
+    let mode = CacheMode::from_env();
+
+    if mode == CacheMode::Off {
+        return vec![];
+    }
+
+    vec![("banker_cache.mode".to_string(), mode.as_ref().to_string())]
+}
+
+/// The cache stats, to be included in the end-of-run summary when the cache
+/// is enabled.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    if CacheMode::from_env() == CacheMode::Off {
+        return vec![];
+    }
+
+    let stats = stats();
+
+    vec![
+        ("banker_cache.hits".to_string(), stats.hits.to_string()),
+        ("banker_cache.misses".to_string(), stats.misses.to_string()),
+        (
+            "banker_cache.stale_served".to_string(),
+            stats.stale_served.to_string(),
+        ),
+    ]
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    transaction: Transaction,
+    /// When the entry was cached, in simulated time since the run started
+    cached_at: Duration,
+    /// The index of the plan interaction that cached the entry
+    step: u64,
+}
+
+/// A banker's read-through cache of `GET_TRANSACTION` responses. Entries
+/// expire after a TTL in simulated time and are invalidated when the banker
+/// voids their transaction.
+///
+/// Every hit is cross-checked against the transactions the banker's plan
+/// voided, so an entry that outlived a void is caught the moment it's
+/// served.
+#[derive(Debug)]
+pub struct TransactionCache {
+    mode: CacheMode,
+    ttl: Duration,
+    entries: BTreeMap<TransactionId, Entry>,
+}
+
+impl TransactionCache {
+    #[must_use]
+    pub const fn new(mode: CacheMode, ttl: Duration) -> Self {
+        Self {
+            mode,
+            ttl,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the cached transaction for `id` when the interaction at index
+    /// `step` of the `plan` reads it at `now`, or `None` if it has to be read
+    /// from the server.
+    ///
+    /// # Panics
+    ///
+    /// * If the cached transaction was voided by the plan after it was cached
+    pub fn get(
+        &mut self,
+        id: TransactionId,
+        step: u64,
+        now: Duration,
+        plan: &BankerInteractionPlan,
+    ) -> Option<Transaction> {
+        if self.mode == CacheMode::Off {
+            return None;
+        }
+
+        let entry = self
+            .entries
+            .get(&id)
+            .filter(|x| now.saturating_sub(x.cached_at) <= self.ttl)
+            .cloned();
+
+        let Some(entry) = entry else {
+            self.entries.remove(&id);
+            STATS.with_borrow_mut(|x| x.misses += 1);
+            return None;
+        };
+
+        let stale = plan.context.voided_between(id, entry.step, step);
+
+        STATS.with_borrow_mut(|x| {
+            x.hits += 1;
+            if stale {
+                x.stale_served += 1;
+            }
+        });
+
+        assert!(
+            !stale,
+            "transaction {} cached by interaction {} was served from the cache by interaction {step} even though it was voided in between (mode={})",
+            entry.transaction,
+            entry.step,
+            self.mode.as_ref(),
+        );
+
+        Some(entry.transaction)
+    }
+
+    /// Caches a transaction read from the server by the interaction at index
+    /// `step` of the plan.
+    pub fn insert(&mut self, transaction: Transaction, step: u64, now: Duration) {
+        if self.mode == CacheMode::Off {
+            return;
+        }
+
+        self.entries.insert(
+            transaction.id,
+            Entry {
+                transaction,
+                cached_at: now,
+                step,
+            },
+        );
+    }
+
+    /// Drops the entry for a transaction the banker just voided.
+    pub fn invalidate(&mut self, id: TransactionId) {
+        if self.mode == CacheMode::SkipInvalidation {
+            log::debug!("invalidate: skipping invalidation of id={id}");
+            return;
+        }
+
+        self.entries.remove(&id);
+    }
+}
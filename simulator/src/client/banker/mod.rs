@@ -1,79 +1,201 @@
-use std::{cell::RefCell, str::FromStr, sync::atomic::AtomicU32};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    str::FromStr,
+    sync::atomic::AtomicU32,
+    time::{Duration, SystemTime},
+};
 
+use cache::{CacheMode, TransactionCache};
 use dst_demo_server::{
     ServerAction,
-    bank::{Transaction, TransactionId},
+    bank::{StatementLine, Transaction, TransactionId},
+    framing::Compression,
+    protocol::{
+        ProtocolError, ProtocolVersion, READ_ONLY_MESSAGE, RESOURCE_EXHAUSTED_MESSAGE, Response,
+    },
+    search::TransactionFilter,
 };
-use plan::{BankerInteractionPlan, Interaction};
+use plan::{BankerInteractionPlan, Interaction, InteractionType, PlanConfig, VoidAck};
+use read_after_void::Verdict;
+use router::ResponseRouter;
 use rust_decimal::Decimal;
 use simvar::{
     Sim,
     plan::InteractionPlan as _,
     switchy::{
-        self,
-        tcp::TcpStream,
-        time::simulator::step_multiplier,
-        unsync::{futures::FutureExt as _, io::AsyncWriteExt as _},
+        self, random::rng, time::simulator::step_multiplier, unsync::io::AsyncWriteExt as _,
     },
 };
 
-mod plan;
+pub mod cache;
+pub mod plan;
+pub mod read_after_void;
+pub mod router;
 
 use crate::{
-    host::server::{HOST, PORT},
-    read_message,
+    assert_eventually, backoff::Backoff, capture::CapturedStream, client::with_deadline,
+    connections, disruption, expected_ledger, observability, progress, replication,
+    rng_trace::rng_labeled, schedule::Schedule, should_start, stats, timeouts, timing,
 };
 
+/// A transaction a banker created, and when the primary acknowledged it
+#[derive(Debug, Clone, Copy)]
+struct Created {
+    id: TransactionId,
+    at: Duration,
+}
+
 thread_local! {
     static ID: RefCell<AtomicU32> = const { RefCell::new(AtomicU32::new(1)) };
+    static LAST_CREATED: RefCell<BTreeMap<String, Created>> = const { RefCell::new(BTreeMap::new()) };
+    static PLAN_CONFIG: RefCell<Option<PlanConfig>> = const { RefCell::new(None) };
+    static COMMITTED_BATCHES: RefCell<Vec<Vec<Transaction>>> = const { RefCell::new(vec![]) };
+    /// The transactions each banker was acknowledged creating, by id
+    static ACKNOWLEDGED: RefCell<BTreeMap<String, BTreeMap<TransactionId, Decimal>>> = const { RefCell::new(BTreeMap::new()) };
 }
 
 pub fn reset_id() {
     ID.with_borrow(|x| x.store(1, std::sync::atomic::Ordering::SeqCst));
+    LAST_CREATED.with_borrow_mut(BTreeMap::clear);
+    COMMITTED_BATCHES.with_borrow_mut(Vec::clear);
+    ACKNOWLEDGED.with_borrow_mut(BTreeMap::clear);
 }
 
-pub fn start(sim: &mut impl Sim) {
-    let server_addr = format!("{HOST}:{PORT}");
+/// Generates the shape of this run's banker plans.
+pub fn reset_plan_config() {
+    let config = PlanConfig::generate(&mut rng_labeled("banker::plan_config"));
+    log::debug!("reset_plan_config: config={config:?}");
+    PLAN_CONFIG.with_borrow_mut(|x| *x = Some(config));
+}
+
+/// The shape of this run's banker plans
+#[must_use]
+pub fn plan_config() -> PlanConfig {
+    PLAN_CONFIG.with_borrow(Clone::clone).unwrap_or_default()
+}
+
+/// How long a transaction acknowledged by the primary may take to become
+/// visible on the replica, configurable through
+/// `SIMULATOR_REPLICA_STALENESS_MS` and scaled by the step multiplier.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_REPLICA_STALENESS_MS` is not a valid integer
+fn replica_staleness() -> Duration {
+    let millis = std::env::var("SIMULATOR_REPLICA_STALENESS_MS")
+        .ok()
+        .map_or(30_000, |x| x.parse::<u64>().unwrap());
+
+    Duration::from_millis(millis * step_multiplier())
+}
+
+/// How many times a banker re-issues a listing that's missing a transaction
+/// acknowledged to it before declaring the transaction lost
+const LIST_RETRIES: u32 = 3;
+
+/// How long, in simulated time, a banker waits before re-issuing a listing
+/// that's missing a transaction acknowledged to it. A transaction must be
+/// listed within `LIST_RETRIES` of these after it was acknowledged.
+fn list_retry_delay() -> Duration {
+    Duration::from_millis(100 * step_multiplier())
+}
+
+/// Records that the primary acknowledged creating the transaction for the
+/// banker, so that every later listing the banker makes must include it.
+fn acknowledge(name: &str, transaction: &Transaction) {
+    ACKNOWLEDGED.with_borrow_mut(|x| {
+        x.entry(name.to_string())
+            .or_default()
+            .insert(transaction.id, transaction.amount);
+    });
+}
+
+/// How long a banker may serve a transaction from its cache, configurable
+/// through `SIMULATOR_BANKER_CACHE_TTL_MS` and scaled by the step multiplier.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_BANKER_CACHE_TTL_MS` is not a valid integer
+fn cache_ttl() -> Duration {
+    let millis = std::env::var("SIMULATOR_BANKER_CACHE_TTL_MS")
+        .ok()
+        .map_or(60_000, |x| x.parse::<u64>().unwrap());
+
+    Duration::from_millis(millis * step_multiplier())
+}
+
+/// Whether bankers negotiate compressed responses on half of their
+/// connections, configured through `SIMULATOR_COMPRESSION=1`
+fn compression_enabled() -> bool {
+    std::env::var("SIMULATOR_COMPRESSION")
+        .as_deref()
+        .is_ok_and(|x| x == "1")
+}
 
+pub fn start(sim: &mut impl Sim) {
     let name = format!(
         "banker_{}",
         ID.with_borrow(|x| x.fetch_add(1, std::sync::atomic::Ordering::SeqCst))
     );
 
+    if !should_start(&name) {
+        return;
+    }
+
     log::debug!("Generating initial test plan");
 
-    let mut plan = BankerInteractionPlan::new().with_gen_interactions(1000);
+    let mut plan = BankerInteractionPlan::with_config(plan_config()).with_gen_interactions(1000);
+
+    progress::touch(&name);
+    disruption::register(&name);
+    stats::register_client(&name);
+
+    let mut cache = TransactionCache::new(CacheMode::from_env(), cache_ttl());
+
+    sim.client(name.clone(), async move {
+        // The pace is read once so every gap of this banker's schedule is
+        // scaled the same way
+        let mut schedule = Schedule::new(
+            switchy::time::now(),
+            Duration::from_secs(step_multiplier() * 60),
+        );
 
-    sim.client(name, async move {
         loop {
             while let Some(interaction) = plan.step().cloned() {
                 static TIMEOUT: u64 = 10;
 
-                #[allow(clippy::cast_possible_truncation)]
-                let interaction_timeout = TIMEOUT * 1000
-                    + if let Interaction::Sleep(duration) = &interaction {
-                        duration.as_millis() as u64
-                    } else {
-                        0
-                    } + step_multiplier() * 1000;
-
-                switchy::unsync::select! {
-                    resp = perform_interaction(&server_addr, &interaction, &plan).fuse() => {
-                        resp?;
-                        switchy::unsync::time::sleep(std::time::Duration::from_secs(step_multiplier() * 60)).await;
-                    }
-                    () = switchy::unsync::time::sleep(std::time::Duration::from_millis(interaction_timeout)) => {
-                        return Err(Box::new(std::io::Error::new(
-                            std::io::ErrorKind::TimedOut,
-                            format!(
-                                "\
-                                Failed to get interaction response within {interaction_timeout}ms:\n\
-                                {interaction:?}
-                                "
-                            )
-                        )) as Box<dyn std::error::Error + Send>);
-                    }
+                if let Interaction::Sleep(duration) = interaction {
+                    log::debug!("[{name}] delaying the next interaction by duration={duration:?}");
+                    schedule.advance(duration);
+                    progress::touch(&name);
+                    continue;
                 }
+
+                let behind = schedule.wait().await;
+                stats::record_behind_schedule(&name, behind, schedule.pace());
+                let intended_start = schedule.next();
+                schedule.advance(Duration::ZERO);
+
+                let base = Duration::from_secs(TIMEOUT + step_multiplier())
+                    + match &interaction {
+                        // Polls the replica for up to the whole staleness
+                        Interaction::GetReplicatedTransaction => replica_staleness(),
+                        _ => Duration::ZERO,
+                    };
+                let interaction_timeout =
+                    timeouts::timeout(base, interaction.expected_round_trips());
+
+                with_deadline(
+                    &name,
+                    interaction_timeout,
+                    format!("{interaction:?}"),
+                    perform_interaction(&name, &interaction, intended_start, &mut plan, &mut cache),
+                )
+                .await?;
+
+                progress::touch(&name);
+                stats::complete_interaction(&name);
             }
 
             plan.gen_interactions(1000);
@@ -84,7 +206,7 @@ pub fn start(sim: &mut impl Sim) {
 async fn send_action(
     server_addr: &str,
     addr: &str,
-    stream: &mut TcpStream,
+    stream: &mut CapturedStream,
     action: ServerAction,
 ) -> bool {
     log::debug!("[{addr}->{server_addr}] send_action: action={action}");
@@ -96,13 +218,17 @@ async fn send_action(
 async fn send_message(
     server_addr: &str,
     addr: &str,
-    stream: &mut TcpStream,
+    stream: &mut CapturedStream,
     message: impl Into<String>,
 ) -> bool {
     let message = message.into();
     log::debug!("[{addr}->{server_addr}] send_message: message={message}");
     let mut bytes = message.clone().into_bytes();
     bytes.push(0_u8);
+    if let Err(e) = disruption::take(addr) {
+        log::error!("[{addr}->{server_addr}] failed to make tcp_request: {e:?}");
+        return false;
+    }
     match stream.write_all(&bytes).await {
         Ok(resp) => resp,
         Err(e) => {
@@ -115,41 +241,257 @@ async fn send_message(
     true
 }
 
+/// Reads the next response from the server through the connection's router,
+/// reading frames until one that isn't unsolicited is queued. A protocol v2
+/// frame that isn't a valid response (e.g. the tail of a frame that was cut
+/// off mid-write) is discarded with a warning and the following frame is read
+/// instead, so the connection resynchronizes on the next terminator.
+///
+/// # Panics
+///
+/// * If a connection that negotiated compression receives a malformed frame,
+///   since the simulated network never corrupts bytes
+async fn read_message(
+    router: &mut ResponseRouter,
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+) -> Result<Option<String>, crate::Error> {
+    loop {
+        if let Some(message) = router.next_response() {
+            return Ok(Some(message));
+        }
+
+        disruption::take(addr)?;
+
+        let message = match router.read_frame(Box::pin(&mut *stream)).await {
+            Err(crate::Error::Framing(e)) => panic!(
+                "[{addr}->{server_addr}] received a malformed frame: {e}\n{}",
+                router.report()
+            ),
+            x => x?,
+        };
+        let Some(message) = message else {
+            return Ok(None);
+        };
+
+        assert!(
+            !message.starts_with("ERR rate_limited"),
+            "[{addr}->{server_addr}] well-behaved banker was rate limited:\n'{message}'\n{}",
+            router.report(),
+        );
+
+        if router.version() == ProtocolVersion::V2 && Response::from_str(&message).is_err() {
+            log::warn!("[{addr}->{server_addr}] discarding malformed frame:\n'{message}'");
+            continue;
+        }
+
+        router.push(message);
+    }
+}
+
+async fn negotiate(
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    let version = router.version();
+
+    if !send_message(server_addr, addr, stream, format!("PROTO {version}")).await {
+        log::debug!("[{addr}->{server_addr}] negotiate: failed to send");
+        return false;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] negotiate: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] negotiate: failed to get response");
+        return false;
+    };
+
+    assert!(
+        Response::from_str(&message).is_ok_and(|x| x == Response::Ok(version.to_string())),
+        "[{addr}->{server_addr}] expected protocol version {version} to be accepted, instead got:\n'{message}'"
+    );
+
+    true
+}
+
+/// Switches the connection's responses to compressed frames.
+async fn negotiate_compression(
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    let compression = Compression::Deflate;
+
+    if !send_message(server_addr, addr, stream, format!("COMPRESS {compression}")).await {
+        log::debug!("[{addr}->{server_addr}] negotiate_compression: failed to send");
+        return false;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] negotiate_compression: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] negotiate_compression: failed to get response");
+        return false;
+    };
+    let message = router.expect_ok(&message);
+
+    assert!(
+        message == compression.to_string(),
+        "[{addr}->{server_addr}] expected {compression} compression to be accepted, instead got:\n'{message}'\n{}",
+        router.report(),
+    );
+
+    router.set_compressed();
+
+    true
+}
+
+/// Whether the server rejected a write because it's not the primary, which
+/// happens while a failover is in progress.
+fn is_read_only(version: ProtocolVersion, message: &str) -> bool {
+    match version {
+        ProtocolVersion::V1 => message == READ_ONLY_MESSAGE,
+        ProtocolVersion::V2 => Response::from_str(message).is_ok_and(|x| {
+            matches!(
+                x,
+                Response::Err {
+                    code: ProtocolError::ReadOnly,
+                    ..
+                }
+            )
+        }),
+    }
+}
+
+/// Gives an in-progress failover a moment to complete before retrying a write.
+async fn wait_for_failover() {
+    switchy::unsync::time::sleep(Duration::from_millis(step_multiplier())).await;
+}
+
+/// Whether the server refused to build a response because it would exceed
+/// its response budget, which is lowered while it's under memory pressure.
+fn is_resource_exhausted(version: ProtocolVersion, message: &str) -> bool {
+    match version {
+        ProtocolVersion::V1 => message == RESOURCE_EXHAUSTED_MESSAGE,
+        ProtocolVersion::V2 => Response::from_str(message).is_ok_and(|x| {
+            matches!(
+                x,
+                Response::Err {
+                    code: ProtocolError::ResourceExhausted,
+                    ..
+                }
+            )
+        }),
+    }
+}
+
+/// Gives the server's memory pressure a while to lift before retrying a
+/// response it refused to build.
+async fn wait_for_memory() {
+    switchy::unsync::time::sleep(Duration::from_millis(1_000 * step_multiplier())).await;
+}
+
 #[allow(clippy::too_many_lines)]
 async fn perform_interaction(
-    server_addr: &str,
+    name: &str,
     interaction: &Interaction,
-    plan: &BankerInteractionPlan,
+    intended_start: SystemTime,
+    plan: &mut BankerInteractionPlan,
+    cache: &mut TransactionCache,
 ) -> Result<(), Box<dyn std::error::Error + Send>> {
     log::debug!("perform_interaction: interaction={interaction:?}");
 
-    if let Interaction::Sleep(duration) = interaction {
-        let duration = *duration;
-        log::debug!("perform_interaction: sleeping for duration={duration:?}");
-        switchy::unsync::time::sleep(duration).await;
+    // Timed from when the schedule wanted it to start, so time spent behind
+    // schedule isn't omitted from the latency
+    let timer = stats::start_at(InteractionType::from(interaction).into(), intended_start);
+
+    // The index of this interaction in the plan, which was already stepped
+    // past it
+    let step = plan.step - 1;
+
+    if let Interaction::GetTransaction { id } = interaction
+        && let Some(transaction) = cache.get(*id, step, timing::elapsed(), plan)
+    {
+        log::debug!("perform_interaction: served from cache transaction={transaction}");
+        timer.finish();
         return Ok(());
     }
 
+    let mut backoff = Backoff::connect();
     loop {
+        let version = if rng().gen_bool(0.5) {
+            ProtocolVersion::V2
+        } else {
+            ProtocolVersion::V1
+        };
+
+        // Resolved on every attempt so that the banker follows a failover,
+        // connecting to the listener for the protocol version it negotiates
+        let server_addr = &if matches!(interaction, Interaction::GetReplicatedTransaction) {
+            replication::replica_addr(version)
+        } else {
+            replication::primary_addr(version)
+        };
+
         log::trace!("Connecting to server...");
-        let mut stream = match TcpStream::connect(server_addr).await {
+        connections::attempt();
+        let mut stream = match CapturedStream::connect_with_identity(server_addr, name).await {
             Ok(stream) => stream,
             Err(e) => {
                 log::debug!("Failed to connect to server: {e:?}");
-                switchy::unsync::time::sleep(std::time::Duration::from_millis(step_multiplier()))
-                    .await;
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
                 continue;
             }
         };
+        backoff.reset();
+        let _connection = connections::track(name);
         let addr = &stream.local_addr().unwrap().to_string();
+        disruption::connected(name, addr);
         log::trace!("[{addr}->{server_addr}] Connected!");
 
+        // Every exchange on the connection goes through the same router, so
+        // the bytes it has read past the end of a response aren't lost
+        // between them
+        let mut router = ResponseRouter::new(version, format!("[{addr}->{server_addr}]"));
+
+        if version != ProtocolVersion::V1
+            && !negotiate(server_addr, addr, &mut router, &mut stream).await
+        {
+            log::debug!("[{addr}->{server_addr}] perform_interaction: negotiate failed");
+            continue;
+        }
+
+        if compression_enabled()
+            && rng().gen_bool(0.5)
+            && !negotiate_compression(server_addr, addr, &mut router, &mut stream).await
+        {
+            log::debug!(
+                "[{addr}->{server_addr}] perform_interaction: negotiate_compression failed"
+            );
+            continue;
+        }
+
         match interaction {
             Interaction::Sleep(..) => {
                 unreachable!();
             }
             Interaction::ListTransactions => {
-                if !list_transactions(server_addr, addr, plan, &mut stream).await {
+                if !list_transactions(name, server_addr, addr, &mut router, &mut stream).await {
                     log::debug!(
                         "[{addr}->{server_addr}] perform_interaction: list_transactions failed"
                     );
@@ -157,15 +499,35 @@ async fn perform_interaction(
                 }
             }
             Interaction::GetTransaction { id } => {
-                if !get_transaction(*id, server_addr, addr, &mut stream).await {
+                if !get_transaction(
+                    *id,
+                    plan,
+                    cache,
+                    server_addr,
+                    addr,
+                    &mut router,
+                    &mut stream,
+                )
+                .await
+                {
                     log::debug!(
                         "[{addr}->{server_addr}] perform_interaction: get_transaction failed"
                     );
                     continue;
                 }
             }
-            Interaction::CreateTransaction { amount } => {
-                if !create_transaction(*amount, server_addr, addr, &mut stream).await {
+            Interaction::CreateTransaction { input, amount } => {
+                if !create_transaction(
+                    name,
+                    input,
+                    *amount,
+                    server_addr,
+                    addr,
+                    &mut router,
+                    &mut stream,
+                )
+                .await
+                {
                     log::debug!(
                         "[{addr}->{server_addr}] perform_interaction: create_transaction failed"
                     );
@@ -173,41 +535,94 @@ async fn perform_interaction(
                 }
             }
             Interaction::VoidTransaction { id } => {
-                if !void_transaction(*id, server_addr, addr, &mut stream).await {
+                if !void_transaction(*id, plan, server_addr, addr, &mut router, &mut stream).await {
                     log::debug!(
                         "[{addr}->{server_addr}] perform_interaction: void_transaction failed"
                     );
                     continue;
                 }
+                cache.invalidate(*id);
+            }
+            Interaction::Batch { amounts } => {
+                if !batch(name, amounts, server_addr, addr, &mut router, &mut stream).await {
+                    log::debug!("[{addr}->{server_addr}] perform_interaction: batch failed");
+                    continue;
+                }
             }
             Interaction::GetBalance => {
-                if !get_balance(server_addr, addr, &mut stream).await {
+                if !get_balance(server_addr, addr, &mut router, &mut stream).await {
                     log::debug!("[{addr}->{server_addr}] perform_interaction: get_balance failed");
                     continue;
                 }
             }
+            Interaction::GetReplicatedTransaction => {
+                if !get_replicated_transaction(name, server_addr, addr, &mut router, &mut stream)
+                    .await
+                {
+                    log::debug!(
+                        "[{addr}->{server_addr}] perform_interaction: get_replicated_transaction failed"
+                    );
+                    continue;
+                }
+            }
+            Interaction::SearchTransactions { input, filter } => {
+                if !search_transactions(
+                    input,
+                    filter.as_ref(),
+                    server_addr,
+                    addr,
+                    plan,
+                    &mut router,
+                    &mut stream,
+                )
+                .await
+                {
+                    log::debug!(
+                        "[{addr}->{server_addr}] perform_interaction: search_transactions failed"
+                    );
+                    continue;
+                }
+            }
+            Interaction::GetStatement { start, end } => {
+                if !get_statement(*start, *end, server_addr, addr, &mut router, &mut stream).await {
+                    log::debug!(
+                        "[{addr}->{server_addr}] perform_interaction: get_statement failed"
+                    );
+                    continue;
+                }
+            }
         }
 
         break;
     }
 
+    timer.finish();
+
     log::debug!("perform_interaction: finished interaction={interaction:?}");
 
     Ok(())
 }
 
+/// Reads a transaction, asserting that the response is consistent with the
+/// voids of it acknowledged to the banker earlier in its plan.
 async fn get_transaction(
     id: TransactionId,
+    plan: &BankerInteractionPlan,
+    cache: &mut TransactionCache,
     server_addr: &str,
     addr: &str,
-    stream: &mut TcpStream,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
 ) -> bool {
+    // The index of this interaction in the plan, which was already stepped
+    // past it
+    let step = plan.step - 1;
     if !send_action(server_addr, addr, stream, ServerAction::GetTransaction).await {
         log::debug!("[{addr}->{server_addr}] get_transaction: failed to send");
         return false;
     }
 
-    let message = match read_message(&mut String::new(), Box::pin(&mut *stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] get_transaction: failed to read: {e:?}");
@@ -219,16 +634,13 @@ async fn get_transaction(
         return false;
     };
 
-    assert!(
-        message == "Enter the transaction ID:",
-        "[{addr}->{server_addr}] expected prompt for transaction ID, instead got:\n'{message}'"
-    );
+    router.expect_prompt(&message, "transaction_id", "Enter the transaction ID:");
     if !send_message(server_addr, addr, stream, id.to_string()).await {
         log::debug!("[{addr}->{server_addr}] get_transaction: id failed to send");
         return false;
     }
 
-    let message = match read_message(&mut String::new(), Box::pin(stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] get_transaction: failed to read: {e:?}");
@@ -240,75 +652,387 @@ async fn get_transaction(
         return false;
     };
 
-    assert!(
-        message == "Transaction not found"
-            || Transaction::from_str(&message).is_ok_and(|x| x.id == id),
-        "[{addr}->{server_addr}] expected transaction response, instead got:\n'{message}'"
+    let transaction = match router.parse_response(&message) {
+        Ok(payload) => {
+            let transaction = Transaction::from_str(&payload).ok().filter(|x| x.id == id);
+
+            assert!(
+                (router.version() == ProtocolVersion::V1 && payload == "Transaction not found")
+                    || transaction.is_some(),
+                "[{addr}->{server_addr}] expected transaction response, instead got:\n'{message}'"
+            );
+
+            transaction
+        }
+        Err(code) => {
+            assert!(
+                code == ProtocolError::NotFound,
+                "[{addr}->{server_addr}] expected not_found error, instead got:\n'{message}'"
+            );
+            None
+        }
+    };
+
+    // Reads of the primary are given the same window as the replica's to
+    // catch up with a void
+    let verdict = read_after_void::check_read(
+        plan.context.last_void_ack(id, step),
+        transaction.as_ref(),
+        timing::elapsed(),
+        replica_staleness(),
     );
+    match verdict {
+        Verdict::Accept => {}
+        Verdict::VerifyVoidingEntry(entry) => {
+            if !verify_voiding_entry(&entry, server_addr, addr, router, stream).await {
+                log::debug!("[{addr}->{server_addr}] get_transaction: failed to verify void");
+                return false;
+            }
+        }
+        Verdict::Reject(reason) => {
+            panic!("[{addr}->{server_addr}] inconsistent read of voided transaction {id}: {reason}")
+        }
+    }
+
+    if let Some(transaction) = transaction {
+        cache.insert(transaction, step, timing::elapsed());
+    }
+
+    true
+}
+
+/// Takes a statement of only the transaction an acknowledged void committed,
+/// asserting that it's there with the reversed amount.
+async fn verify_voiding_entry(
+    entry: &Transaction,
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    if !send_action(server_addr, addr, stream, ServerAction::GetStatement).await {
+        log::debug!("[{addr}->{server_addr}] verify_voiding_entry: failed to send");
+        return false;
+    }
+
+    for (field, prompt) in [
+        ("start_transaction_id", "Enter the start transaction ID:"),
+        ("end_transaction_id", "Enter the end transaction ID:"),
+    ] {
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] verify_voiding_entry: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!(
+                "[{addr}->{server_addr}] verify_voiding_entry: failed to get prompt response"
+            );
+            return false;
+        };
+
+        router.expect_prompt(&message, field, prompt);
+        if !send_message(server_addr, addr, stream, entry.id.to_string()).await {
+            log::debug!("[{addr}->{server_addr}] verify_voiding_entry: id failed to send");
+            return false;
+        }
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] verify_voiding_entry: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!(
+            "[{addr}->{server_addr}] verify_voiding_entry: failed to get statement response"
+        );
+        return false;
+    };
+    if is_resource_exhausted(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] verify_voiding_entry: resource exhausted");
+        wait_for_memory().await;
+        return false;
+    }
+    let message = router.expect_ok(&message);
+
+    let lines = if message.is_empty() {
+        vec![]
+    } else {
+        message
+            .split('\n')
+            .map(StatementLine::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| {
+                panic!("[{addr}->{server_addr}] Invalid formatted statement ({e:?}):\n{message}")
+            })
+    };
+
+    if let Err(reason) = read_after_void::check_voiding_entry(entry, &lines) {
+        panic!("[{addr}->{server_addr}] {reason}\nActual statement:\n{message}");
+    }
 
     true
 }
+
+/// Lists every transaction, asserting that each transaction acknowledged to
+/// the banker is listed with its acknowledged amount. A listing that's missing
+/// one is re-issued up to `LIST_RETRIES` times before the transaction is
+/// declared lost.
+#[allow(clippy::too_many_lines)]
 async fn list_transactions(
+    name: &str,
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    let acknowledged = ACKNOWLEDGED.with_borrow(|x| x.get(name).cloned().unwrap_or_default());
+    let mut retries = 0;
+
+    // Re-issued on the same connection, so a retry still reads from the
+    // primary the transactions were acknowledged by
+    let (message, transactions) = loop {
+        if !send_action(server_addr, addr, stream, ServerAction::ListTransactions).await {
+            log::debug!("[{addr}->{server_addr}] list_transactions: failed to send");
+            return false;
+        }
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] list_transactions: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!("[{addr}->{server_addr}] list_transactions: failed to get response");
+            return false;
+        };
+        if is_resource_exhausted(router.version(), &message) {
+            log::debug!("[{addr}->{server_addr}] list_transactions: resource exhausted");
+            wait_for_memory().await;
+            return false;
+        }
+        let message = router.expect_ok(&message);
+
+        let transactions = if message.is_empty() {
+            log::debug!(
+                "[{addr}->{server_addr}] list_transactions: got 'not transactions' response"
+            );
+            vec![]
+        } else {
+            message
+                .split('\n')
+                .map(Transaction::from_str)
+                .collect::<Result<Vec<Transaction>, _>>()
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "[{addr}->{server_addr}] Invalid formatted transactions ({e:?}):\n{message}"
+                    )
+                })
+        };
+
+        let missing = acknowledged
+            .keys()
+            .filter(|id| !transactions.iter().any(|x| x.id == **id))
+            .collect::<Vec<_>>();
+
+        log::debug!(
+            "[{addr}->{server_addr}] acknowledged.len={} transactions.len={} missing={missing:?} retries={retries}",
+            acknowledged.len(),
+            transactions.len(),
+        );
+
+        if missing.is_empty() {
+            break (message, transactions);
+        }
+
+        assert!(
+            retries < LIST_RETRIES,
+            "\
+            [{addr}->{server_addr}] missing acknowledged transactions with ids={missing:?} after {retries} retries\n\
+            Actual transactions:\n\
+            {message}\
+            ",
+        );
+
+        retries += 1;
+        switchy::unsync::time::sleep(list_retry_delay()).await;
+    };
+
+    stats::record_list_retries(retries);
+
+    for transaction in &transactions {
+        if let Some(amount) = acknowledged.get(&transaction.id) {
+            assert!(
+                transaction.amount == *amount,
+                "\
+                [{addr}->{server_addr}] transaction id={} was acknowledged with amount={amount}, but was listed with amount={}\n\
+                Actual transactions:\n\
+                {message}\
+                ",
+                transaction.id,
+                transaction.amount,
+            );
+        }
+    }
+
+    // The list is a single snapshot of the ledger, so a batch that committed
+    // must be either entirely in it or entirely missing from it
+    let listed = transactions
+        .iter()
+        .map(|x| (x.id, x.amount))
+        .collect::<BTreeMap<_, _>>();
+
+    COMMITTED_BATCHES.with_borrow(|batches| {
+        for batch in batches {
+            let visible = batch
+                .iter()
+                .filter(|x| listed.get(&x.id) == Some(&x.amount))
+                .count();
+
+            assert!(
+                visible == 0 || visible == batch.len(),
+                "\
+                [{addr}->{server_addr}] only {visible} of the {} transactions of batch id={}..={} were listed\n\
+                Actual transactions:\n\
+                {message}\
+                ",
+                batch.len(),
+                batch[0].id,
+                batch[batch.len() - 1].id,
+            );
+        }
+    });
+
+    true
+}
+
+#[allow(clippy::too_many_lines)]
+async fn search_transactions(
+    input: &str,
+    filter: Option<&TransactionFilter>,
     server_addr: &str,
     addr: &str,
     plan: &BankerInteractionPlan,
-    stream: &mut TcpStream,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
 ) -> bool {
-    if !send_action(server_addr, addr, stream, ServerAction::ListTransactions).await {
-        log::debug!("[{addr}->{server_addr}] list_transactions: failed to send");
+    if !send_action(server_addr, addr, stream, ServerAction::SearchTransactions).await {
+        log::debug!("[{addr}->{server_addr}] search_transactions: failed to send");
+        return false;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] search_transactions: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] search_transactions: failed to get prompt response");
+        return false;
+    };
+
+    router.expect_prompt(&message, "filter", "Enter the search filter:");
+    if !send_message(server_addr, addr, stream, input).await {
+        log::debug!("[{addr}->{server_addr}] search_transactions: filter failed to send");
         return false;
     }
-    let message = match read_message(&mut String::new(), Box::pin(stream)).await {
+
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
-            log::debug!("[{addr}->{server_addr}] list_transactions: failed to read: {e:?}");
+            log::debug!("[{addr}->{server_addr}] search_transactions: failed to read: {e:?}");
             return false;
         }
     };
     let Some(message) = message else {
-        log::debug!("[{addr}->{server_addr}] list_transactions: failed to get response");
+        log::debug!("[{addr}->{server_addr}] search_transactions: failed to get response");
         return false;
     };
 
-    if message.is_empty() {
-        log::debug!("[{addr}->{server_addr}] list_transactions: got 'not transactions' response");
+    let Some(filter) = filter else {
+        match router.version() {
+            ProtocolVersion::V1 => assert!(
+                message.starts_with("Invalid"),
+                "[{addr}->{server_addr}] expected filter '{input}' to be rejected, instead got:\n'{message}'"
+            ),
+            ProtocolVersion::V2 => assert!(
+                router.expect_error(&message) == ProtocolError::InvalidInput,
+                "[{addr}->{server_addr}] expected filter '{input}' to be rejected, instead got:\n'{message}'"
+            ),
+        }
         return true;
+    };
+
+    let message = router.expect_ok(&message);
+
+    let transactions = if message.is_empty() {
+        vec![]
+    } else {
+        message
+            .split('\n')
+            .map(Transaction::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| {
+                panic!("[{addr}->{server_addr}] Invalid formatted transactions ({e:?}):\n{message}")
+            })
+    };
+
+    assert!(
+        transactions.len() <= filter.limit,
+        "[{addr}->{server_addr}] expected at most {} transactions for filter '{input}', instead got {}",
+        filter.limit,
+        transactions.len(),
+    );
+
+    for transaction in &transactions {
+        assert!(
+            filter.matches(transaction),
+            "[{addr}->{server_addr}] transaction {transaction} doesn't match filter '{input}'\n\
+            Actual transactions:\n\
+            {message}",
+        );
     }
 
-    let transactions = message.split('\n');
-    let transactions = transactions
-        .map(Transaction::from_str)
-        .collect::<Result<Vec<Transaction>, _>>()
-        .unwrap_or_else(|e| {
-            panic!("[{addr}->{server_addr}] Invalid formatted transactions ({e:?}):\n{message}")
-        });
+    for window in transactions.windows(2) {
+        assert!(
+            window[1].id > window[0].id,
+            "[{addr}->{server_addr}] search results out of order: {} then {}\n\
+            Actual transactions:\n\
+            {message}",
+            window[0].id,
+            window[1].id,
+        );
+    }
+
+    // When the limit cut the results short, the transactions this banker
+    // created may have been left out
+    if transactions.len() == filter.limit {
+        return true;
+    }
 
     let amounts = plan
         .plan
         .iter()
         .take(usize::try_from(plan.step).unwrap())
         .filter_map(|x| match x {
-            Interaction::CreateTransaction { amount } => Some(amount),
+            Interaction::CreateTransaction {
+                amount: Some(amount),
+                ..
+            } => Some(*amount),
             _ => None,
         })
-        .collect::<Vec<_>>();
-
-    log::debug!(
-        "[{addr}->{server_addr}] amounts.len={} transactions.len={}",
-        amounts.len(),
-        transactions.len(),
-    );
-
-    assert!(
-        transactions.len() >= amounts.len(),
-        "\
-        [{addr}->{server_addr}] expected at least {} transactions, but only saw {}\n\
-        Actual transactions:\n\
-        {message}\
-        ",
-        amounts.len(),
-        transactions.len(),
-    );
+        .filter(|x| {
+            filter.min_amount.is_none_or(|min| *x >= min)
+                && filter.max_amount.is_none_or(|max| *x <= max)
+        });
 
     for amount in amounts {
         assert!(
@@ -316,7 +1040,7 @@ async fn list_transactions(
                 .iter()
                 .any(|x| format!("{:.2}", x.amount) == format!("{amount:.2}")),
             "\
-            [{addr}->{server_addr}] missing transaction with amount={amount}\n\
+            [{addr}->{server_addr}] missing transaction with amount={amount} for filter '{input}'\n\
             Actual transactions:\n\
             {message}\
             "
@@ -327,21 +1051,27 @@ async fn list_transactions(
 }
 
 async fn create_transaction(
-    amount: Decimal,
+    name: &str,
+    input: &str,
+    amount: Option<Decimal>,
     server_addr: &str,
     addr: &str,
-    stream: &mut TcpStream,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
 ) -> bool {
     if !send_action(server_addr, addr, stream, ServerAction::CreateTransaction).await {
         log::debug!("[{addr}->{server_addr}] create_transaction: failed to send");
         return false;
     }
-    if !send_message(server_addr, addr, stream, amount.to_string()).await {
+    if let Some(amount) = amount {
+        expected_ledger::sent_create(amount);
+    }
+    if !send_message(server_addr, addr, stream, input).await {
         log::debug!("[{addr}->{server_addr}] create_transaction: amount failed to send");
         return false;
     }
 
-    let message = match read_message(&mut String::new(), Box::pin(&mut *stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] create_transaction: failed to read: {e:?}");
@@ -352,13 +1082,18 @@ async fn create_transaction(
         log::debug!("[{addr}->{server_addr}] create_transaction: failed to get prompt response");
         return false;
     };
+    if is_read_only(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] create_transaction: primary is read-only");
+        if let Some(amount) = amount {
+            expected_ledger::rejected_create(amount);
+        }
+        wait_for_failover().await;
+        return false;
+    }
 
-    assert!(
-        message == "Enter the transaction amount:",
-        "[{addr}->{server_addr}] expected prompt for transaction amount, instead got:\n'{message}'"
-    );
+    router.expect_prompt(&message, "amount", "Enter the transaction amount:");
 
-    let message = match read_message(&mut String::new(), Box::pin(stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] create_transaction: failed to read: {e:?}");
@@ -371,31 +1106,235 @@ async fn create_transaction(
         );
         return false;
     };
+    // The primary can be demoted between accepting the action and creating
+    // the transaction
+    if is_read_only(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] create_transaction: primary is read-only");
+        if let Some(amount) = amount {
+            expected_ledger::rejected_create(amount);
+        }
+        wait_for_failover().await;
+        return false;
+    }
+
+    let Some(amount) = amount else {
+        match router.version() {
+            ProtocolVersion::V1 => assert!(
+                message.starts_with("Invalid amount"),
+                "[{addr}->{server_addr}] expected amount '{input}' to be rejected, instead got:\n'{message}'"
+            ),
+            ProtocolVersion::V2 => assert!(
+                router.expect_error(&message) == ProtocolError::InvalidInput,
+                "[{addr}->{server_addr}] expected amount '{input}' to be rejected, instead got:\n'{message}'"
+            ),
+        }
+        return true;
+    };
+
+    let transaction = router.expect_transaction(&message);
+    expected_ledger::acknowledged_create(name, &transaction);
+    acknowledge(name, &transaction);
 
     assert!(
-        Transaction::from_str(&message).is_ok(),
-        "[{addr}->{server_addr}] expected to be able to parse create_transaction response as a transaction:\n'{message}'",
+        transaction.amount == amount,
+        "[{addr}->{server_addr}] expected amount '{input}' to be stored as {amount}, instead got {}",
+        transaction.amount,
     );
 
+    // each banker creates its transactions one after the other, so the server
+    // must never hand it an older seq than one it already saw. created_at
+    // isn't checked since the server's clock may be skewed backwards
+    observability::monotonic_check(&format!("{name}.seq"), transaction.seq);
+
+    LAST_CREATED.with_borrow_mut(|x| {
+        x.insert(
+            name.to_string(),
+            Created {
+                id: transaction.id,
+                at: timing::elapsed(),
+            },
+        );
+    });
+
     true
 }
 
+/// Queues the amounts in a batch and commits it, asserting that the created
+/// transactions have contiguous ids and the batch's amounts in order.
+#[allow(clippy::too_many_lines)]
+async fn batch(
+    name: &str,
+    amounts: &[Decimal],
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    if !send_action(server_addr, addr, stream, ServerAction::BeginBatch).await {
+        log::debug!("[{addr}->{server_addr}] batch: failed to send");
+        return false;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] batch: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] batch: failed to get begin response");
+        return false;
+    };
+    let message = router.expect_ok(&message);
+
+    assert!(
+        message == "batch=open",
+        "[{addr}->{server_addr}] expected the batch to be opened, instead got:\n'{message}'"
+    );
+
+    for (index, amount) in amounts.iter().enumerate() {
+        if !send_action(server_addr, addr, stream, ServerAction::CreateTransaction).await {
+            log::debug!("[{addr}->{server_addr}] batch: failed to send create");
+            return false;
+        }
+        if !send_message(server_addr, addr, stream, amount.to_string()).await {
+            log::debug!("[{addr}->{server_addr}] batch: amount failed to send");
+            return false;
+        }
+
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] batch: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!("[{addr}->{server_addr}] batch: failed to get prompt response");
+            return false;
+        };
+        if is_read_only(router.version(), &message) {
+            log::debug!("[{addr}->{server_addr}] batch: primary is read-only");
+            wait_for_failover().await;
+            return false;
+        }
+
+        router.expect_prompt(&message, "amount", "Enter the transaction amount:");
+
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] batch: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!("[{addr}->{server_addr}] batch: failed to get batched response");
+            return false;
+        };
+        let message = router.expect_ok(&message);
+
+        assert!(
+            message == format!("batched={}", index + 1),
+            "[{addr}->{server_addr}] expected amount={amount} to be batched as entry {}, instead got:\n'{message}'",
+            index + 1,
+        );
+    }
+
+    for amount in amounts {
+        expected_ledger::sent_create(*amount);
+    }
+    if !send_action(server_addr, addr, stream, ServerAction::CommitBatch).await {
+        log::debug!("[{addr}->{server_addr}] batch: failed to send commit");
+        return false;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] batch: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] batch: failed to get commit response");
+        return false;
+    };
+    // The primary can be demoted while the batch is open
+    if is_read_only(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] batch: primary is read-only");
+        for amount in amounts {
+            expected_ledger::rejected_create(*amount);
+        }
+        wait_for_failover().await;
+        return false;
+    }
+    let message = router.expect_ok(&message);
+
+    let transactions = message
+        .split('\n')
+        .map(Transaction::from_str)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| {
+            panic!("[{addr}->{server_addr}] Invalid formatted batch ({e:?}):\n{message}")
+        });
+
+    for transaction in &transactions {
+        expected_ledger::acknowledged_create(name, transaction);
+        acknowledge(name, transaction);
+    }
+
+    assert!(
+        transactions.len() == amounts.len(),
+        "[{addr}->{server_addr}] expected the batch to create {} transactions, instead got:\n{message}",
+        amounts.len(),
+    );
+
+    for (transaction, amount) in transactions.iter().zip(amounts) {
+        assert!(
+            transaction.amount == *amount,
+            "[{addr}->{server_addr}] expected batched amount={amount} to be stored in order, instead got:\n{message}"
+        );
+    }
+
+    for window in transactions.windows(2) {
+        assert!(
+            window[1].id == window[0].id + 1,
+            "[{addr}->{server_addr}] batch ids aren't contiguous: {} then {}\n\
+            Actual transactions:\n\
+            {message}",
+            window[0].id,
+            window[1].id,
+        );
+    }
+
+    COMMITTED_BATCHES.with_borrow_mut(|x| x.push(transactions));
+
+    true
+}
+
+/// Voids a transaction, recording the void in the plan's context if the
+/// server acknowledges it.
 async fn void_transaction(
     id: TransactionId,
+    plan: &mut BankerInteractionPlan,
     server_addr: &str,
     addr: &str,
-    stream: &mut TcpStream,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
 ) -> bool {
     if !send_action(server_addr, addr, stream, ServerAction::VoidTransaction).await {
         log::debug!("[{addr}->{server_addr}] void_transaction: failed to send");
         return false;
     }
+    expected_ledger::sent_void(id);
     if !send_message(server_addr, addr, stream, id.to_string()).await {
         log::debug!("[{addr}->{server_addr}] void_transaction: id failed to send");
         return false;
     }
 
-    let message = match read_message(&mut String::new(), Box::pin(stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] void_transaction: failed to read: {e:?}");
@@ -407,21 +1346,191 @@ async fn void_transaction(
         return false;
     };
 
-    assert!(
-        message == "Enter the transaction ID:",
-        "[{addr}->{server_addr}] expected prompt for transaction ID, instead got:\n'{message}'"
-    );
+    if is_read_only(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] void_transaction: primary is read-only");
+        wait_for_failover().await;
+        return false;
+    }
+
+    router.expect_prompt(&message, "transaction_id", "Enter the transaction ID:");
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] void_transaction: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] void_transaction: failed to get void response");
+        return false;
+    };
+    // The primary can be demoted between accepting the action and voiding
+    // the transaction
+    if is_read_only(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] void_transaction: primary is read-only");
+        wait_for_failover().await;
+        return false;
+    }
+
+    match router.parse_response(&message) {
+        Ok(payload)
+            if router.version() == ProtocolVersion::V1 && payload == "Transaction not found" => {}
+        Ok(..) => {
+            let entry = router.expect_transaction(&message);
+            // Unlike a create, a void is never dated before the transaction
+            // it voids, even if the server's clock is skewed backwards
+            if let Some(created_at) = expected_ledger::acknowledged_created_at(id) {
+                assert!(
+                    entry.created_at >= created_at,
+                    "[{addr}->{server_addr}] void of id={id} was dated at created_at={} before the transaction it voids at created_at={created_at}",
+                    entry.created_at,
+                );
+            }
+            plan.context.acknowledge_void(
+                id,
+                VoidAck {
+                    step: plan.step - 1,
+                    at: timing::elapsed(),
+                    entry,
+                },
+            );
+        }
+        Err(code) => assert!(
+            code == ProtocolError::NotFound,
+            "[{addr}->{server_addr}] expected not_found error, instead got:\n'{message}'"
+        ),
+    }
 
     true
 }
 
-async fn get_balance(server_addr: &str, addr: &str, stream: &mut TcpStream) -> bool {
+/// When the replica's window to show a transaction acknowledged at
+/// `acknowledged_at` started. A bounce of either host or a failover restarts
+/// the window, since replication has to reconnect and catch up.
+fn replica_window_start(acknowledged_at: Duration) -> Duration {
+    [timing::last_bounce(), replication::last_failover()]
+        .into_iter()
+        .flatten()
+        .fold(acknowledged_at, Duration::max)
+}
+
+/// Reads the last transaction the banker created back from the replica,
+/// polling until it becomes visible within the bounded staleness.
+async fn get_replicated_transaction(
+    name: &str,
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    let Some(created) = LAST_CREATED.with_borrow(|x| x.get(name).copied()) else {
+        log::debug!("[{addr}->{server_addr}] get_replicated_transaction: nothing created yet");
+        return true;
+    };
+
+    let window_start = replica_window_start(created.at);
+    let staleness = replica_staleness();
+    let mut disconnected = false;
+
+    // Re-read on the same connection, so every poll asks the same replica
+    assert_eventually!(
+        async {
+            if replica_window_start(created.at) != window_start {
+                log::debug!(
+                    "[{addr}->{server_addr}] get_replicated_transaction: the visibility window restarted"
+                );
+                return Ok(());
+            }
+            read_replicated(created.id, router, server_addr, addr, stream)
+                .await
+                .unwrap_or_else(|| {
+                    disconnected = true;
+                    Ok(())
+                })
+        },
+        within = (window_start + staleness).saturating_sub(timing::elapsed()),
+        poll_every = Duration::from_millis(1_000 * step_multiplier()),
+        context = "[{addr}->{server_addr}] transaction {} acknowledged by the primary at {:?} was still not visible on the replica \
+            (bounded_staleness={staleness:?} window_start={window_start:?})",
+        created.id,
+        created.at,
+    );
+
+    !disconnected
+}
+
+/// Reads the transaction from the replica, returning why it isn't visible
+/// if it's not, or `None` if the connection failed.
+async fn read_replicated(
+    id: TransactionId,
+    router: &mut ResponseRouter,
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+) -> Option<Result<(), String>> {
+    if !send_action(server_addr, addr, stream, ServerAction::GetTransaction).await {
+        log::debug!("[{addr}->{server_addr}] get_replicated_transaction: failed to send");
+        return None;
+    }
+    if !send_message(server_addr, addr, stream, id.to_string()).await {
+        log::debug!("[{addr}->{server_addr}] get_replicated_transaction: id failed to send");
+        return None;
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!(
+                "[{addr}->{server_addr}] get_replicated_transaction: failed to read: {e:?}"
+            );
+            return None;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] get_replicated_transaction: failed to get response");
+        return None;
+    };
+
+    router.expect_prompt(&message, "transaction_id", "Enter the transaction ID:");
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!(
+                "[{addr}->{server_addr}] get_replicated_transaction: failed to read: {e:?}"
+            );
+            return None;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] get_replicated_transaction: failed to get response");
+        return None;
+    };
+
+    let visible = router
+        .parse_response(&message)
+        .is_ok_and(|payload| Transaction::from_str(&payload).is_ok_and(|x| x.id == id));
+
+    Some(if visible {
+        Ok(())
+    } else {
+        Err(format!("got '{message}'"))
+    })
+}
+
+async fn get_balance(
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
     if !send_action(server_addr, addr, stream, ServerAction::GetBalance).await {
         log::debug!("[{addr}->{server_addr}] get_balance: failed to send");
         return false;
     }
 
-    let message = match read_message(&mut String::new(), Box::pin(stream)).await {
+    let message = match read_message(router, server_addr, addr, stream).await {
         Ok(x) => x,
         Err(e) => {
             log::debug!("[{addr}->{server_addr}] get_balance: failed to read: {e:?}");
@@ -432,6 +1541,7 @@ async fn get_balance(server_addr: &str, addr: &str, stream: &mut TcpStream) -> b
         log::debug!("[{addr}->{server_addr}] get_balance: failed to get response");
         return false;
     };
+    let message = router.expect_ok(&message);
 
     assert!(
         message.starts_with('$'),
@@ -447,3 +1557,151 @@ async fn get_balance(server_addr: &str, addr: &str, stream: &mut TcpStream) -> b
 
     true
 }
+
+#[allow(clippy::too_many_lines)]
+async fn get_statement(
+    start: TransactionId,
+    end: TransactionId,
+    server_addr: &str,
+    addr: &str,
+    router: &mut ResponseRouter,
+    stream: &mut CapturedStream,
+) -> bool {
+    let covers_everything = start <= 1 && end == TransactionId::MAX;
+
+    // Other bankers may create transactions between the GET_BALANCE and the
+    // GET_STATEMENT, so the balance can't be expected to equal the final line.
+    // Since the statement is taken after the balance was observed, the balance
+    // must be equal to the running balance after some line in the statement.
+    let balance = if covers_everything {
+        if !send_action(server_addr, addr, stream, ServerAction::GetBalance).await {
+            log::debug!("[{addr}->{server_addr}] get_statement: failed to send balance");
+            return false;
+        }
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] get_statement: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!("[{addr}->{server_addr}] get_statement: failed to get balance response");
+            return false;
+        };
+        let message = router.expect_ok(&message);
+        let balance = message
+            .strip_prefix('$')
+            .and_then(|x| Decimal::from_str(x).ok())
+            .unwrap_or_else(|| {
+                panic!(
+                    "[{addr}->{server_addr}] expected a decimal balance, instead got:\n'{message}'"
+                )
+            });
+
+        Some(balance)
+    } else {
+        None
+    };
+
+    if !send_action(server_addr, addr, stream, ServerAction::GetStatement).await {
+        log::debug!("[{addr}->{server_addr}] get_statement: failed to send");
+        return false;
+    }
+
+    for (field, prompt, value) in [
+        (
+            "start_transaction_id",
+            "Enter the start transaction ID:",
+            start,
+        ),
+        ("end_transaction_id", "Enter the end transaction ID:", end),
+    ] {
+        let message = match read_message(router, server_addr, addr, stream).await {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("[{addr}->{server_addr}] get_statement: failed to read: {e:?}");
+                return false;
+            }
+        };
+        let Some(message) = message else {
+            log::debug!("[{addr}->{server_addr}] get_statement: failed to get prompt response");
+            return false;
+        };
+
+        router.expect_prompt(&message, field, prompt);
+        if !send_message(server_addr, addr, stream, value.to_string()).await {
+            log::debug!("[{addr}->{server_addr}] get_statement: value failed to send");
+            return false;
+        }
+    }
+
+    let message = match read_message(router, server_addr, addr, stream).await {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("[{addr}->{server_addr}] get_statement: failed to read: {e:?}");
+            return false;
+        }
+    };
+    let Some(message) = message else {
+        log::debug!("[{addr}->{server_addr}] get_statement: failed to get statement response");
+        return false;
+    };
+    if is_resource_exhausted(router.version(), &message) {
+        log::debug!("[{addr}->{server_addr}] get_statement: resource exhausted");
+        wait_for_memory().await;
+        return false;
+    }
+    let message = router.expect_ok(&message);
+
+    let lines = if message.is_empty() {
+        vec![]
+    } else {
+        message
+            .split('\n')
+            .map(StatementLine::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| {
+                panic!("[{addr}->{server_addr}] Invalid formatted statement ({e:?}):\n{message}")
+            })
+    };
+
+    for line in &lines {
+        assert!(
+            (start..=end).contains(&line.id),
+            "[{addr}->{server_addr}] statement line id={} outside of range {start}..={end}\n\
+            Actual statement:\n\
+            {message}",
+            line.id,
+        );
+    }
+
+    for window in lines.windows(2) {
+        let (prev, next) = (&window[0], &window[1]);
+        assert!(
+            next.id > prev.id,
+            "[{addr}->{server_addr}] statement ids out of order: {} then {}\n\
+            Actual statement:\n\
+            {message}",
+            prev.id,
+            next.id,
+        );
+        assert!(
+            next.balance_after - prev.balance_after == next.amount,
+            "[{addr}->{server_addr}] statement balance delta mismatch: {prev} then {next}\n\
+            Actual statement:\n\
+            {message}",
+        );
+    }
+
+    if let Some(balance) = balance {
+        assert!(
+            balance == Decimal::ZERO || lines.iter().any(|x| x.balance_after == balance),
+            "[{addr}->{server_addr}] balance=${balance} does not match the running balance of any statement line\n\
+            Actual statement:\n\
+            {message}",
+        );
+    }
+
+    true
+}
@@ -1,19 +1,44 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use dst_demo_server::bank::{Transaction, TransactionId};
+use dst_demo_server::{
+    bank::{Transaction, TransactionId},
+    search::{DEFAULT_LIMIT, TransactionFilter},
+};
 use rust_decimal::Decimal;
 use simvar::{
     plan::InteractionPlan,
     switchy::random::{
-        rand::rand::{Rng, seq::IteratorRandom as _},
+        rand::rand::{
+            Rng,
+            seq::{IteratorRandom as _, SliceRandom as _},
+        },
         rng,
     },
 };
-use strum::{EnumDiscriminants, EnumIter, IntoEnumIterator as _};
+use strum::{EnumDiscriminants, EnumIter, IntoEnumIterator as _, IntoStaticStr};
+
+use crate::{rng_trace::rng_labeled, shuffle::shuffle};
+
+/// A void the server acknowledged, with the transaction it committed to
+/// reverse the voided one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoidAck {
+    /// The index of the plan interaction that voided the transaction
+    pub step: u64,
+    /// When the void was acknowledged, in simulated time since the run started
+    pub at: Duration,
+    /// The transaction that reverses the voided one
+    pub entry: Transaction,
+}
 
 pub struct InteractionPlanContext {
     curr_id: TransactionId,
     transactions: Vec<Transaction>,
+    /// The plan indices of the interactions that void each transaction
+    voided: BTreeMap<TransactionId, Vec<u64>>,
+    /// The voids of each transaction the server acknowledged, in the order
+    /// they were performed
+    void_acks: BTreeMap<TransactionId, Vec<VoidAck>>,
 }
 
 impl Default for InteractionPlanContext {
@@ -28,6 +53,8 @@ impl InteractionPlanContext {
         Self {
             curr_id: 1,
             transactions: vec![],
+            voided: BTreeMap::new(),
+            void_acks: BTreeMap::new(),
         }
     }
 
@@ -45,30 +72,139 @@ impl InteractionPlanContext {
         self.get_random_existing_transaction(rng).map(|x| x.id)
     }
 
+    /// Whether the plan voids `id` in an interaction strictly between the
+    /// plan indices `after` and `before`.
+    #[must_use]
+    pub fn voided_between(&self, id: TransactionId, after: u64, before: u64) -> bool {
+        self.voided
+            .get(&id)
+            .is_some_and(|x| x.iter().any(|step| *step > after && *step < before))
+    }
+
+    /// Records that the server acknowledged voiding `id`.
+    pub fn acknowledge_void(&mut self, id: TransactionId, ack: VoidAck) {
+        self.void_acks.entry(id).or_default().push(ack);
+    }
+
+    /// The last acknowledged void of `id` performed by an interaction before
+    /// the plan index `before`.
+    #[must_use]
+    pub fn last_void_ack(&self, id: TransactionId, before: u64) -> Option<&VoidAck> {
+        self.void_acks
+            .get(&id)
+            .and_then(|x| x.iter().rev().find(|ack| ack.step < before))
+    }
+
     #[allow(unused)]
     fn clear(&mut self) {
         self.transactions.clear();
+        self.voided.clear();
+        self.void_acks.clear();
         self.curr_id = 1;
     }
 }
 
+/// Formats the amount the way a user might type it, e.g. `-$1,234.56`
+fn format_currency(amount: Decimal) -> String {
+    let formatted = format!("{:.2}", amount.abs());
+    let (whole, cents) = formatted.split_once('.').unwrap();
+    let whole = whole
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|x| std::str::from_utf8(x).unwrap())
+        .collect::<Vec<_>>()
+        .join(",");
+    let sign = if amount.is_sign_negative() { "-" } else { "" };
+
+    format!("{sign}${whole}.{cents}")
+}
+
+/// The chance that a generated void is immediately followed by a read of the
+/// transaction it voids
+const VOID_THEN_GET_PROBABILITY: f64 = 0.25;
+
+/// The most entries a generated batch commits at once
+pub const MAX_BATCH_ENTRIES: u32 = 5;
+
+/// The amounts a run's created transactions can range up to
+const MAX_AMOUNTS: [f64; 3] = [100.0, 1_000_000.0, 100_000_000_000.0];
+
+/// The shape of the bankers' plans. A config is generated for each run so
+/// that runs exercise different mixes of interactions, and it's recorded in
+/// the run's props so a failing shape can be reproduced.
+#[derive(Debug, Clone)]
+pub struct PlanConfig {
+    /// Created transaction amounts are drawn from `-max_amount..max_amount`
+    pub max_amount: f64,
+    /// Sleeps are drawn from `0..max_sleep_millis`
+    pub max_sleep_millis: u64,
+    /// The relative weight of each interaction type
+    pub weights: Vec<(InteractionType, u32)>,
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        Self {
+            max_amount: 100_000_000_000.0,
+            max_sleep_millis: 100_000,
+            weights: InteractionType::iter().map(|x| (x, 1)).collect(),
+        }
+    }
+}
+
+impl PlanConfig {
+    #[must_use]
+    pub fn generate(rng: &mut impl Rng) -> Self {
+        Self {
+            max_amount: MAX_AMOUNTS[rng.gen_range(0..MAX_AMOUNTS.len())],
+            max_sleep_millis: rng.gen_range(10_000..=100_000),
+            weights: InteractionType::iter()
+                .map(|x| (x, rng.gen_range(1..=10)))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn props(&self) -> Vec<(String, String)> {
+        let mut props = vec![
+            ("banker.max_amount".to_string(), self.max_amount.to_string()),
+            (
+                "banker.max_sleep_ms".to_string(),
+                self.max_sleep_millis.to_string(),
+            ),
+        ];
+
+        props.extend(self.weights.iter().map(|(interaction_type, weight)| {
+            (
+                format!("banker.weight.{}", <&'static str>::from(interaction_type)),
+                weight.to_string(),
+            )
+        }));
+
+        props
+    }
+}
+
 pub struct BankerInteractionPlan {
     pub context: InteractionPlanContext,
+    pub config: PlanConfig,
     pub step: u64,
     pub plan: Vec<Interaction>,
 }
 
 impl Default for BankerInteractionPlan {
     fn default() -> Self {
-        Self::new()
+        Self::with_config(PlanConfig::default())
     }
 }
 
 impl BankerInteractionPlan {
     #[must_use]
-    pub const fn new() -> Self {
+    pub const fn with_config(config: PlanConfig) -> Self {
         Self {
             context: InteractionPlanContext::new(),
+            config,
             step: 0,
             plan: vec![],
         }
@@ -76,15 +212,122 @@ impl BankerInteractionPlan {
 }
 
 #[derive(Clone, Debug, EnumDiscriminants)]
-#[strum_discriminants(derive(EnumIter))]
+#[strum_discriminants(derive(EnumIter, IntoStaticStr))]
 #[strum_discriminants(name(InteractionType))]
 pub enum Interaction {
     Sleep(Duration),
     ListTransactions,
-    GetTransaction { id: TransactionId },
-    CreateTransaction { amount: Decimal },
-    VoidTransaction { id: TransactionId },
+    GetTransaction {
+        id: TransactionId,
+    },
+    CreateTransaction {
+        /// The text sent to the server for the amount
+        input: String,
+        /// The normalized amount the server is expected to store, or `None`
+        /// if the input is expected to be rejected
+        amount: Option<Decimal>,
+    },
+    VoidTransaction {
+        id: TransactionId,
+    },
+    /// Commits the amounts atomically in a single batch
+    Batch {
+        amounts: Vec<Decimal>,
+    },
     GetBalance,
+    GetStatement {
+        start: TransactionId,
+        end: TransactionId,
+    },
+    SearchTransactions {
+        /// The filter expression sent to the server
+        input: String,
+        /// The filter the server is expected to apply, or `None` if the input
+        /// is expected to be rejected
+        filter: Option<TransactionFilter>,
+    },
+    /// Reads the banker's last created transaction back from the replica
+    GetReplicatedTransaction,
+}
+
+impl BankerInteractionPlan {
+    /// Generates a search for the amounts between two of the context's
+    /// transactions, or occasionally a malformed filter.
+    fn gen_search(&self, rng: &mut impl Rng) -> Interaction {
+        if rng.gen_range(0..10) == 0 {
+            let input = ["amount>5", "limit=abc", "amount>=$abc", "created_after=-1"]
+                .choose(&mut *rng)
+                .unwrap();
+
+            return Interaction::SearchTransactions {
+                input: (*input).to_string(),
+                filter: None,
+            };
+        }
+
+        let mut bound = || {
+            self.context
+                .get_random_existing_transaction(&mut *rng)
+                .map(|x| x.amount)
+        };
+        let (a, b) = (bound(), bound());
+        let (min_amount, max_amount) = match (a, b) {
+            (Some(a), Some(b)) => (Some(a.min(b)), Some(a.max(b))),
+            _ => (a, None),
+        };
+        let limit = if rng.gen_bool(0.5) {
+            rng.gen_range(1..=1000)
+        } else {
+            DEFAULT_LIMIT
+        };
+
+        let filter = TransactionFilter {
+            min_amount,
+            max_amount,
+            limit,
+            ..TransactionFilter::default()
+        };
+
+        let mut terms = vec![];
+        if let Some(amount) = min_amount {
+            terms.push(format!("amount>={}", format_currency(amount)));
+        }
+        if let Some(amount) = max_amount {
+            terms.push(format!("amount<={amount}"));
+        }
+        if limit != DEFAULT_LIMIT || rng.gen_bool(0.5) {
+            terms.push(format!("limit={limit}"));
+        }
+        shuffle(rng, &mut terms);
+
+        Interaction::SearchTransactions {
+            input: terms.join(" "),
+            filter: Some(filter),
+        }
+    }
+}
+
+impl Interaction {
+    /// How many round trips to the server a single attempt at the interaction
+    /// makes, counting connecting and negotiating the protocol
+    #[must_use]
+    pub const fn expected_round_trips(&self) -> u32 {
+        let exchanges = match self {
+            Self::Sleep(..) => return 0,
+            Self::ListTransactions | Self::GetBalance => 1,
+            Self::GetTransaction { .. }
+            | Self::CreateTransaction { .. }
+            | Self::VoidTransaction { .. }
+            | Self::SearchTransactions { .. }
+            | Self::GetReplicatedTransaction => 2,
+            // The balance is read first when the statement covers everything
+            Self::GetStatement { .. } => 4,
+            // Opening and committing the batch, plus two for each entry
+            Self::Batch { .. } => 2 + 2 * MAX_BATCH_ENTRIES,
+        };
+
+        2 + exchanges
+    }
 }
 
 impl InteractionPlan<Interaction> for BankerInteractionPlan {
@@ -99,13 +342,21 @@ impl InteractionPlan<Interaction> for BankerInteractionPlan {
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     fn gen_interactions(&mut self, count: u64) {
         let len = self.plan.len() as u64;
 
         let mut rng = rng();
 
         for i in 1..=count {
-            let interaction_type = InteractionType::iter().choose(&mut rng).unwrap();
+            let (interaction_type, _) = *self
+                .config
+                .weights
+                .choose_weighted(
+                    &mut rng_labeled("banker::interaction_type"),
+                    |(_, weight)| *weight,
+                )
+                .unwrap();
             log::trace!(
                 "gen_interactions: generating interaction {i}/{count} ({}) interaction_type={interaction_type:?}",
                 i + len
@@ -113,7 +364,7 @@ impl InteractionPlan<Interaction> for BankerInteractionPlan {
             match interaction_type {
                 InteractionType::Sleep => {
                     self.add_interaction(Interaction::Sleep(Duration::from_millis(
-                        rng.gen_range(0..100_000),
+                        rng_labeled("banker::sleep").gen_range(0..self.config.max_sleep_millis),
                     )));
                 }
                 InteractionType::ListTransactions => {
@@ -127,12 +378,45 @@ impl InteractionPlan<Interaction> for BankerInteractionPlan {
 
                     self.add_interaction(Interaction::GetTransaction { id });
                 }
+                InteractionType::Batch => {
+                    let range = self.config.max_amount;
+                    let count = rng.gen_range(2..=MAX_BATCH_ENTRIES);
+                    let amounts = (0..count)
+                        .map(|_| {
+                            let amount: Decimal = rng_labeled("banker::amount")
+                                .gen_range(-range..range)
+                                .try_into()
+                                .unwrap();
+                            amount.round_dp(2)
+                        })
+                        .collect();
+
+                    self.add_interaction(Interaction::Batch { amounts });
+                }
                 InteractionType::CreateTransaction => {
-                    const RANGE: f64 = 100_000_000_000.0;
-                    let amount = rng.gen_range(-RANGE..RANGE);
-                    let amount = amount.try_into().unwrap();
+                    let range = self.config.max_amount;
+                    let amount: Decimal = rng_labeled("banker::amount")
+                        .gen_range(-range..range)
+                        .try_into()
+                        .unwrap();
+                    let amount = amount.round_dp(2);
+
+                    let interaction = match rng.gen_range(0..10) {
+                        0 => Interaction::CreateTransaction {
+                            input: (amount + Decimal::new(rng.gen_range(1..10), 3)).to_string(),
+                            amount: None,
+                        },
+                        1..=2 => Interaction::CreateTransaction {
+                            input: format_currency(amount),
+                            amount: Some(amount),
+                        },
+                        _ => Interaction::CreateTransaction {
+                            input: amount.to_string(),
+                            amount: Some(amount),
+                        },
+                    };
 
-                    self.add_interaction(Interaction::CreateTransaction { amount });
+                    self.add_interaction(interaction);
                 }
                 InteractionType::VoidTransaction => {
                     let id = self
@@ -141,10 +425,40 @@ impl InteractionPlan<Interaction> for BankerInteractionPlan {
                         .unwrap_or_else(|| rng.r#gen());
 
                     self.add_interaction(Interaction::VoidTransaction { id });
+
+                    // Probes the read-after-void consistency of the id while
+                    // the void is as fresh as a banker can observe it
+                    if rng_labeled("banker::void_then_get").gen_bool(VOID_THEN_GET_PROBABILITY) {
+                        self.add_interaction(Interaction::GetTransaction { id });
+                    }
                 }
                 InteractionType::GetBalance => {
                     self.add_interaction(Interaction::GetBalance);
                 }
+                InteractionType::GetReplicatedTransaction => {
+                    self.add_interaction(Interaction::GetReplicatedTransaction);
+                }
+                InteractionType::SearchTransactions => {
+                    let interaction = self.gen_search(&mut rng);
+                    self.add_interaction(interaction);
+                }
+                InteractionType::GetStatement => {
+                    let (start, end) = if rng.gen_bool(0.5) {
+                        (1, TransactionId::MAX)
+                    } else {
+                        let a = self
+                            .context
+                            .get_random_existing_transaction_id(&mut rng)
+                            .unwrap_or(1);
+                        let b = self
+                            .context
+                            .get_random_existing_transaction_id(&mut rng)
+                            .unwrap_or(1);
+                        (a.min(b), a.max(b))
+                    };
+
+                    self.add_interaction(Interaction::GetStatement { start, end });
+                }
             }
         }
         drop(rng);
@@ -156,19 +470,45 @@ impl InteractionPlan<Interaction> for BankerInteractionPlan {
             Interaction::Sleep(..)
             | Interaction::ListTransactions
             | Interaction::GetBalance
-            | Interaction::GetTransaction { .. } => {}
-            Interaction::CreateTransaction { amount } => {
+            | Interaction::GetReplicatedTransaction
+            | Interaction::GetStatement { .. }
+            | Interaction::SearchTransactions { .. }
+            | Interaction::GetTransaction { .. }
+            | Interaction::CreateTransaction { amount: None, .. } => {}
+            Interaction::CreateTransaction {
+                amount: Some(amount),
+                ..
+            } => {
                 self.context.transactions.push(Transaction {
                     id: self.context.curr_id,
+                    seq: 0,
                     amount: *amount,
                     created_at: 0,
                 });
                 self.context.curr_id += 1;
             }
+            Interaction::Batch { amounts } => {
+                for amount in amounts {
+                    self.context.transactions.push(Transaction {
+                        id: self.context.curr_id,
+                        seq: 0,
+                        amount: *amount,
+                        created_at: 0,
+                    });
+                    self.context.curr_id += 1;
+                }
+            }
             Interaction::VoidTransaction { id } => {
+                self.context
+                    .voided
+                    .entry(*id)
+                    .or_default()
+                    .push(self.plan.len() as u64);
+
                 if let Some(existing) = self.context.transactions.iter().find(|x| x.id == *id) {
                     self.context.transactions.push(Transaction {
                         id: self.context.curr_id,
+                        seq: 0,
                         amount: existing.amount,
                         created_at: 0,
                     });
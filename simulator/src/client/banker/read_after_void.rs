@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use dst_demo_server::bank::{StatementLine, Transaction};
+
+use super::plan::VoidAck;
+
+/// What a banker has to do with a `GET_TRANSACTION` response for a
+/// transaction it may have voided
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The response is consistent with every void acknowledged before it
+    Accept,
+    /// The response is the original transaction, so the statement must also
+    /// show the transaction that reversed it
+    VerifyVoidingEntry(Transaction),
+    /// The response contradicts an acknowledged void
+    Reject(String),
+}
+
+/// Decides whether `read`, the transaction a banker read at `now`, or `None`
+/// if it wasn't found, is consistent with `ack`, the last void of it the
+/// server acknowledged to the banker before the read.
+///
+/// Voiding doesn't change the voided transaction, it commits a transaction
+/// reversing its amount, so a read always returns the original. Within
+/// `staleness` of the void's acknowledgment the read may have been served
+/// from before the void committed and is accepted as is. After that, the
+/// reversing transaction must be visible too.
+#[must_use]
+pub fn check_read(
+    ack: Option<&VoidAck>,
+    read: Option<&Transaction>,
+    now: Duration,
+    staleness: Duration,
+) -> Verdict {
+    let Some(ack) = ack else {
+        return Verdict::Accept;
+    };
+
+    let Some(read) = read else {
+        return Verdict::Reject(format!(
+            "the transaction was not found even though interaction {} was acknowledged voiding it at {:?} with {}",
+            ack.step, ack.at, ack.entry,
+        ));
+    };
+
+    if read.amount != -ack.entry.amount {
+        return Verdict::Reject(format!(
+            "{read} doesn't match the amount reversed by {}, which interaction {} was acknowledged voiding it with at {:?}",
+            ack.entry, ack.step, ack.at,
+        ));
+    }
+
+    if now <= ack.at + staleness {
+        Verdict::Accept
+    } else {
+        Verdict::VerifyVoidingEntry(ack.entry.clone())
+    }
+}
+
+/// Checks that a statement covering only the id of `entry`, the transaction
+/// an acknowledged void committed, shows it with its amount.
+///
+/// # Errors
+///
+/// * If the statement doesn't show `entry` with its amount
+pub fn check_voiding_entry(entry: &Transaction, lines: &[StatementLine]) -> Result<(), String> {
+    match lines {
+        [line] if line.id == entry.id && line.amount == entry.amount => Ok(()),
+        [] => Err(format!("the voiding transaction {entry} is missing")),
+        lines => Err(format!(
+            "expected only the voiding transaction {entry}, instead got {}",
+            lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )),
+    }
+}
@@ -0,0 +1,233 @@
+use std::{collections::VecDeque, fmt::Write as _, pin::Pin, str::FromStr as _};
+
+use dst_demo_server::{
+    bank::Transaction,
+    message::MessageBuffer,
+    protocol::{ProtocolError, ProtocolVersion, Response},
+};
+use simvar::switchy::unsync::io::AsyncReadExt;
+
+/// How many unsolicited frames may be skipped while waiting for a single
+/// response before the router gives up on the connection
+pub const MAX_SKIPPED_FRAMES: usize = 8;
+
+/// Matches the frames read from a connection against the responses a banker
+/// expects.
+///
+/// Protocol v2 frames tagged as unsolicited (`PUSH`) are logged and skipped
+/// instead of being mistaken for the response to the banker's request, and
+/// every frame seen is kept so that a mismatch is reported along with the
+/// whole exchange rather than just the last frame.
+///
+/// A router lives as long as its connection, so it also owns the bytes read
+/// past the end of the last frame, which belong to the next one.
+#[derive(Debug)]
+pub struct ResponseRouter {
+    version: ProtocolVersion,
+    context: String,
+    queue: VecDeque<String>,
+    seen: Vec<String>,
+    skipped: usize,
+    buffer: MessageBuffer,
+    frames: Vec<u8>,
+    compressed: bool,
+}
+
+impl ResponseRouter {
+    /// `context` prefixes every log and failure message, e.g.
+    /// `[client_addr->server_addr]`.
+    #[must_use]
+    pub fn new(version: ProtocolVersion, context: impl Into<String>) -> Self {
+        Self {
+            version,
+            context: context.into(),
+            queue: VecDeque::new(),
+            seen: vec![],
+            skipped: 0,
+            buffer: MessageBuffer::new(),
+            frames: vec![],
+            compressed: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Reads the following frames as compressed, length prefixed frames.
+    pub const fn set_compressed(&mut self) {
+        self.compressed = true;
+    }
+
+    /// Reads the next frame from the connection's `stream`, starting with the
+    /// bytes left over from the previous read. Returns `None` if the
+    /// connection was closed.
+    ///
+    /// # Errors
+    ///
+    /// * If the frame is malformed
+    pub async fn read_frame(
+        &mut self,
+        stream: Pin<Box<impl AsyncReadExt>>,
+    ) -> Result<Option<String>, crate::Error> {
+        if self.compressed {
+            crate::read_frame(&mut self.frames, stream).await
+        } else {
+            crate::read_message(&mut self.buffer, stream).await
+        }
+    }
+
+    /// Queues a frame read from the connection.
+    pub fn push(&mut self, frame: String) {
+        self.seen.push(frame.clone());
+        self.queue.push_back(frame);
+    }
+
+    /// Takes the next queued frame that isn't unsolicited, or `None` if
+    /// another frame has to be read from the connection first.
+    ///
+    /// # Panics
+    ///
+    /// * If more than [`MAX_SKIPPED_FRAMES`] unsolicited frames were skipped
+    ///   since the last response
+    pub fn next_response(&mut self) -> Option<String> {
+        while let Some(frame) = self.queue.pop_front() {
+            if !self.is_unsolicited(&frame) {
+                self.skipped = 0;
+                return Some(frame);
+            }
+
+            self.skipped += 1;
+            log::debug!("{} skipping unsolicited frame:\n'{frame}'", self.context);
+
+            assert!(
+                self.skipped <= MAX_SKIPPED_FRAMES,
+                "{} skipped {} unsolicited frames without getting a response\n{}",
+                self.context,
+                self.skipped,
+                self.report(),
+            );
+        }
+
+        None
+    }
+
+    fn is_unsolicited(&self, frame: &str) -> bool {
+        self.version == ProtocolVersion::V2
+            && matches!(Response::from_str(frame), Ok(Response::Push(..)))
+    }
+
+    /// Every frame read from the connection so far, in order, including the
+    /// skipped ones.
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut report = format!("Frames seen ({}):", self.seen.len());
+
+        for (index, frame) in self.seen.iter().enumerate() {
+            write!(report, "\n  {index}: '{frame}'").unwrap();
+        }
+
+        report
+    }
+
+    /// # Panics
+    ///
+    /// * If the frame isn't the prompt for `field`, which is shown to v1
+    ///   clients as `text`
+    pub fn expect_prompt(&self, frame: &str, field: &str, text: &str) {
+        let context = &self.context;
+
+        match self.version {
+            ProtocolVersion::V1 => assert!(
+                frame == text,
+                "{context} expected prompt '{text}', instead got:\n'{frame}'\n{}",
+                self.report(),
+            ),
+            ProtocolVersion::V2 => assert!(
+                Response::from_str(frame).is_ok_and(|x| x == Response::Prompt(field.to_string())),
+                "{context} expected prompt for {field}, instead got:\n'{frame}'\n{}",
+                self.report(),
+            ),
+        }
+    }
+
+    /// Returns the payload of the response, or the error code if the server
+    /// responded with a protocol v2 error.
+    ///
+    /// # Errors
+    ///
+    /// * If the server responded with a protocol v2 error
+    ///
+    /// # Panics
+    ///
+    /// * If the frame is a protocol v2 frame that isn't a response
+    pub fn parse_response(&self, frame: &str) -> Result<String, ProtocolError> {
+        match self.version {
+            ProtocolVersion::V1 => Ok(frame.to_string()),
+            ProtocolVersion::V2 => match Response::from_str(frame) {
+                Ok(Response::Ok(payload)) => Ok(payload),
+                Ok(Response::Err { code, .. }) => Err(code),
+                Ok(Response::Prompt(..) | Response::Push(..)) | Err(..) => panic!(
+                    "{} expected a response, instead got:\n'{frame}'\n{}",
+                    self.context,
+                    self.report(),
+                ),
+            },
+        }
+    }
+
+    /// # Panics
+    ///
+    /// * If the frame isn't a successful response
+    #[must_use]
+    pub fn expect_ok(&self, frame: &str) -> String {
+        self.parse_response(frame).unwrap_or_else(|code| {
+            panic!(
+                "{} expected a successful response, instead got {code} error:\n'{frame}'\n{}",
+                self.context,
+                self.report(),
+            )
+        })
+    }
+
+    /// # Panics
+    ///
+    /// * If the frame isn't a successful response with a single transaction
+    #[must_use]
+    pub fn expect_transaction(&self, frame: &str) -> Transaction {
+        let payload = self.expect_ok(frame);
+
+        Transaction::from_str(&payload).unwrap_or_else(|e| {
+            panic!(
+                "{} expected a transaction, instead got ({e:?}):\n'{frame}'\n{}",
+                self.context,
+                self.report(),
+            )
+        })
+    }
+
+    /// Returns the code of a protocol v2 error response.
+    ///
+    /// # Panics
+    ///
+    /// * If the connection is using protocol v1, whose errors are plain text
+    /// * If the frame isn't an error response
+    #[must_use]
+    pub fn expect_error(&self, frame: &str) -> ProtocolError {
+        assert!(
+            self.version == ProtocolVersion::V2,
+            "{} protocol v1 errors can't be told apart from responses",
+            self.context,
+        );
+
+        match self.parse_response(frame) {
+            Ok(..) => panic!(
+                "{} expected an error, instead got:\n'{frame}'\n{}",
+                self.context,
+                self.report(),
+            ),
+            Err(code) => code,
+        }
+    }
+}
@@ -1,19 +1,52 @@
+use std::{str::FromStr as _, time::Duration};
+
+use dst_demo_server::{
+    ServerAction,
+    config::{ReloadableConfig, ServerConfig},
+    message::MessageBuffer,
+    protocol::{ProtocolError, ProtocolVersion, Response},
+};
 use plan::{FaultInjectionInteractionPlan, Interaction};
-use simvar::{Sim, plan::InteractionPlan as _, switchy};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{self, random::rng, unsync::io::AsyncWriteExt as _},
+};
 
 pub mod plan;
 
-use crate::queue_bounce;
+use crate::{
+    backoff::Backoff,
+    capture::CapturedStream,
+    connections, faults, progress, queue_bounce, queue_crash, queue_disruption, replication,
+    server_config, should_start,
+    timing::{self, Phase},
+};
+
+const NAME: &str = "fault_injector";
+
+/// Identifies the fault injector's connections as an admin, since it's the
+/// client that promotes, demotes, and reloads the hosts
+const IDENTITY: &str = "admin_fault_injector";
 
 pub fn start(sim: &mut impl Sim) {
+    if !should_start(NAME) {
+        return;
+    }
+
     log::debug!("Generating initial test plan");
 
     let mut plan = FaultInjectionInteractionPlan::new().with_gen_interactions(1000);
 
-    sim.client("fault_injector", async move {
+    progress::touch(NAME);
+
+    sim.client(NAME, async move {
         loop {
-            while let Some(interaction) = plan.step() {
-                perform_interaction(interaction).await?;
+            while let Some(interaction) = plan.step().cloned() {
+                let phase = timing::phase();
+                plan.enter_phase(phase);
+                perform_interaction(&interaction, phase).await?;
+                progress::touch(NAME);
             }
 
             plan.gen_interactions(1000);
@@ -23,19 +56,303 @@ pub fn start(sim: &mut impl Sim) {
 
 async fn perform_interaction(
     interaction: &Interaction,
+    phase: Phase,
 ) -> Result<(), Box<dyn std::error::Error + Send>> {
-    log::debug!("perform_interaction: interaction={interaction:?}");
+    log::debug!("perform_interaction: interaction={interaction:?} phase={phase}");
+
+    if faults::is_paused() && !matches!(interaction, Interaction::Sleep(..)) {
+        log::debug!("perform_interaction: fault injection is paused, skipping {interaction:?}");
+        faults::skip();
+        return Ok(());
+    }
 
     match interaction {
         Interaction::Sleep(duration) => {
+            let duration = duration.mul_f64(phase.config().sleep_multiplier);
             log::debug!("perform_interaction: sleeping for duration={duration:?}");
-            switchy::unsync::time::sleep(*duration).await;
+            switchy::unsync::time::sleep(duration).await;
         }
         Interaction::Bounce(host) => {
             log::debug!("perform_interaction: queueing bouncing '{host}'");
-            queue_bounce(host);
+            if !queue_bounce(NAME, host) {
+                log::debug!("perform_interaction: action queue is full, dropped bouncing '{host}'");
+            }
+        }
+        Interaction::Crash(host) => {
+            log::debug!("perform_interaction: queueing crashing '{host}'");
+            if !queue_crash(NAME, host) {
+                log::debug!("perform_interaction: action queue is full, dropped crashing '{host}'");
+            }
+        }
+        Interaction::Failover => {
+            if phase.config().faults {
+                failover().await;
+            } else {
+                log::debug!("perform_interaction: skipping failover during the {phase} phase");
+            }
+        }
+        Interaction::ReloadConfig { host, overrides } => {
+            reload_config(host, overrides).await;
+        }
+        Interaction::DisruptClient {
+            client_name,
+            failures,
+        } => {
+            log::debug!("perform_interaction: queueing disrupting '{client_name}'");
+            if !queue_disruption(NAME, client_name, *failures) {
+                log::debug!(
+                    "perform_interaction: action queue is full, dropped disrupting '{client_name}'"
+                );
+            }
+        }
+        Interaction::MemoryPressure {
+            host,
+            budget_bytes,
+            duration,
+        } => {
+            if phase.config().faults {
+                memory_pressure(host, *budget_bytes, *duration).await;
+            } else {
+                log::debug!(
+                    "perform_interaction: skipping memory pressure during the {phase} phase"
+                );
+            }
         }
     }
 
     Ok(())
 }
+
+/// Fails over from the primary to the replica. The primary is demoted first
+/// so it stops accepting writes, and the replica is only promoted once it has
+/// every transaction the primary committed, so no transaction id is ever
+/// handed out twice. If the replica can't be promoted, the old primary is
+/// promoted again instead.
+async fn failover() {
+    let from = replication::primary();
+    let to = replication::peer(from);
+
+    log::info!("failover: failing over from '{from}' to '{to}'");
+    replication::begin_failover();
+
+    let last = match request(from, ServerAction::Demote, None).await {
+        Some(Response::Ok(last)) => last,
+        response => {
+            // The demotion may or may not have been applied, but promoting
+            // the old primary again is safe either way
+            log::info!("failover: failed to demote '{from}': {response:?}");
+            promote(from).await;
+            replication::complete_failover(from);
+            return;
+        }
+    };
+
+    match request(to, ServerAction::Promote, Some(&last)).await {
+        Some(Response::Ok(..)) => {
+            log::info!("failover: promoted '{to}' at id={last}");
+            replication::complete_failover(to);
+        }
+        response => {
+            log::info!("failover: failed to promote '{to}': {response:?}");
+            // Without a response it's unknown whether the replica was
+            // promoted, so make sure it isn't before restoring the old
+            // primary
+            if response.is_none() {
+                demote(to).await;
+            }
+            promote(from).await;
+            replication::complete_failover(from);
+        }
+    }
+}
+
+/// Reloads the config of `host`, asserting that valid overrides are applied
+/// on top of the host's current config and that invalid ones are rejected.
+async fn reload_config(host: &str, overrides: &ReloadableConfig) {
+    let payload = serde_json::to_string(overrides).unwrap();
+    let valid = ServerConfig::from_env().reload(overrides).is_ok();
+    let expected = server_config::effective(host).and_then(|x| x.reload(overrides).ok());
+
+    log::info!("reload_config: reloading '{host}' with {payload} (valid={valid})");
+
+    match request(host, ServerAction::ReloadConfig, Some(&payload)).await {
+        Some(Response::Ok(effective)) => {
+            assert!(valid, "'{host}' accepted an invalid config:\n{payload}");
+            let effective =
+                serde_json::from_str::<ReloadableConfig>(&effective).unwrap_or_else(|e| {
+                    panic!("'{host}' responded with an invalid config ({e}):\n{effective}")
+                });
+            if let Some(expected) = expected {
+                assert_eq!(
+                    effective,
+                    expected.reloadable(),
+                    "'{host}' reloaded {payload} into an unexpected config"
+                );
+            }
+            server_config::reloaded(host, &effective);
+        }
+        Some(Response::Err {
+            code: ProtocolError::InvalidInput,
+            message,
+        }) => {
+            assert!(
+                !valid,
+                "'{host}' rejected a valid config:\n{payload}\n{message}"
+            );
+        }
+        Some(response) => {
+            panic!("'{host}' responded to a config reload with:\n{response:?}");
+        }
+        None => {
+            log::debug!("reload_config: no response from '{host}'");
+            if valid {
+                server_config::uncertain(host);
+            }
+        }
+    }
+}
+
+/// Lowers the response budget of `host` for `duration`, during which it must
+/// refuse large responses with `resource_exhausted` rather than crash, then
+/// restores the budget and asserts that `host` serves a full listing again.
+async fn memory_pressure(host: &str, budget_bytes: u64, duration: Duration) {
+    log::info!(
+        "memory_pressure: lowering the response budget of '{host}' to {budget_bytes} bytes for {duration:?}"
+    );
+
+    reload_config(
+        host,
+        &ReloadableConfig {
+            response_budget_bytes: Some(budget_bytes),
+            ..ReloadableConfig::default()
+        },
+    )
+    .await;
+
+    switchy::unsync::time::sleep(duration).await;
+
+    let restored = ServerConfig::from_env().response_budget_bytes;
+
+    log::info!("memory_pressure: restoring the response budget of '{host}' to {restored} bytes");
+
+    reload_config(
+        host,
+        &ReloadableConfig {
+            response_budget_bytes: Some(u64::try_from(restored).unwrap_or(u64::MAX)),
+            ..ReloadableConfig::default()
+        },
+    )
+    .await;
+
+    // The restore may not have been applied if its response was lost
+    if server_config::effective(host).is_none_or(|x| x.response_budget_bytes != restored) {
+        return;
+    }
+
+    match request(host, ServerAction::ListTransactions, None).await {
+        Some(Response::Err {
+            code: ProtocolError::ResourceExhausted,
+            message,
+        }) => {
+            panic!(
+                "'{host}' still refused a listing after its response budget was restored:\n{message}"
+            );
+        }
+        response => {
+            log::debug!(
+                "memory_pressure: '{host}' listed after the budget was restored: {}",
+                matches!(response, Some(Response::Ok(..))),
+            );
+        }
+    }
+}
+
+async fn promote(host: &str) {
+    while !matches!(
+        request(host, ServerAction::Promote, Some("0")).await,
+        Some(Response::Ok(..))
+    ) {
+        log::debug!("promote: retrying promoting '{host}'");
+    }
+}
+
+async fn demote(host: &str) {
+    while !matches!(
+        request(host, ServerAction::Demote, None).await,
+        Some(Response::Ok(..))
+    ) {
+        log::debug!("demote: retrying demoting '{host}'");
+    }
+}
+
+/// Sends an action over protocol v2, answering its prompt with `input` if
+/// given. Returns `None` if the connection failed along the way.
+async fn request(host: &str, action: ServerAction, input: Option<&str>) -> Option<Response> {
+    let server_addr = replication::addr(host, ProtocolVersion::V2);
+
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Fault Injector] Connecting to '{host}'...");
+        connections::attempt();
+        match CapturedStream::connect_with_identity(&server_addr, IDENTITY).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Fault Injector] Failed to connect to '{host}': {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track(NAME);
+    let addr = stream.local_addr().ok()?.to_string();
+    let mut buffer = MessageBuffer::new();
+
+    send_message(&server_addr, &addr, &mut stream, "PROTO 2").await?;
+    read_response(&server_addr, &addr, &mut stream, &mut buffer).await?;
+
+    send_message(&server_addr, &addr, &mut stream, action.as_ref()).await?;
+
+    if let Some(input) = input {
+        let prompt = read_response(&server_addr, &addr, &mut stream, &mut buffer).await?;
+        if !matches!(prompt, Response::Prompt(..)) {
+            return Some(prompt);
+        }
+        send_message(&server_addr, &addr, &mut stream, input).await?;
+    }
+
+    read_response(&server_addr, &addr, &mut stream, &mut buffer).await
+}
+
+async fn send_message(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    message: &str,
+) -> Option<()> {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.push(0_u8);
+
+    if let Err(e) = stream.write_all(&bytes).await {
+        log::debug!("[{addr}->{server_addr}] failed to send message: {e:?}");
+        return None;
+    }
+
+    Some(())
+}
+
+async fn read_response(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    buffer: &mut MessageBuffer,
+) -> Option<Response> {
+    let message = crate::read_message(buffer, Box::pin(stream))
+        .await
+        .inspect_err(|e| log::debug!("[{addr}->{server_addr}] failed to read message: {e:?}"))
+        .ok()??;
+
+    Response::from_str(&message)
+        .inspect_err(|e| {
+            log::debug!("[{addr}->{server_addr}] unexpected response ({e:?}):\n'{message}'");
+        })
+        .ok()
+}
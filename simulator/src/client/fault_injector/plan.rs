@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use dst_demo_server::config::ReloadableConfig;
 use simvar::{
     plan::InteractionPlan,
     switchy::{
@@ -9,9 +10,11 @@ use simvar::{
 };
 use strum::{EnumDiscriminants, EnumIter, IntoEnumIterator as _};
 
-use crate::host::server::HOST;
+use crate::{banker_count, replication::HOSTS, timeouts, timing::Phase};
 
-pub struct InteractionPlanContext {}
+pub struct InteractionPlanContext {
+    phase: Option<Phase>,
+}
 
 impl Default for InteractionPlanContext {
     fn default() -> Self {
@@ -22,12 +25,11 @@ impl Default for InteractionPlanContext {
 impl InteractionPlanContext {
     #[must_use]
     pub const fn new() -> Self {
-        Self {}
+        Self { phase: None }
     }
 }
 
 pub struct FaultInjectionInteractionPlan {
-    #[allow(unused)]
     context: InteractionPlanContext,
     step: u64,
     pub plan: Vec<Interaction>,
@@ -48,6 +50,24 @@ impl FaultInjectionInteractionPlan {
             plan: vec![],
         }
     }
+
+    /// Records the phase the run is in, logging the step at which the phase
+    /// changed so failures can be correlated with it.
+    pub fn enter_phase(&mut self, phase: Phase) {
+        if self.context.phase == Some(phase) {
+            return;
+        }
+
+        log::info!(
+            "fault_injector: entering phase {phase} at step {} (previous phase: {})",
+            self.step,
+            self.context
+                .phase
+                .map_or_else(|| "none".to_string(), |x| x.to_string()),
+        );
+
+        self.context.phase = Some(phase);
+    }
 }
 
 #[derive(Clone, Debug, EnumDiscriminants)]
@@ -56,6 +76,27 @@ impl FaultInjectionInteractionPlan {
 pub enum Interaction {
     Sleep(Duration),
     Bounce(String),
+    Crash(String),
+    /// Demotes the primary and promotes the replica in its place
+    Failover,
+    /// Reloads the host's config with the overrides, which may be invalid
+    ReloadConfig {
+        host: String,
+        overrides: ReloadableConfig,
+    },
+    /// Fails the client's next reads/writes with a connection reset, while
+    /// every other client proceeds undisturbed
+    DisruptClient {
+        client_name: String,
+        failures: u64,
+    },
+    /// Lowers the host's response budget for a while, as if it were running
+    /// low on memory, then restores it
+    MemoryPressure {
+        host: String,
+        budget_bytes: u64,
+        duration: Duration,
+    },
 }
 
 impl InteractionPlan<Interaction> for FaultInjectionInteractionPlan {
@@ -74,6 +115,10 @@ impl InteractionPlan<Interaction> for FaultInjectionInteractionPlan {
         let len = self.plan.len() as u64;
 
         let mut rng = rng();
+        // reloaded write timeouts get the same allowance for the network's
+        // latency as the one the hosts start with
+        let write_latency_ms =
+            u64::try_from(timeouts::timeout(Duration::ZERO, 1).as_millis()).unwrap_or(u64::MAX);
 
         for i in 1..=count {
             loop {
@@ -93,7 +138,82 @@ impl InteractionPlan<Interaction> for FaultInjectionInteractionPlan {
                         if rng.gen_bool(0.9) {
                             continue;
                         }
-                        self.add_interaction(Interaction::Bounce(HOST.to_string()));
+                        let host = HOSTS.iter().choose(&mut rng).unwrap();
+                        self.add_interaction(Interaction::Bounce((*host).to_string()));
+                        break;
+                    }
+                    InteractionType::Crash => {
+                        if rng.gen_bool(0.98) {
+                            continue;
+                        }
+                        let host = HOSTS.iter().choose(&mut rng).unwrap();
+                        self.add_interaction(Interaction::Crash((*host).to_string()));
+                        break;
+                    }
+                    InteractionType::Failover => {
+                        if rng.gen_bool(0.99) {
+                            continue;
+                        }
+                        self.add_interaction(Interaction::Failover);
+                        break;
+                    }
+                    InteractionType::ReloadConfig => {
+                        if rng.gen_bool(0.98) {
+                            continue;
+                        }
+                        let host = HOSTS.iter().choose(&mut rng).unwrap();
+                        // the limits stay generous enough that the
+                        // well-behaved clients are never rate limited, with
+                        // the occasional invalid value
+                        let overrides = ReloadableConfig {
+                            write_timeout_ms: rng
+                                .gen_bool(0.5)
+                                .then(|| rng.gen_range(30_000..=120_000) + write_latency_ms),
+                            rate_limit_capacity: rng.gen_bool(0.5).then(|| {
+                                if rng.gen_bool(0.1) {
+                                    0
+                                } else {
+                                    rng.gen_range(10..=100)
+                                }
+                            }),
+                            rate_limit_refill_per_second: rng
+                                .gen_bool(0.5)
+                                .then(|| rng.gen_range(1..=20)),
+                            // only lowered by MemoryPressure, which restores
+                            // it afterwards
+                            response_budget_bytes: None,
+                        };
+                        self.add_interaction(Interaction::ReloadConfig {
+                            host: (*host).to_string(),
+                            overrides,
+                        });
+                        break;
+                    }
+                    InteractionType::DisruptClient => {
+                        if rng.gen_bool(0.95) {
+                            continue;
+                        }
+                        let banker = rng.gen_range(1..=banker_count().max(1));
+                        self.add_interaction(Interaction::DisruptClient {
+                            client_name: format!("banker_{banker}"),
+                            failures: rng.gen_range(1..=3),
+                        });
+                        break;
+                    }
+                    InteractionType::MemoryPressure => {
+                        if rng.gen_bool(0.98) {
+                            continue;
+                        }
+                        let host = HOSTS.iter().choose(&mut rng).unwrap();
+                        // small enough that a listing of a few dozen
+                        // transactions is refused
+                        self.add_interaction(Interaction::MemoryPressure {
+                            host: (*host).to_string(),
+                            budget_bytes: rng.gen_range(256..=4096),
+                            duration: Duration::from_millis(
+                                rng.gen_range(1_000..=30_000) * step_multiplier(),
+                            ),
+                        });
                         break;
                     }
                 }
@@ -105,7 +225,13 @@ impl InteractionPlan<Interaction> for FaultInjectionInteractionPlan {
     fn add_interaction(&mut self, interaction: Interaction) {
         log::trace!("add_interaction: adding interaction interaction={interaction:?}");
         match &interaction {
-            Interaction::Sleep(..) | Interaction::Bounce(..) => {}
+            Interaction::Sleep(..)
+            | Interaction::Bounce(..)
+            | Interaction::Crash(..)
+            | Interaction::Failover
+            | Interaction::ReloadConfig { .. }
+            | Interaction::DisruptClient { .. }
+            | Interaction::MemoryPressure { .. } => {}
         }
         self.plan.push(interaction);
     }
@@ -0,0 +1,182 @@
+use std::str::FromStr as _;
+
+use dst_demo_server::{
+    connections as server_connections,
+    message::MessageBuffer,
+    protocol::{ProtocolError, ProtocolVersion, Response},
+};
+use plan::{GreedyInteractionPlan, Interaction};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{self, random::rng, unsync::io::AsyncWriteExt as _},
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff, capture::CapturedStream, connections, host::server::HOST, read_message,
+    server_config, should_start, timing, topology,
+};
+
+/// Starts a client that sends bursts of actions as fast as possible, asserting
+/// that the server rate limits it with sane `retry_after_ms` values for the
+/// config the server is currently running with.
+pub fn start(sim: &mut impl Sim) {
+    if !should_start("greedy") {
+        return;
+    }
+
+    let server_addr = topology::addr(HOST, ProtocolVersion::V1);
+
+    let mut plan = GreedyInteractionPlan::new().with_gen_interactions(1000);
+
+    sim.client("greedy", async move {
+        loop {
+            while let Some(interaction) = plan.step() {
+                perform_interaction(&server_addr, interaction).await?;
+            }
+
+            plan.gen_interactions(1000);
+        }
+    });
+}
+
+async fn perform_interaction(
+    server_addr: &str,
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::Burst(count) => {
+            burst(server_addr, *count).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+async fn burst(server_addr: &str, count: u64) {
+    // the limits are only checked when the server's config is known and
+    // doesn't change over the course of the burst
+    let config = server_config::effective(HOST);
+    let since = timing::elapsed();
+    let start = switchy::time::now();
+
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Greedy] Connecting to server...");
+        connections::attempt();
+        match CapturedStream::connect(server_addr).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Greedy] Failed to connect to server: {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track("greedy");
+
+    let bytes = b"HEALTH\0".repeat(usize::try_from(count).unwrap());
+    if let Err(e) = stream.write_all(&bytes).await {
+        log::debug!("[Greedy] failed to send burst: {e:?}");
+        return;
+    }
+
+    let max_retry_after_ms = match config.as_ref().map(|x| x.rate_limit_refill_per_second) {
+        Some(0) | None => u128::MAX,
+        Some(refill) => 1000_u128.div_ceil(u128::from(refill)),
+    };
+    let mut buffer = MessageBuffer::new();
+    let mut rate_limited = 0;
+
+    for _ in 0..count {
+        let Ok(Some(message)) = read_message(&mut buffer, Box::pin(&mut stream)).await else {
+            log::debug!("[Greedy] connection dropped mid-burst");
+            return;
+        };
+
+        if message == "healthy" {
+            continue;
+        }
+
+        let Ok(Response::Err {
+            code: ProtocolError::RateLimited,
+            message: details,
+        }) = Response::from_str(&message)
+        else {
+            panic!(
+                "[Greedy] expected a healthy or rate limited response, instead got:\n'{message}'"
+            );
+        };
+
+        let retry_after_ms = details
+            .strip_prefix("retry_after_ms=")
+            .and_then(|x| x.parse::<u128>().ok())
+            .unwrap_or_else(|| panic!("[Greedy] invalid rate limit error:\n'{message}'"));
+
+        assert!(
+            retry_after_ms > 0
+                && (retry_after_ms <= max_retry_after_ms
+                    || server_config::changed_since(HOST, since)),
+            "[Greedy] expected 0 < retry_after_ms <= {max_retry_after_ms}, instead got:\n'{message}'"
+        );
+
+        rate_limited += 1;
+    }
+
+    // Every action in the burst has been responded to, so the server has
+    // counted all of them against the connection. The last response may not
+    // have been counted in `bytes_out` yet, but the ones before it were
+    let frame_len = b"HEALTH\0".len() as u64;
+    let registered = server_connections::list().into_iter().any(|x| {
+        x.actions == count
+            && x.last_action.as_deref() == Some("HEALTH")
+            && x.bytes_in == count * frame_len
+            && (x.bytes_out > 0 || count == 1)
+    });
+    assert!(
+        registered,
+        "[Greedy] expected the server to have registered a connection with {count} HEALTH actions, instead it has:\n{}",
+        server_connections::list()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+
+    let Some(config) = config.filter(|_| !server_config::changed_since(HOST, since)) else {
+        log::debug!("[Greedy] the server's config changed or is unknown, not checking the limit");
+        return;
+    };
+
+    // The connection's bucket starts full, so at most the capacity plus
+    // whatever was refilled over the course of the burst can be allowed
+    let elapsed = switchy::time::now()
+        .duration_since(start)
+        .unwrap_or_default();
+    let allowed = elapsed.as_secs_f64().mul_add(
+        f64::from(config.rate_limit_refill_per_second),
+        f64::from(config.rate_limit_capacity),
+    );
+    let min_rate_limited = count.saturating_sub(allowed.floor() as u64);
+
+    log::debug!(
+        "[Greedy] burst of {count} actions over {elapsed:?}: rate_limited={rate_limited} min_rate_limited={min_rate_limited}"
+    );
+
+    assert!(
+        rate_limited >= min_rate_limited,
+        "[Greedy] expected at least {min_rate_limited} of {count} actions to be rate limited over {elapsed:?}, but only {rate_limited} were"
+    );
+}
@@ -1,26 +1,44 @@
+use std::{cell::RefCell, time::Duration};
+
+use dst_demo_server::message::MessageBuffer;
 use plan::{HealthCheckInteractionPlan, Interaction};
 use simvar::{
     Sim,
     plan::InteractionPlan as _,
-    switchy::{
-        self,
-        tcp::TcpStream,
-        time::simulator::step_multiplier,
-        unsync::{futures::FutureExt, io::AsyncWriteExt},
-    },
+    switchy::{self, time::simulator::step_multiplier, unsync::io::AsyncWriteExt},
 };
 
 pub mod plan;
 
-use crate::read_message;
+use crate::{
+    assert_eventually, availability, capture::CapturedStream, client::with_deadline, connections,
+    observability, progress, read_message, should_start, timeouts, topology,
+};
+
+const NAME: &str = "health_check";
+
+thread_local! {
+    static SENT: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Starts the health checker, which checks each of `hosts` in turn and
+/// records their availability.
+pub fn start(sim: &mut impl Sim, hosts: &[&str]) {
+    if !should_start(NAME) {
+        return;
+    }
+
+    SENT.with_borrow_mut(|x| *x = 0);
+    progress::touch(NAME);
 
-pub fn start(sim: &mut impl Sim) {
-    let mut plan = HealthCheckInteractionPlan::new().with_gen_interactions(1000);
+    let hosts = hosts.iter().map(ToString::to_string).collect();
+    let mut plan = HealthCheckInteractionPlan::with_hosts(hosts).with_gen_interactions(1000);
 
-    sim.client("health_check", async move {
+    sim.client(NAME, async move {
         loop {
             while let Some(interaction) = plan.step() {
                 perform_interaction(interaction).await?;
+                progress::touch(NAME);
                 switchy::unsync::time::sleep(std::time::Duration::from_secs(
                     step_multiplier() * 60,
                 ))
@@ -52,55 +70,74 @@ async fn perform_interaction(
 }
 
 async fn health_check(host: &str) -> Result<(), Box<dyn std::error::Error + Send>> {
-    let timeout = 10 * step_multiplier();
+    // The deadline only catches an attempt that hangs, so it leaves room for
+    // one past the end of the window assert_health polls each listener within
+    let listeners = u32::try_from(topology::current().listeners.len()).unwrap();
+    let timeout = (listeners + 1) * health_window();
 
-    switchy::unsync::select! {
-        resp = assert_health(host).fuse() => {
-            resp?;
-        }
-        () = switchy::unsync::time::sleep(std::time::Duration::from_secs(timeout)) => {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::TimedOut,
-                format!("Failed to get healthy response within {timeout} seconds")
-            )) as Box<dyn std::error::Error + Send>);
-        }
-    }
+    with_deadline(NAME, timeout, "health check", assert_health(host)).await
+}
 
-    Ok(())
+/// How long a host has to respond as healthy, covering connecting and the
+/// `HEALTH` action
+fn health_window() -> Duration {
+    timeouts::timeout(Duration::from_secs(10 * step_multiplier()), 2)
 }
 
+/// Checks each of `host`'s listeners in turn until it responds as healthy.
+/// Every attempt is recorded as an availability observation for the host.
 async fn assert_health(host: &str) -> Result<(), Box<dyn std::error::Error + Send>> {
-    let response = loop {
-        log::trace!("[Health Client] Connecting to server...");
-        let mut stream = match TcpStream::connect(host).await {
-            Ok(stream) => stream,
-            Err(e) => {
-                log::debug!("[Health Client] Failed to connect to server: {e:?}");
-                switchy::unsync::time::sleep(std::time::Duration::from_millis(step_multiplier()))
-                    .await;
-                continue;
-            }
-        };
-        log::trace!("[Health Client] Connected!");
-        match stream.write_all(b"HEALTH\0").await {
-            Ok(resp) => resp,
-            Err(e) => {
-                log::error!("failed to make http_request: {e:?}");
-                continue;
-            }
-        }
+    for listener in topology::current().listeners {
+        let server_addr = format!("{host}:{}", listener.port);
+
+        assert_eventually!(
+            async {
+                let healthy = check_health(&server_addr).await;
+                availability::observe(host, healthy.is_ok());
+                healthy
+            },
+            within = health_window(),
+            poll_every = Duration::from_millis(100 * step_multiplier()),
+            context = "[Health Client] {host} at {server_addr} never responded as healthy",
+        );
+    }
+
+    Ok(())
+}
 
-        let Ok(Some(resp)) = read_message(&mut String::new(), Box::pin(&mut stream)).await else {
-            log::debug!("failed to receive healthy response");
-            continue;
-        };
+/// Sends a single `HEALTH` action to `server_addr`, returning why the host
+/// isn't healthy if it didn't respond as such.
+async fn check_health(server_addr: &str) -> Result<(), String> {
+    log::trace!("[Health Client] Connecting to {server_addr}...");
+    connections::attempt();
+    let mut stream = CapturedStream::connect(server_addr)
+        .await
+        .map_err(|e| format!("failed to connect: {e:?}"))?;
+    let _connection = connections::track(NAME);
+    log::trace!("[Health Client] Connected!");
+    let sequence = SENT.with_borrow_mut(|x| {
+        *x += 1;
+        *x
+    });
 
-        log::debug!("Received response={resp}");
+    stream
+        .write_all(b"HEALTH\0")
+        .await
+        .map_err(|e| format!("failed to send HEALTH: {e:?}"))?;
 
-        break resp;
+    let Ok(Some(resp)) = read_message(&mut MessageBuffer::new(), Box::pin(&mut stream)).await
+    else {
+        return Err("failed to receive a response".to_string());
     };
 
-    assert!(response == "healthy");
+    log::debug!("Received response={resp}");
 
-    Ok(())
+    // responses must arrive in the order their requests were sent
+    observability::monotonic_check("health_check.response_sequence", sequence);
+
+    if resp == "healthy" {
+        Ok(())
+    } else {
+        Err(format!("responded with '{resp}'"))
+    }
 }
@@ -3,7 +3,7 @@ use std::time::Duration;
 use simvar::plan::InteractionPlan;
 use strum::{EnumDiscriminants, EnumIter};
 
-use crate::host::server::{HOST, PORT};
+use crate::host::server::HOST;
 
 pub struct InteractionPlanContext {}
 
@@ -23,21 +23,24 @@ impl InteractionPlanContext {
 pub struct HealthCheckInteractionPlan {
     #[allow(unused)]
     context: InteractionPlanContext,
+    /// The hosts that are checked in turn, with a sleep after each round
+    hosts: Vec<String>,
     step: u64,
     pub plan: Vec<Interaction>,
 }
 
 impl Default for HealthCheckInteractionPlan {
     fn default() -> Self {
-        Self::new()
+        Self::with_hosts(vec![HOST.to_string()])
     }
 }
 
 impl HealthCheckInteractionPlan {
     #[must_use]
-    pub const fn new() -> Self {
+    pub const fn with_hosts(hosts: Vec<String>) -> Self {
         Self {
             context: InteractionPlanContext::new(),
+            hosts,
             step: 0,
             plan: vec![],
         }
@@ -66,9 +69,11 @@ impl InteractionPlan<Interaction> for HealthCheckInteractionPlan {
 
     fn gen_interactions(&mut self, count: u64) {
         let len = self.plan.len() as u64;
+        let round = self.hosts.len() as u64 + 1;
 
         for i in 1..=count {
-            let interaction_type = if (i + len) % 2 == 0 {
+            let position = (i + len) % round;
+            let interaction_type = if position == 0 {
                 InteractionType::Sleep
             } else {
                 InteractionType::HealthCheck
@@ -80,10 +85,12 @@ impl InteractionPlan<Interaction> for HealthCheckInteractionPlan {
             match interaction_type {
                 InteractionType::Sleep => {
                     #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-                    self.add_interaction(Interaction::Sleep(Duration::from_millis(1000)));
+                    self.add_interaction(Interaction::Sleep(Duration::from_secs(1)));
                 }
                 InteractionType::HealthCheck => {
-                    self.add_interaction(Interaction::HealthCheck(format!("{HOST}:{PORT}")));
+                    #[allow(clippy::cast_possible_truncation)]
+                    let host = self.hosts[position as usize - 1].clone();
+                    self.add_interaction(Interaction::HealthCheck(host));
                 }
             }
         }
@@ -0,0 +1,184 @@
+use std::{str::FromStr as _, time::Duration};
+
+use dst_demo_server::{
+    ServerAction,
+    message::MessageBuffer,
+    protocol::{ProtocolError, ProtocolVersion, Response},
+};
+use plan::{Interaction, IntruderInteractionPlan};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{
+        self,
+        random::rng,
+        unsync::{futures::FutureExt as _, io::AsyncWriteExt as _},
+    },
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff, capture::CapturedStream, connections, faults, host::server, read_message,
+    replication, should_start, timing,
+};
+
+const NAME: &str = "intruder";
+
+/// How long to wait on a connection to a server that may have gone down
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts a client that attempts admin actions on the primary without an
+/// admin identity, asserting that the server rejects every one of them as
+/// `unauthorized`.
+pub fn start(sim: &mut impl Sim) {
+    if !should_start(NAME) {
+        return;
+    }
+
+    let mut plan = IntruderInteractionPlan::new().with_gen_interactions(1000);
+
+    sim.client(NAME, async move {
+        loop {
+            while let Some(interaction) = plan.step() {
+                perform_interaction(interaction).await?;
+            }
+
+            plan.gen_interactions(1000);
+        }
+    });
+}
+
+async fn perform_interaction(
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::Attempt { action, identity } => {
+            attempt(*action, *identity).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn connect(server_addr: &str, identity: Option<&str>) -> CapturedStream {
+    let mut backoff = Backoff::connect();
+    loop {
+        log::trace!("[Intruder] Connecting to server...");
+        connections::attempt();
+        let stream = match identity {
+            Some(identity) => CapturedStream::connect_with_identity(server_addr, identity)
+                .await
+                .map_err(|e| format!("{e:?}")),
+            None => CapturedStream::connect(server_addr)
+                .await
+                .map_err(|e| format!("{e:?}")),
+        };
+        match stream {
+            Ok(stream) => return stream,
+            Err(e) => {
+                log::debug!("[Intruder] Failed to connect to server: {e}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    }
+}
+
+/// Sends `message` and reads the response to it, or `None` if the
+/// connection failed along the way
+async fn request(
+    stream: &mut CapturedStream,
+    buffer: &mut MessageBuffer,
+    message: &str,
+) -> Option<String> {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.push(0_u8);
+
+    if let Err(e) = stream.write_all(&bytes).await {
+        log::debug!("[Intruder] failed to send message: {e:?}");
+        return None;
+    }
+
+    read_message(buffer, Box::pin(stream))
+        .await
+        .inspect_err(|e| log::debug!("[Intruder] failed to read message: {e:?}"))
+        .ok()?
+}
+
+async fn attempt(action: ServerAction, identity: Option<&str>) {
+    let host = replication::primary();
+    let server_addr = replication::addr(host, ProtocolVersion::V2);
+    let since = timing::elapsed();
+    let stops = server::stops(host);
+
+    let mut stream = connect(&server_addr, identity).await;
+    let connection = connections::track(NAME);
+    let mut buffer = MessageBuffer::new();
+
+    log::info!("[Intruder] attempting {action} on '{host}' as identity={identity:?}");
+
+    if request(&mut stream, &mut buffer, "PROTO 2").await.is_none() {
+        return;
+    }
+
+    let Some(response) = request(&mut stream, &mut buffer, action.as_ref()).await else {
+        if action != ServerAction::Exit {
+            return;
+        }
+
+        drop(stream);
+        drop(connection);
+
+        // A closed connection on its own doesn't mean the EXIT went through,
+        // since the network may have dropped it. It only did if the server
+        // went down without a host being bounced or crashed by a fault.
+        let went_down = server::stops(host) > stops || !accepts_connections(&server_addr).await;
+        let faulted = faults::applied()
+            .iter()
+            .any(|x| x.host == host && x.at >= since);
+        assert!(
+            faulted || !went_down,
+            "[Intruder] '{host}' went down after an EXIT as identity={identity:?}, which it must reject as unauthorized"
+        );
+        log::debug!(
+            "[Intruder] '{host}' closed the connection after an EXIT as identity={identity:?}, but is still up"
+        );
+        return;
+    };
+
+    assert!(
+        matches!(
+            Response::from_str(&response),
+            Ok(Response::Err {
+                code: ProtocolError::Unauthorized,
+                ..
+            })
+        ),
+        "[Intruder] expected '{host}' to reject {action} as identity={identity:?} as unauthorized, instead got:\n'{response}'"
+    );
+}
+
+/// Whether `server_addr` still accepts a connection. A connect that doesn't
+/// finish within [`PROBE_TIMEOUT`] is inconclusive, so it counts as accepted.
+async fn accepts_connections(server_addr: &str) -> bool {
+    connections::attempt();
+    switchy::unsync::select! {
+        stream = CapturedStream::connect(server_addr).fuse() => match stream {
+            Ok(_stream) => {
+                let _connection = connections::track(NAME);
+                true
+            }
+            Err(e) => {
+                log::debug!("[Intruder] '{server_addr}' refused a connection: {e:?}");
+                false
+            }
+        },
+        () = switchy::unsync::time::sleep(PROBE_TIMEOUT) => true,
+    }
+}
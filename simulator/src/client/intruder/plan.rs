@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use dst_demo_server::ServerAction;
+use simvar::{
+    plan::InteractionPlan,
+    switchy::{
+        random::{rand::rand::seq::SliceRandom as _, rng},
+        time::simulator::step_multiplier,
+    },
+};
+use strum::{EnumDiscriminants, EnumIter};
+
+/// The admin actions the intruder attempts
+pub const ACTIONS: &[ServerAction] = &[ServerAction::Exit, ServerAction::ImportLedger];
+
+/// The identities the intruder attempts them with. None of them start with
+/// the admin prefix, even the ones that mention it.
+pub const IDENTITIES: &[Option<&str>] = &[
+    None,
+    Some("intruder"),
+    Some("banker_intruder"),
+    Some("not_admin"),
+];
+
+pub struct InteractionPlanContext {}
+
+impl Default for InteractionPlanContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InteractionPlanContext {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+pub struct IntruderInteractionPlan {
+    #[allow(unused)]
+    context: InteractionPlanContext,
+    step: u64,
+    pub plan: Vec<Interaction>,
+}
+
+impl Default for IntruderInteractionPlan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntruderInteractionPlan {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            context: InteractionPlanContext::new(),
+            step: 0,
+            plan: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, EnumDiscriminants)]
+#[strum_discriminants(derive(EnumIter))]
+#[strum_discriminants(name(InteractionType))]
+pub enum Interaction {
+    Sleep(Duration),
+    /// Attempts an admin action on the primary, identified as `identity` or
+    /// not identified at all
+    Attempt {
+        action: ServerAction,
+        identity: Option<&'static str>,
+    },
+}
+
+impl InteractionPlan<Interaction> for IntruderInteractionPlan {
+    fn step(&mut self) -> Option<&Interaction> {
+        #[allow(clippy::cast_possible_truncation)]
+        if let Some(item) = self.plan.get(self.step as usize) {
+            self.step += 1;
+            log::trace!("step: {}", self.step);
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    fn gen_interactions(&mut self, count: u64) {
+        let len = self.plan.len() as u64;
+
+        let mut rng = rng();
+
+        for i in 1..=count {
+            let interaction_type = if (i + len).is_multiple_of(2) {
+                InteractionType::Sleep
+            } else {
+                InteractionType::Attempt
+            };
+            log::trace!(
+                "gen_interactions: generating interaction {i}/{count} ({}) interaction_type={interaction_type:?}",
+                i + len
+            );
+            match interaction_type {
+                InteractionType::Sleep => {
+                    self.add_interaction(Interaction::Sleep(Duration::from_millis(
+                        rng.gen_range(0..60_000) * step_multiplier(),
+                    )));
+                }
+                InteractionType::Attempt => {
+                    self.add_interaction(Interaction::Attempt {
+                        action: *ACTIONS.choose(&mut rng).unwrap(),
+                        identity: *IDENTITIES.choose(&mut rng).unwrap(),
+                    });
+                }
+            }
+        }
+        drop(rng);
+    }
+
+    fn add_interaction(&mut self, interaction: Interaction) {
+        log::trace!("add_interaction: adding interaction interaction={interaction:?}");
+        match &interaction {
+            Interaction::Sleep(..) | Interaction::Attempt { .. } => {}
+        }
+        self.plan.push(interaction);
+    }
+}
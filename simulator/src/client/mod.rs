@@ -1,3 +1,208 @@
+use std::{cell::RefCell, time::Duration};
+
+use simvar::{
+    Sim,
+    switchy::{self, random::rand::rand::Rng, unsync::futures::FutureExt as _},
+};
+use strum::{AsRefStr, EnumIter, IntoEnumIterator as _};
+
+use crate::{banker_count, replication, rng_trace::rng_labeled, shuffle::shuffled, timing};
+
+pub mod auditor;
 pub mod banker;
 pub mod fault_injector;
+pub mod greedy;
 pub mod health_checker;
+pub mod intruder;
+pub mod observer;
+pub mod scraper;
+pub mod slow_reader;
+
+/// The clients that are started at the beginning of every run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Client {
+    HealthChecker,
+    FaultInjector,
+    SlowReader,
+    /// Both observers
+    Observer,
+    Greedy,
+    Scraper,
+    Auditor,
+    Intruder,
+    /// A single banker. There are `banker_count` of these in a run.
+    Banker,
+}
+
+impl Client {
+    /// How many server connections the client can legitimately still have
+    /// when a run ends.
+    ///
+    /// That's the one it's mid-interaction on, if any, for each of the two
+    /// observers `Observer` stands for. The health checker and the intruder
+    /// open a fresh connection for every check or attempt, and those can
+    /// follow each other with little to no sleep in between, so the server
+    /// may not have noticed the previous one closing yet either. The scraper
+    /// only talks to the metrics host.
+    #[must_use]
+    pub const fn server_connections(self) -> u64 {
+        match self {
+            Self::Scraper => 0,
+            Self::FaultInjector
+            | Self::SlowReader
+            | Self::Greedy
+            | Self::Auditor
+            | Self::Banker => 1,
+            Self::Observer | Self::HealthChecker | Self::Intruder => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+thread_local! {
+    static START_ORDER: RefCell<Vec<Client>> = const { RefCell::new(vec![]) };
+}
+
+/// Every client a run with `banker_count` bankers starts, in an order
+/// shuffled over `rng`
+///
+/// # Panics
+///
+/// * If `banker_count` doesn't fit in a `usize`
+#[must_use]
+pub fn start_order<R: Rng + ?Sized>(rng: &mut R, banker_count: u64) -> Vec<Client> {
+    let banker_count = usize::try_from(banker_count).unwrap();
+    let clients = Client::iter()
+        .filter(|x| *x != Client::Banker)
+        .chain(std::iter::repeat_n(Client::Banker, banker_count));
+
+    shuffled(rng, clients)
+}
+
+/// Shuffles the order the current run's clients will be started in
+///
+/// It's drawn while the run is built, before its props are read, so that the
+/// order recorded in the props is the one the run goes on to use.
+pub fn reset_start_order() {
+    let order = start_order(&mut rng_labeled("client_start_order"), banker_count());
+
+    START_ORDER.with_borrow_mut(|x| *x = order);
+}
+
+/// Starts every client in the order drawn by [`reset_start_order`], so that
+/// which one gets to the servers first differs between seeds. The order is
+/// recorded in the run's props.
+pub fn start(sim: &mut impl Sim) {
+    let order = started_order();
+    log::debug!("starting clients in order: {order:?}");
+
+    for client in &order {
+        match client {
+            Client::HealthChecker => health_checker::start(sim, &replication::HOSTS),
+            Client::FaultInjector => fault_injector::start(sim),
+            Client::SlowReader => slow_reader::start(sim),
+            Client::Observer => observer::start(sim),
+            Client::Greedy => greedy::start(sim),
+            Client::Scraper => scraper::start(sim),
+            Client::Auditor => auditor::start(sim),
+            Client::Intruder => intruder::start(sim),
+            Client::Banker => banker::start(sim),
+        }
+    }
+}
+
+/// The order the current run's clients were started in
+#[must_use]
+pub fn started_order() -> Vec<Client> {
+    START_ORDER.with_borrow(Clone::clone)
+}
+
+#[must_use]
+pub fn props() -> Vec<(String, String)> {
+    vec![(
+        "client_start_order".to_string(),
+        started_order()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    )]
+}
+
+pub type ClientResult<T> = Result<T, Box<dyn std::error::Error + Send>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("[{name}] failed after {elapsed:?} of simulated time: {source}")]
+    Failed {
+        name: String,
+        elapsed: Duration,
+        source: Box<dyn std::error::Error + Send>,
+    },
+    #[error(
+        "[{name}] liveness failure after {elapsed:?} of simulated time: still running {context} after the {deadline:?} deadline"
+    )]
+    DeadlineExceeded {
+        name: String,
+        elapsed: Duration,
+        deadline: Duration,
+        context: String,
+    },
+}
+
+/// Runs a client's future, tagging any error it returns with the client's
+/// name and the simulated time of the failure so the run's result shows which
+/// client failed.
+///
+/// # Errors
+///
+/// * If the client's future fails
+pub async fn with_context<T>(
+    name: &str,
+    future: impl Future<Output = ClientResult<T>>,
+) -> ClientResult<T> {
+    future.await.map_err(|source| {
+        if source.is::<ClientError>() {
+            return source;
+        }
+
+        Box::new(ClientError::Failed {
+            name: name.to_string(),
+            elapsed: timing::elapsed(),
+            source,
+        }) as Box<dyn std::error::Error + Send>
+    })
+}
+
+/// Runs `future`, treating it as a liveness failure of the client if it's still
+/// running after `deadline` so a silently hung client turns into a failure
+/// instead of the run timing out.
+///
+/// # Errors
+///
+/// * If the future fails
+/// * If the future doesn't complete within the deadline
+pub async fn with_deadline<T>(
+    name: &str,
+    deadline: Duration,
+    context: impl std::fmt::Display,
+    future: impl Future<Output = ClientResult<T>>,
+) -> ClientResult<T> {
+    switchy::unsync::select! {
+        resp = with_context(name, future).fuse() => resp,
+        () = switchy::unsync::time::sleep(deadline) => {
+            Err(Box::new(ClientError::DeadlineExceeded {
+                name: name.to_string(),
+                elapsed: timing::elapsed(),
+                deadline,
+                context: context.to_string(),
+            }) as Box<dyn std::error::Error + Send>)
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{cell::RefCell, str::FromStr as _, time::Duration};
+
+use dst_demo_server::{
+    ServerAction,
+    bank::{Transaction, TransactionId},
+    message::MessageBuffer,
+    protocol::{ProtocolVersion, READ_ONLY_MESSAGE},
+};
+use plan::{Interaction, ObserverInteractionPlan, Role};
+use rust_decimal::Decimal;
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{
+        self, random::rng, time::simulator::step_multiplier, unsync::io::AsyncWriteExt as _,
+    },
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff, capture::CapturedStream, connections, expected_ledger, replication,
+    should_start, timing,
+};
+
+/// A transaction that the server acknowledged to the writer observer
+#[derive(Debug, Clone, Copy)]
+struct Acknowledged {
+    id: TransactionId,
+    /// The elapsed simulated time at which the server acknowledged it
+    at: Duration,
+}
+
+thread_local! {
+    static ACKNOWLEDGED: RefCell<Vec<Acknowledged>> = const { RefCell::new(vec![]) };
+}
+
+/// Clears the transactions acknowledged to the writer observer in the previous
+/// run.
+pub fn reset() {
+    ACKNOWLEDGED.with_borrow_mut(Vec::clear);
+}
+
+/// How long, in simulated time, a transaction acknowledged to the writer may
+/// take to become visible to the reader.
+///
+/// Configurable in milliseconds through `SIMULATOR_OBSERVER_STALENESS_MS` and
+/// scaled by the step multiplier.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_OBSERVER_STALENESS_MS` is not a valid integer
+#[must_use]
+pub fn bounded_staleness() -> Duration {
+    let millis = std::env::var("SIMULATOR_OBSERVER_STALENESS_MS")
+        .ok()
+        .map_or(10_000, |x| x.parse::<u64>().unwrap());
+
+    Duration::from_millis(millis * step_multiplier())
+}
+
+/// Starts a pair of cooperating observers.
+///
+/// `observer_a` creates transactions and records when they were acknowledged,
+/// and `observer_b` asserts that each acknowledged transaction becomes visible
+/// to it within the bounded staleness.
+pub fn start(sim: &mut impl Sim) {
+    for (name, role) in [("observer_a", Role::Writer), ("observer_b", Role::Reader)] {
+        if !should_start(name) {
+            continue;
+        }
+
+        let mut plan = ObserverInteractionPlan::new(role).with_gen_interactions(1000);
+
+        sim.client(name, async move {
+            loop {
+                while let Some(interaction) = plan.step() {
+                    perform_interaction(
+                        &replication::primary_addr(ProtocolVersion::V1),
+                        interaction,
+                    )
+                    .await?;
+                }
+
+                plan.gen_interactions(1000);
+            }
+        });
+    }
+}
+
+async fn perform_interaction(
+    server_addr: &str,
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::CreateTransaction { amount } => {
+            create_transaction(server_addr, *amount).await;
+        }
+        Interaction::VerifyAcknowledged => {
+            verify_acknowledged(server_addr).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn create_transaction(server_addr: &str, amount: Decimal) {
+    expected_ledger::sent_create(amount);
+
+    let Some(response) = request(
+        "observer_a",
+        server_addr,
+        ServerAction::CreateTransaction,
+        &amount.to_string(),
+    )
+    .await
+    else {
+        log::debug!("[observer_a] create_transaction: failed to get a response");
+        return;
+    };
+    if response == READ_ONLY_MESSAGE {
+        log::debug!("[observer_a] create_transaction: primary is read-only during a failover");
+        expected_ledger::rejected_create(amount);
+        return;
+    }
+
+    let transaction = Transaction::from_str(&response).unwrap_or_else(|e| {
+        panic!("[observer_a] expected a transaction in the response ({e:?}):\n'{response}'")
+    });
+    expected_ledger::acknowledged_create("observer_a", &transaction);
+
+    let acknowledged = Acknowledged {
+        id: transaction.id,
+        at: timing::elapsed(),
+    };
+
+    log::debug!("[observer_a] acknowledged {acknowledged:?}");
+
+    ACKNOWLEDGED.with_borrow_mut(|x| x.push(acknowledged));
+}
+
+async fn verify_acknowledged(server_addr: &str) {
+    let pending = ACKNOWLEDGED.with_borrow(Clone::clone);
+    let staleness = bounded_staleness();
+
+    for acknowledged in pending {
+        let Some(response) = request(
+            "observer_b",
+            server_addr,
+            ServerAction::GetTransaction,
+            &acknowledged.id.to_string(),
+        )
+        .await
+        else {
+            log::debug!("[observer_b] verify_acknowledged: failed to get a response");
+            return;
+        };
+
+        if Transaction::from_str(&response).is_ok_and(|x| x.id == acknowledged.id) {
+            ACKNOWLEDGED.with_borrow_mut(|x| x.retain(|x| x.id != acknowledged.id));
+            continue;
+        }
+
+        // A bounce restarts the visibility window, since the server can't
+        // serve anything while it is down
+        let window_start =
+            timing::last_bounce().map_or(acknowledged.at, |x| x.max(acknowledged.at));
+        let elapsed = timing::elapsed();
+
+        assert!(
+            elapsed <= window_start + staleness,
+            "\
+            [observer_b] transaction {} acknowledged to observer_a at {:?} was still not visible at {elapsed:?} \
+            (bounded_staleness={staleness:?} window_start={window_start:?}):\n\
+            '{response}'\
+            ",
+            acknowledged.id,
+            acknowledged.at,
+        );
+    }
+}
+
+/// Sends an action that prompts for a single input, returning the final
+/// response, or `None` if the connection failed along the way.
+async fn request(
+    client: &str,
+    server_addr: &str,
+    action: ServerAction,
+    input: &str,
+) -> Option<String> {
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Observer] Connecting to server...");
+        connections::attempt();
+        match CapturedStream::connect(server_addr).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Observer] Failed to connect to server: {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track(client);
+    let addr = stream.local_addr().ok()?.to_string();
+    let mut buffer = MessageBuffer::new();
+
+    send_message(server_addr, &addr, &mut stream, action.as_ref()).await?;
+    let prompt = read_message(server_addr, &addr, &mut stream, &mut buffer).await?;
+    if prompt == READ_ONLY_MESSAGE {
+        log::debug!("[{client}] request: primary is read-only during a failover");
+        return None;
+    }
+    send_message(server_addr, &addr, &mut stream, input).await?;
+    read_message(server_addr, &addr, &mut stream, &mut buffer).await
+}
+
+async fn send_message(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    message: &str,
+) -> Option<()> {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.push(0_u8);
+
+    if let Err(e) = stream.write_all(&bytes).await {
+        log::debug!("[{addr}->{server_addr}] failed to send message: {e:?}");
+        return None;
+    }
+
+    Some(())
+}
+
+async fn read_message(
+    server_addr: &str,
+    addr: &str,
+    stream: &mut CapturedStream,
+    buffer: &mut MessageBuffer,
+) -> Option<String> {
+    crate::read_message(buffer, Box::pin(stream))
+        .await
+        .inspect_err(|e| log::debug!("[{addr}->{server_addr}] failed to read message: {e:?}"))
+        .ok()?
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+use simvar::{
+    plan::InteractionPlan,
+    switchy::{random::rng, time::simulator::step_multiplier},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Creates transactions and records when the server acknowledged them
+    Writer,
+    /// Polls for the transactions acknowledged to the writer
+    Reader,
+}
+
+pub struct InteractionPlanContext {
+    role: Role,
+}
+
+impl InteractionPlanContext {
+    #[must_use]
+    pub const fn new(role: Role) -> Self {
+        Self { role }
+    }
+}
+
+pub struct ObserverInteractionPlan {
+    context: InteractionPlanContext,
+    step: u64,
+    pub plan: Vec<Interaction>,
+}
+
+impl ObserverInteractionPlan {
+    #[must_use]
+    pub const fn new(role: Role) -> Self {
+        Self {
+            context: InteractionPlanContext::new(role),
+            step: 0,
+            plan: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Interaction {
+    Sleep(Duration),
+    CreateTransaction { amount: Decimal },
+    VerifyAcknowledged,
+}
+
+impl InteractionPlan<Interaction> for ObserverInteractionPlan {
+    fn step(&mut self) -> Option<&Interaction> {
+        #[allow(clippy::cast_possible_truncation)]
+        if let Some(item) = self.plan.get(self.step as usize) {
+            self.step += 1;
+            log::trace!("step: {}", self.step);
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    fn gen_interactions(&mut self, count: u64) {
+        let len = self.plan.len() as u64;
+
+        let rng = rng();
+
+        for i in 1..=count {
+            log::trace!(
+                "gen_interactions: generating interaction {i}/{count} ({}) role={:?}",
+                i + len,
+                self.context.role,
+            );
+            if (i + len).is_multiple_of(2) {
+                self.add_interaction(Interaction::Sleep(Duration::from_millis(
+                    rng.gen_range(0..10_000) * step_multiplier(),
+                )));
+                continue;
+            }
+            match self.context.role {
+                Role::Writer => {
+                    let amount = Decimal::new(rng.gen_range(-1_000_000..1_000_000), 2);
+                    self.add_interaction(Interaction::CreateTransaction { amount });
+                }
+                Role::Reader => {
+                    self.add_interaction(Interaction::VerifyAcknowledged);
+                }
+            }
+        }
+        drop(rng);
+    }
+
+    fn add_interaction(&mut self, interaction: Interaction) {
+        log::trace!("add_interaction: adding interaction interaction={interaction:?}");
+        match &interaction {
+            Interaction::Sleep(..)
+            | Interaction::CreateTransaction { .. }
+            | Interaction::VerifyAcknowledged => {}
+        }
+        self.plan.push(interaction);
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use plan::{Interaction, ScraperInteractionPlan};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{self, random::rng},
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff,
+    capture::CapturedStream,
+    connections,
+    host::metrics::{HOST, PORT},
+    http::{http_request, parse_http_response},
+    prometheus, should_start, timing,
+};
+
+/// The samples from the previous scrape and when they were scraped
+struct Previous {
+    samples: BTreeMap<String, f64>,
+    at: Duration,
+}
+
+/// Starts a client that scrapes the metrics host every simulated minute and
+/// asserts that its counters never decrease, except across server bounces.
+pub fn start(sim: &mut impl Sim) {
+    if !should_start("scraper") {
+        return;
+    }
+
+    let metrics_addr = format!("{HOST}:{PORT}");
+
+    let mut plan = ScraperInteractionPlan::new().with_gen_interactions(1000);
+
+    sim.client("scraper", async move {
+        let mut previous = None;
+
+        loop {
+            while let Some(interaction) = plan.step() {
+                perform_interaction(&metrics_addr, interaction, &mut previous).await?;
+            }
+
+            plan.gen_interactions(1000);
+        }
+    });
+}
+
+async fn perform_interaction(
+    metrics_addr: &str,
+    interaction: &Interaction,
+    previous: &mut Option<Previous>,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::Scrape => {
+            scrape(metrics_addr, previous).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn scrape(metrics_addr: &str, previous: &mut Option<Previous>) {
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Scraper] Connecting to metrics host...");
+        connections::attempt();
+        match CapturedStream::connect(metrics_addr).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Scraper] Failed to connect to metrics host: {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track("scraper");
+
+    let response = match http_request("GET", &mut stream, "/metrics").await {
+        Ok(response) => response,
+        Err(e) => {
+            log::debug!("[Scraper] failed to scrape metrics: {e:?}");
+            return;
+        }
+    };
+    let response = parse_http_response(&response)
+        .unwrap_or_else(|e| panic!("[Scraper] invalid HTTP response ({e}):\n{response}"));
+
+    assert!(
+        response.status_code == 200,
+        "[Scraper] expected a 200 response, instead got {}:\n{}",
+        response.status_code,
+        response.body,
+    );
+
+    let samples = prometheus::parse(&response.body)
+        .unwrap_or_else(|e| panic!("[Scraper] invalid metrics ({e}):\n{}", response.body));
+    let at = timing::elapsed();
+
+    // Counters are allowed to reset when the server restarts, so only compare
+    // against the previous scrape if the server wasn't bounced since then
+    let bounced = |previous: &Previous| timing::last_bounce().is_some_and(|x| x >= previous.at);
+
+    if let Some(previous) = previous.as_ref().filter(|x| !bounced(x)) {
+        for (name, value) in &previous.samples {
+            let current = samples
+                .get(name)
+                .copied()
+                .unwrap_or_else(|| panic!("[Scraper] counter {name} disappeared from the metrics"));
+
+            assert!(
+                current >= *value,
+                "[Scraper] counter {name} decreased from {value} to {current} between {:?} and {at:?}",
+                previous.at,
+            );
+        }
+    }
+
+    log::debug!("[Scraper] scraped {} samples at {at:?}", samples.len());
+
+    *previous = Some(Previous { samples, at });
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use simvar::plan::InteractionPlan;
+
+pub struct InteractionPlanContext {}
+
+impl Default for InteractionPlanContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InteractionPlanContext {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+pub struct ScraperInteractionPlan {
+    #[allow(unused)]
+    context: InteractionPlanContext,
+    step: u64,
+    pub plan: Vec<Interaction>,
+}
+
+impl Default for ScraperInteractionPlan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScraperInteractionPlan {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            context: InteractionPlanContext::new(),
+            step: 0,
+            plan: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Interaction {
+    Sleep(Duration),
+    Scrape,
+}
+
+impl InteractionPlan<Interaction> for ScraperInteractionPlan {
+    fn step(&mut self) -> Option<&Interaction> {
+        #[allow(clippy::cast_possible_truncation)]
+        if let Some(item) = self.plan.get(self.step as usize) {
+            self.step += 1;
+            log::trace!("step: {}", self.step);
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    fn gen_interactions(&mut self, count: u64) {
+        let len = self.plan.len() as u64;
+
+        for i in 1..=count {
+            log::trace!(
+                "gen_interactions: generating interaction {i}/{count} ({})",
+                i + len
+            );
+            // Scrape once every simulated minute
+            if (i + len).is_multiple_of(2) {
+                self.add_interaction(Interaction::Sleep(Duration::from_mins(1)));
+            } else {
+                self.add_interaction(Interaction::Scrape);
+            }
+        }
+    }
+
+    fn add_interaction(&mut self, interaction: Interaction) {
+        log::trace!("add_interaction: adding interaction interaction={interaction:?}");
+        match &interaction {
+            Interaction::Sleep(..) | Interaction::Scrape => {}
+        }
+        self.plan.push(interaction);
+    }
+}
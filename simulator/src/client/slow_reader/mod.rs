@@ -0,0 +1,109 @@
+use dst_demo_server::{
+    connections as server_connections, message::MessageBuffer, protocol::ProtocolVersion,
+};
+use plan::{Interaction, SlowReaderInteractionPlan};
+use simvar::{
+    Sim,
+    plan::InteractionPlan as _,
+    switchy::{self, random::rng, unsync::io::AsyncWriteExt as _},
+};
+
+pub mod plan;
+
+use crate::{
+    backoff::Backoff, capture::CapturedStream, connections, host::server::HOST, read_message,
+    should_start, topology,
+};
+
+/// Starts a client that sends requests but stalls before reading the
+/// responses, exercising the server's write path under a stalled peer.
+pub fn start(sim: &mut impl Sim) {
+    if !should_start("slow_reader") {
+        return;
+    }
+
+    let server_addr = topology::addr(HOST, ProtocolVersion::V1);
+
+    let mut plan = SlowReaderInteractionPlan::new().with_gen_interactions(1000);
+
+    sim.client("slow_reader", async move {
+        loop {
+            while let Some(interaction) = plan.step() {
+                perform_interaction(&server_addr, interaction).await?;
+            }
+
+            plan.gen_interactions(1000);
+        }
+    });
+}
+
+async fn perform_interaction(
+    server_addr: &str,
+    interaction: &Interaction,
+) -> Result<(), Box<dyn std::error::Error + Send>> {
+    log::debug!("perform_interaction: interaction={interaction:?}");
+
+    match interaction {
+        Interaction::Sleep(duration) => {
+            log::debug!("perform_interaction: sleeping for duration={duration:?}");
+            switchy::unsync::time::sleep(*duration).await;
+        }
+        Interaction::SlowListTransactions(stall) => {
+            slow_list_transactions(server_addr, *stall).await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn slow_list_transactions(server_addr: &str, stall: std::time::Duration) {
+    let mut backoff = Backoff::connect();
+    let mut stream = loop {
+        log::trace!("[Slow Reader] Connecting to server...");
+        connections::attempt();
+        match CapturedStream::connect(server_addr).await {
+            Ok(stream) => break stream,
+            Err(e) => {
+                log::debug!("[Slow Reader] Failed to connect to server: {e:?}");
+                switchy::unsync::time::sleep(backoff.next_delay(&mut rng())).await;
+            }
+        }
+    };
+    let _connection = connections::track("slow_reader");
+
+    if let Err(e) = stream.write_all(b"LIST_TRANSACTIONS\0").await {
+        log::debug!("[Slow Reader] failed to send action: {e:?}");
+        return;
+    }
+
+    log::debug!("[Slow Reader] stalling for {stall:?} before reading the response");
+    switchy::unsync::time::sleep(stall).await;
+
+    // The server is allowed to drop the connection if the stall exceeded its
+    // write timeout, so either outcome is acceptable here. The invariant is
+    // that the other clients keep making progress in the meantime.
+    match read_message(&mut MessageBuffer::new(), Box::pin(&mut stream)).await {
+        Ok(Some(..)) => {
+            log::debug!("[Slow Reader] received response after stalling");
+
+            // The server has counted the action against the connection, which
+            // stays registered until it's closed
+            let registered = server_connections::list().into_iter().any(|x| {
+                x.actions == 1
+                    && x.last_action.as_deref() == Some("LIST_TRANSACTIONS")
+                    && x.bytes_in == b"LIST_TRANSACTIONS\0".len() as u64
+            });
+            assert!(
+                registered,
+                "[Slow Reader] expected the server to have registered the connection, instead it has:\n{}",
+                server_connections::list()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+        Ok(None) => log::debug!("[Slow Reader] connection was dropped while stalling"),
+        Err(e) => log::debug!("[Slow Reader] failed to read response: {e:?}"),
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use simvar::{
+    plan::InteractionPlan,
+    switchy::{random::rng, time::simulator::step_multiplier},
+};
+use strum::{EnumDiscriminants, EnumIter};
+
+pub struct InteractionPlanContext {}
+
+impl Default for InteractionPlanContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InteractionPlanContext {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+pub struct SlowReaderInteractionPlan {
+    #[allow(unused)]
+    context: InteractionPlanContext,
+    step: u64,
+    pub plan: Vec<Interaction>,
+}
+
+impl Default for SlowReaderInteractionPlan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlowReaderInteractionPlan {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            context: InteractionPlanContext::new(),
+            step: 0,
+            plan: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, EnumDiscriminants)]
+#[strum_discriminants(derive(EnumIter))]
+#[strum_discriminants(name(InteractionType))]
+pub enum Interaction {
+    Sleep(Duration),
+    /// Sends a `LIST_TRANSACTIONS` action and then stalls for the given
+    /// duration before reading the response
+    SlowListTransactions(Duration),
+}
+
+impl InteractionPlan<Interaction> for SlowReaderInteractionPlan {
+    fn step(&mut self) -> Option<&Interaction> {
+        #[allow(clippy::cast_possible_truncation)]
+        if let Some(item) = self.plan.get(self.step as usize) {
+            self.step += 1;
+            log::trace!("step: {}", self.step);
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    fn gen_interactions(&mut self, count: u64) {
+        let len = self.plan.len() as u64;
+
+        let rng = rng();
+
+        for i in 1..=count {
+            let interaction_type = if (i + len).is_multiple_of(2) {
+                InteractionType::Sleep
+            } else {
+                InteractionType::SlowListTransactions
+            };
+            log::trace!(
+                "gen_interactions: generating interaction {i}/{count} ({}) interaction_type={interaction_type:?}",
+                i + len
+            );
+            match interaction_type {
+                InteractionType::Sleep => {
+                    self.add_interaction(Interaction::Sleep(Duration::from_millis(
+                        rng.gen_range(0..10_000) * step_multiplier(),
+                    )));
+                }
+                InteractionType::SlowListTransactions => {
+                    self.add_interaction(Interaction::SlowListTransactions(Duration::from_millis(
+                        rng.gen_range(0..300_000) * step_multiplier(),
+                    )));
+                }
+            }
+        }
+        drop(rng);
+    }
+
+    fn add_interaction(&mut self, interaction: Interaction) {
+        log::trace!("add_interaction: adding interaction interaction={interaction:?}");
+        match &interaction {
+            Interaction::Sleep(..) | Interaction::SlowListTransactions(..) => {}
+        }
+        self.plan.push(interaction);
+    }
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Budget {
+    max: u64,
+    current: u64,
+    peak: u64,
+    step_attempts: u64,
+    peak_step_attempts: u64,
+}
+
+thread_local! {
+    static BUDGET: RefCell<Budget> = RefCell::new(Budget::default());
+}
+
+/// Resets the connection tracking for a new run with the given budget of
+/// concurrent connections.
+pub fn reset(budget: u64) {
+    BUDGET.with_borrow_mut(|x| {
+        *x = Budget {
+            max: budget,
+            ..Budget::default()
+        };
+    });
+}
+
+/// The most connections that were open at the same time in this run
+#[must_use]
+pub fn peak() -> u64 {
+    BUDGET.with_borrow(|x| x.peak)
+}
+
+/// Tracks a connection that `client` just opened until the returned guard is
+/// dropped, which should happen alongside the connection's stream.
+///
+/// # Panics
+///
+/// * If opening the connection exceeded the run's connection budget. Failing
+///   here names the offending client, instead of the harness producing an
+///   opaque connect error much later in the run
+#[must_use]
+pub fn track(client: &str) -> ConnectionGuard {
+    BUDGET.with_borrow_mut(|x| {
+        assert!(
+            x.current < x.max,
+            "[{client}] opening a connection would exceed the run's budget of {} concurrent connections (peak={})",
+            x.max,
+            x.peak,
+        );
+
+        x.current += 1;
+        x.peak = x.peak.max(x.current);
+    });
+
+    ConnectionGuard
+}
+
+/// Records a connect attempt in the current step, whether or not it succeeds.
+pub fn attempt() {
+    BUDGET.with_borrow_mut(|x| x.step_attempts += 1);
+}
+
+/// Closes out the current step's connect attempts.
+pub fn end_step() {
+    BUDGET.with_borrow_mut(|x| {
+        x.peak_step_attempts = x.peak_step_attempts.max(x.step_attempts);
+        x.step_attempts = 0;
+    });
+}
+
+/// The most connect attempts that were made within a single step in this run
+#[must_use]
+pub fn peak_attempts_per_step() -> u64 {
+    BUDGET.with_borrow(|x| x.peak_step_attempts.max(x.step_attempts))
+}
+
+/// Checks the peak number of connect attempts within a single step against
+/// `SIMULATOR_MAX_CONNECT_ATTEMPTS_PER_STEP`, if set.
+///
+/// Clients retrying in lockstep against a bounced server show up here as a
+/// spike.
+///
+/// # Panics
+///
+/// * If the peak exceeded the threshold
+/// * If `SIMULATOR_MAX_CONNECT_ATTEMPTS_PER_STEP` is not a valid integer
+pub fn check_attempts() {
+    let peak = peak_attempts_per_step();
+
+    log::info!("peak connect attempts per step: {peak}");
+
+    let Some(threshold) = std::env::var("SIMULATOR_MAX_CONNECT_ATTEMPTS_PER_STEP")
+        .ok()
+        .map(|x| x.parse::<u64>().unwrap())
+    else {
+        return;
+    };
+
+    assert!(
+        peak <= threshold,
+        "{peak} connect attempts were made within a single step (threshold={threshold})",
+    );
+}
+
+pub struct ConnectionGuard;
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        BUDGET.with_borrow_mut(|x| x.current = x.current.saturating_sub(1));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use crate::timing;
+
+/// The targeted network disruptions of a single client
+#[derive(Debug, Clone, Default)]
+pub struct ClientDisruption {
+    /// How many failures were requested for the client
+    pub requested: u64,
+    /// How many reads/writes were failed on the client's connections
+    pub delivered: u64,
+    /// Failures that were requested but not delivered yet
+    pub pending: u64,
+    /// The step at which the oldest pending failure was requested
+    pending_since: Option<u64>,
+    /// How many connections the client opened while failures were pending
+    connects_while_pending: u64,
+}
+
+thread_local! {
+    static CLIENTS: RefCell<BTreeMap<String, ClientDisruption>> = const { RefCell::new(BTreeMap::new()) };
+    /// The client each connection's local address belongs to
+    static CONNECTIONS: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+pub fn reset() {
+    CLIENTS.with_borrow_mut(BTreeMap::clear);
+    CONNECTIONS.with_borrow_mut(BTreeMap::clear);
+}
+
+/// Registers a client whose reads and writes consult the registry, so that
+/// it can be targeted.
+pub fn register(client: &str) {
+    CLIENTS.with_borrow_mut(|x| {
+        x.entry(client.to_string()).or_default();
+    });
+}
+
+/// Attributes the connection with the local address `addr` to `client`.
+pub fn connected(client: &str, addr: &str) {
+    CONNECTIONS.with_borrow_mut(|x| {
+        x.insert(addr.to_string(), client.to_string());
+    });
+    CLIENTS.with_borrow_mut(|x| {
+        if let Some(disruption) = x.get_mut(client).filter(|x| x.pending > 0) {
+            disruption.connects_while_pending += 1;
+        }
+    });
+}
+
+/// Makes the next `failures` reads/writes on `client`'s connections fail with
+/// `ErrorKind::ConnectionReset`, leaving every other client alone.
+///
+/// Returns `false` if the client never registered, e.g. because
+/// `SIMULATOR_CLIENTS` filtered it out.
+#[must_use]
+pub fn disrupt(client: &str, failures: u64) -> bool {
+    let step = timing::step();
+
+    CLIENTS.with_borrow_mut(|x| {
+        let Some(disruption) = x.get_mut(client) else {
+            log::debug!("disrupt: skipping unregistered client '{client}'");
+            return false;
+        };
+
+        log::debug!("disrupt: failing the next {failures} reads/writes of '{client}'");
+        disruption.requested += failures;
+        disruption.pending += failures;
+        disruption.pending_since.get_or_insert(step);
+        true
+    })
+}
+
+/// Consulted before each read/write on the connection with the local address
+/// `addr`, failing it if its client has a pending disruption.
+///
+/// # Errors
+///
+/// * If the connection's client has a pending disruption
+///
+/// # Panics
+///
+/// * If the connection was never attributed to a client, since a disruption
+///   could then never be delivered to it
+pub fn take(addr: &str) -> Result<(), std::io::Error> {
+    let client = CONNECTIONS.with_borrow(|x| x.get(addr).cloned());
+    let Some(client) = client else {
+        panic!("test infrastructure error: connection {addr} was never attributed to a client");
+    };
+
+    CLIENTS.with_borrow_mut(|x| {
+        let disruption = x.entry(client.clone()).or_default();
+
+        if disruption.pending == 0 {
+            return Ok(());
+        }
+
+        disruption.pending -= 1;
+        disruption.delivered += 1;
+        if disruption.pending == 0 {
+            disruption.pending_since = None;
+            disruption.connects_while_pending = 0;
+        }
+
+        log::debug!(
+            "take: failing a read/write of '{client}' on {addr} ({} pending)",
+            disruption.pending
+        );
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            format!("simulated disruption of '{client}'"),
+        ))
+    })
+}
+
+#[must_use]
+pub fn snapshot() -> BTreeMap<String, ClientDisruption> {
+    CLIENTS.with_borrow(Clone::clone)
+}
+
+/// The failures requested and delivered for each disrupted client, to be
+/// included in the end-of-run summary.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    snapshot()
+        .into_iter()
+        .filter(|(_, x)| x.requested > 0)
+        .flat_map(|(client, x)| {
+            [
+                (
+                    format!("disruptions_requested.{client}"),
+                    x.requested.to_string(),
+                ),
+                (
+                    format!("disruptions_delivered.{client}"),
+                    x.delivered.to_string(),
+                ),
+            ]
+        })
+        .collect()
+}
+
+/// Fails the run as a test infrastructure error if a disruption wasn't
+/// delivered to the client it targeted even though that client kept connecting
+/// afterwards.
+///
+/// A single connection is tolerated since an interaction may connect and finish
+/// without reading or writing anything.
+///
+/// # Panics
+///
+/// * If a requested disruption went undelivered
+pub fn check() {
+    for (client, disruption) in snapshot() {
+        log::debug!("check: client '{client}' disruptions {disruption:?}");
+
+        let Some(since) = disruption.pending_since else {
+            continue;
+        };
+
+        assert!(
+            disruption.connects_while_pending < 2,
+            "test infrastructure error: {} disruptions requested for '{client}' at step {since} were never delivered, even though it opened {} connections since",
+            disruption.pending,
+            disruption.connects_while_pending,
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::{Duration, SystemTime};
+
+use simvar::switchy;
+
+/// Asserts that a condition becomes true within a window of simulated time.
+///
+/// The condition is a future returning `Result<(), String>`, with the error
+/// describing why the evaluation failed, such as an async block. The
+/// expression is evaluated afresh every `poll_every` until it succeeds, and
+/// the macro evaluates to the number of evaluations it took. Since it's
+/// expanded in place rather than captured in a closure, it can borrow from
+/// the caller like any other code in the enclosing async fn.
+///
+/// The durations are taken as they are, so callers scale them by the step
+/// multiplier like the rest of the simulator's waits.
+///
+/// # Panics
+///
+/// * If the condition still fails once the window elapsed, with `context`,
+///   the simulated time spent, the number of evaluations, and the last
+///   evaluation's error
+#[macro_export]
+macro_rules! assert_eventually {
+    (
+        $condition:expr,
+        within = $within:expr,
+        poll_every = $poll_every:expr,
+        context = $($context:tt)+
+    ) => {{
+        let mut eventually = $crate::eventually::Eventually::new($within, $poll_every);
+
+        loop {
+            match $condition.await {
+                Ok(()) => break eventually.succeeded(),
+                Err(last) => {
+                    if let Err(timeout) = eventually.failed(last).await {
+                        panic!("{}: {timeout}", format_args!($($context)+));
+                    }
+                }
+            }
+        }
+    }};
+}
+
+/// A condition that was still false once its window elapsed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "still failing after {polls} polls over {elapsed:?} of simulated time (within={within:?}): {last}"
+)]
+pub struct Timeout {
+    pub within: Duration,
+    /// The simulated time spent polling
+    pub elapsed: Duration,
+    pub polls: u32,
+    /// Why the last evaluation failed
+    pub last: String,
+}
+
+/// The polling state of a condition that's expected to become true within a
+/// window of simulated time
+#[derive(Debug, Clone, Copy)]
+pub struct Eventually {
+    within: Duration,
+    poll_every: Duration,
+    start: SystemTime,
+    polls: u32,
+}
+
+impl Eventually {
+    /// Starts the window now
+    #[must_use]
+    pub fn new(within: Duration, poll_every: Duration) -> Self {
+        Self {
+            within,
+            poll_every,
+            start: switchy::time::now(),
+            polls: 0,
+        }
+    }
+
+    /// Records a passing evaluation, returning the number of evaluations it
+    /// took.
+    pub const fn succeeded(&mut self) -> u32 {
+        self.polls += 1;
+        self.polls
+    }
+
+    /// Records a failing evaluation and waits until the next one is due.
+    ///
+    /// The last wait is shortened so that the condition is evaluated for the
+    /// last time `within` after the first.
+    ///
+    /// # Errors
+    ///
+    /// * If the window already elapsed
+    pub async fn failed(&mut self, last: String) -> Result<(), Timeout> {
+        self.polls += 1;
+        let polls = self.polls;
+
+        let elapsed = switchy::time::now()
+            .duration_since(self.start)
+            .unwrap_or_default();
+
+        if elapsed >= self.within {
+            return Err(Timeout {
+                within: self.within,
+                elapsed,
+                polls,
+                last,
+            });
+        }
+
+        log::trace!("eventually: poll {polls} failed after {elapsed:?}: {last}");
+        switchy::unsync::time::sleep(self.poll_every.min(self.within.saturating_sub(elapsed)))
+            .await;
+
+        Ok(())
+    }
+}
+
+/// Evaluates the future returned by `condition` every `poll_every` of
+/// simulated time until it succeeds, returning the number of evaluations it
+/// took.
+///
+/// # Errors
+///
+/// * If the condition is still failing once `within` elapsed
+pub async fn eventually<F: Future<Output = Result<(), String>>>(
+    within: Duration,
+    poll_every: Duration,
+    mut condition: impl FnMut() -> F,
+) -> Result<u32, Timeout> {
+    let mut eventually = Eventually::new(within, poll_every);
+
+    loop {
+        match condition().await {
+            Ok(()) => return Ok(eventually.succeeded()),
+            Err(last) => eventually.failed(last).await?,
+        }
+    }
+}
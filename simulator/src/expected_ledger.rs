@@ -0,0 +1,228 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use dst_demo_server::bank::{CreateTime, Transaction, TransactionId};
+use rust_decimal::Decimal;
+
+/// A transaction the server acknowledged creating, and the client it was
+/// acknowledged to
+#[derive(Debug, Clone)]
+struct Acknowledged {
+    client: String,
+    amount: Decimal,
+    created_at: CreateTime,
+}
+
+/// Every write the clients made in a run, as far as they know.
+///
+/// A write whose response the client never read (e.g. because the connection
+/// dropped) is in doubt, and may or may not be in the ledger. A write the
+/// server explicitly rejected is forgotten.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedLedger {
+    acknowledged: BTreeMap<TransactionId, Acknowledged>,
+    in_doubt_creates: Vec<Decimal>,
+    /// The ids of the transactions voided without the void being
+    /// acknowledged. The bankers never read a void's response, so every void
+    /// is in doubt.
+    in_doubt_voids: Vec<TransactionId>,
+}
+
+thread_local! {
+    static EXPECTED: RefCell<ExpectedLedger> = const {
+        RefCell::new(ExpectedLedger {
+            acknowledged: BTreeMap::new(),
+            in_doubt_creates: vec![],
+            in_doubt_voids: vec![],
+        })
+    };
+}
+
+pub fn reset() {
+    EXPECTED.with_borrow_mut(|x| *x = ExpectedLedger::default());
+}
+
+/// Records a create that's about to be sent, which is in doubt until it's
+/// acknowledged or rejected.
+pub fn sent_create(amount: Decimal) {
+    EXPECTED.with_borrow_mut(|x| x.in_doubt_creates.push(amount));
+}
+
+/// Records that the server rejected a create sent with [`sent_create`]
+/// without applying it.
+pub fn rejected_create(amount: Decimal) {
+    EXPECTED.with_borrow_mut(|x| {
+        take_one(&mut x.in_doubt_creates, |x| *x == amount);
+    });
+}
+
+/// Records that the server acknowledged creating the transaction.
+///
+/// # Panics
+///
+/// * If the transaction's id was already acknowledged with a different amount,
+///   i.e. the server handed the same id out twice
+pub fn acknowledged_create(client: &str, transaction: &Transaction) {
+    EXPECTED.with_borrow_mut(|x| {
+        take_one(&mut x.in_doubt_creates, |x| *x == transaction.amount);
+
+        if let Some(existing) = x.acknowledged.get(&transaction.id) {
+            assert!(
+                existing.amount == transaction.amount,
+                "transaction id={} was acknowledged to {} with amount=${} and to {client} with amount=${}",
+                transaction.id,
+                existing.client,
+                existing.amount,
+                transaction.amount,
+            );
+            return;
+        }
+
+        x.acknowledged.insert(
+            transaction.id,
+            Acknowledged {
+                client: client.to_string(),
+                amount: transaction.amount,
+                created_at: transaction.created_at,
+            },
+        );
+    });
+}
+
+/// Records the transactions the ledger was seeded with before the run
+/// started, as if they had been acknowledged to a `seed` client.
+pub fn seeded(transactions: &[Transaction]) {
+    EXPECTED.with_borrow_mut(|x| {
+        x.acknowledged
+            .extend(transactions.iter().map(|transaction| {
+                (
+                    transaction.id,
+                    Acknowledged {
+                        client: "seed".to_string(),
+                        amount: transaction.amount,
+                        created_at: transaction.created_at,
+                    },
+                )
+            }));
+    });
+}
+
+/// Records a void that's about to be sent.
+pub fn sent_void(id: TransactionId) {
+    EXPECTED.with_borrow_mut(|x| x.in_doubt_voids.push(id));
+}
+
+/// When the acknowledged transaction `id` was created, or `None` if it wasn't
+/// acknowledged to any client.
+#[must_use]
+pub fn acknowledged_created_at(id: TransactionId) -> Option<CreateTime> {
+    EXPECTED.with_borrow(|x| x.acknowledged.get(&id).map(|x| x.created_at))
+}
+
+#[must_use]
+pub fn snapshot() -> ExpectedLedger {
+    EXPECTED.with_borrow(Clone::clone)
+}
+
+/// Removes the first value matching the predicate, returning whether there
+/// was one.
+fn take_one<T>(values: &mut Vec<T>, predicate: impl Fn(&T) -> bool) -> bool {
+    let Some(index) = values.iter().position(predicate) else {
+        return false;
+    };
+    values.swap_remove(index);
+    true
+}
+
+/// Compares a full listing of the ledger against the writes the clients
+/// made, returning a readable diff if they don't match.
+///
+/// `before` and `after` are snapshots taken before the listing was requested
+/// and after it was received. Every write acknowledged before the listing
+/// must be in it exactly once with its amount. Any other transaction in it
+/// has to be explained by a write acknowledged while it was being listed, or
+/// by a write that's still in doubt.
+#[must_use]
+pub fn diff(
+    before: &ExpectedLedger,
+    after: &ExpectedLedger,
+    ledger: &[Transaction],
+) -> Option<String> {
+    let mut by_id = BTreeMap::new();
+    let mut duplicated = vec![];
+
+    for transaction in ledger {
+        if by_id.insert(transaction.id, transaction).is_some() {
+            duplicated.push(format!("id={}", transaction.id));
+        }
+    }
+
+    let mut missing = vec![];
+    let mut mismatched = vec![];
+
+    for (id, acknowledged) in &before.acknowledged {
+        match by_id.get(id) {
+            None => missing.push(format!(
+                "id={id} amount=${} acknowledged to {}",
+                acknowledged.amount, acknowledged.client,
+            )),
+            Some(transaction) if transaction.amount != acknowledged.amount => {
+                mismatched.push(format!(
+                    "id={id} amount=${} acknowledged to {} as amount=${}",
+                    transaction.amount, acknowledged.client, acknowledged.amount,
+                ));
+            }
+            Some(..) => {}
+        }
+    }
+
+    let mut in_doubt_creates = after.in_doubt_creates.clone();
+    let mut in_doubt_voids = after.in_doubt_voids.clone();
+    let mut unexpected = vec![];
+
+    for transaction in by_id
+        .values()
+        .filter(|x| !before.acknowledged.contains_key(&x.id))
+    {
+        if let Some(acknowledged) = after.acknowledged.get(&transaction.id) {
+            if acknowledged.amount != transaction.amount {
+                mismatched.push(format!(
+                    "id={} amount=${} acknowledged to {} as amount=${}",
+                    transaction.id, transaction.amount, acknowledged.client, acknowledged.amount,
+                ));
+            }
+            continue;
+        }
+
+        // A void creates a transaction that negates the one it voids
+        let explained = take_one(&mut in_doubt_creates, |x| *x == transaction.amount)
+            || take_one(&mut in_doubt_voids, |id| {
+                by_id
+                    .get(id)
+                    .is_some_and(|x| x.amount == -transaction.amount)
+            });
+
+        if !explained {
+            unexpected.push(format!(
+                "id={} amount=${}",
+                transaction.id, transaction.amount
+            ));
+        }
+    }
+
+    let sections = [
+        ("missing", missing),
+        ("unexpected", unexpected),
+        ("amount mismatches", mismatched),
+        ("duplicated", duplicated),
+    ]
+    .into_iter()
+    .filter(|(_, lines)| !lines.is_empty())
+    .map(|(name, lines)| format!("{name} ({}):\n  {}", lines.len(), lines.join("\n  ")))
+    .collect::<Vec<_>>();
+
+    if sections.is_empty() {
+        return None;
+    }
+
+    Some(sections.join("\n"))
+}
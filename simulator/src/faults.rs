@@ -0,0 +1,239 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    sync::{LazyLock, Once},
+    time::Duration,
+};
+
+use strum::AsRefStr;
+
+use crate::timing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum FaultKind {
+    /// The host was restarted
+    Bounce,
+    /// The host was killed mid-await with no chance to clean up, then
+    /// restarted
+    Crash,
+}
+
+impl std::fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// A fault that was applied to a host during the run
+#[derive(Debug, Clone)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub host: String,
+    /// The elapsed simulated time at which the fault was applied
+    pub at: Duration,
+}
+
+/// Fault injection being paused or resumed during the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GateEvent {
+    pub paused: bool,
+    /// The simulation step at which fault injection was paused or resumed
+    pub step: u64,
+    /// The elapsed simulated time at which fault injection was paused or
+    /// resumed
+    pub at: Duration,
+}
+
+/// An entry in the run's applied-fault log
+#[derive(Debug, Clone)]
+pub enum LogEntry {
+    Fault(Fault),
+    Gate(GateEvent),
+}
+
+static PAUSE_ON_FAILURE: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("SIMULATOR_PAUSE_FAULTS_ON_FAILURE").is_ok_and(|x| x == "1" || x == "true")
+});
+
+thread_local! {
+    static APPLIED: RefCell<Vec<LogEntry>> = const { RefCell::new(vec![]) };
+    static PENDING_RESTART: RefCell<BTreeMap<String, FaultKind>> = const { RefCell::new(BTreeMap::new()) };
+    static PAUSED: Cell<bool> = const { Cell::new(false) };
+    static SKIPPED: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn reset() {
+    APPLIED.with_borrow_mut(Vec::clear);
+    PENDING_RESTART.with_borrow_mut(BTreeMap::clear);
+    PAUSED.set(false);
+    SKIPPED.set(0);
+}
+
+/// Records a fault applied to a host so that clients can branch their
+/// invariants on which faults occurred, and so the host can tell why it's
+/// being restarted.
+pub fn record(kind: FaultKind, host: &str) {
+    let fault = Fault {
+        kind,
+        host: host.to_string(),
+        at: timing::elapsed(),
+    };
+    log::debug!("faults: applied {fault:?}");
+
+    APPLIED.with_borrow_mut(|x| x.push(LogEntry::Fault(fault)));
+    PENDING_RESTART.with_borrow_mut(|x| x.insert(host.to_string(), kind));
+}
+
+/// The faults applied so far in this run, in the order they were applied.
+#[must_use]
+pub fn applied() -> Vec<Fault> {
+    APPLIED.with_borrow(|x| {
+        x.iter()
+            .filter_map(|x| match x {
+                LogEntry::Fault(fault) => Some(fault.clone()),
+                LogEntry::Gate(..) => None,
+            })
+            .collect()
+    })
+}
+
+/// The faults applied so far in this run, interleaved with the times fault
+/// injection was paused and resumed.
+#[must_use]
+pub fn log() -> Vec<LogEntry> {
+    APPLIED.with_borrow(Clone::clone)
+}
+
+/// The most recent fault of the given kind applied to `host`.
+#[must_use]
+pub fn last(kind: FaultKind, host: &str) -> Option<Fault> {
+    applied()
+        .into_iter()
+        .rev()
+        .find(|x| x.kind == kind && x.host == host)
+}
+
+/// Takes the fault that caused `host` to be restarted, if any. Returns `None`
+/// for the host's initial start.
+#[must_use]
+pub fn take_pending_restart(host: &str) -> Option<FaultKind> {
+    PENDING_RESTART.with_borrow_mut(|x| x.remove(host))
+}
+
+fn set_paused(paused: bool) {
+    if PAUSED.replace(paused) == paused {
+        return;
+    }
+
+    let event = GateEvent {
+        paused,
+        step: timing::step(),
+        at: timing::elapsed(),
+    };
+    log::info!("faults: {event:?}");
+
+    APPLIED.with_borrow_mut(|x| x.push(LogEntry::Gate(event)));
+}
+
+/// Stops the fault injector from injecting faults until [`resume`] is called.
+/// Faults it would have injected in the meantime are skipped, not deferred.
+pub fn pause() {
+    set_paused(true);
+}
+
+/// Lets the fault injector inject faults again after [`pause`].
+pub fn resume() {
+    set_paused(false);
+}
+
+#[must_use]
+pub fn is_paused() -> bool {
+    PAUSED.get()
+}
+
+/// Counts a fault the fault injector skipped because injection was paused.
+pub fn skip() {
+    SKIPPED.set(SKIPPED.get() + 1);
+}
+
+/// The number of faults skipped so far in this run because injection was
+/// paused
+#[must_use]
+pub fn skipped() -> u64 {
+    SKIPPED.get()
+}
+
+/// Pauses fault injection as soon as an invariant fails, if
+/// `SIMULATOR_PAUSE_FAULTS_ON_FAILURE` is enabled.
+///
+/// The rest of the run then captures a clean recovery. The panic hook pauses
+/// the panicking run's thread, and is only installed once per process.
+pub fn pause_on_failure() {
+    static INSTALL: Once = Once::new();
+
+    if !*PAUSE_ON_FAILURE {
+        return;
+    }
+
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // The panic may have happened while the fault log was borrowed
+            if APPLIED
+                .try_with(|x| x.try_borrow_mut().is_ok())
+                .unwrap_or(false)
+            {
+                pause();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// The steps between which no faults were injected because injection was
+/// paused, as `start-end` (or `start-` if it was still paused at the end)
+#[must_use]
+pub fn paused_windows() -> Vec<String> {
+    let mut windows = vec![];
+    let mut start = None;
+
+    for entry in log() {
+        match entry {
+            LogEntry::Gate(GateEvent {
+                paused: true, step, ..
+            }) => start = Some(step),
+            LogEntry::Gate(GateEvent {
+                paused: false,
+                step,
+                ..
+            }) => {
+                if let Some(start) = start.take() {
+                    windows.push(format!("{start}-{step}"));
+                }
+            }
+            LogEntry::Fault(..) => {}
+        }
+    }
+    if let Some(start) = start {
+        windows.push(format!("{start}-"));
+    }
+
+    windows
+}
+
+/// The faults skipped while injection was paused and the paused windows, to
+/// be included in the end-of-run summary.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    vec![
+        (
+            "faults_skipped_while_paused".to_string(),
+            skipped().to_string(),
+        ),
+        (
+            "faults_paused_windows".to_string(),
+            paused_windows().join(","),
+        ),
+    ]
+}
@@ -0,0 +1,90 @@
+use simvar::{
+    Sim,
+    switchy::{
+        self,
+        tcp::{GenericTcpListener as _, GenericTcpStream as _, TcpListener, TcpStream},
+        unsync::io::{AsyncReadExt as _, AsyncWriteExt as _},
+    },
+    utils::run_until_simulation_cancelled,
+};
+
+pub const HOST: &str = "dst_demo_metrics";
+pub const PORT: u16 = 9090;
+
+/// Starts a minimal HTTP/1.1 host serving the bank server's counters at
+/// `/metrics` in the Prometheus text exposition format.
+pub fn start(sim: &mut impl Sim) {
+    let addr = format!("0.0.0.0:{PORT}");
+
+    sim.host(HOST, move || {
+        let addr = addr.clone();
+        async move {
+            log::debug!("starting 'dst_demo' metrics server");
+            run_until_simulation_cancelled(serve(&addr))
+                .await
+                .transpose()
+                .map_err(|x| Box::new(x) as Box<dyn std::error::Error + Send>)?;
+            log::debug!("finished 'dst_demo' metrics server");
+
+            Ok(())
+        }
+    });
+}
+
+async fn serve(addr: &str) -> Result<(), std::io::Error> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    while let Ok((stream, addr)) = listener.accept().await {
+        switchy::unsync::task::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                log::debug!("[{addr}] metrics: failed to handle request: {e:?}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream) -> Result<(), std::io::Error> {
+    let (mut read, mut write) = stream.into_split();
+    let mut request = vec![];
+    let mut buf = [0_u8; 1024];
+
+    while !request.windows(4).any(|x| x == b"\r\n\r\n") {
+        let count = read.read(&mut buf).await?;
+        if count == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..count]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, body) = if method == "GET" && path == "/metrics" {
+        ("200 OK", dst_demo_server::metrics::render())
+    } else {
+        ("404 Not Found", String::new())
+    };
+
+    log::debug!("metrics: {method} {path} -> {status}");
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+
+    write.write_all(response.as_bytes()).await?;
+    write.flush().await?;
+
+    Ok(())
+}
@@ -1,26 +1,148 @@
-use simvar::{Sim, utils::run_until_simulation_cancelled};
+use std::{cell::RefCell, collections::BTreeMap, path::PathBuf};
+
+use dst_demo_server::{
+    SERVER_CANCELLATION_TOKEN, ServerAction,
+    bank::{Bank, LocalBank},
+    config::{ServerConfig, default_db_path},
+    protocol::ProtocolVersion,
+};
+use simvar::{Sim, switchy::tcp::TcpListener, utils::run_until_simulation_cancelled};
+
+use crate::{
+    faults::{self, FaultKind},
+    replication, server_config, topology,
+};
 
 pub const HOST: &str = "dst_demo_server";
-pub const PORT: u16 = 1234;
+pub const REPLICA_HOST: &str = "dst_demo_replica";
+
+/// The identity prefix the hosts require for the [`ADMIN_ACTIONS`]
+pub const ADMIN_IDENTITY_PREFIX: &str = "admin";
+
+/// The actions only connections identified as an admin may perform
+pub const ADMIN_ACTIONS: &[ServerAction] = &[
+    ServerAction::Exit,
+    ServerAction::ImportLedger,
+    ServerAction::Promote,
+    ServerAction::Demote,
+    ServerAction::ReloadConfig,
+];
+
+thread_local! {
+    static STOPS: RefCell<BTreeMap<&'static str, u64>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Resets the count of server stops for a new run
+pub fn reset_stops() {
+    STOPS.with_borrow_mut(BTreeMap::clear);
+}
+
+/// How many times `host`'s server stopped on its own this run, e.g. after an
+/// `EXIT`. Crashes and bounces don't count, since they tear the host down
+/// instead of letting its server return.
+#[must_use]
+pub fn stops(host: &str) -> u64 {
+    STOPS.with_borrow(|x| x.get(host).copied().unwrap_or_default())
+}
+
+fn authorization() -> BTreeMap<ServerAction, String> {
+    ADMIN_ACTIONS
+        .iter()
+        .map(|x| (*x, ADMIN_IDENTITY_PREFIX.to_string()))
+        .collect()
+}
+
+/// The file `host` persists its ledger to
+#[must_use]
+pub fn db_path(host: &str) -> PathBuf {
+    default_db_path().with_file_name(format!("{host}.db"))
+}
 
+/// Starts both server hosts. Each persists its ledger to its own file,
+/// listens on every port in the run's topology, and replicates to the other
+/// while it's the primary.
 pub fn start(sim: &mut impl Sim) {
-    let host = "0.0.0.0";
-    let addr = format!("{host}:{PORT}");
+    start_with_bank(sim, |config| LocalBank::open(&config.db_path));
+}
+
+/// Starts both server hosts like [`start`], serving the bank `open_bank`
+/// returns for the host's config every time a host (re)starts.
+pub fn start_with_bank<B: Bank + Clone + 'static>(
+    sim: &mut impl Sim,
+    open_bank: fn(&ServerConfig) -> Result<B, std::io::Error>,
+) {
+    for host in replication::HOSTS {
+        start_host(sim, host, open_bank);
+    }
+}
+
+/// Binds each of `addrs` and serves the bank `open_bank` returns on them
+async fn serve<B: Bank + Clone + 'static>(
+    addrs: Vec<String>,
+    config: ServerConfig,
+    open_bank: fn(&ServerConfig) -> Result<B, std::io::Error>,
+) -> Result<(), dst_demo_server::Error> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in &addrs {
+        listeners.push(TcpListener::bind(addr).await?);
+        log::info!("Server listening on {addr}");
+    }
+
+    let bank = open_bank(&config)?;
+
+    dst_demo_server::serve_with_config(listeners, bank, config, SERVER_CANCELLATION_TOKEN.clone())
+        .await
+}
+
+fn start_host<B: Bank + Clone + 'static>(
+    sim: &mut impl Sim,
+    host: &'static str,
+    open_bank: fn(&ServerConfig) -> Result<B, std::io::Error>,
+) {
+    let addrs = topology::current()
+        .listeners
+        .iter()
+        .map(|x| format!("0.0.0.0:{}", x.port))
+        .collect::<Vec<_>>();
+
+    sim.host(host, move || {
+        let addrs = addrs.clone();
+
+        match faults::take_pending_restart(host) {
+            Some(FaultKind::Crash) => {
+                log::info!("restarting '{host}' server after a crash");
+            }
+            Some(FaultKind::Bounce) => {
+                log::info!("restarting '{host}' server after a graceful bounce");
+            }
+            None => {}
+        }
+
+        // a restarted server comes back with the config from the
+        // environment, dropping anything that was reloaded into it
+        server_config::restarted(host);
+
+        let config = ServerConfig {
+            db_path: db_path(host),
+            role: replication::role(host),
+            replica_addr: Some(replication::addr(
+                replication::peer(host),
+                ProtocolVersion::V2,
+            )),
+            authorization: authorization(),
+            ..server_config::from_env()
+        };
 
-    sim.host(HOST, move || {
-        let addr = addr.clone();
         async move {
-            log::debug!("starting 'dst_demo' server");
-            run_until_simulation_cancelled(dst_demo_server::run(&addr))
-                .await
-                .transpose()
-                .map_err(|x| {
-                    Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        x.to_string(),
-                    )) as Box<dyn std::error::Error + Send>
-                })?;
-            log::debug!("finished 'dst_demo' server");
+            log::debug!("starting '{host}' server as {}", config.role);
+            let result = run_until_simulation_cancelled(serve(addrs, config, open_bank)).await;
+            if result.is_some() {
+                STOPS.with_borrow_mut(|x| *x.entry(host).or_default() += 1);
+            }
+            result.transpose().map_err(|x| {
+                Box::new(std::io::Error::other(x.to_string())) as Box<dyn std::error::Error + Send>
+            })?;
+            log::debug!("finished '{host}' server");
 
             Ok(())
         }
@@ -1,9 +1,8 @@
 use std::collections::BTreeMap;
 
-use simvar::switchy::{
-    tcp::TcpStream,
-    unsync::io::{AsyncReadExt as _, AsyncWriteExt as _},
-};
+use simvar::switchy::unsync::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+use crate::capture::CapturedStream;
 
 pub struct HttpResponse {
     pub status_code: u16,
@@ -34,10 +33,10 @@ pub fn headers_contains_in_order(
 
 /// # Errors
 ///
-/// * If fails to read/write any bytes from/to the `TcpStream`
+/// * If fails to read/write any bytes from/to the stream
 pub async fn http_request(
     method: &str,
-    stream: &mut TcpStream,
+    stream: &mut CapturedStream,
     path: &str,
 ) -> std::io::Result<String> {
     let host = "127.0.0.1";
@@ -115,18 +114,17 @@ pub fn parse_http_response(raw_response: &str) -> Result<HttpResponse, &'static
         } else {
             None
         }
-    }) {
-        if let Ok(content_length) = content_length_str.parse::<usize>() {
-            // Ensure we don't read beyond the specified content length
-            // This is a simplification; actual HTTP might have complex encoding
-            if body.len() >= content_length {
-                let truncated_body = &body[..content_length];
-                return Ok(HttpResponse {
-                    status_code,
-                    headers,
-                    body: truncated_body.to_string(),
-                });
-            }
+    }) && let Ok(content_length) = content_length_str.parse::<usize>()
+    {
+        // Ensure we don't read beyond the specified content length
+        // This is a simplification; actual HTTP might have complex encoding
+        if body.len() >= content_length {
+            let truncated_body = &body[..content_length];
+            return Ok(HttpResponse {
+                status_code,
+                headers,
+                body: truncated_body.to_string(),
+            });
         }
     }
 
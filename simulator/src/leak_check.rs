@@ -0,0 +1,73 @@
+use dst_demo_server::{connections, tasks};
+
+/// Whether `SIMULATOR_TASK_LEAK_CHECK` was enabled for this process.
+#[must_use]
+pub fn enabled() -> bool {
+    std::env::var("SIMULATOR_TASK_LEAK_CHECK").is_ok_and(|x| x == "1" || x == "true")
+}
+
+/// Resets the server task and connection registries at the start of a run.
+///
+/// The registries are process-wide, so the counts are only attributable to a
+/// single run when runs aren't executed in parallel.
+pub fn reset() {
+    tasks::reset();
+    connections::reset();
+}
+
+/// Checks how many server tasks and connections are still alive at the end of
+/// the run.
+///
+/// The threshold defaults to `expected`, the number of connections the
+/// clients can legitimately still have when the run ends, overridable
+/// through `SIMULATOR_TASK_LEAK_THRESHOLD`. Any connection beyond that wasn't
+/// removed from the registry when its handler exited.
+///
+/// # Panics
+///
+/// * If more tasks than the threshold are still alive
+/// * If more connections than the threshold are still registered
+/// * If `SIMULATOR_TASK_LEAK_THRESHOLD` is not a valid integer
+pub fn check(expected: u64) {
+    let stats = tasks::stats();
+    let alive = stats.alive.len() as u64;
+    let connections = connections::list();
+
+    log::info!(
+        "task stats: spawned={} completed={} alive={alive} connections={}",
+        stats.spawned,
+        stats.completed,
+        connections.len(),
+    );
+
+    if !enabled() {
+        return;
+    }
+
+    let threshold = std::env::var("SIMULATOR_TASK_LEAK_THRESHOLD")
+        .ok()
+        .map_or(expected, |x| x.parse::<u64>().unwrap());
+
+    assert!(
+        alive <= threshold,
+        "\
+        {alive} server tasks were still alive at the end of the run (threshold={threshold}):\n\
+        {}\
+        ",
+        stats.alive.join("\n"),
+    );
+
+    assert!(
+        connections.len() as u64 <= threshold,
+        "\
+        {} server connections were still registered at the end of the run (threshold={threshold}):\n\
+        {}\
+        ",
+        connections.len(),
+        connections
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+}
@@ -3,28 +3,56 @@
 #![allow(clippy::multiple_crate_versions)]
 
 use std::{
-    collections::VecDeque,
+    collections::BTreeMap,
     pin::Pin,
-    string::FromUtf8Error,
-    sync::{Arc, LazyLock, Mutex, RwLock},
+    sync::{LazyLock, RwLock},
 };
 
+use actions::Action;
+use dst_demo_server::{
+    framing::{self, FramingError},
+    message::{InvalidMessage, MessageBuffer, READ_CHUNK_SIZE},
+};
+use faults::FaultKind;
 use simvar::{
     Sim,
-    switchy::{random::rng, unsync::io::AsyncReadExt},
+    switchy::{random::rand::rand::Rng as _, unsync::io::AsyncReadExt},
 };
 
+pub mod actions;
+pub mod availability;
+pub mod backoff;
+pub mod cancel_safety;
+pub mod capture;
 pub mod client;
+pub mod connections;
+pub mod disruption;
+pub mod eventually;
+pub mod expected_ledger;
+pub mod faults;
 pub mod host;
 pub mod http;
-
-static ACTIONS: LazyLock<Arc<Mutex<VecDeque<Action>>>> =
-    LazyLock::new(|| Arc::new(Mutex::new(VecDeque::new())));
+pub mod leak_check;
+pub mod observability;
+pub mod progress;
+pub mod prometheus;
+pub mod replication;
+pub mod rng_trace;
+pub mod schedule;
+pub mod seed;
+pub mod server_config;
+pub mod shuffle;
+pub mod stats;
+pub mod step_budget;
+pub mod timeouts;
+pub mod timing;
+pub mod topology;
+pub mod triage;
 
 static BANKER_COUNT: LazyLock<RwLock<Option<u64>>> = LazyLock::new(|| RwLock::new(None));
 
 fn gen_banker_count() -> u64 {
-    let value = rng().gen_range(1..30u64);
+    let value = rng_trace::rng_labeled("banker_count").gen_range(1..30u64);
 
     std::env::var("SIMULATOR_BANKER_COUNT")
         .ok()
@@ -53,40 +81,143 @@ pub fn banker_count() -> u64 {
     })
 }
 
+/// The batch-level labels from `SIMULATOR_LABELS`, formatted as
+/// `key=value,key2=value2`, used to group the results of parameter sweeps.
+///
+/// # Panics
+///
+/// * If a label is missing its `=`
+#[must_use]
+pub fn labels() -> BTreeMap<String, String> {
+    std::env::var("SIMULATOR_LABELS")
+        .ok()
+        .map(|x| {
+            x.split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(|label| {
+                    let (key, value) = label
+                        .split_once('=')
+                        .unwrap_or_else(|| panic!("Invalid label '{label}', expected key=value"));
+                    (key.trim().to_string(), value.trim().to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether every balance read on the server hosts doubles as a check that
+/// the cached balance hasn't diverged from the ledger.
+///
+/// Enabled unless `SIMULATOR_BALANCE_CHECK` is `0` or `false`, since the
+/// check walks the whole ledger on every read.
+#[must_use]
+pub fn balance_check() -> bool {
+    std::env::var("SIMULATOR_BALANCE_CHECK").map_or(true, |x| x != "0" && x != "false")
+}
+
+/// The comma-separated list of substrings from `SIMULATOR_CLIENTS`, if set
+#[must_use]
+pub fn clients_filter() -> Option<Vec<String>> {
+    std::env::var("SIMULATOR_CLIENTS").ok().map(|x| {
+        x.split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(ToString::to_string)
+            .collect()
+    })
+}
+
+/// Whether the client with the given name should be started, based on the
+/// `SIMULATOR_CLIENTS` filter. All clients are started if no filter is set.
+#[must_use]
+pub fn should_start(name: &str) -> bool {
+    let Some(filter) = clients_filter() else {
+        return true;
+    };
+
+    let start = filter.iter().any(|x| name.contains(x.as_str()));
+
+    if !start {
+        log::info!("skipping client '{name}' not matching SIMULATOR_CLIENTS={filter:?}");
+    }
+
+    start
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
     IO(#[from] std::io::Error),
     #[error(transparent)]
-    FromUtf8(#[from] FromUtf8Error),
+    InvalidMessage(#[from] InvalidMessage),
+    #[error(transparent)]
+    Framing(#[from] FramingError),
 }
 
-enum Action {
-    Bounce(String),
+/// Returns whether the bounce was queued, see [`actions::queue`].
+#[must_use]
+pub fn queue_bounce(producer: &str, host: impl Into<String>) -> bool {
+    actions::queue(producer, Action::Bounce(host.into()))
 }
 
-/// # Panics
-///
-/// * If the `ACTIONS` `Mutex` fails to lock
-pub fn queue_bounce(host: impl Into<String>) {
-    ACTIONS
-        .lock()
-        .unwrap()
-        .push_back(Action::Bounce(host.into()));
+/// Returns whether the crash was queued, see [`actions::queue`].
+#[must_use]
+pub fn queue_crash(producer: &str, host: impl Into<String>) -> bool {
+    actions::queue(producer, Action::Crash(host.into()))
 }
 
-/// # Panics
+/// Queues failing the next `failures` reads/writes of a single client's
+/// connections, without touching any host. Returns whether the disruption was
+/// queued, see [`actions::queue`].
+#[must_use]
+pub fn queue_disruption(producer: &str, client: impl Into<String>, failures: u64) -> bool {
+    actions::queue(
+        producer,
+        Action::Disrupt {
+            client: client.into(),
+            failures,
+        },
+    )
+}
+
+/// Applies the actions queued in earlier steps, in the order documented on
+/// [`actions::take`], recording each in the run's applied-action log.
 ///
-/// * If `ACTIONS` `Mutex` fails to lock
+/// At most the run's [`actions::Limits::max_per_step`] are applied, and the
+/// rest are carried forward to the following steps.
 pub fn handle_actions(sim: &mut impl Sim) {
-    let actions = ACTIONS.lock().unwrap().drain(..).collect::<Vec<_>>();
-    for action in actions {
-        match action {
-            Action::Bounce(host) => {
-                log::debug!("bouncing '{host}'");
-                sim.bounce(host);
-            }
+    for queued in actions::take_for_step() {
+        if !timing::faults_enabled() {
+            log::debug!(
+                "skipping {queued} during phase {} elapsed={:?}",
+                timing::phase(),
+                timing::elapsed()
+            );
+            actions::record_applied(queued, true);
+            continue;
         }
+
+        log::debug!("applying {queued} elapsed={:?}", timing::elapsed());
+
+        let (kind, host) = match &queued.action {
+            Action::Bounce(host) => (FaultKind::Bounce, host.clone()),
+            Action::Crash(host) => (FaultKind::Crash, host.clone()),
+            Action::Disrupt { client, failures } => {
+                // A client that never registered has nothing to disrupt
+                let disrupted = disruption::disrupt(client, *failures);
+                actions::record_applied(queued, !disrupted);
+                continue;
+            }
+        };
+
+        timing::record_bounce();
+        faults::record(kind, &host);
+        actions::record_applied(queued, false);
+        // The harness only exposes bounce, which drops the host's future
+        // wherever it's awaiting. The recorded fault kind tells the host and
+        // the clients whether to treat it as a crash.
+        sim.bounce(host);
     }
 }
 
@@ -94,33 +225,61 @@ pub fn handle_actions(sim: &mut impl Sim) {
 ///
 /// * If there is an IO error
 pub async fn read_message(
-    message: &mut String,
+    message: &mut MessageBuffer,
     mut stream: Pin<Box<impl AsyncReadExt>>,
 ) -> Result<Option<String>, Error> {
-    let mut buf = [0_u8; 1024];
+    let mut buf = [0_u8; READ_CHUNK_SIZE];
+
+    loop {
+        if let Some(value) = message.next_message()? {
+            return Ok(Some(value));
+        }
 
-    Ok(loop {
         let count = match stream.read(&mut buf).await {
             Ok(count) => count,
             Err(e) => {
                 log::error!("read_message: failed to read from stream: {e:?}");
-                break None;
+                return Ok(None);
             }
         };
         if count == 0 {
             log::debug!("read_message: received empty response");
-            break None;
+            return Ok(None);
         }
         log::trace!("read count={count}");
-        let value = String::from_utf8(buf[..count].to_vec())?;
-        message.push_str(&value);
-
-        if let Some(index) = value.chars().position(|x| x == 0 as char) {
-            let mut remaining = message.split_off(message.len() - value.len() + index);
-            let value = message.clone();
-            remaining.remove(0);
-            *message = remaining;
-            break Some(value);
+        message.extend(&buf[..count]);
+    }
+}
+
+/// Reads the next length prefixed frame from a connection that negotiated
+/// compression.
+///
+/// # Errors
+///
+/// * If the frame is malformed, e.g. its compressed body is corrupted
+pub async fn read_frame(
+    buffer: &mut Vec<u8>,
+    mut stream: Pin<Box<impl AsyncReadExt>>,
+) -> Result<Option<String>, Error> {
+    let mut buf = [0_u8; 1024];
+
+    loop {
+        if let Some(message) = framing::decode(buffer)? {
+            return Ok(Some(message));
         }
-    })
+
+        let count = match stream.read(&mut buf).await {
+            Ok(count) => count,
+            Err(e) => {
+                log::error!("read_frame: failed to read from stream: {e:?}");
+                return Ok(None);
+            }
+        };
+        if count == 0 {
+            log::debug!("read_frame: received empty response");
+            return Ok(None);
+        }
+        log::trace!("read count={count}");
+        buffer.extend_from_slice(&buf[..count]);
+    }
 }
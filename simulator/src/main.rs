@@ -2,44 +2,205 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)]
 
-use std::process::ExitCode;
+use std::{process::ExitCode, time::Instant};
 
-use dst_demo_server_simulator::{banker_count, client, handle_actions, host, reset_banker_count};
+use dst_demo_server::{balance, hooks, version::VersionInfo};
+use dst_demo_server_simulator::{
+    actions, availability, balance_check, banker_count, cancel_safety, capture, client,
+    clients_filter, connections, disruption, expected_ledger, faults, handle_actions, host, labels,
+    leak_check, observability, progress, replication, reset_banker_count, rng_trace, seed,
+    server_config, stats, step_budget, timeouts, timing, topology, triage,
+};
 use simvar::{Sim, SimBootstrap, SimConfig, run_simulation};
 
 pub struct Simulator;
 
 impl SimBootstrap for Simulator {
     fn build_sim(&self, mut config: SimConfig) -> SimConfig {
+        // started first so the draws for the banker count, plan config, client
+        // start order, and initial transactions are part of this run's trace
+        rng_trace::reset(config.seed);
         reset_banker_count();
         client::banker::reset_id();
+        client::banker::reset_plan_config();
+        client::reset_start_order();
+        seed::reset();
+        client::banker::cache::reset();
+        client::observer::reset();
+        expected_ledger::reset();
+        capture::reset(config.seed);
+        timing::reset_duration(config.duration);
+        timeouts::reset(&config);
+        faults::reset();
+        disruption::reset();
+        actions::reset();
+        leak_check::reset();
+        cancel_safety::reset();
+        hooks::reset();
+        stats::reset();
+        availability::reset();
+        observability::reset();
+        replication::reset();
+        host::server::reset_stops();
+        server_config::reset();
+        topology::reset();
+        progress::reset();
+        step_budget::reset();
+
+        // Every balance read doubles as a check that the server's cached
+        // balance hasn't diverged from its ledger, unless it's turned off
+        balance::set_check_enabled(balance_check());
 
         let tcp_capacity = std::cmp::max(banker_count(), 1) * 64;
         config.tcp_capacity(tcp_capacity);
+        connections::reset(tcp_capacity);
         config
     }
 
     fn props(&self) -> Vec<(String, String)> {
-        vec![("banker_count".to_string(), banker_count().to_string())]
+        let version = VersionInfo::current();
+        let mut props = vec![
+            ("banker_count".to_string(), banker_count().to_string()),
+            ("server_version".to_string(), version.version),
+            ("server_git_hash".to_string(), version.git_hash),
+            ("server_features".to_string(), version.features.join(",")),
+        ];
+
+        if let Some(filter) = clients_filter() {
+            props.push(("clients_filter".to_string(), filter.join(",")));
+        }
+
+        props.extend(
+            labels()
+                .into_iter()
+                .map(|(key, value)| (format!("label.{key}"), value)),
+        );
+
+        props.extend(topology::props());
+        props.extend(client::props());
+        props.extend(seed::props());
+        props.extend(client::banker::plan_config().props());
+        props.extend(client::banker::cache::props());
+
+        props
     }
 
     fn on_start(&self, sim: &mut impl Sim) {
-        host::server::start(sim);
+        timing::start();
+        seed::start();
 
-        client::health_checker::start(sim);
-        client::fault_injector::start(sim);
+        // the hosts are always started, regardless of SIMULATOR_CLIENTS. This
+        // starts both the primary and the replica server hosts
+        host::server::start(sim);
+        host::metrics::start(sim);
 
-        for _ in 0..banker_count() {
-            client::banker::start(sim);
-        }
+        client::start(sim);
     }
 
     fn on_step(&self, sim: &mut impl Sim) {
+        let started = Instant::now();
+
         handle_actions(sim);
+        connections::end_step();
+        timing::advance_step();
+        progress::check();
+
+        step_budget::record(started);
+
+        // The harness only catches the panics of the steps, so the end-of-run
+        // checks run in the last one for a failed check to fail the run
+        if timing::is_last_step() {
+            check_end_of_run();
+        }
+    }
+
+    fn on_end(&self, _sim: &mut impl Sim) {
+        capture::flush();
+        rng_trace::flush();
+        stats::log_summary();
+        availability::log_summary();
+        log_summary();
+
+        let fired = hooks::fired();
+        if !fired.is_empty() {
+            log::info!("delay points fired: {fired:?}");
+        }
     }
 }
 
+/// What came out of the run, as opposed to the props that describe how it
+/// was set up. Only known once the run is over, after the props were read.
+fn summary() -> Vec<(String, String)> {
+    let mut summary = vec![
+        (
+            "peak_connections".to_string(),
+            connections::peak().to_string(),
+        ),
+        (
+            "peak_connect_attempts_per_step".to_string(),
+            connections::peak_attempts_per_step().to_string(),
+        ),
+        (
+            "failovers".to_string(),
+            replication::failovers().to_string(),
+        ),
+    ];
+
+    summary.extend(actions::summary());
+    summary.extend(seed::summary());
+    summary.extend(client::banker::cache::summary());
+    summary.extend(stats::summary());
+    summary.extend(availability::summary());
+    summary.extend(disruption::summary());
+    summary.extend(faults::summary());
+    summary.extend(step_budget::summary());
+
+    summary
+}
+
+fn log_summary() {
+    let summary = summary()
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    log::info!("end of run summary:\n{summary}");
+}
+
+/// Asserts the invariants that only hold once the run is over.
+///
+/// # Panics
+///
+/// * If any of the checks fails
+fn check_end_of_run() {
+    // every started client's connections, plus the primary's replication
+    // connection to the replica
+    let connections = client::started_order()
+        .into_iter()
+        .map(client::Client::server_connections)
+        .sum::<u64>();
+    leak_check::check(connections + 1);
+    cancel_safety::check();
+    connections::check_attempts();
+    availability::check();
+    disruption::check();
+    stats::check_fairness();
+}
+
 fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if let Some(seeds) = triage::requested_seeds(std::env::args().skip(1))? {
+        let triaged = triage::run(&seeds)?;
+
+        if triaged.iter().any(triage::SeedTriage::reproduced) {
+            return Ok(ExitCode::FAILURE);
+        }
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    faults::pause_on_failure();
+
     let results = run_simulation(Simulator)?;
 
     if results.iter().any(|x| !x.is_success()) {
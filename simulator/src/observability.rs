@@ -0,0 +1,50 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+use crate::timing;
+
+#[derive(Debug, Clone, Copy)]
+struct Observation {
+    value: u64,
+    step: u64,
+    elapsed: Duration,
+}
+
+thread_local! {
+    static MAX_OBSERVED: RefCell<BTreeMap<String, Observation>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Clears the observed values at the start of a run.
+pub fn reset() {
+    MAX_OBSERVED.with_borrow_mut(BTreeMap::clear);
+}
+
+/// Records `value` as observed for `label` and checks that it didn't regress
+/// from the largest value previously observed for the same label in this run.
+///
+/// # Panics
+///
+/// * If `value` is less than a value previously observed for `label`
+pub fn monotonic_check(label: &str, value: u64) {
+    let observation = Observation {
+        value,
+        step: timing::step(),
+        elapsed: timing::elapsed(),
+    };
+
+    MAX_OBSERVED.with_borrow_mut(|observed| {
+        if let Some(previous) = observed.get(label) {
+            assert!(
+                value >= previous.value,
+                "[{label}] observed value went backwards: previous={} (step={} elapsed={:?}) new={value} (step={} elapsed={:?})",
+                previous.value,
+                previous.step,
+                previous.elapsed,
+                observation.step,
+                observation.elapsed,
+            );
+        }
+
+        observed.insert(label.to_string(), observation);
+    });
+}
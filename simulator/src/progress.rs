@@ -0,0 +1,88 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use simvar::switchy::time::simulator::step_multiplier;
+
+use crate::timing;
+
+thread_local! {
+    static LAST_TOUCH: RefCell<BTreeMap<String, u64>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Clears the progress reported by the previous run's clients.
+pub fn reset() {
+    LAST_TOUCH.with_borrow_mut(BTreeMap::clear);
+}
+
+/// Records that `client` made progress in the current step, i.e. it just
+/// completed an interaction.
+///
+/// Clients also touch once when they start so a client that never gets
+/// anything done still shows up in the report.
+pub fn touch(client: &str) {
+    let step = timing::step();
+
+    LAST_TOUCH.with_borrow_mut(|x| {
+        if let Some(last) = x.get_mut(client) {
+            *last = step;
+        } else {
+            x.insert(client.to_string(), step);
+        }
+    });
+}
+
+/// How many steps may pass without any client making progress before the run
+/// is considered stuck, configurable through `SIMULATOR_PROGRESS_TIMEOUT_STEPS`
+/// and scaled by the step multiplier.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_PROGRESS_TIMEOUT_STEPS` is not a valid integer
+#[must_use]
+pub fn timeout_steps() -> u64 {
+    std::env::var("SIMULATOR_PROGRESS_TIMEOUT_STEPS")
+        .ok()
+        .map_or(300_000, |x| x.parse::<u64>().unwrap())
+        * step_multiplier()
+}
+
+/// Fails the run if none of the clients that report progress have completed
+/// an interaction within the last [`timeout_steps`] steps.
+///
+/// This keeps a wedged run from stepping on until the wall-clock timeout kills
+/// it. Runs where no reporting client was started are never considered stuck.
+///
+/// # Panics
+///
+/// * If no client made progress within the timeout
+pub fn check() {
+    let step = timing::step();
+    let timeout = timeout_steps();
+
+    LAST_TOUCH.with_borrow(|x| {
+        let Some(latest) = x.values().copied().max() else {
+            return;
+        };
+
+        let idle = step.saturating_sub(latest);
+
+        if idle < timeout {
+            return;
+        }
+
+        // the client that went quiet first is listed first
+        let mut clients = x.iter().collect::<Vec<_>>();
+        clients.sort_by_key(|(name, last)| (**last, *name));
+
+        panic!(
+            "\
+            no client progress for {idle} steps (timeout={timeout} step={step}):\n\
+            {}\
+            ",
+            clients
+                .iter()
+                .map(|(name, last)| format!("{name}: last progress at step {last}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    });
+}
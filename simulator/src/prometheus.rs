@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Missing value on line {0}: '{1}'")]
+    MissingValue(usize, String),
+    #[error("Invalid value on line {0}: '{1}'")]
+    InvalidValue(usize, String),
+}
+
+/// Parses the samples of a Prometheus text exposition, keyed by the metric
+/// name including any labels. Comment and blank lines are skipped.
+///
+/// # Errors
+///
+/// * If a sample line is missing its value
+/// * If a sample's value is not a valid number
+pub fn parse(exposition: &str) -> Result<BTreeMap<String, f64>, ParseError> {
+    let mut samples = BTreeMap::new();
+
+    for (index, line) in exposition.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Labels can contain spaces, so split after the closing brace if
+        // there is one
+        let split_at = line.rfind('}').map_or(0, |x| x + 1);
+        let Some(value_start) = line[split_at..].find(' ').map(|x| x + split_at) else {
+            return Err(ParseError::MissingValue(index + 1, line.to_string()));
+        };
+        let name = &line[..value_start];
+        // A timestamp may follow the value
+        let value = line[value_start..]
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| ParseError::MissingValue(index + 1, line.to_string()))?;
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| ParseError::InvalidValue(index + 1, line.to_string()))?;
+
+        samples.insert(name.to_string(), value);
+    }
+
+    Ok(samples)
+}
@@ -0,0 +1,115 @@
+use std::{cell::RefCell, time::Duration};
+
+use dst_demo_server::{protocol::ProtocolVersion, replication::ServerRole};
+
+use crate::{
+    host::server::{HOST, REPLICA_HOST},
+    timing, topology,
+};
+
+/// The server hosts, each of which is either the primary or the replica
+pub const HOSTS: [&str; 2] = [HOST, REPLICA_HOST];
+
+#[derive(Debug, Clone, Copy)]
+struct Topology {
+    primary: &'static str,
+    /// Whether a failover is in progress, during which neither host may start
+    /// as the primary
+    failing_over: bool,
+    failovers: u64,
+    last_failover: Option<Duration>,
+}
+
+impl Topology {
+    const fn new() -> Self {
+        Self {
+            primary: HOST,
+            failing_over: false,
+            failovers: 0,
+            last_failover: None,
+        }
+    }
+}
+
+thread_local! {
+    static TOPOLOGY: RefCell<Topology> = const { RefCell::new(Topology::new()) };
+}
+
+pub fn reset() {
+    TOPOLOGY.with_borrow_mut(|x| *x = Topology::new());
+}
+
+/// The role `host` should start with if it's (re)started now.
+#[must_use]
+pub fn role(host: &str) -> ServerRole {
+    TOPOLOGY.with_borrow(|x| {
+        if !x.failing_over && x.primary == host {
+            ServerRole::Primary
+        } else {
+            ServerRole::Replica
+        }
+    })
+}
+
+/// The host currently acting as the primary. During a failover this is still
+/// the old primary, which rejects writes until the failover completes.
+#[must_use]
+pub fn primary() -> &'static str {
+    TOPOLOGY.with_borrow(|x| x.primary)
+}
+
+#[must_use]
+pub fn replica() -> &'static str {
+    peer(primary())
+}
+
+/// The other server host
+#[must_use]
+pub fn peer(host: &str) -> &'static str {
+    if host == HOST { REPLICA_HOST } else { HOST }
+}
+
+/// The address of `host`'s listener for clients negotiating `protocol`
+#[must_use]
+pub fn addr(host: &str, protocol: ProtocolVersion) -> String {
+    topology::addr(host, protocol)
+}
+
+#[must_use]
+pub fn primary_addr(protocol: ProtocolVersion) -> String {
+    addr(primary(), protocol)
+}
+
+#[must_use]
+pub fn replica_addr(protocol: ProtocolVersion) -> String {
+    addr(replica(), protocol)
+}
+
+pub fn begin_failover() {
+    TOPOLOGY.with_borrow_mut(|x| x.failing_over = true);
+}
+
+/// Finishes a failover with `primary` as the primary, which may be the same
+/// host as before if the replica couldn't be promoted.
+pub fn complete_failover(primary: &'static str) {
+    TOPOLOGY.with_borrow_mut(|x| {
+        if x.primary != primary {
+            x.failovers += 1;
+            x.last_failover = Some(timing::elapsed());
+        }
+        x.primary = primary;
+        x.failing_over = false;
+    });
+}
+
+/// How many times the primary changed in this run
+#[must_use]
+pub fn failovers() -> u64 {
+    TOPOLOGY.with_borrow(|x| x.failovers)
+}
+
+/// The elapsed simulated time of the last completed failover in this run
+#[must_use]
+pub fn last_failover() -> Option<Duration> {
+    TOPOLOGY.with_borrow(|x| x.last_failover)
+}
@@ -0,0 +1,190 @@
+use std::{cell::RefCell, collections::VecDeque, io::Write as _, path::PathBuf, sync::LazyLock};
+
+use simvar::switchy::{
+    self,
+    random::{
+        rand::rand::{self, RngCore},
+        rng,
+    },
+};
+
+/// The most draws kept per run. Older draws are dropped, but the sequence
+/// numbers keep counting so the dump shows how many were skipped.
+pub const CAPACITY: usize = 100_000;
+
+static ENABLED: LazyLock<bool> =
+    LazyLock::new(|| std::env::var("SIMULATOR_RNG_TRACE").is_ok_and(|x| x == "1" || x == "true"));
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+struct Draw {
+    sequence: u64,
+    label: &'static str,
+    value: u64,
+}
+
+struct Trace {
+    path: PathBuf,
+    sequence: u64,
+    draws: VecDeque<Draw>,
+}
+
+impl Trace {
+    fn flush(&self) {
+        if self.draws.is_empty() {
+            return;
+        }
+
+        let mut contents = Vec::new();
+        for draw in &self.draws {
+            writeln!(
+                contents,
+                "{}\t{}\t{}",
+                draw.sequence, draw.label, draw.value
+            )
+            .unwrap();
+        }
+
+        if let Err(e) = std::fs::write(&self.path, contents) {
+            log::error!(
+                "rng_trace: failed to write rng trace file {}: {e:?}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Whether `SIMULATOR_RNG_TRACE` was enabled for this process.
+#[must_use]
+pub fn enabled() -> bool {
+    *ENABLED
+}
+
+/// Starts a new draw log for the current run if `SIMULATOR_RNG_TRACE` is
+/// enabled, writing out the log left over from a previous run on this thread.
+///
+/// The log is written to `SIMULATOR_CAPTURE_DIR`, or the working directory if
+/// that isn't set.
+///
+/// # Panics
+///
+/// * If the `SIMULATOR_CAPTURE_DIR` directory fails to be created
+pub fn reset(seed: u64) {
+    let trace = enabled().then(|| {
+        let dir =
+            std::env::var("SIMULATOR_CAPTURE_DIR").map_or_else(|_| PathBuf::new(), PathBuf::from);
+        if !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(&dir).unwrap();
+        }
+
+        let path = dir.join(format!(
+            "rng-trace-seed-{seed}-thread-{}.tsv",
+            switchy::unsync::thread_id()
+        ));
+        log::debug!("rng_trace: tracing rng draws to {}", path.display());
+
+        Trace {
+            path,
+            sequence: 0,
+            draws: VecDeque::new(),
+        }
+    });
+
+    TRACE.with_borrow_mut(|x| *x = trace);
+}
+
+/// Writes the current run's draw log to disk.
+pub fn flush() {
+    TRACE.with_borrow(|x| {
+        if let Some(trace) = x {
+            trace.flush();
+        }
+    });
+}
+
+fn record(label: &'static str, value: u64) {
+    TRACE.with_borrow_mut(|x| {
+        let Some(trace) = x else {
+            return;
+        };
+
+        trace.sequence += 1;
+        if trace.draws.len() == CAPACITY {
+            trace.draws.pop_front();
+        }
+        trace.draws.push_back(Draw {
+            sequence: trace.sequence,
+            label,
+            value,
+        });
+    });
+}
+
+fn record_bytes(label: &'static str, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+        let mut value = [0_u8; 8];
+        value[..chunk.len()].copy_from_slice(chunk);
+        record(label, u64::from_le_bytes(value));
+    }
+}
+
+/// An rng that records every value drawn from it under `label` when
+/// `SIMULATOR_RNG_TRACE` is enabled, and otherwise just forwards to `inner`.
+pub struct LabeledRng<R> {
+    inner: R,
+    label: &'static str,
+}
+
+impl<R: RngCore> RngCore for LabeledRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.inner.next_u32();
+        if *ENABLED {
+            record(self.label, u64::from(value));
+        }
+        value
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.inner.next_u64();
+        if *ENABLED {
+            record(self.label, value);
+        }
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        if *ENABLED {
+            record_bytes(self.label, dest);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.inner.try_fill_bytes(dest)?;
+        if *ENABLED {
+            record_bytes(self.label, dest);
+        }
+        Ok(())
+    }
+}
+
+/// Wraps `rng` so the values drawn from it are recorded under `label`.
+#[must_use]
+pub const fn labeled<R: RngCore>(label: &'static str, rng: R) -> LabeledRng<R> {
+    LabeledRng { inner: rng, label }
+}
+
+/// The simulator's rng, with the values drawn from it recorded under `label`
+/// (e.g. `"banker::amount"`).
+#[must_use]
+pub fn rng_labeled(label: &'static str) -> LabeledRng<impl RngCore> {
+    labeled(label, rng())
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, SystemTime};
+
+use simvar::switchy;
+
+/// When a client intends to start each of its interactions, independent of
+/// how long the previous ones took to complete.
+///
+/// Measuring latency from the intended start rather than from when the client
+/// finally got around to sending avoids coordinated omission: when the server
+/// stalls, the interactions the client couldn't start in time count the
+/// stall against their latency instead of hiding it.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    next: SystemTime,
+    /// The gap between two consecutive interactions, on top of the plan's
+    /// sleeps
+    pace: Duration,
+}
+
+impl Schedule {
+    /// A schedule whose first interaction is intended to start at `start`,
+    /// with every following one `pace` after the one before it.
+    #[must_use]
+    pub const fn new(start: SystemTime, pace: Duration) -> Self {
+        Self { next: start, pace }
+    }
+
+    /// When the next interaction is intended to start
+    #[must_use]
+    pub const fn next(&self) -> SystemTime {
+        self.next
+    }
+
+    #[must_use]
+    pub const fn pace(&self) -> Duration {
+        self.pace
+    }
+
+    /// Moves the intended start of the next interaction one pace, plus
+    /// `gap`, past the current one's. It doesn't depend on when the current
+    /// one actually started or completed.
+    pub fn advance(&mut self, gap: Duration) {
+        self.next += self.pace + gap;
+    }
+
+    /// How far behind the intended start of the next interaction `now` is,
+    /// or zero if it's not due yet
+    #[must_use]
+    pub fn behind(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.next).unwrap_or_default()
+    }
+
+    /// Sleeps until the next interaction is due, returning how far behind
+    /// schedule the client already was if it's overdue.
+    pub async fn wait(&self) -> Duration {
+        let now = switchy::time::now();
+
+        match self.next.duration_since(now) {
+            Ok(until) => {
+                switchy::unsync::time::sleep(until).await;
+                Duration::ZERO
+            }
+            Err(_) => self.behind(now),
+        }
+    }
+}
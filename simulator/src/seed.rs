@@ -0,0 +1,139 @@
+use std::{cell::RefCell, time::Instant};
+
+use dst_demo_server::bank::{CreateTime, LocalBank};
+use rust_decimal::Decimal;
+use simvar::switchy::{self, random::rand::rand::Rng as _};
+
+use crate::{expected_ledger, host, replication, rng_trace::rng_labeled};
+
+/// The most transactions a run is seeded with unless
+/// `SIMULATOR_INITIAL_TRANSACTIONS` is set
+pub const MAX_INITIAL_TRANSACTIONS: u64 = 1000;
+
+/// The most time between two seeded transactions
+const MAX_SPACING_MILLIS: CreateTime = 1000;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Seed {
+    transactions: u64,
+    /// How long it took to load the seeded ledger, measured with
+    /// `std::time::Instant` since the simulated clock doesn't move while it's
+    /// loaded
+    load_micros: Option<u128>,
+}
+
+thread_local! {
+    static SEED: RefCell<Seed> = const {
+        RefCell::new(Seed {
+            transactions: 0,
+            load_micros: None,
+        })
+    };
+}
+
+/// Picks how many transactions the run's ledger starts with, from
+/// `SIMULATOR_INITIAL_TRANSACTIONS` or else at random up to
+/// [`MAX_INITIAL_TRANSACTIONS`].
+///
+/// # Panics
+///
+/// * If `SIMULATOR_INITIAL_TRANSACTIONS` is not a valid integer
+pub fn reset() {
+    let value = rng_labeled("initial_transactions").gen_range(0..=MAX_INITIAL_TRANSACTIONS);
+    let transactions = std::env::var("SIMULATOR_INITIAL_TRANSACTIONS")
+        .ok()
+        .map_or(value, |x| x.parse::<u64>().unwrap());
+
+    SEED.with_borrow_mut(|x| {
+        *x = Seed {
+            transactions,
+            load_micros: None,
+        };
+    });
+}
+
+#[must_use]
+pub fn initial_transactions() -> u64 {
+    SEED.with_borrow(|x| x.transactions)
+}
+
+/// Generates the amounts and creation times of `count` transactions, spaced
+/// out so that the last one was created before `start` and the first one
+/// after the epoch.
+fn history(count: u64, start: CreateTime) -> Vec<(Decimal, CreateTime)> {
+    let count = CreateTime::try_from(count).unwrap();
+    let spacing = (start / (count + 1)).min(MAX_SPACING_MILLIS);
+
+    (0..count)
+        .map(|i| {
+            let amount: Decimal = rng_labeled("seed::amount")
+                .gen_range(-1000.0f64..1000.0)
+                .try_into()
+                .unwrap();
+            (amount.round_dp(2), start - (count - i) * spacing)
+        })
+        .collect()
+}
+
+/// Writes the run's initial ledger to every server host's ledger file and
+/// records it in the expected ledger, then times how long the primary takes to
+/// load it.
+///
+/// Has to be called before the hosts are started.
+///
+/// # Panics
+///
+/// * If the simulated clock is before the epoch
+/// * If a ledger file fails to be written or loaded
+pub fn start() {
+    let count = initial_transactions();
+    if count == 0 {
+        return;
+    }
+
+    let start = switchy::time::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let history = history(count, CreateTime::try_from(start).unwrap());
+
+    let mut transactions = vec![];
+    for host in replication::HOSTS {
+        transactions =
+            LocalBank::seed_history(host::server::db_path(host), history.clone()).unwrap();
+    }
+    expected_ledger::seeded(&transactions);
+
+    let started = Instant::now();
+    drop(LocalBank::open(host::server::db_path(host::server::HOST)).unwrap());
+    let load_micros = started.elapsed().as_micros();
+
+    log::info!("seeded {count} transactions, loaded in {load_micros}us");
+    SEED.with_borrow_mut(|x| x.load_micros = Some(load_micros));
+}
+
+#[must_use]
+pub fn props() -> Vec<(String, String)> {
+    vec![(
+        "initial_transactions".to_string(),
+        initial_transactions().to_string(),
+    )]
+}
+
+/// How long the primary took to load the seeded ledger, to be included in
+/// the end-of-run summary. The ledger is only seeded once the run has
+/// started, after its props are read.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    SEED.with_borrow(|x| {
+        x.load_micros
+            .map(|load_micros| {
+                (
+                    "initial_ledger_load_us".to_string(),
+                    load_micros.to_string(),
+                )
+            })
+            .into_iter()
+            .collect()
+    })
+}
@@ -0,0 +1,82 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+use dst_demo_server::config::{ReloadableConfig, ServerConfig};
+
+use crate::{timeouts, timing};
+
+#[derive(Debug, Clone)]
+struct HostConfig {
+    /// The host's active config, or `None` if a reload may or may not have
+    /// been applied
+    config: Option<ServerConfig>,
+    /// When the host's config last changed
+    changed_at: Duration,
+}
+
+thread_local! {
+    static CONFIGS: RefCell<BTreeMap<String, HostConfig>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+pub fn reset() {
+    CONFIGS.with_borrow_mut(BTreeMap::clear);
+}
+
+/// The config the hosts start with
+///
+/// It's the one from the environment, with the write timeout stretched by a round trip at the run's max message
+/// latency so that a slow network isn't mistaken for a stalled peer.
+#[must_use]
+pub fn from_env() -> ServerConfig {
+    let config = ServerConfig::from_env();
+
+    ServerConfig {
+        write_timeout: timeouts::timeout(config.write_timeout, 1),
+        ..config
+    }
+}
+
+fn set(host: &str, config: Option<ServerConfig>) {
+    let changed_at = timing::elapsed();
+
+    CONFIGS.with_borrow_mut(|x| {
+        x.insert(host.to_string(), HostConfig { config, changed_at });
+    });
+}
+
+/// Records that `host` (re)started with the config from the environment,
+/// dropping any config that was reloaded into its previous incarnation.
+pub fn restarted(host: &str) {
+    set(host, Some(from_env()));
+}
+
+/// Records that `host` reloaded its config, responding with the `effective`
+/// values of the reloadable fields.
+///
+/// # Panics
+///
+/// * If the effective values are invalid
+pub fn reloaded(host: &str, effective: &ReloadableConfig) {
+    set(host, Some(from_env().reload(effective).unwrap()));
+}
+
+/// Records that a reload may or may not have been applied to `host`, e.g.
+/// because the connection dropped before the response was read.
+pub fn uncertain(host: &str) {
+    set(host, None);
+}
+
+/// The config `host` is currently running with, or `None` if it isn't known.
+#[must_use]
+pub fn effective(host: &str) -> Option<ServerConfig> {
+    CONFIGS.with_borrow(|x| {
+        x.get(host)
+            .map_or_else(|| Some(from_env()), |x| x.config.clone())
+    })
+}
+
+/// Whether the config of `host` changed at or after `since`, which makes any
+/// behavior observed across the change ambiguous.
+#[must_use]
+pub fn changed_since(host: &str, since: Duration) -> bool {
+    CONFIGS.with_borrow(|x| x.get(host).is_some_and(|x| x.changed_at >= since))
+}
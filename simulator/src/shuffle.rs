@@ -0,0 +1,17 @@
+use simvar::switchy::random::rand::rand::Rng;
+
+/// Shuffles `items` in place with a Fisher-Yates shuffle over `rng`, so the
+/// same seeded stream always produces the same order.
+pub fn shuffle<T, R: Rng + ?Sized>(rng: &mut R, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.gen_range(0..=i));
+    }
+}
+
+/// Collects `items` into a `Vec` in an order shuffled like [`shuffle`].
+#[must_use]
+pub fn shuffled<T, R: Rng + ?Sized>(rng: &mut R, items: impl IntoIterator<Item = T>) -> Vec<T> {
+    let mut items = items.into_iter().collect::<Vec<_>>();
+    shuffle(rng, &mut items);
+    items
+}
@@ -0,0 +1,395 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use simvar::switchy;
+
+use crate::timing;
+
+/// The inclusive upper bounds, in simulated milliseconds, of the latency
+/// histogram buckets. Anything above the last bound lands in an overflow
+/// bucket.
+const BUCKETS: [u64; 16] = [
+    1, 5, 10, 50, 100, 500, 1_000, 5_000, 10_000, 30_000, 60_000, 120_000, 300_000, 600_000,
+    1_800_000, 3_600_000,
+];
+
+thread_local! {
+    static STATS: RefCell<BTreeMap<&'static str, Histogram>> = const { RefCell::new(BTreeMap::new()) };
+    static COMPLETED: RefCell<BTreeMap<String, u64>> = const { RefCell::new(BTreeMap::new()) };
+    static LIST_RETRIES: RefCell<ListRetries> = const { RefCell::new(ListRetries::new()) };
+    static BEHIND_SCHEDULE: RefCell<BTreeMap<String, ScheduleLag>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// How far a client's actual progress lagged its intended schedule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScheduleLag {
+    /// The lag when the client last started an interaction
+    pub current: Duration,
+    pub max: Duration,
+    /// The client's gap between two interactions, which the lag can be
+    /// compared against regardless of the step multiplier
+    pub pace: Duration,
+}
+
+/// How many times the bankers had to re-issue listings before they showed
+/// every transaction acknowledged to them
+#[derive(Debug, Clone, Copy)]
+pub struct ListRetries {
+    pub listings: u64,
+    /// The listings that needed at least one retry
+    pub retried: u64,
+    pub total: u64,
+    pub max: u32,
+}
+
+impl ListRetries {
+    const fn new() -> Self {
+        Self {
+            listings: 0,
+            retried: 0,
+            total: 0,
+            max: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS.len() + 1],
+    pub count: u64,
+    pub max: u64,
+    /// Interactions that started but never completed before the run ended
+    pub incomplete: u64,
+}
+
+impl Histogram {
+    fn record(&mut self, millis: u64) {
+        let index = BUCKETS
+            .iter()
+            .position(|&bound| millis <= bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.max = self.max.max(millis);
+    }
+
+    /// An estimate of the given percentile: the upper bound of the bucket the
+    /// percentile falls into, capped at the max observed value.
+    #[must_use]
+    pub fn percentile(&self, percentile: u64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = (self.count * percentile).div_ceil(100).max(1);
+        let mut seen = 0;
+
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return BUCKETS.get(index).map_or(self.max, |&x| x.min(self.max));
+            }
+        }
+
+        self.max
+    }
+}
+
+/// Tracks a single interaction from when it was intended to start until it
+/// completes. Interactions that are never finished are reported as incomplete.
+pub struct Timer {
+    interaction_type: &'static str,
+    start: SystemTime,
+}
+
+impl Timer {
+    /// Records the simulated time elapsed since the interaction was intended
+    /// to start.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn finish(self) {
+        let millis = switchy::time::now()
+            .duration_since(self.start)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        record(self.interaction_type, millis);
+    }
+}
+
+/// Starts timing an interaction now. Until the returned `Timer` is finished,
+/// the interaction is counted as incomplete.
+#[must_use]
+pub fn start(interaction_type: &'static str) -> Timer {
+    start_at(interaction_type, switchy::time::now())
+}
+
+/// Starts timing an interaction that was intended to start at
+/// `intended_start`, so any time the client spent behind schedule counts
+/// against its latency.
+#[must_use]
+pub fn start_at(interaction_type: &'static str, intended_start: SystemTime) -> Timer {
+    STATS.with_borrow_mut(|x| x.entry(interaction_type).or_default().incomplete += 1);
+
+    Timer {
+        interaction_type,
+        start: intended_start,
+    }
+}
+
+/// Records a completed interaction that took `millis` simulated milliseconds.
+pub fn record(interaction_type: &'static str, millis: u64) {
+    STATS.with_borrow_mut(|x| {
+        let histogram = x.entry(interaction_type).or_default();
+        histogram.incomplete = histogram.incomplete.saturating_sub(1);
+        histogram.record(millis);
+    });
+}
+
+pub fn reset() {
+    STATS.with_borrow_mut(BTreeMap::clear);
+    COMPLETED.with_borrow_mut(BTreeMap::clear);
+    LIST_RETRIES.with_borrow_mut(|x| *x = ListRetries::new());
+    BEHIND_SCHEDULE.with_borrow_mut(BTreeMap::clear);
+}
+
+/// Records how far behind its intended schedule `client` was when it started
+/// an interaction.
+pub fn record_behind_schedule(client: &str, behind: Duration, pace: Duration) {
+    BEHIND_SCHEDULE.with_borrow_mut(|x| {
+        let lag = x.entry(client.to_string()).or_default();
+        lag.current = behind;
+        lag.max = lag.max.max(behind);
+        lag.pace = pace;
+    });
+}
+
+/// How far behind its intended schedule each client has been
+#[must_use]
+pub fn behind_schedule() -> BTreeMap<String, ScheduleLag> {
+    BEHIND_SCHEDULE.with_borrow(Clone::clone)
+}
+
+/// The client that fell furthest behind its schedule, with its lag
+#[must_use]
+pub fn max_behind_schedule() -> Option<(String, ScheduleLag)> {
+    behind_schedule().into_iter().max_by_key(|(_, lag)| lag.max)
+}
+
+/// The largest lag any client had when it last started an interaction
+fn final_behind_schedule() -> Duration {
+    BEHIND_SCHEDULE.with_borrow(|x| x.values().map(|lag| lag.current).max().unwrap_or_default())
+}
+
+/// Records a listing that showed every transaction acknowledged to the
+/// banker after `retries` re-issues.
+pub fn record_list_retries(retries: u32) {
+    LIST_RETRIES.with_borrow_mut(|x| {
+        x.listings += 1;
+        x.retried += u64::from(retries > 0);
+        x.total += u64::from(retries);
+        x.max = x.max.max(retries);
+    });
+}
+
+#[must_use]
+pub fn list_retries() -> ListRetries {
+    LIST_RETRIES.with_borrow(|x| *x)
+}
+
+/// Starts counting the interactions a client completes, so that a client
+/// that never completes one is still reported.
+pub fn register_client(name: &str) {
+    COMPLETED.with_borrow_mut(|x| {
+        x.entry(name.to_string()).or_default();
+    });
+}
+
+/// Counts an interaction the client completed.
+pub fn complete_interaction(name: &str) {
+    COMPLETED.with_borrow_mut(|x| *x.entry(name.to_string()).or_default() += 1);
+}
+
+/// The number of interactions each registered client completed
+#[must_use]
+pub fn completed() -> BTreeMap<String, u64> {
+    COMPLETED.with_borrow(Clone::clone)
+}
+
+/// The min, median, and max of the clients' completed interaction counts, or
+/// `None` if no clients were registered.
+#[must_use]
+pub fn completed_spread() -> Option<(u64, u64, u64)> {
+    let mut counts = completed().into_values().collect::<Vec<_>>();
+    counts.sort_unstable();
+
+    Some((
+        *counts.first()?,
+        counts[(counts.len() - 1) / 2],
+        *counts.last()?,
+    ))
+}
+
+#[must_use]
+pub fn snapshot() -> BTreeMap<&'static str, Histogram> {
+    STATS.with_borrow(Clone::clone)
+}
+
+/// Logs a table of the latencies recorded for each interaction type.
+pub fn log_summary() {
+    let stats = snapshot();
+
+    if stats.is_empty() {
+        return;
+    }
+
+    let header = format!(
+        "{:<20} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "interaction", "count", "p50 (ms)", "p95 (ms)", "max (ms)", "incomplete"
+    );
+    let rows = stats.iter().map(|(interaction_type, histogram)| {
+        format!(
+            "{interaction_type:<20} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            histogram.count,
+            histogram.percentile(50),
+            histogram.percentile(95),
+            histogram.max,
+            histogram.incomplete,
+        )
+    });
+    let table = std::iter::once(header)
+        .chain(rows)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    log::info!("interaction latencies:\n{table}");
+
+    if let Some((min, median, max)) = completed_spread() {
+        log::info!("completed interactions per client: min={min} median={median} max={max}");
+    }
+
+    if let Some((client, lag)) = max_behind_schedule() {
+        log::info!(
+            "behind schedule: max={}ms ({client}, {:.2} paces) final={}ms",
+            lag.max.as_millis(),
+            paces(lag),
+            final_behind_schedule().as_millis(),
+        );
+    }
+
+    let retries = list_retries();
+    if retries.retried > 0 {
+        log::info!(
+            "list retries: {} of {} listings retried, total={} max={}",
+            retries.retried,
+            retries.listings,
+            retries.total,
+            retries.max,
+        );
+    }
+}
+
+/// How many of the client's paces its max lag amounts to
+fn paces(lag: ScheduleLag) -> f64 {
+    if lag.pace.is_zero() {
+        return 0.0;
+    }
+
+    lag.max.as_secs_f64() / lag.pace.as_secs_f64()
+}
+
+/// The p95 latency and incomplete count of each interaction type, the spread
+/// of the clients' completed interaction counts, and the list retries, to be
+/// included in the end-of-run summary.
+///
+/// Also includes how far behind their schedules the clients fell.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    let spread = completed_spread().map(|(min, median, max)| {
+        [
+            ("completed_interactions.min".to_string(), min.to_string()),
+            (
+                "completed_interactions.median".to_string(),
+                median.to_string(),
+            ),
+            ("completed_interactions.max".to_string(), max.to_string()),
+        ]
+    });
+
+    let behind = max_behind_schedule().map(|(_, lag)| {
+        [
+            (
+                "behind_schedule_ms.max".to_string(),
+                lag.max.as_millis().to_string(),
+            ),
+            (
+                "behind_schedule_ms.final".to_string(),
+                final_behind_schedule().as_millis().to_string(),
+            ),
+            (
+                "behind_schedule_paces.max".to_string(),
+                format!("{:.2}", paces(lag)),
+            ),
+        ]
+    });
+
+    let retries = list_retries();
+
+    snapshot()
+        .into_iter()
+        .flat_map(|(interaction_type, histogram)| {
+            [
+                (
+                    format!("p95_ms.{interaction_type}"),
+                    histogram.percentile(95).to_string(),
+                ),
+                (
+                    format!("incomplete.{interaction_type}"),
+                    histogram.incomplete.to_string(),
+                ),
+            ]
+        })
+        .chain(spread.into_iter().flatten())
+        .chain(behind.into_iter().flatten())
+        .chain([
+            ("list_retries.total".to_string(), retries.total.to_string()),
+            ("list_retries.max".to_string(), retries.max.to_string()),
+        ])
+        .collect()
+}
+
+/// Fails the run if `SIMULATOR_MIN_INTERACTIONS_PER_CLIENT` is set and a
+/// client completed fewer than that many interactions per simulated hour.
+///
+/// A client below the threshold kept losing its retries to the other clients.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_MIN_INTERACTIONS_PER_CLIENT` is not a valid integer
+/// * If a client completed fewer interactions than the run's minimum
+pub fn check_fairness() {
+    let Some(per_hour) = std::env::var("SIMULATOR_MIN_INTERACTIONS_PER_CLIENT")
+        .ok()
+        .map(|x| x.parse::<u64>().unwrap())
+    else {
+        return;
+    };
+
+    let elapsed = timing::elapsed();
+    let min = per_hour * elapsed.as_secs() / 3600;
+
+    let starved = completed()
+        .into_iter()
+        .filter(|(_, count)| *count < min)
+        .map(|(name, count)| format!("{name}={count}"))
+        .collect::<Vec<_>>();
+
+    assert!(
+        starved.is_empty(),
+        "{} clients completed fewer than {min} interactions ({per_hour} per simulated hour over {elapsed:?}): {}",
+        starved.len(),
+        starved.join(", "),
+    );
+}
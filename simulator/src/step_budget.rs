@@ -0,0 +1,112 @@
+use std::{
+    cell::RefCell,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use crate::timing;
+
+/// How many steps in a row have to go over the budget before a strict run
+/// fails
+pub const STRICT_VIOLATIONS_IN_A_ROW: u64 = 100;
+
+/// Over budget `on_step`s are only warned about once per this many steps so
+/// that a consistently slow `on_step` doesn't flood the logs
+const WARN_INTERVAL_STEPS: u64 = 10_000;
+
+/// The wall-clock time each `on_step` may take from
+/// `SIMULATOR_STEP_BUDGET_US`. Defaults to 5ms.
+static BUDGET: LazyLock<Duration> = LazyLock::new(|| {
+    std::env::var("SIMULATOR_STEP_BUDGET_US")
+        .ok()
+        .map_or(Duration::from_millis(5), |x| {
+            Duration::from_micros(x.parse::<u64>().unwrap())
+        })
+});
+
+static STRICT: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("SIMULATOR_STRICT_STEP_BUDGET")
+        .as_deref()
+        .is_ok_and(|x| x == "1")
+});
+
+#[derive(Debug, Clone, Copy, Default)]
+struct StepBudget {
+    violations: u64,
+    in_a_row: u64,
+    max: Duration,
+    last_warned_step: Option<u64>,
+}
+
+thread_local! {
+    static STEP_BUDGET: RefCell<StepBudget> = const {
+        RefCell::new(StepBudget {
+            violations: 0,
+            in_a_row: 0,
+            max: Duration::ZERO,
+            last_warned_step: None,
+        })
+    };
+}
+
+pub fn reset() {
+    STEP_BUDGET.with_borrow_mut(|x| *x = StepBudget::default());
+}
+
+/// Records how much wall-clock time an `on_step` took, measured with
+/// `std::time::Instant` since the simulated clock doesn't move within a step.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_STEP_BUDGET_US` is not a valid integer
+/// * If `SIMULATOR_STRICT_STEP_BUDGET=1` and the last
+///   [`STRICT_VIOLATIONS_IN_A_ROW`] `on_step`s all went over the budget
+pub fn record(started: Instant) {
+    let elapsed = started.elapsed();
+    let budget = *BUDGET;
+    let step = timing::step();
+
+    STEP_BUDGET.with_borrow_mut(|x| {
+        x.max = x.max.max(elapsed);
+
+        if elapsed <= budget {
+            x.in_a_row = 0;
+            return;
+        }
+
+        x.violations += 1;
+        x.in_a_row += 1;
+
+        if x
+            .last_warned_step
+            .is_none_or(|last| step >= last + WARN_INTERVAL_STEPS)
+        {
+            x.last_warned_step = Some(step);
+            log::warn!(
+                "on_step took {elapsed:?} at step {step}, over its {budget:?} budget ({} violations so far)",
+                x.violations
+            );
+        }
+
+        assert!(
+            !*STRICT || x.in_a_row < STRICT_VIOLATIONS_IN_A_ROW,
+            "on_step went over its {budget:?} wall-clock budget {} steps in a row, most recently taking {elapsed:?} at step {step}",
+            x.in_a_row,
+        );
+    });
+}
+
+/// The number of over budget `on_step`s and the slowest one, to be included
+/// in the end-of-run summary.
+#[must_use]
+pub fn summary() -> Vec<(String, String)> {
+    STEP_BUDGET.with_borrow(|x| {
+        vec![
+            (
+                "step_budget_violations".to_string(),
+                x.violations.to_string(),
+            ),
+            ("max_on_step_us".to_string(), x.max.as_micros().to_string()),
+        ]
+    })
+}
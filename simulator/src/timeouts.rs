@@ -0,0 +1,39 @@
+use std::{cell::RefCell, time::Duration};
+
+use simvar::SimConfig;
+
+/// How many times the run's max message latency a single round trip may take.
+/// A round trip crosses the network twice, once for the request and once for
+/// the response.
+pub const LATENCIES_PER_ROUND_TRIP: u32 = 2;
+
+thread_local! {
+    static MAX_MESSAGE_LATENCY: RefCell<Duration> = const { RefCell::new(Duration::ZERO) };
+}
+
+/// Records the latency knobs of the run's `SimConfig` so that the clients'
+/// timeouts account for them, logging the formula the timeouts are derived
+/// with.
+pub fn reset(config: &SimConfig) {
+    let max_message_latency = config.max_message_latency;
+
+    log::info!(
+        "timeouts: timeout = base + {LATENCIES_PER_ROUND_TRIP} * max_message_latency * expected_round_trips (max_message_latency={max_message_latency:?})"
+    );
+
+    MAX_MESSAGE_LATENCY.with_borrow_mut(|x| *x = max_message_latency);
+}
+
+/// The run's max message latency
+#[must_use]
+pub fn max_message_latency() -> Duration {
+    MAX_MESSAGE_LATENCY.with_borrow(|x| *x)
+}
+
+/// How long an interaction that's expected to make `expected_round_trips`
+/// round trips to the server may take, on top of the client's own `base`
+/// allowance.
+#[must_use]
+pub fn timeout(base: Duration, expected_round_trips: u32) -> Duration {
+    base + max_message_latency() * LATENCIES_PER_ROUND_TRIP * expected_round_trips
+}
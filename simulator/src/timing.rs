@@ -0,0 +1,150 @@
+use std::{
+    cell::RefCell,
+    time::{Duration, SystemTime},
+};
+
+use simvar::switchy::{self, time::simulator::current_step};
+use strum::AsRefStr;
+
+/// The phases of a run, each with a different fault intensity.
+///
+/// Faults are not injected while warming up so the system can settle, nor while
+/// recovering so the run ends in a quiesced state that stricter end-of-run
+/// invariants can rely on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Phase {
+    WarmUp,
+    FaultStorm,
+    Recovery,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseConfig {
+    /// Whether faults are injected at all
+    pub faults: bool,
+    /// Multiplier applied to the fault injector's sleeps between faults
+    pub sleep_multiplier: f64,
+}
+
+/// The phase schedule as `(start fraction of the run duration, phase)`
+pub const PHASES: [(f64, Phase); 3] = [
+    (0.0, Phase::WarmUp),
+    (0.1, Phase::FaultStorm),
+    (0.7, Phase::Recovery),
+];
+
+impl Phase {
+    #[must_use]
+    pub const fn config(self) -> PhaseConfig {
+        match self {
+            Self::WarmUp | Self::Recovery => PhaseConfig {
+                faults: false,
+                sleep_multiplier: 1.0,
+            },
+            Self::FaultStorm => PhaseConfig {
+                faults: true,
+                sleep_multiplier: 0.25,
+            },
+        }
+    }
+}
+
+thread_local! {
+    static RUN_DURATION: RefCell<Option<Duration>> = const { RefCell::new(None) };
+    static RUN_START: RefCell<Option<SystemTime>> = const { RefCell::new(None) };
+    static LAST_BOUNCE: RefCell<Option<Duration>> = const { RefCell::new(None) };
+    static STEP: RefCell<u64> = const { RefCell::new(0) };
+}
+
+pub fn reset_duration(duration: Duration) {
+    RUN_DURATION.with_borrow_mut(|x| *x = Some(duration));
+    RUN_START.with_borrow_mut(|x| *x = None);
+    LAST_BOUNCE.with_borrow_mut(|x| *x = None);
+    STEP.with_borrow_mut(|x| *x = 0);
+}
+
+/// Marks the current simulated time as the start of the run.
+pub fn start() {
+    RUN_START.with_borrow_mut(|x| *x = Some(switchy::time::now()));
+}
+
+/// The simulated time that has elapsed since the run started.
+#[must_use]
+pub fn elapsed() -> Duration {
+    RUN_START
+        .with_borrow(|x| *x)
+        .and_then(|start| switchy::time::now().duration_since(start).ok())
+        .unwrap_or_default()
+}
+
+/// Advances the step counter, called once per simulation step.
+pub fn advance_step() {
+    STEP.with_borrow_mut(|x| *x += 1);
+}
+
+/// The number of simulation steps completed so far in this run.
+#[must_use]
+pub fn step() -> u64 {
+    STEP.with_borrow(|x| *x)
+}
+
+/// Whether the harness is on the run's last step, after which it stops the
+/// run without stepping it again. Runs without a bounded duration don't have
+/// one.
+#[must_use]
+pub fn is_last_step() -> bool {
+    RUN_DURATION
+        .with_borrow(|x| *x)
+        .filter(|x| *x < Duration::MAX)
+        .is_some_and(|duration| u128::from(current_step()) + 1 >= duration.as_millis())
+}
+
+/// Records that a host was bounced at the current simulated time.
+pub fn record_bounce() {
+    LAST_BOUNCE.with_borrow_mut(|x| *x = Some(elapsed()));
+}
+
+/// The elapsed simulated time at which a host was last bounced in this run.
+#[must_use]
+pub fn last_bounce() -> Option<Duration> {
+    LAST_BOUNCE.with_borrow(|x| *x)
+}
+
+/// The simulated time remaining until the run's configured duration is reached.
+#[must_use]
+pub fn remaining() -> Option<Duration> {
+    RUN_DURATION
+        .with_borrow(|x| *x)
+        .map(|duration| duration.saturating_sub(elapsed()))
+}
+
+/// The phase the run is currently in, based on how much of the run's duration
+/// has elapsed. Runs without a known duration are always in the fault storm
+/// phase.
+#[must_use]
+pub fn phase() -> Phase {
+    let Some(duration) = RUN_DURATION.with_borrow(|x| *x).filter(|x| !x.is_zero()) else {
+        return Phase::FaultStorm;
+    };
+
+    let fraction = elapsed().as_secs_f64() / duration.as_secs_f64();
+
+    PHASES
+        .iter()
+        .rev()
+        .find(|(start, _)| fraction >= *start)
+        .map_or(Phase::WarmUp, |(_, phase)| *phase)
+}
+
+/// Whether the run is currently in a phase that injects faults.
+#[must_use]
+pub fn faults_enabled() -> bool {
+    phase().config().faults
+}
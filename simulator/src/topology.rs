@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+
+use dst_demo_server::protocol::ProtocolVersion;
+
+/// A port the server hosts listen on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Listener {
+    pub port: u16,
+    /// The protocol version the clients connecting to this listener
+    /// negotiate, or `None` if it's meant for clients of any version. The
+    /// server itself accepts every version on every listener.
+    pub protocol: Option<ProtocolVersion>,
+}
+
+/// The ports each server host listens on in a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    pub name: &'static str,
+    pub listeners: &'static [Listener],
+}
+
+/// The topologies a run can be configured with through `SIMULATOR_TOPOLOGY`.
+/// The first one is the default.
+pub const TOPOLOGIES: &[Topology] = &[
+    Topology {
+        name: "default",
+        listeners: &[Listener {
+            port: 1234,
+            protocol: None,
+        }],
+    },
+    Topology {
+        name: "dual_listener",
+        listeners: &[
+            Listener {
+                port: 1234,
+                protocol: Some(ProtocolVersion::V1),
+            },
+            Listener {
+                port: 1235,
+                protocol: Some(ProtocolVersion::V2),
+            },
+        ],
+    },
+];
+
+thread_local! {
+    static TOPOLOGY: RefCell<Topology> = const { RefCell::new(TOPOLOGIES[0]) };
+}
+
+/// Picks the run's topology from `SIMULATOR_TOPOLOGY`.
+///
+/// # Panics
+///
+/// * If `SIMULATOR_TOPOLOGY` isn't the name of one of the [`TOPOLOGIES`]
+pub fn reset() {
+    let topology = std::env::var("SIMULATOR_TOPOLOGY").map_or(TOPOLOGIES[0], |name| {
+        *TOPOLOGIES
+            .iter()
+            .find(|x| x.name == name)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Invalid SIMULATOR_TOPOLOGY '{name}', expected one of: {}",
+                    TOPOLOGIES
+                        .iter()
+                        .map(|x| x.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    });
+
+    TOPOLOGY.with_borrow_mut(|x| *x = topology);
+}
+
+#[must_use]
+pub fn current() -> Topology {
+    TOPOLOGY.with_borrow(|x| *x)
+}
+
+impl Topology {
+    /// The listener clients negotiating `protocol` connect to: the first one
+    /// tagged with it, or else the first untagged one, or else the first one.
+    #[must_use]
+    pub fn listener(&self, protocol: ProtocolVersion) -> Listener {
+        *self
+            .listeners
+            .iter()
+            .find(|x| x.protocol == Some(protocol))
+            .or_else(|| self.listeners.iter().find(|x| x.protocol.is_none()))
+            .unwrap_or(&self.listeners[0])
+    }
+}
+
+/// The address of the listener on `host` for clients negotiating `protocol`
+#[must_use]
+pub fn addr(host: &str, protocol: ProtocolVersion) -> String {
+    format!("{host}:{}", current().listener(protocol).port)
+}
+
+#[must_use]
+pub fn props() -> Vec<(String, String)> {
+    let topology = current();
+
+    vec![
+        ("topology".to_string(), topology.name.to_string()),
+        (
+            "listeners".to_string(),
+            topology
+                .listeners
+                .iter()
+                .map(|x| {
+                    x.protocol.map_or_else(
+                        || x.port.to_string(),
+                        |protocol| format!("{}:v{protocol}", x.port),
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    ]
+}
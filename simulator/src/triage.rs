@@ -0,0 +1,211 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+/// Where each triaged seed's log and artifacts are written, unless
+/// `SIMULATOR_TRIAGE_DIR` is set
+pub const DEFAULT_DIR: &str = "triage";
+
+/// The log level of a triaged run, unless `RUST_LOG` is set
+pub const DEFAULT_LOG_LEVEL: &str = "debug";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error("Invalid seed '{0}'")]
+    InvalidSeed(String),
+    #[error("--triage expects a file of seeds")]
+    MissingSeedsFile,
+}
+
+/// Parses seeds separated by commas or whitespace, ignoring anything after a
+/// `#` on each line.
+///
+/// # Errors
+///
+/// * If a seed isn't a valid integer
+pub fn parse_seeds(value: &str) -> Result<Vec<u64>, Error> {
+    value
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse().map_err(|_| Error::InvalidSeed(x.to_string())))
+        .collect()
+}
+
+/// The seeds to triage, from `SIMULATOR_TRIAGE_SEEDS` or the file passed with
+/// `--triage`, or `None` if the simulator should run normally.
+///
+/// # Errors
+///
+/// * If `--triage` isn't followed by a file
+/// * If the seeds file fails to be read
+/// * If a seed isn't a valid integer
+pub fn requested_seeds(mut args: impl Iterator<Item = String>) -> Result<Option<Vec<u64>>, Error> {
+    if let Ok(seeds) = std::env::var("SIMULATOR_TRIAGE_SEEDS") {
+        return parse_seeds(&seeds).map(Some);
+    }
+
+    while let Some(arg) = args.next() {
+        if arg == "--triage" {
+            let path = args.next().ok_or(Error::MissingSeedsFile)?;
+            return parse_seeds(&std::fs::read_to_string(path)?).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// The first panic in a run's log: the location it panicked at followed by
+/// the first line of its message, which for a failed invariant is the
+/// assertion that failed.
+#[must_use]
+pub fn first_failure(log: &str) -> Option<String> {
+    let mut lines = log.lines();
+    let panicked = lines.find(|x| x.contains(" panicked at "))?;
+    let location = panicked
+        .split(" panicked at ")
+        .nth(1)
+        .unwrap_or_default()
+        .trim_end_matches(':');
+
+    Some(lines.next().map_or_else(
+        || location.to_string(),
+        |message| format!("{message} ({location})"),
+    ))
+}
+
+#[derive(Debug)]
+pub struct SeedTriage {
+    pub seed: u64,
+    /// How the run's process exited
+    pub status: ExitStatus,
+    pub first_failure: Option<String>,
+    /// The directory holding the run's log and artifacts
+    pub dir: PathBuf,
+}
+
+impl SeedTriage {
+    /// Whether the seed failed again. A seed that now passes points at flaky
+    /// infrastructure rather than at a regression.
+    #[must_use]
+    pub fn reproduced(&self) -> bool {
+        !self.status.success()
+    }
+
+    fn outcome(&self) -> &'static str {
+        if self.reproduced() {
+            "reproduced"
+        } else {
+            "NON-REPRODUCING"
+        }
+    }
+}
+
+impl std::fmt::Display for SeedTriage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "seed {}: {}", self.seed, self.outcome())?;
+        writeln!(f, "  termination:   {}", self.status)?;
+        writeln!(
+            f,
+            "  first failure: {}",
+            self.first_failure.as_deref().unwrap_or("-")
+        )?;
+        write!(f, "  artifacts:     {}", self.dir.display())
+    }
+}
+
+/// Reruns `seed` alone in a child process of the simulator with its log and
+/// artifacts written to `dir`.
+fn run_seed(seed: u64, dir: &Path) -> Result<SeedTriage, Error> {
+    std::fs::create_dir_all(dir)?;
+    let log_path = dir.join("run.log");
+    let log = File::create(&log_path)?;
+
+    let status = Command::new(std::env::current_exe()?)
+        .env_remove("SIMULATOR_TRIAGE_SEEDS")
+        .env("SIMULATOR_SEED", seed.to_string())
+        .env("SIMULATOR_RUNS", "1")
+        .env("SIMULATOR_MAX_PARALLEL", "1")
+        .env("SIMULATOR_CAPTURE_DIR", dir)
+        .env("SIMULATOR_RNG_TRACE", "1")
+        .env(
+            "RUST_LOG",
+            std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_LEVEL.to_string()),
+        )
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()?;
+
+    Ok(SeedTriage {
+        seed,
+        status,
+        first_failure: first_failure(&std::fs::read_to_string(&log_path)?),
+        dir: dir.to_path_buf(),
+    })
+}
+
+/// Reruns each of `seeds` one at a time, printing a summary of each as it
+/// finishes and a table of all of them at the end.
+///
+/// Every run gets its own directory under `SIMULATOR_TRIAGE_DIR` holding its
+/// log, its capture, and its RNG trace.
+///
+/// # Errors
+///
+/// * If a run's directory or log fails to be written
+/// * If the simulator fails to be rerun
+pub fn run(seeds: &[u64]) -> Result<Vec<SeedTriage>, Error> {
+    let root = PathBuf::from(
+        std::env::var("SIMULATOR_TRIAGE_DIR").unwrap_or_else(|_| DEFAULT_DIR.to_string()),
+    );
+    let mut stdout = std::io::stdout().lock();
+    let mut triaged = Vec::with_capacity(seeds.len());
+
+    for (i, seed) in seeds.iter().enumerate() {
+        writeln!(
+            stdout,
+            "[{}/{}] triaging seed {seed}...",
+            i + 1,
+            seeds.len()
+        )?;
+        let triage = run_seed(*seed, &root.join(format!("seed-{seed}")))?;
+        writeln!(stdout, "{triage}")?;
+        triaged.push(triage);
+    }
+
+    writeln!(
+        stdout,
+        "\n{:<20} {:<16} {:<8} first failure",
+        "seed", "outcome", "exit"
+    )?;
+    for triage in &triaged {
+        writeln!(
+            stdout,
+            "{:<20} {:<16} {:<8} {}",
+            triage.seed,
+            triage.outcome(),
+            triage
+                .status
+                .code()
+                .map_or_else(|| "signal".to_string(), |x| x.to_string()),
+            triage.first_failure.as_deref().unwrap_or("-"),
+        )?;
+    }
+
+    let reproduced = triaged.iter().filter(|x| x.reproduced()).count();
+    writeln!(
+        stdout,
+        "\n{reproduced} of {} seeds reproduced, {} non-reproducing",
+        triaged.len(),
+        triaged.len() - reproduced,
+    )?;
+
+    Ok(triaged)
+}
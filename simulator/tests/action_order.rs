@@ -0,0 +1,97 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Queues actions from several producers in different interleavings to prove
+//! that they're always taken off the queue in the documented order: by step,
+//! then producer name, then the producer's own sequence number.
+
+use dst_demo_server_simulator::{
+    actions::{self, Action, QueuedAction},
+    queue_bounce, queue_crash, queue_disruption, timing,
+};
+
+fn taken() -> Vec<QueuedAction> {
+    actions::take(usize::MAX)
+}
+
+fn expected(entries: &[(&str, u64, u64, Action)]) -> Vec<QueuedAction> {
+    entries
+        .iter()
+        .map(|(producer, step, seq, action)| QueuedAction {
+            producer: (*producer).to_string(),
+            step: *step,
+            seq: *seq,
+            action: action.clone(),
+        })
+        .collect()
+}
+
+#[test]
+fn producers_in_one_step_apply_in_name_order_regardless_of_interleaving() {
+    let order = expected(&[
+        ("chaos", 0, 0, Action::Bounce("replica".to_string())),
+        ("fault_injector", 0, 0, Action::Crash("primary".to_string())),
+        (
+            "fault_injector",
+            0,
+            1,
+            Action::Disrupt {
+                client: "banker_0".to_string(),
+                failures: 2,
+            },
+        ),
+    ]);
+
+    actions::reset();
+    assert!(queue_crash("fault_injector", "primary"));
+    assert!(queue_disruption("fault_injector", "banker_0", 2));
+    assert!(queue_bounce("chaos", "replica"));
+    assert_eq!(taken(), order);
+
+    actions::reset();
+    assert!(queue_bounce("chaos", "replica"));
+    assert!(queue_crash("fault_injector", "primary"));
+    assert!(queue_disruption("fault_injector", "banker_0", 2));
+    assert_eq!(taken(), order);
+
+    actions::reset();
+    assert!(queue_crash("fault_injector", "primary"));
+    assert!(queue_bounce("chaos", "replica"));
+    assert!(queue_disruption("fault_injector", "banker_0", 2));
+    assert_eq!(taken(), order);
+}
+
+#[test]
+fn earlier_steps_apply_first() {
+    actions::reset();
+
+    assert!(queue_bounce("zeta", "primary"));
+    timing::advance_step();
+    assert!(queue_bounce("alpha", "replica"));
+    assert!(queue_bounce("zeta", "replica"));
+
+    assert_eq!(
+        taken(),
+        expected(&[
+            ("zeta", 0, 0, Action::Bounce("primary".to_string())),
+            ("alpha", 1, 0, Action::Bounce("replica".to_string())),
+            ("zeta", 1, 1, Action::Bounce("replica".to_string())),
+        ]),
+    );
+}
+
+#[test]
+fn take_is_bounded_and_keeps_the_rest_queued() {
+    actions::reset();
+
+    for host in ["a", "b", "c"] {
+        assert!(queue_bounce("fault_injector", host));
+    }
+
+    assert_eq!(actions::take(2).len(), 2);
+    assert_eq!(
+        taken(),
+        expected(&[("fault_injector", 0, 2, Action::Bounce("c".to_string()))]),
+    );
+}
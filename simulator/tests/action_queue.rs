@@ -0,0 +1,100 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Fills the action queue past its cap and drains it a step at a time to
+//! prove that it stays bounded, that the rest is carried forward instead of
+//! being applied in a burst, and that repeated bounces are collapsed.
+
+use dst_demo_server_simulator::{
+    actions::{self, Action, Limits, QueueStats},
+    queue_bounce, queue_crash, timing,
+};
+
+fn hosts(taken: &[actions::QueuedAction]) -> Vec<String> {
+    taken
+        .iter()
+        .map(|x| match &x.action {
+            Action::Bounce(host) | Action::Crash(host) => host.clone(),
+            Action::Disrupt { client, .. } => client.clone(),
+        })
+        .collect()
+}
+
+#[test]
+fn actions_past_the_cap_are_dropped() {
+    actions::reset_with_limits(Limits {
+        max_queued: 3,
+        max_per_step: usize::MAX,
+    });
+
+    assert!(queue_crash("fault_injector", "a"));
+    assert!(queue_crash("fault_injector", "b"));
+    assert!(queue_crash("fault_injector", "c"));
+    assert!(!queue_crash("fault_injector", "d"));
+    assert!(!queue_bounce("chaos", "e"));
+
+    assert_eq!(hosts(&actions::take_for_step()), vec!["a", "b", "c"]);
+    assert_eq!(actions::stats().dropped, 2);
+    assert_eq!(actions::stats().peak_queued, 3);
+
+    // Taking actions off the queue makes room again
+    assert!(queue_crash("fault_injector", "f"));
+    assert_eq!(hosts(&actions::take_for_step()), vec!["f"]);
+}
+
+#[test]
+fn at_most_max_per_step_are_taken_each_step() {
+    actions::reset_with_limits(Limits {
+        max_queued: 100,
+        max_per_step: 2,
+    });
+
+    for host in ["a", "b", "c", "d", "e"] {
+        assert!(queue_crash("fault_injector", host));
+    }
+
+    assert_eq!(hosts(&actions::take_for_step()), vec!["a", "b"]);
+    assert_eq!(hosts(&actions::take_for_step()), vec!["c", "d"]);
+    assert_eq!(hosts(&actions::take_for_step()), vec!["e"]);
+    assert!(actions::take_for_step().is_empty());
+
+    // 3 actions were left after the first step, then 1 after the second
+    assert_eq!(actions::stats().carried_forward, 4);
+}
+
+#[test]
+fn repeated_bounces_in_one_step_are_collapsed() {
+    actions::reset_with_limits(Limits {
+        max_queued: 100,
+        max_per_step: usize::MAX,
+    });
+
+    assert!(queue_bounce("fault_injector", "a"));
+    assert!(queue_bounce("fault_injector", "a"));
+    assert!(queue_bounce("fault_injector", "a"));
+    // Not a repeat of the last bounce, so they're all kept
+    assert!(queue_bounce("chaos", "a"));
+    assert!(queue_bounce("fault_injector", "b"));
+    assert!(queue_bounce("fault_injector", "a"));
+    // Crashes are never collapsed
+    assert!(queue_crash("fault_injector", "a"));
+    assert!(queue_crash("fault_injector", "a"));
+    timing::advance_step();
+    // A bounce in a later step is kept
+    assert!(queue_bounce("fault_injector", "a"));
+
+    assert_eq!(
+        hosts(&actions::take_for_step()),
+        vec!["a", "a", "b", "a", "a", "a", "a"],
+    );
+    assert_eq!(
+        actions::stats(),
+        QueueStats {
+            dropped: 0,
+            collapsed: 2,
+            carried_forward: 0,
+            peak_queued: 7,
+        },
+    );
+}
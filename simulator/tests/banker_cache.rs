@@ -0,0 +1,111 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Replays a read, void, read sequence through the banker's transaction cache
+//! to prove that the stale read check catches a cache that skips
+//! invalidation, and stays quiet for the correct one.
+
+use std::time::Duration;
+
+use dst_demo_server::bank::Transaction;
+use dst_demo_server_simulator::client::banker::{
+    cache::{self, CacheMode, TransactionCache},
+    plan::{BankerInteractionPlan, Interaction},
+};
+use rust_decimal::Decimal;
+use simvar::plan::InteractionPlan as _;
+
+const TTL: Duration = Duration::from_mins(1);
+
+fn transaction() -> Transaction {
+    Transaction {
+        id: 1,
+        seq: 1,
+        amount: Decimal::new(1234, 2),
+        created_at: 0,
+    }
+}
+
+/// Reads transaction 1, voids it, then reads it again, returning whether the
+/// second read was served from the cache.
+fn read_void_read(mode: CacheMode) -> bool {
+    cache::reset();
+
+    let mut plan = BankerInteractionPlan::default();
+    plan.add_interaction(Interaction::CreateTransaction {
+        input: "12.34".to_string(),
+        amount: Some(Decimal::new(1234, 2)),
+    });
+    plan.add_interaction(Interaction::GetTransaction { id: 1 });
+    plan.add_interaction(Interaction::VoidTransaction { id: 1 });
+    plan.add_interaction(Interaction::GetTransaction { id: 1 });
+
+    let mut cache = TransactionCache::new(mode, TTL);
+    let mut now = Duration::ZERO;
+
+    while let Some(interaction) = plan.step().cloned() {
+        let step = plan.step - 1;
+        now += Duration::from_secs(1);
+
+        match interaction {
+            Interaction::GetTransaction { id } => {
+                if cache.get(id, step, now, &plan).is_some() {
+                    return true;
+                }
+                cache.insert(transaction(), step, now);
+            }
+            Interaction::VoidTransaction { id } => cache.invalidate(id),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+#[test]
+fn void_invalidates_the_cached_transaction() {
+    assert!(!read_void_read(CacheMode::On));
+
+    let stats = cache::stats();
+    assert_eq!(stats.hits, 0);
+    assert_eq!(stats.misses, 2);
+    assert_eq!(stats.stale_served, 0);
+}
+
+#[test]
+#[should_panic(expected = "even though it was voided in between")]
+fn skipping_invalidation_is_caught() {
+    read_void_read(CacheMode::SkipInvalidation);
+}
+
+#[test]
+fn entries_expire_after_the_ttl() {
+    cache::reset();
+
+    let plan = BankerInteractionPlan::default();
+    let mut cache = TransactionCache::new(CacheMode::On, TTL);
+
+    cache.insert(transaction(), 0, Duration::ZERO);
+    assert!(cache.get(1, 1, TTL, &plan).is_some());
+    assert!(
+        cache
+            .get(1, 2, TTL + Duration::from_millis(1), &plan)
+            .is_none()
+    );
+
+    assert_eq!(cache::stats().hits, 1);
+    assert_eq!(cache::stats().misses, 1);
+}
+
+#[test]
+fn disabled_cache_never_serves() {
+    cache::reset();
+
+    let plan = BankerInteractionPlan::default();
+    let mut cache = TransactionCache::new(CacheMode::Off, TTL);
+
+    cache.insert(transaction(), 0, Duration::ZERO);
+    assert!(cache.get(1, 1, Duration::ZERO, &plan).is_none());
+    assert_eq!(cache::stats(), cache::CacheStats::default());
+}
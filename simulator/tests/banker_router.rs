@@ -0,0 +1,165 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Feeds crafted frame sequences through the banker's response router to
+//! prove that unsolicited frames are skipped up to the bound, even when they
+//! share a read with a response, and that a mismatch is reported along with
+//! every frame seen.
+
+use dst_demo_server::protocol::{ProtocolError, ProtocolVersion};
+use dst_demo_server_simulator::client::banker::router::{MAX_SKIPPED_FRAMES, ResponseRouter};
+use simvar::switchy;
+
+fn router(version: ProtocolVersion, frames: &[&str]) -> ResponseRouter {
+    let mut router = ResponseRouter::new(version, "[client->server]");
+
+    for frame in frames {
+        router.push((*frame).to_string());
+    }
+
+    router
+}
+
+fn block_on<T: Send + 'static>(future: impl Future<Output = T> + Send + 'static) -> T {
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+    runtime.block_on(future)
+}
+
+/// Reads frames from `chunk` until a response is queued, the way the banker
+/// does, or returns `None` if `chunk` ran out first.
+async fn read_response(router: &mut ResponseRouter, mut chunk: &[u8]) -> Option<String> {
+    loop {
+        if let Some(response) = router.next_response() {
+            return Some(response);
+        }
+
+        let frame = router.read_frame(Box::pin(&mut chunk)).await.unwrap()?;
+        router.push(frame);
+    }
+}
+
+#[test]
+fn unsolicited_frames_are_skipped() {
+    let mut router = router(
+        ProtocolVersion::V2,
+        &[
+            "PUSH balance=$1.00",
+            "PROMPT amount",
+            "PUSH balance=$2.00",
+            "PUSH balance=$3.00",
+            "OK id=1 seq=1 created_at=1700000000000 amount=$12.34",
+            "ERR invalid_input Invalid amount",
+        ],
+    );
+
+    let frame = router.next_response().unwrap();
+    router.expect_prompt(&frame, "amount", "Enter the transaction amount:");
+
+    let frame = router.next_response().unwrap();
+    let transaction = router.expect_transaction(&frame);
+    assert_eq!(transaction.id, 1);
+    assert_eq!(transaction.seq, 1);
+
+    let frame = router.next_response().unwrap();
+    assert_eq!(router.expect_error(&frame), ProtocolError::InvalidInput);
+
+    assert_eq!(router.next_response(), None);
+}
+
+#[test]
+fn v1_frames_are_never_skipped() {
+    let mut router = router(ProtocolVersion::V1, &["PUSH balance=$1.00"]);
+
+    assert_eq!(
+        router.next_response().as_deref(),
+        Some("PUSH balance=$1.00")
+    );
+}
+
+#[test]
+fn skip_count_resets_after_each_response() {
+    let mut frames = vec![];
+    for _ in 0..2 {
+        frames.extend(["PUSH balance=$1.00"; MAX_SKIPPED_FRAMES]);
+        frames.push("OK");
+    }
+
+    let mut router = router(ProtocolVersion::V2, &frames);
+
+    assert_eq!(router.next_response().as_deref(), Some("OK"));
+    assert_eq!(router.next_response().as_deref(), Some("OK"));
+}
+
+#[test]
+#[should_panic(expected = "skipped 9 unsolicited frames without getting a response")]
+fn too_many_unsolicited_frames_fail() {
+    let mut router = router(
+        ProtocolVersion::V2,
+        &["PUSH balance=$1.00"; MAX_SKIPPED_FRAMES + 1],
+    );
+
+    router.next_response();
+}
+
+#[test]
+fn mismatch_reports_every_frame_seen() {
+    let mut router = router(
+        ProtocolVersion::V2,
+        &["PUSH balance=$1.00", "OK batch=open", "ERR not_found Nope"],
+    );
+
+    router.next_response();
+    let frame = router.next_response().unwrap();
+
+    let panic = std::panic::catch_unwind(|| {
+        router.expect_prompt(&frame, "amount", "Enter the transaction amount:");
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+
+    assert!(message.contains("expected prompt for amount"), "{message}");
+    assert!(
+        message.contains(
+            "Frames seen (3):\n  0: 'PUSH balance=$1.00'\n  1: 'OK batch=open'\n  2: 'ERR not_found Nope'"
+        ),
+        "{message}"
+    );
+}
+
+#[test]
+fn push_and_response_in_one_read_chunk() {
+    block_on(async {
+        let mut router = ResponseRouter::new(ProtocolVersion::V2, "[client->server]");
+
+        let response = read_response(
+            &mut router,
+            b"PUSH balance=$1.00\0OK id=1 seq=1 created_at=1700000000000 amount=$12.34\0",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(router.expect_transaction(&response).id, 1);
+        assert_eq!(router.next_response(), None);
+    });
+}
+
+#[test]
+fn push_split_across_the_reads_of_two_exchanges() {
+    block_on(async {
+        let mut router = ResponseRouter::new(ProtocolVersion::V2, "[client->server]");
+
+        // The first exchange's read also picks up the start of a push
+        let response = read_response(&mut router, b"OK batch=open\0PUSH bal")
+            .await
+            .unwrap();
+        assert_eq!(router.expect_ok(&response), "batch=open");
+
+        // The rest of the push arrives with the next exchange's response, and
+        // is only recognized as a push if the start of it was kept
+        let response = read_response(&mut router, b"ance=$1.00\0OK batch=committed\0")
+            .await
+            .unwrap();
+        assert_eq!(router.expect_ok(&response), "batch=committed");
+    });
+}
@@ -0,0 +1,112 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Polls conditions through `assert_eventually!` on the simulator's runtime
+//! to pin down how many times they're evaluated, and what's reported when
+//! one never becomes true.
+
+use std::{future::poll_fn, pin::pin, time::Duration};
+
+use dst_demo_server_simulator::{
+    assert_eventually,
+    eventually::{Timeout, eventually},
+};
+use simvar::switchy;
+
+const POLL_EVERY: Duration = Duration::from_millis(10);
+
+/// Runs `future` to completion, advancing simulated time by a millisecond
+/// every time it's pending, the way simvar's step loop would.
+///
+/// Without the steps, the simulator's `sleep` never elapses and keeps waking
+/// itself.
+fn block_on<T: Send + 'static>(future: impl Future<Output = T> + Send + 'static) -> T {
+    // SAFETY: every test in this binary sets the same value, and it's only
+    // read by the reset below
+    unsafe {
+        std::env::set_var("SIMULATOR_STEP_MULTIPLIER", "1");
+    }
+    switchy::time::simulator::reset_step_multiplier();
+    switchy::time::simulator::reset_step();
+
+    let runtime = switchy::unsync::runtime::Builder::new().build().unwrap();
+    runtime.block_on(async move {
+        let mut future = pin!(future);
+
+        poll_fn(|cx| {
+            let poll = future.as_mut().poll(cx);
+            if poll.is_pending() {
+                switchy::time::simulator::next_step();
+            }
+            poll
+        })
+        .await
+    })
+}
+
+/// A condition that fails `failures` times before succeeding
+fn ready_after(failures: u32) -> impl FnMut() -> std::future::Ready<Result<(), String>> {
+    let mut evaluations = 0;
+
+    move || {
+        evaluations += 1;
+        std::future::ready(if evaluations > failures {
+            Ok(())
+        } else {
+            Err(format!("not ready after {evaluations} evaluations"))
+        })
+    }
+}
+
+#[test]
+fn succeeds_on_the_first_passing_poll() {
+    let polls = block_on(async {
+        let mut ready = ready_after(3);
+        assert_eventually!(
+            ready(),
+            within = Duration::from_secs(1),
+            poll_every = POLL_EVERY,
+            context = "ready_after({})",
+            3,
+        )
+    });
+
+    assert_eq!(polls, 4);
+}
+
+#[test]
+fn times_out_with_the_last_evaluation() {
+    let timeout = block_on(eventually(
+        Duration::from_millis(20),
+        POLL_EVERY,
+        ready_after(5),
+    ))
+    .unwrap_err();
+
+    assert_eq!(timeout.polls, 3);
+    assert!(timeout.elapsed >= timeout.within);
+    assert_eq!(
+        Timeout {
+            elapsed: Duration::ZERO,
+            ..timeout
+        }
+        .to_string(),
+        "still failing after 3 polls over 0ns of simulated time (within=20ms): not ready after 3 evaluations",
+    );
+}
+
+#[test]
+#[should_panic(expected = "ready_after(5) never passed: still failing after 3 polls over ")]
+fn timeout_panics_with_the_context() {
+    block_on(async {
+        let mut ready = ready_after(5);
+        assert_eventually!(
+            ready(),
+            within = Duration::from_millis(20),
+            poll_every = POLL_EVERY,
+            context = "ready_after({}) never passed",
+            5,
+        )
+    });
+}
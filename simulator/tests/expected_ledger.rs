@@ -0,0 +1,120 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Records writes in the expected-ledger model and diffs crafted listings
+//! against it, to prove that in-doubt writes explain extra transactions and
+//! that every kind of discrepancy is reported.
+
+use std::str::FromStr as _;
+
+use dst_demo_server::bank::Transaction;
+use dst_demo_server_simulator::expected_ledger::{self, diff, snapshot};
+use rust_decimal::Decimal;
+
+fn transaction(id: i32, amount: &str) -> Transaction {
+    Transaction::from_str(&format!(
+        "id={id} seq={id} created_at=1700000000000 amount=${amount}"
+    ))
+    .unwrap()
+}
+
+fn amount(amount: &str) -> Decimal {
+    Decimal::from_str(amount).unwrap()
+}
+
+fn acknowledge(client: &str, transaction: &Transaction) {
+    expected_ledger::sent_create(transaction.amount);
+    expected_ledger::acknowledged_create(client, transaction);
+}
+
+#[test]
+fn matching_ledger_has_no_diff() {
+    expected_ledger::reset();
+    acknowledge("banker_0", &transaction(1, "12.34"));
+    acknowledge("banker_1", &transaction(2, "-5.00"));
+    let before = snapshot();
+
+    let ledger = [transaction(2, "-5.00"), transaction(1, "12.34")];
+
+    assert_eq!(diff(&before, &snapshot(), &ledger), None);
+}
+
+#[test]
+fn in_doubt_writes_explain_extra_transactions() {
+    expected_ledger::reset();
+    acknowledge("banker_0", &transaction(1, "12.34"));
+    expected_ledger::sent_create(amount("7.00"));
+    expected_ledger::sent_void(1);
+    let before = snapshot();
+
+    let ledger = [
+        transaction(1, "12.34"),
+        transaction(2, "7.00"),
+        transaction(3, "-12.34"),
+    ];
+
+    assert_eq!(diff(&before, &snapshot(), &ledger), None);
+}
+
+#[test]
+fn rejected_creates_explain_nothing() {
+    expected_ledger::reset();
+    expected_ledger::sent_create(amount("7.00"));
+    expected_ledger::rejected_create(amount("7.00"));
+    let before = snapshot();
+
+    let ledger = [transaction(1, "7.00")];
+
+    assert_eq!(
+        diff(&before, &snapshot(), &ledger).as_deref(),
+        Some("unexpected (1):\n  id=1 amount=$7.00"),
+    );
+}
+
+#[test]
+fn writes_acknowledged_during_the_listing_may_be_missing() {
+    expected_ledger::reset();
+    let before = snapshot();
+    acknowledge("banker_0", &transaction(1, "12.34"));
+
+    assert_eq!(diff(&before, &snapshot(), &[]), None);
+    assert_eq!(diff(&before, &snapshot(), &[transaction(1, "12.34")]), None);
+}
+
+#[test]
+fn discrepancies_are_reported_as_a_diff() {
+    expected_ledger::reset();
+    acknowledge("banker_0", &transaction(1, "12.34"));
+    acknowledge("banker_1", &transaction(2, "-5.00"));
+    let before = snapshot();
+
+    let ledger = [
+        transaction(2, "5.00"),
+        transaction(3, "1.00"),
+        transaction(3, "1.00"),
+    ];
+
+    assert_eq!(
+        diff(&before, &snapshot(), &ledger).as_deref(),
+        Some(
+            "\
+missing (1):
+  id=1 amount=$12.34 acknowledged to banker_0
+unexpected (1):
+  id=3 amount=$1.00
+amount mismatches (1):
+  id=2 amount=$5.00 acknowledged to banker_1 as amount=$-5.00
+duplicated (1):
+  id=3"
+        ),
+    );
+}
+
+#[test]
+#[should_panic(expected = "transaction id=1 was acknowledged to banker_0 with amount=$12.34")]
+fn same_id_acknowledged_twice_fails() {
+    expected_ledger::reset();
+    acknowledge("banker_0", &transaction(1, "12.34"));
+    acknowledge("banker_1", &transaction(1, "7.00"));
+}
@@ -0,0 +1,86 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Pauses and resumes fault injection between recorded faults to prove that
+//! the gate events land in the applied-fault log with their step numbers,
+//! and that the chaos-free windows can be read back from it.
+
+use std::time::Duration;
+
+use dst_demo_server_simulator::{
+    faults::{self, FaultKind, GateEvent, LogEntry},
+    timing,
+};
+
+fn advance(steps: u64) {
+    for _ in 0..steps {
+        timing::advance_step();
+    }
+}
+
+#[test]
+fn pause_and_resume_are_logged_with_their_steps() {
+    timing::reset_duration(Duration::from_mins(1));
+    faults::reset();
+
+    faults::record(FaultKind::Bounce, "server");
+    advance(10);
+    faults::pause();
+    assert!(faults::is_paused());
+
+    // Pausing again doesn't start another window
+    advance(5);
+    faults::pause();
+
+    advance(5);
+    faults::resume();
+    assert!(!faults::is_paused());
+    faults::record(FaultKind::Crash, "server");
+
+    advance(10);
+    faults::pause();
+
+    let gates = faults::log()
+        .into_iter()
+        .filter_map(|x| match x {
+            LogEntry::Gate(GateEvent { paused, step, .. }) => Some((paused, step)),
+            LogEntry::Fault(..) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(gates, [(true, 10), (false, 20), (true, 30)]);
+
+    assert!(matches!(
+        faults::log().as_slice(),
+        [
+            LogEntry::Fault(..),
+            LogEntry::Gate(..),
+            LogEntry::Gate(..),
+            LogEntry::Fault(..),
+            LogEntry::Gate(..),
+        ]
+    ));
+
+    let applied = faults::applied();
+    assert_eq!(applied.len(), 2);
+    assert_eq!(applied[1].kind, FaultKind::Crash);
+    assert!(faults::last(FaultKind::Bounce, "server").is_some());
+
+    assert_eq!(faults::paused_windows(), ["10-20", "30-"]);
+}
+
+#[test]
+fn skipped_faults_are_counted_and_reset() {
+    timing::reset_duration(Duration::from_mins(1));
+    faults::reset();
+
+    faults::pause();
+    faults::skip();
+    faults::skip();
+    assert_eq!(faults::skipped(), 2);
+
+    faults::reset();
+    assert!(!faults::is_paused());
+    assert_eq!(faults::skipped(), 0);
+    assert!(faults::log().is_empty());
+}
@@ -0,0 +1,123 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Walks the decision table a banker applies to reading a transaction it
+//! voided, so the outcomes that are allowed and the ones that fail the run are
+//! spelled out.
+
+use std::time::Duration;
+
+use dst_demo_server::bank::{StatementLine, Transaction};
+use dst_demo_server_simulator::client::banker::{
+    plan::{InteractionPlanContext, VoidAck},
+    read_after_void::{Verdict, check_read, check_voiding_entry},
+};
+use rust_decimal::Decimal;
+
+const STALENESS: Duration = Duration::from_secs(30);
+
+fn original() -> Transaction {
+    Transaction {
+        id: 1,
+        seq: 1,
+        amount: Decimal::new(1234, 2),
+        created_at: 0,
+    }
+}
+
+fn ack() -> VoidAck {
+    VoidAck {
+        step: 3,
+        at: Duration::from_secs(100),
+        entry: Transaction {
+            id: 2,
+            seq: 2,
+            amount: Decimal::new(-1234, 2),
+            created_at: 0,
+        },
+    }
+}
+
+#[test]
+fn reads_without_an_acknowledged_void_are_accepted() {
+    let now = Duration::from_secs(1000);
+
+    assert_eq!(
+        check_read(None, Some(&original()), now, STALENESS),
+        Verdict::Accept
+    );
+    assert_eq!(check_read(None, None, now, STALENESS), Verdict::Accept);
+}
+
+#[test]
+fn original_is_accepted_within_the_staleness_window() {
+    for now in [Duration::from_secs(100), Duration::from_secs(130)] {
+        assert_eq!(
+            check_read(Some(&ack()), Some(&original()), now, STALENESS),
+            Verdict::Accept,
+        );
+    }
+}
+
+#[test]
+fn original_after_the_staleness_window_requires_the_voiding_entry() {
+    assert_eq!(
+        check_read(
+            Some(&ack()),
+            Some(&original()),
+            Duration::from_secs(131),
+            STALENESS,
+        ),
+        Verdict::VerifyVoidingEntry(ack().entry),
+    );
+}
+
+#[test]
+fn missing_or_changed_voided_transaction_is_rejected() {
+    let changed = Transaction {
+        amount: Decimal::new(999, 2),
+        ..original()
+    };
+
+    for now in [Duration::from_secs(100), Duration::from_secs(1000)] {
+        assert!(matches!(
+            check_read(Some(&ack()), None, now, STALENESS),
+            Verdict::Reject(..),
+        ));
+        assert!(matches!(
+            check_read(Some(&ack()), Some(&changed), now, STALENESS),
+            Verdict::Reject(..),
+        ));
+    }
+}
+
+#[test]
+fn voiding_entry_must_be_the_only_statement_line() {
+    let entry = ack().entry;
+    let line = |id, amount| StatementLine {
+        id,
+        amount,
+        balance_after: Decimal::ZERO,
+    };
+
+    assert_eq!(
+        check_voiding_entry(&entry, &[line(2, Decimal::new(-1234, 2))]),
+        Ok(()),
+    );
+    assert!(check_voiding_entry(&entry, &[]).is_err());
+    assert!(check_voiding_entry(&entry, &[line(2, Decimal::new(1234, 2))]).is_err());
+    assert!(check_voiding_entry(&entry, &[line(3, Decimal::new(-1234, 2))]).is_err());
+}
+
+#[test]
+fn only_voids_before_the_read_are_consulted() {
+    let mut context = InteractionPlanContext::new();
+    context.acknowledge_void(1, ack());
+    context.acknowledge_void(1, VoidAck { step: 7, ..ack() });
+
+    assert_eq!(context.last_void_ack(1, 3), None);
+    assert_eq!(context.last_void_ack(1, 4).map(|x| x.step), Some(3));
+    assert_eq!(context.last_void_ack(1, 8).map(|x| x.step), Some(7));
+    assert_eq!(context.last_void_ack(2, 8), None);
+}
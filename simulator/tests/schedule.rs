@@ -0,0 +1,82 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Walks a banker's schedule past slow interactions to prove that the
+//! intended start times don't drift with how long interactions take, and that
+//! the lag behind them is tracked per client for the run's props.
+
+use std::time::{Duration, SystemTime};
+
+use dst_demo_server_simulator::{
+    schedule::Schedule,
+    stats::{self, ScheduleLag},
+};
+
+const PACE: Duration = Duration::from_mins(1);
+
+fn at(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn intended_starts_ignore_how_long_interactions_take() {
+    let mut schedule = Schedule::new(at(0), PACE);
+    assert_eq!(schedule.next(), at(0));
+
+    // The first interaction stalls for 150 seconds, which doesn't push the
+    // following ones back
+    schedule.advance(Duration::ZERO);
+    assert_eq!(schedule.next(), at(60));
+    assert_eq!(schedule.behind(at(150)), Duration::from_secs(90));
+
+    schedule.advance(Duration::ZERO);
+    assert_eq!(schedule.next(), at(120));
+    assert_eq!(schedule.behind(at(150)), Duration::from_secs(30));
+
+    // A planned sleep delays the next intended start on top of the pace
+    schedule.advance(Duration::from_secs(15));
+    assert_eq!(schedule.next(), at(195));
+    assert_eq!(schedule.behind(at(150)), Duration::ZERO);
+}
+
+#[test]
+fn behind_schedule_is_tracked_per_client() {
+    stats::reset();
+    assert!(stats::max_behind_schedule().is_none());
+
+    stats::record_behind_schedule("banker_1", Duration::from_secs(90), PACE);
+    stats::record_behind_schedule("banker_1", Duration::from_secs(30), PACE);
+    stats::record_behind_schedule("banker_2", Duration::from_secs(45), PACE);
+
+    let behind = stats::behind_schedule();
+    assert_eq!(
+        behind["banker_1"],
+        ScheduleLag {
+            current: Duration::from_secs(30),
+            max: Duration::from_secs(90),
+            pace: PACE,
+        }
+    );
+    assert_eq!(behind["banker_2"].max, Duration::from_secs(45));
+    assert_eq!(
+        stats::max_behind_schedule()
+            .map(|(client, _)| client)
+            .as_deref(),
+        Some("banker_1")
+    );
+
+    let summary = stats::summary();
+    let prop = |key: &str| {
+        summary
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(prop("behind_schedule_ms.max"), Some("90000"));
+    assert_eq!(prop("behind_schedule_ms.final"), Some("45000"));
+    assert_eq!(prop("behind_schedule_paces.max"), Some("1.50"));
+
+    stats::reset();
+    assert!(stats::behind_schedule().is_empty());
+}
@@ -0,0 +1,110 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Shuffles over seeded streams to prove that a shuffle is always a
+//! permutation, that it only depends on the seed, and that different seeds
+//! start the clients in different orders.
+
+use dst_demo_server_simulator::{
+    client::{self, Client},
+    shuffle::{shuffle, shuffled},
+};
+use simvar::switchy::random::rand::rand::{self, RngCore};
+use strum::IntoEnumIterator as _;
+
+/// A `SplitMix64` stream, so the tests don't depend on the simulator's rng
+struct SeededRng(u64);
+
+impl RngCore for SeededRng {
+    #[allow(clippy::cast_possible_truncation)]
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[test]
+fn shuffle_is_a_permutation() {
+    for seed in 0..200 {
+        for len in 0..20 {
+            let mut items = (0..len).collect::<Vec<u32>>();
+            shuffle(&mut SeededRng(seed), &mut items);
+
+            items.sort_unstable();
+            assert_eq!(items, (0..len).collect::<Vec<_>>(), "seed={seed}");
+        }
+    }
+}
+
+#[test]
+fn same_seed_shuffles_the_same() {
+    for seed in 0..200 {
+        let mut items = (0..50).collect::<Vec<u32>>();
+        shuffle(&mut SeededRng(seed), &mut items);
+
+        assert_eq!(shuffled(&mut SeededRng(seed), 0..50), items, "seed={seed}");
+        assert_eq!(shuffled(&mut SeededRng(seed), 0..50), items, "seed={seed}");
+    }
+}
+
+#[test]
+fn shuffle_moves_items() {
+    let unshuffled = (0..50).collect::<Vec<u32>>();
+    let moved = (0..200)
+        .filter(|seed| shuffled(&mut SeededRng(*seed), 0..50) != unshuffled)
+        .count();
+
+    assert_eq!(moved, 200);
+}
+
+#[test]
+fn start_order_starts_every_client() {
+    for seed in 0..50 {
+        let order = client::start_order(&mut SeededRng(seed), 5);
+
+        assert_eq!(order.len(), Client::iter().count() - 1 + 5);
+        for client in Client::iter() {
+            let expected = if client == Client::Banker { 5 } else { 1 };
+            assert_eq!(
+                order.iter().filter(|x| **x == client).count(),
+                expected,
+                "seed={seed} client={client}"
+            );
+        }
+    }
+}
+
+#[test]
+fn different_seeds_start_clients_in_different_orders() {
+    let differing = (0..100)
+        .filter(|seed| {
+            client::start_order(&mut SeededRng(*seed), 5)
+                != client::start_order(&mut SeededRng(seed + 1000), 5)
+        })
+        .count();
+
+    assert!(
+        differing >= 90,
+        "only {differing} of 100 seed pairs started clients in different orders"
+    );
+}
@@ -0,0 +1,38 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions, unused_imports)]
+
+//! Names every item of the `simvar` facade that the simulator and its
+//! examples import. The simulator only reaches the harness and the switchy
+//! backends through `simvar`, so a harness release that moves or drops one of
+//! these paths fails to compile here, pointing at the missing path, instead
+//! of deep inside a client.
+
+use simvar::{
+    Sim, SimBootstrap, SimConfig, SimResult,
+    plan::InteractionPlan,
+    run_simulation,
+    switchy::{
+        random::{
+            rand::rand::{
+                Rng, RngCore,
+                seq::{IteratorRandom, SliceRandom},
+            },
+            rng,
+        },
+        tcp::{Error as TcpError, GenericTcpListener, GenericTcpStream, TcpListener, TcpStream},
+        time::{
+            now,
+            simulator::{current_step, step_multiplier},
+        },
+        unsync::{
+            futures::FutureExt,
+            io::{AsyncReadExt, AsyncWriteExt},
+            select,
+            task::spawn,
+            thread_id,
+            time::sleep,
+        },
+    },
+    utils::run_until_simulation_cancelled,
+};
@@ -0,0 +1,48 @@
+#![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Parses seed lists the way a nightly batch's failures get pasted into
+//! `SIMULATOR_TRIAGE_SEEDS` or a `--triage` file, and pulls the first failed
+//! invariant out of a run's log.
+
+use dst_demo_server_simulator::triage::{first_failure, parse_seeds};
+
+#[test]
+fn seeds_are_split_on_commas_whitespace_and_lines() {
+    assert_eq!(parse_seeds("123,456, 789").unwrap(), vec![123, 456, 789]);
+    assert_eq!(
+        parse_seeds("# nightly failures\n123\n456 # flaky?\n\n789,1011\n").unwrap(),
+        vec![123, 456, 789, 1011],
+    );
+    assert_eq!(parse_seeds("").unwrap(), Vec::<u64>::new());
+}
+
+#[test]
+fn invalid_seeds_are_named() {
+    assert_eq!(
+        parse_seeds("123,abc").unwrap_err().to_string(),
+        "Invalid seed 'abc'",
+    );
+}
+
+#[test]
+fn first_failure_is_the_first_panic() {
+    let log = "\
+ INFO  simvar > starting run seed=123
+thread 'simvar-1' panicked at simulator/src/expected_ledger.rs:72:17:
+transaction id=4 was acknowledged to banker_0 with amount=$1.00 and to banker_1 with amount=$2.00
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+thread 'main' panicked at simulator/src/main.rs:10:5:
+run failed
+";
+
+    assert_eq!(
+        first_failure(log).as_deref(),
+        Some(
+            "transaction id=4 was acknowledged to banker_0 with amount=$1.00 and to banker_1 \
+             with amount=$2.00 (simulator/src/expected_ledger.rs:72:17)"
+        ),
+    );
+    assert_eq!(first_failure(" INFO  simvar > run passed\n"), None);
+}
@@ -2,10 +2,13 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)]
 
-use std::{pin::Pin, string::FromUtf8Error, sync::LazyLock};
+use std::{pin::Pin, string::FromUtf8Error, sync::LazyLock, time::Duration};
 
 use clap::Parser;
-use rustyline::{DefaultEditor, error::ReadlineError};
+use rustyline::{
+    Context, Editor, Helper, completion::Completer, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::DefaultHistory, validate::Validator,
+};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
@@ -23,6 +26,8 @@ pub enum Error {
     FromUtf8(#[from] FromUtf8Error),
     #[error(transparent)]
     Join(#[from] JoinError),
+    #[error("Invalid frame: {0}")]
+    Framing(String),
 }
 
 #[derive(Parser, Debug)]
@@ -30,6 +35,15 @@ pub enum Error {
 struct Args {
     #[arg(index = 1)]
     addr: String,
+
+    /// Negotiates compressed responses with `COMPRESS deflate`
+    #[arg(long)]
+    compress: bool,
+
+    /// Identifies the connection to the server with an `IDENTITY` preamble,
+    /// which the server authorizes some actions by
+    #[arg(long)]
+    identity: Option<String>,
 }
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
@@ -46,14 +60,28 @@ async fn main() -> Result<(), Error> {
     let addr = args.addr;
     log::info!("Connecting to TCP on addr={addr}...");
 
-    let stream = TcpStream::connect(addr).await?;
+    let actions = fetch_actions(&addr).await;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    if let Some(identity) = &args.identity {
+        stream
+            .write_all(format!("IDENTITY {identity}\0").as_bytes())
+            .await?;
+    }
+    let compressed = args.compress && negotiate_compression(&mut stream).await?;
     let (mut reader, mut writer) = stream.into_split();
 
     let reader_handle = CANCELLATION_TOKEN.run_until_cancelled(async move {
         let mut message = String::new();
+        let mut frames = vec![];
 
         loop {
-            let Some(response) = read_message(&mut message, Box::pin(&mut reader)).await? else {
+            let response = if compressed {
+                read_frame(&mut frames, Box::pin(&mut reader)).await?
+            } else {
+                read_message(&mut message, Box::pin(&mut reader)).await?
+            };
+            let Some(response) = response else {
                 break;
             };
 
@@ -80,12 +108,18 @@ async fn main() -> Result<(), Error> {
     // tokio::io::stdin is naturally blocking and non-cancellable, so this
     // is the best we can do
     let read_line_handle = std::thread::spawn(move || {
-        let mut rl = DefaultEditor::new().unwrap();
+        let mut rl = Editor::<ActionCompleter, DefaultHistory>::new().unwrap();
+        rl.set_helper(Some(ActionCompleter { actions }));
 
         loop {
             let readline = rl.readline("");
 
             match readline {
+                Ok(message) if message.trim() == "version" => {
+                    println!("tcp_client version={}", env!("CARGO_PKG_VERSION"));
+                    log::debug!("Sending message=\"VERSION\"");
+                    tx.send("VERSION".to_string()).unwrap();
+                }
                 Ok(message) => {
                     log::debug!("Sending message=\"{message}\"");
                     tx.send(message).unwrap();
@@ -115,6 +149,113 @@ async fn main() -> Result<(), Error> {
     Ok(())
 }
 
+/// How long to wait for the server to accept `COMPRESS`, which servers that
+/// predate it never answer for protocol v1 connections
+const COMPRESS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Asks the server to compress its responses, returning whether it agreed.
+/// The session continues uncompressed if it didn't.
+async fn negotiate_compression(stream: &mut TcpStream) -> Result<bool, Error> {
+    stream.write_all(b"COMPRESS deflate\0").await?;
+    stream.flush().await?;
+
+    let mut message = String::new();
+    let read = read_message(&mut message, Box::pin(&mut *stream));
+
+    let Ok(response) = tokio::time::timeout(COMPRESS_TIMEOUT, read).await else {
+        log::warn!("Timed out negotiating compression");
+        return Ok(false);
+    };
+    let response = response?.unwrap_or_default();
+
+    if response != "deflate" {
+        log::warn!("The server doesn't support compression: {response}");
+        return Ok(false);
+    }
+
+    log::debug!("Negotiated deflate compression");
+    Ok(true)
+}
+
+/// How long to wait for the server to answer `HELP`, which servers that
+/// predate it never do for protocol v1 connections
+const HELP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Fetches the server's actions over a separate connection so that the
+/// `HELP` response doesn't get printed in the session. Returns no actions if
+/// the server doesn't support `HELP`, which just disables completion.
+async fn fetch_actions(addr: &str) -> Vec<String> {
+    let fetch = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(b"HELP\0").await?;
+        stream.flush().await?;
+
+        let mut message = String::new();
+        let help = read_message(&mut message, Box::pin(&mut stream)).await?;
+
+        Ok::<_, Error>(help.unwrap_or_default())
+    };
+
+    let help = match tokio::time::timeout(HELP_TIMEOUT, fetch).await {
+        Ok(Ok(help)) => help,
+        Ok(Err(e)) => {
+            log::debug!("Failed to fetch the server's actions: {e:?}");
+            return vec![];
+        }
+        Err(..) => {
+            log::debug!("Timed out fetching the server's actions");
+            return vec![];
+        }
+    };
+
+    help.lines()
+        .filter_map(|x| x.split_whitespace().next())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Completes the action names listed by the server's `HELP`
+struct ActionCompleter {
+    actions: Vec<String>,
+}
+
+impl Completer for ActionCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let prefix = line[..pos].to_uppercase();
+
+        // Only the action itself is completed, not prompt answers
+        if prefix.contains(' ') {
+            return Ok((0, vec![]));
+        }
+
+        Ok((
+            0,
+            self.actions
+                .iter()
+                .filter(|x| x.starts_with(&prefix))
+                .cloned()
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for ActionCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for ActionCompleter {}
+
+impl Validator for ActionCompleter {}
+
+impl Helper for ActionCompleter {}
+
 async fn read_message(
     message: &mut String,
     mut stream: Pin<Box<impl AsyncReadExt>>,
@@ -153,3 +294,72 @@ async fn read_message(
         }
     })
 }
+
+/// The flag byte and big-endian `u32` body length that precede each
+/// compressed connection's frame body, the same as the server's framing
+const FRAME_HEADER_LEN: usize = 5;
+
+/// The largest frame body the server sends, before or after decompression
+const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Reads the next length prefixed frame from a connection that negotiated
+/// compression.
+async fn read_frame(
+    buffer: &mut Vec<u8>,
+    mut stream: Pin<Box<impl AsyncReadExt>>,
+) -> Result<Option<String>, Error> {
+    let mut buf = [0_u8; 1024];
+
+    loop {
+        if let Some(message) = decode_frame(buffer)? {
+            return Ok(Some(message));
+        }
+
+        let Ok(count) = stream
+            .read(&mut buf)
+            .await
+            .inspect_err(|e| log::trace!("Failed to read from stream: {e:?}"))
+        else {
+            return Ok(None);
+        };
+        if count == 0 {
+            return Ok(None);
+        }
+        log::trace!("read count={count}");
+        buffer.extend_from_slice(&buf[..count]);
+    }
+}
+
+/// Takes the first complete frame off the front of `buffer`, inflating its
+/// body if it's flagged as compressed.
+fn decode_frame(buffer: &mut Vec<u8>) -> Result<Option<String>, Error> {
+    let Some(header) = buffer.get(..FRAME_HEADER_LEN) else {
+        return Ok(None);
+    };
+
+    let flag = header[0];
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+
+    if len > MAX_FRAME_LEN {
+        return Err(Error::Framing(format!(
+            "Frame body of {len} bytes exceeds the maximum"
+        )));
+    }
+    if buffer.len() < FRAME_HEADER_LEN + len {
+        return Ok(None);
+    }
+
+    let body = buffer
+        .drain(..FRAME_HEADER_LEN + len)
+        .skip(FRAME_HEADER_LEN)
+        .collect::<Vec<_>>();
+
+    let payload = match flag {
+        0x00 => body,
+        0x01 => miniz_oxide::inflate::decompress_to_vec_with_limit(&body, MAX_FRAME_LEN)
+            .map_err(|e| Error::Framing(format!("Invalid compressed frame body: {e:?}")))?,
+        flag => return Err(Error::Framing(format!("Unknown frame flag {flag:#04x}"))),
+    };
+
+    Ok(Some(String::from_utf8(payload)?))
+}